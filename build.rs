@@ -18,6 +18,9 @@ fn main() -> Result<()> {
     // Download JAR files
     rt.block_on(download_jar_files(&jars_dir))?;
 
+    // Download JADX and keep only its all-in-one JAR
+    rt.block_on(download_jadx(jars_dir))?;
+
     // Download and extract dex2jar tools
     rt.block_on(download_dex2jar(&tools_dir))?;
 
@@ -38,6 +41,14 @@ async fn download_jar_files(jars_dir: &Path) -> Result<()> {
             "procyon-decompiler-v0.6.1.jar",
             "https://github.com/dmikushin/procyon/releases/download/v0.6.1/procyon-decompiler-v0.6.1.jar"
         ),
+        (
+            "cfr-0.152.jar",
+            "https://github.com/leibnitz27/cfr/releases/download/0.152/cfr-0.152.jar"
+        ),
+        (
+            "vineflower-1.10.1.jar",
+            "https://github.com/Vineflower/vineflower/releases/download/1.10.1/vineflower-1.10.1.jar"
+        ),
     ];
 
     for (filename, url) in downloads {
@@ -77,6 +88,34 @@ async fn download_dex2jar(tools_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+async fn download_jadx(jars_dir: &Path) -> Result<()> {
+    let jadx_url = "https://github.com/skylot/jadx/releases/download/v1.5.1/jadx-1.5.1.zip";
+    let jar_name = "jadx-1.5.1-all.jar";
+    let zip_path = jars_dir.join("jadx.zip");
+
+    if jars_dir.join(jar_name).exists() {
+        println!("cargo:warning={} already exists, skipping", jar_name);
+        return Ok(());
+    }
+
+    println!("cargo:warning=Downloading jadx...");
+    download_file(jadx_url, &zip_path).await?;
+
+    {
+        let file = fs::File::open(&zip_path)?;
+        let mut archive = zip::ZipArchive::new(file)?;
+        let mut jar = archive.by_name(&format!("lib/{}", jar_name))
+            .with_context(|| format!("{} not found in jadx distribution", jar_name))?;
+        let mut outfile = fs::File::create(jars_dir.join(jar_name))?;
+        std::io::copy(&mut jar, &mut outfile)?;
+    }
+
+    // Clean up zip file
+    let _ = fs::remove_file(&zip_path);
+
+    Ok(())
+}
+
 async fn extract_aapt_from_apktool(jars_dir: &Path, tools_dir: &Path) -> Result<()> {
     let apktool_path = jars_dir.join("apktool.jar");
     let prebuilt_dir = tools_dir.join("prebuilt");
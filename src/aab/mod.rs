@@ -0,0 +1,203 @@
+//! Android App Bundles: one directory per module, with the manifest, XML resources and
+//! resource table compiled to aapt2's protobuf format instead of binary XML
+//!
+//! `unpack` keeps the bundle as it is under `bundle/` and writes readable XML to `decoded/`;
+//! `pack` zips `bundle/` back into an `.aab`. XML is not compiled back to protobuf, so edits
+//! in `decoded/` cannot be packed and are reported by [`decoded_changes`].
+
+use crate::signing::is_signature_entry;
+use crate::Result;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub mod proto;
+pub mod resources;
+pub mod xml;
+
+/// The bundle contents, unchanged; this is what `pack` turns back into an `.aab`
+pub const BUNDLE_DIR: &str = "bundle";
+
+/// Manifest and resources of every module as XML text, for reading
+pub const DECODED_DIR: &str = "decoded";
+
+/// Present at the top of every bundle
+pub const BUNDLE_CONFIG: &str = "BundleConfig.pb";
+
+const MANIFEST_PATH: &str = "manifest/AndroidManifest.xml";
+const RESOURCE_TABLE: &str = "resources.pb";
+
+/// Extract every entry of the bundle into `dir` unchanged; returns the module names
+pub fn extract(aab: &Path, dir: &Path) -> Result<Vec<String>> {
+    let mut archive = ZipArchive::new(File::open(aab)?)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let Some(relative) = file.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+
+        let target = dir.join(relative);
+        if file.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&target)?)?;
+    }
+
+    let modules = modules(dir)?;
+    if modules.is_empty() {
+        anyhow::bail!("No module with {} in the bundle", MANIFEST_PATH);
+    }
+    Ok(modules)
+}
+
+/// Modules of an extracted bundle: the top-level directories holding a manifest, `base` first
+pub fn modules(dir: &Path) -> Result<Vec<String>> {
+    let mut modules = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.join(MANIFEST_PATH).is_file() {
+            modules.push(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+        }
+    }
+    modules.sort_by_key(|module| (module != "base", module.clone()));
+
+    Ok(modules)
+}
+
+/// Write the manifest, XML resources and resource table of `module_dir` as XML text into `out_dir`
+///
+/// Files under `res/` that are not XML are copied so `out_dir/res` is complete.
+pub fn decode_module(module_dir: &Path, out_dir: &Path) -> Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let manifest = fs::read(module_dir.join(MANIFEST_PATH))?;
+    fs::write(out_dir.join("AndroidManifest.xml"), xml::decode(&manifest)?)?;
+
+    let res_dir = module_dir.join("res");
+    if res_dir.is_dir() {
+        for entry in WalkDir::new(&res_dir) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let target = out_dir.join("res").join(entry.path().strip_prefix(&res_dir)?);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // Raw XML under res/raw is not compiled, and a file that fails to decode is better kept as it is
+            let decoded = match entry.path().extension().and_then(|s| s.to_str()) {
+                Some("xml") => xml::decode(&fs::read(entry.path())?).ok(),
+                _ => None,
+            };
+            match decoded {
+                Some(text) => fs::write(&target, text)?,
+                None => {
+                    fs::copy(entry.path(), &target)?;
+                }
+            }
+        }
+    }
+
+    let table_path = module_dir.join(RESOURCE_TABLE);
+    if table_path.is_file() {
+        for (relative, xml) in resources::decode_table(&fs::read(&table_path)?)? {
+            let target = out_dir.join("res").join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(target, xml)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Zip the extracted bundle in `dir` into `aab`, leaving out old JAR signatures
+pub fn pack(dir: &Path, aab: &Path) -> Result<()> {
+    if !dir.join(BUNDLE_CONFIG).is_file() {
+        anyhow::bail!("{} not found in {}", BUNDLE_CONFIG, dir.display());
+    }
+
+    let mut writer = ZipWriter::new(File::create(aab)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir)?;
+        let name = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        if is_signature_entry(&name) {
+            continue;
+        }
+
+        writer.start_file(name, options)?;
+        io::copy(&mut File::open(entry.path())?, &mut writer)?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// Files under `decoded_dir` that differ from what the modules in `bundle_dir` decode to, were
+/// added or were deleted, relative to `decoded_dir`; modules without a directory there are not checked
+pub fn decoded_changes(bundle_dir: &Path, decoded_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !decoded_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let fresh_dir = tempfile::tempdir()?;
+    let mut changes = Vec::new();
+    let mut modules = Vec::new();
+    for entry in fs::read_dir(decoded_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            modules.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    modules.sort();
+
+    for module in modules {
+        let fresh = fresh_dir.path().join(&module);
+        if bundle_dir.join(&module).join(MANIFEST_PATH).is_file() {
+            decode_module(&bundle_dir.join(&module), &fresh)?;
+        }
+        let mut files = relative_files(&fresh)?;
+        files.extend(relative_files(&decoded_dir.join(&module))?);
+        for file in files {
+            let (old, new) = (fs::read(fresh.join(&file)).ok(), fs::read(decoded_dir.join(&module).join(&file)).ok());
+            if old != new {
+                changes.push(Path::new(&module).join(file));
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Paths of the files under `dir`, relative to it; empty if `dir` does not exist
+fn relative_files(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files.insert(entry.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,130 @@
+//! Just enough of the protobuf wire format to read aapt2's `Resources.proto` messages and
+//! Kotlin metadata
+
+use crate::Result;
+
+/// One field of a message; length-delimited fields keep their raw bytes
+#[derive(Debug, Clone, Copy)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    pub fn as_u64(&self) -> u64 {
+        match *self {
+            Value::Varint(v) | Value::Fixed64(v) => v,
+            Value::Fixed32(v) => v as u64,
+            Value::Bytes(_) => 0,
+        }
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        self.as_u64() as u32
+    }
+
+    pub fn as_bool(&self) -> bool {
+        self.as_u64() != 0
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        f32::from_bits(self.as_u32())
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match *self {
+            Value::Bytes(bytes) => bytes,
+            _ => &[],
+        }
+    }
+
+    pub fn as_str(&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).into_owned()
+    }
+}
+
+/// Fields of an encoded message in wire order, as `(field number, value)`
+pub fn fields(data: &[u8]) -> Result<Vec<(u32, Value<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let key = varint(data, &mut pos)?;
+        let number = (key >> 3) as u32;
+
+        let value = match key & 7 {
+            0 => Value::Varint(varint(data, &mut pos)?),
+            1 => Value::Fixed64(u64::from_le_bytes(take(data, &mut pos, 8)?.try_into()?)),
+            2 => {
+                let len = varint(data, &mut pos)? as usize;
+                Value::Bytes(take(data, &mut pos, len)?)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(take(data, &mut pos, 4)?.try_into()?)),
+            wire_type => anyhow::bail!("Unsupported protobuf wire type {}", wire_type),
+        };
+
+        fields.push((number, value));
+    }
+
+    Ok(fields)
+}
+
+/// The last occurrence of field `number`, as protobuf does for singular fields
+pub fn field<'a>(fields: &[(u32, Value<'a>)], number: u32) -> Option<Value<'a>> {
+    fields.iter().rev().find(|(n, _)| *n == number).map(|(_, value)| *value)
+}
+
+/// All occurrences of the repeated field `number`
+pub fn repeated<'a, 'f>(fields: &'f [(u32, Value<'a>)], number: u32) -> impl Iterator<Item = Value<'a>> + 'f {
+    fields.iter().filter(move |(n, _)| *n == number).map(|(_, value)| *value)
+}
+
+/// All values of the repeated integer field `number`, packed or not
+pub fn repeated_varints(fields: &[(u32, Value<'_>)], number: u32) -> Result<Vec<u64>> {
+    let mut values = Vec::new();
+    for value in repeated(fields, number) {
+        match value {
+            Value::Bytes(packed) => {
+                let mut pos = 0;
+                while pos < packed.len() {
+                    values.push(varint(packed, &mut pos)?);
+                }
+            }
+            value => values.push(value.as_u64()),
+        }
+    }
+    Ok(values)
+}
+
+/// A message written with its length in front, as `writeDelimitedTo` does, and the bytes after it
+pub fn delimited(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let mut pos = 0;
+    let len = varint(data, &mut pos)? as usize;
+    let message = take(data, &mut pos, len)?;
+    Ok((message, &data[pos..]))
+}
+
+fn varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or_else(|| anyhow::anyhow!("Truncated protobuf varint"))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    anyhow::bail!("Protobuf varint is too long")
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = pos.checked_add(len).filter(|end| *end <= data.len())
+        .ok_or_else(|| anyhow::anyhow!("Truncated protobuf field"))?;
+    let bytes = &data[*pos..end];
+    *pos = end;
+    Ok(bytes)
+}
@@ -0,0 +1,425 @@
+//! `resources.pb` (aapt2 `ResourceTable`) to `res/values*/*.xml`
+
+use super::proto::{self, field, repeated, Value};
+use crate::Result;
+use std::collections::BTreeMap;
+
+const PLURAL_QUANTITIES: [&str; 6] = ["zero", "one", "two", "few", "many", "other"];
+
+/// `Attribute.format_flags` bits that turn symbols into `<enum>` or `<flag>` children
+const FORMAT_ENUM: u32 = 1 << 16;
+const FORMAT_FLAGS: u32 = 1 << 17;
+
+/// Values XML files for every value resource in the table, keyed by path relative to `res/`
+///
+/// File-based resources (layouts, drawables, …) are skipped: they live under `res/` already.
+pub fn decode_table(data: &[u8]) -> Result<BTreeMap<String, String>> {
+    // (values directory, file name) → rendered elements
+    let mut files: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+
+    let table = proto::fields(data)?;
+    for package in repeated(&table, 2) {
+        let package = proto::fields(package.as_bytes())?;
+
+        for resource_type in repeated(&package, 3) {
+            let resource_type = proto::fields(resource_type.as_bytes())?;
+            let type_name = field(&resource_type, 2).map(|v| v.as_str()).unwrap_or_default();
+
+            for entry in repeated(&resource_type, 3) {
+                let entry = proto::fields(entry.as_bytes())?;
+                let name = field(&entry, 2).map(|v| v.as_str()).unwrap_or_default();
+
+                for config_value in repeated(&entry, 6) {
+                    let config_value = proto::fields(config_value.as_bytes())?;
+                    let qualifiers = match field(&config_value, 1) {
+                        Some(config) => qualifiers(config.as_bytes())?,
+                        None => String::new(),
+                    };
+                    let Some(value) = field(&config_value, 2) else { continue };
+
+                    if let Some(element) = render_value(&type_name, &name, value.as_bytes())? {
+                        let dir = if qualifiers.is_empty() { "values".to_string() } else { format!("values-{}", qualifiers) };
+                        files.entry((dir, values_file(&type_name))).or_default().push(element);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .map(|((dir, file), elements)| {
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n");
+            for element in elements {
+                xml.push_str(&element);
+            }
+            xml.push_str("</resources>\n");
+            (format!("{}/{}", dir, file), xml)
+        })
+        .collect())
+}
+
+/// Text form of an aapt2 `Item`, as it would appear in a resource XML file
+pub fn format_item(data: &[u8]) -> Result<String> {
+    let item = proto::fields(data)?;
+
+    if let Some(reference) = field(&item, 1) {
+        return format_reference(reference.as_bytes());
+    }
+    // String, RawString, StyledString (spans are dropped) and FileReference all keep the text in field 1
+    for number in [2, 3, 4, 5] {
+        if let Some(value) = field(&item, number) {
+            let value = proto::fields(value.as_bytes())?;
+            return Ok(field(&value, 1).map(|v| v.as_str()).unwrap_or_default());
+        }
+    }
+    if field(&item, 6).is_some() {
+        return Ok(String::new());
+    }
+    if let Some(primitive) = field(&item, 7) {
+        return format_primitive(primitive.as_bytes());
+    }
+
+    Ok(String::new())
+}
+
+fn format_reference(data: &[u8]) -> Result<String> {
+    let reference = proto::fields(data)?;
+    let prefix = if field(&reference, 1).map_or(0, |v| v.as_u32()) == 1 { "?" } else { "@" };
+    let id = field(&reference, 2).map_or(0, |v| v.as_u32());
+    let name = field(&reference, 3).map(|v| v.as_str()).unwrap_or_default();
+
+    Ok(match (name.is_empty(), id) {
+        (false, _) => format!("{}{}", prefix, name),
+        (true, 0) => "@null".to_string(),
+        (true, id) => format!("{}0x{:08x}", prefix, id),
+    })
+}
+
+fn format_primitive(data: &[u8]) -> Result<String> {
+    let primitive = proto::fields(data)?;
+    let Some((number, value)) = primitive.last().copied() else {
+        return Ok(String::new());
+    };
+
+    let argb = value.as_u32();
+    let nibble = |shift: u32| (argb >> (shift + 4)) & 0xf;
+
+    Ok(match number {
+        1 => "@null".to_string(),
+        2 => "@empty".to_string(),
+        3 | 4 => value.as_f32().to_string(),
+        5 => format!("{}%", value.as_f32() * 100.0),
+        6 => (value.as_u64() as i64 as i32).to_string(),
+        7 => format!("0x{:x}", argb),
+        8 => value.as_bool().to_string(),
+        9 => format!("#{:08x}", argb),
+        10 => format!("#{:06x}", argb & 0xff_ffff),
+        11 => format!("#{:x}{:x}{:x}{:x}", nibble(24), nibble(16), nibble(8), nibble(0)),
+        12 => format!("#{:x}{:x}{:x}", nibble(16), nibble(8), nibble(0)),
+        13 => format_complex(argb, &["px", "dp", "sp", "pt", "in", "mm"]),
+        14 => format_complex(argb, &["%", "%p"]),
+        _ => String::new(),
+    })
+}
+
+/// Decode a `TypedValue` complex number: 24-bit mantissa, radix and unit
+pub(crate) fn format_complex(data: u32, units: &[&str]) -> String {
+    const RADIX_SHIFTS: [i32; 4] = [8, 15, 23, 31];
+
+    // The mantissa stays in the top 24 bits, so each radix divides by 2^8 more than its own shift
+    let radix = ((data >> 4) & 3) as usize;
+    let mantissa = (data & 0xffff_ff00) as i32 as f64;
+    let mut value = mantissa / 2f64.powi(RADIX_SHIFTS[radix]);
+    let unit = units.get((data & 0xf) as usize).copied().unwrap_or("");
+
+    // Fractions are stored as 0..1 and written as percentages
+    if unit.starts_with('%') {
+        value *= 100.0;
+    }
+    format!("{}{}", value as f32, unit)
+}
+
+fn render_value(type_name: &str, name: &str, data: &[u8]) -> Result<Option<String>> {
+    let value = proto::fields(data)?;
+
+    if let Some(item) = field(&value, 4) {
+        let item_fields = proto::fields(item.as_bytes())?;
+        if field(&item_fields, 5).is_some() {
+            // A file under res/, not a value
+            return Ok(None);
+        }
+
+        let text = format_item(item.as_bytes())?;
+        return Ok(Some(match type_name {
+            "id" => format!("    <item type=\"id\" name=\"{}\" />\n", escape_attr(name)),
+            "string" => format!("    <string name=\"{}\">{}</string>\n", escape_attr(name), escape_string(&text)),
+            "color" | "dimen" | "bool" | "integer" | "fraction" => {
+                format!("    <{} name=\"{}\">{}</{}>\n", type_name, escape_attr(name), escape_text(&text), type_name)
+            }
+            _ => format!("    <item type=\"{}\" name=\"{}\">{}</item>\n", type_name, escape_attr(name), escape_text(&text)),
+        }));
+    }
+
+    let Some(compound) = field(&value, 5) else { return Ok(None) };
+    let compound = proto::fields(compound.as_bytes())?;
+    let Some((kind, body)) = compound.last().copied() else { return Ok(None) };
+
+    let rendered = match kind {
+        1 => render_attr(name, body)?,
+        2 => render_style(name, body)?,
+        3 => render_styleable(name, body)?,
+        4 => render_array(name, body)?,
+        5 => render_plurals(name, body)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(rendered))
+}
+
+fn render_attr(name: &str, body: Value<'_>) -> Result<String> {
+    let attr = proto::fields(body.as_bytes())?;
+    let format_flags = field(&attr, 1).map_or(0, |v| v.as_u32());
+    let child = if format_flags & FORMAT_FLAGS != 0 {
+        "flag"
+    } else if format_flags & FORMAT_ENUM != 0 {
+        "enum"
+    } else {
+        ""
+    };
+
+    let mut out = format!("    <attr name=\"{}\"", escape_attr(name));
+    let formats = attr_formats(format_flags);
+    if !formats.is_empty() {
+        out.push_str(&format!(" format=\"{}\"", formats));
+    }
+
+    let symbols: Vec<Value<'_>> = repeated(&attr, 4).collect();
+    if symbols.is_empty() || child.is_empty() {
+        out.push_str(" />\n");
+        return Ok(out);
+    }
+
+    out.push_str(">\n");
+    for symbol in symbols {
+        let symbol = proto::fields(symbol.as_bytes())?;
+        let symbol_name = match field(&symbol, 3) {
+            Some(reference) => entry_name(&format_reference(reference.as_bytes())?),
+            None => String::new(),
+        };
+        let symbol_value = field(&symbol, 4).map_or(0, |v| v.as_u32());
+        out.push_str(&format!("        <{} name=\"{}\" value=\"0x{:x}\" />\n", child, escape_attr(&symbol_name), symbol_value));
+    }
+    out.push_str("    </attr>\n");
+    Ok(out)
+}
+
+fn render_style(name: &str, body: Value<'_>) -> Result<String> {
+    let style = proto::fields(body.as_bytes())?;
+
+    let mut out = format!("    <style name=\"{}\"", escape_attr(name));
+    if let Some(parent) = field(&style, 1) {
+        out.push_str(&format!(" parent=\"{}\"", escape_attr(&format_reference(parent.as_bytes())?)));
+    }
+    out.push_str(">\n");
+
+    for entry in repeated(&style, 3) {
+        let entry = proto::fields(entry.as_bytes())?;
+        let key = match field(&entry, 3) {
+            Some(reference) => attr_name(&format_reference(reference.as_bytes())?),
+            None => continue,
+        };
+        let value = match field(&entry, 4) {
+            Some(item) => format_item(item.as_bytes())?,
+            None => String::new(),
+        };
+        out.push_str(&format!("        <item name=\"{}\">{}</item>\n", escape_attr(&key), escape_text(&value)));
+    }
+
+    out.push_str("    </style>\n");
+    Ok(out)
+}
+
+fn render_styleable(name: &str, body: Value<'_>) -> Result<String> {
+    let styleable = proto::fields(body.as_bytes())?;
+
+    let mut out = format!("    <declare-styleable name=\"{}\">\n", escape_attr(name));
+    for entry in repeated(&styleable, 1) {
+        let entry = proto::fields(entry.as_bytes())?;
+        if let Some(reference) = field(&entry, 3) {
+            let attr = attr_name(&format_reference(reference.as_bytes())?);
+            out.push_str(&format!("        <attr name=\"{}\" />\n", escape_attr(&attr)));
+        }
+    }
+    out.push_str("    </declare-styleable>\n");
+    Ok(out)
+}
+
+fn render_array(name: &str, body: Value<'_>) -> Result<String> {
+    let array = proto::fields(body.as_bytes())?;
+
+    let mut out = format!("    <array name=\"{}\">\n", escape_attr(name));
+    for element in repeated(&array, 1) {
+        let element = proto::fields(element.as_bytes())?;
+        let value = match field(&element, 3) {
+            Some(item) => format_item(item.as_bytes())?,
+            None => String::new(),
+        };
+        out.push_str(&format!("        <item>{}</item>\n", escape_string(&value)));
+    }
+    out.push_str("    </array>\n");
+    Ok(out)
+}
+
+fn render_plurals(name: &str, body: Value<'_>) -> Result<String> {
+    let plural = proto::fields(body.as_bytes())?;
+
+    let mut out = format!("    <plurals name=\"{}\">\n", escape_attr(name));
+    for entry in repeated(&plural, 1) {
+        let entry = proto::fields(entry.as_bytes())?;
+        let arity = field(&entry, 3).map_or(0, |v| v.as_u64() as usize);
+        let quantity = PLURAL_QUANTITIES.get(arity).copied().unwrap_or("other");
+        let value = match field(&entry, 4) {
+            Some(item) => format_item(item.as_bytes())?,
+            None => String::new(),
+        };
+        out.push_str(&format!("        <item quantity=\"{}\">{}</item>\n", quantity, escape_string(&value)));
+    }
+    out.push_str("    </plurals>\n");
+    Ok(out)
+}
+
+/// Resource qualifiers of an aapt2 `Configuration`, e.g. `fr-rCA-night-xxhdpi-v26`
+///
+/// Covers the qualifiers apps commonly use; rarer ones are left out of the directory name.
+pub fn qualifiers(data: &[u8]) -> Result<String> {
+    let config = proto::fields(data)?;
+    let number = |n: u32| field(&config, n).map_or(0, |v| v.as_u32());
+    let mut parts = Vec::new();
+
+    if number(1) != 0 {
+        parts.push(format!("mcc{}", number(1)));
+    }
+    if number(2) != 0 {
+        parts.push(format!("mnc{:02}", number(2)));
+    }
+    if let Some(locale) = field(&config, 3).map(|v| v.as_str()).filter(|l| !l.is_empty()) {
+        parts.push(locale_qualifier(&locale));
+    }
+    match number(4) {
+        1 => parts.push("ldltr".to_string()),
+        2 => parts.push("ldrtl".to_string()),
+        _ => {}
+    }
+    if number(9) != 0 {
+        parts.push(format!("sw{}dp", number(9)));
+    }
+    if number(7) != 0 {
+        parts.push(format!("w{}dp", number(7)));
+    }
+    if number(8) != 0 {
+        parts.push(format!("h{}dp", number(8)));
+    }
+    if let Some(size) = ["", "small", "normal", "large", "xlarge"].get(number(10) as usize).filter(|s| !s.is_empty()) {
+        parts.push(size.to_string());
+    }
+    if let Some(orientation) = ["", "port", "land", "square"].get(number(15) as usize).filter(|s| !s.is_empty()) {
+        parts.push(orientation.to_string());
+    }
+    if let Some(ui_mode) = ["", "", "desk", "car", "television", "appliance", "watch", "vrheadset"].get(number(16) as usize).filter(|s| !s.is_empty()) {
+        parts.push(ui_mode.to_string());
+    }
+    match number(17) {
+        1 => parts.push("night".to_string()),
+        2 => parts.push("notnight".to_string()),
+        _ => {}
+    }
+    if number(18) != 0 {
+        parts.push(density_qualifier(number(18)));
+    }
+    if number(24) != 0 {
+        parts.push(format!("v{}", number(24)));
+    }
+
+    Ok(parts.join("-"))
+}
+
+/// BCP-47 `en-US` becomes `en-rUS`; anything with a script or variant uses the `b+` form
+pub(crate) fn locale_qualifier(locale: &str) -> String {
+    let parts: Vec<&str> = locale.split(['-', '_']).collect();
+    match parts.as_slice() {
+        [language] => language.to_string(),
+        [language, region] if region.len() == 2 || region.chars().all(|c| c.is_ascii_digit()) => {
+            format!("{}-r{}", language, region)
+        }
+        _ => format!("b+{}", parts.join("+")),
+    }
+}
+
+pub(crate) fn density_qualifier(density: u32) -> String {
+    match density {
+        120 => "ldpi".to_string(),
+        160 => "mdpi".to_string(),
+        213 => "tvdpi".to_string(),
+        240 => "hdpi".to_string(),
+        320 => "xhdpi".to_string(),
+        480 => "xxhdpi".to_string(),
+        640 => "xxxhdpi".to_string(),
+        0xfffe => "anydpi".to_string(),
+        0xffff => "nodpi".to_string(),
+        other => format!("{}dpi", other),
+    }
+}
+
+fn attr_formats(flags: u32) -> String {
+    const NAMES: [(u32, &str); 8] = [
+        (1, "reference"),
+        (1 << 1, "string"),
+        (1 << 2, "integer"),
+        (1 << 3, "boolean"),
+        (1 << 4, "color"),
+        (1 << 5, "float"),
+        (1 << 6, "dimension"),
+        (1 << 7, "fraction"),
+    ];
+    NAMES.iter().filter(|(bit, _)| flags & bit != 0).map(|(_, name)| *name).collect::<Vec<_>>().join("|")
+}
+
+fn values_file(type_name: &str) -> String {
+    match type_name {
+        "plurals" => "plurals.xml".to_string(),
+        "styleable" => "attrs.xml".to_string(),
+        other => format!("{}s.xml", other),
+    }
+}
+
+/// `@android:attr/textColor` → `android:textColor`
+fn attr_name(reference: &str) -> String {
+    let reference = reference.trim_start_matches(['@', '?']);
+    match reference.split_once(':') {
+        Some((package, rest)) => format!("{}:{}", package, entry_name(rest)),
+        None => entry_name(reference),
+    }
+}
+
+/// `@id/horizontal` → `horizontal`
+fn entry_name(reference: &str) -> String {
+    reference.rsplit('/').next().unwrap_or(reference).to_string()
+}
+
+pub(crate) fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub(crate) fn escape_attr(text: &str) -> String {
+    escape_text(text).replace('"', "&quot;")
+}
+
+/// String resources also need quotes escaped and a leading `@` or `?` kept literal
+fn escape_string(text: &str) -> String {
+    let escaped = escape_text(text).replace('\\', "\\\\").replace('\'', "\\'").replace('"', "\\\"");
+    if escaped.starts_with(['@', '?']) {
+        format!("\\{}", escaped)
+    } else {
+        escaped
+    }
+}
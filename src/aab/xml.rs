@@ -0,0 +1,108 @@
+//! aapt2 `XmlNode` (manifest and `res/**/*.xml` in a bundle) to XML text
+
+use super::proto::{self, field, repeated};
+use super::resources::{escape_attr, escape_text, format_item};
+use crate::Result;
+
+const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+/// Render an encoded `XmlNode` as an XML document
+pub fn decode(data: &[u8]) -> Result<String> {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let mut namespaces = Vec::new();
+    write_node(data, 0, &mut namespaces, &mut out)?;
+    Ok(out)
+}
+
+/// `namespaces` holds the `(prefix, uri)` declarations in scope, innermost last
+fn write_node(data: &[u8], depth: usize, namespaces: &mut Vec<(String, String)>, out: &mut String) -> Result<()> {
+    let node = proto::fields(data)?;
+
+    if let Some(element) = field(&node, 1) {
+        write_element(element.as_bytes(), depth, namespaces, out)?;
+    } else if let Some(text) = field(&node, 2) {
+        let text = text.as_str();
+        if !text.trim().is_empty() {
+            out.push_str(&"    ".repeat(depth));
+            out.push_str(&escape_text(text.trim()));
+            out.push('\n');
+        }
+    }
+
+    Ok(())
+}
+
+fn write_element(data: &[u8], depth: usize, namespaces: &mut Vec<(String, String)>, out: &mut String) -> Result<()> {
+    let element = proto::fields(data)?;
+    let indent = "    ".repeat(depth);
+
+    let mut declared = Vec::new();
+    for namespace in repeated(&element, 1) {
+        let namespace = proto::fields(namespace.as_bytes())?;
+        let prefix = field(&namespace, 1).map(|v| v.as_str()).unwrap_or_default();
+        let uri = field(&namespace, 2).map(|v| v.as_str()).unwrap_or_default();
+        declared.push((prefix, uri));
+    }
+    let scope = namespaces.len();
+    namespaces.extend(declared.iter().cloned());
+
+    let text = |number: u32, fields: &[(u32, proto::Value<'_>)]| field(fields, number).map(|v| v.as_str()).unwrap_or_default();
+    let name = qualified(namespaces, &text(2, &element), &text(3, &element));
+
+    out.push_str(&indent);
+    out.push('<');
+    out.push_str(&name);
+
+    for (prefix, uri) in &declared {
+        if prefix.is_empty() {
+            out.push_str(&format!(" xmlns=\"{}\"", escape_attr(uri)));
+        } else {
+            out.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_attr(uri)));
+        }
+    }
+
+    for attribute in repeated(&element, 4) {
+        let attribute = proto::fields(attribute.as_bytes())?;
+        let mut value = text(3, &attribute);
+        // The source text is usually kept; fall back to the compiled value
+        if value.is_empty() {
+            if let Some(item) = field(&attribute, 6) {
+                value = format_item(item.as_bytes())?;
+            }
+        }
+        let attribute_name = qualified(namespaces, &text(1, &attribute), &text(2, &attribute));
+        out.push_str(&format!(" {}=\"{}\"", attribute_name, escape_attr(&value)));
+    }
+
+    let children: Vec<_> = repeated(&element, 5).collect();
+    if children.is_empty() {
+        out.push_str(" />\n");
+    } else {
+        out.push_str(">\n");
+        for child in children {
+            write_node(child.as_bytes(), depth + 1, namespaces, out)?;
+        }
+        out.push_str(&format!("{}</{}>\n", indent, name));
+    }
+
+    namespaces.truncate(scope);
+    Ok(())
+}
+
+fn qualified(namespaces: &[(String, String)], uri: &str, name: &str) -> String {
+    if uri.is_empty() {
+        return name.to_string();
+    }
+
+    let prefix = namespaces
+        .iter()
+        .rev()
+        .find(|(_, declared)| declared == uri)
+        .map(|(prefix, _)| prefix.as_str())
+        .or((uri == ANDROID_NAMESPACE).then_some("android"));
+
+    match prefix {
+        Some(prefix) if !prefix.is_empty() => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
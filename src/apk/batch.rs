@@ -0,0 +1,106 @@
+use super::extractor::Extractor;
+use super::report::UnpackReport;
+use super::splits::ContainerKind;
+use crate::{ApkextError, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Result of unpacking one APK of a batch
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub apk: PathBuf,
+    pub result: std::result::Result<UnpackReport, ApkextError>,
+}
+
+/// APK paths named by `inputs`: files as given, the APKs and split containers directly
+/// inside directories, and the matches of glob patterns such as `apks/*.apk`
+///
+/// Paths are deduplicated and keep the order of `inputs`. A path that does not exist
+/// and is not a pattern is kept, so it fails on its own when unpacked.
+pub fn collect_apks(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut apks = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && (matches!(path.extension().and_then(|s| s.to_str()), Some("apk" | "aab")) || ContainerKind::from_path(path).is_some()))
+                .collect();
+            if found.is_empty() {
+                anyhow::bail!("No APK files in directory '{}'", input);
+            }
+            found.sort();
+            apks.extend(found);
+        } else if !path.exists() && input.contains(['*', '?', '[']) {
+            let mut found = Vec::new();
+            for entry in glob::glob(input)? {
+                found.push(entry?);
+            }
+            if found.is_empty() {
+                anyhow::bail!("No files match '{}'", input);
+            }
+            apks.extend(found);
+        } else {
+            apks.push(path.to_path_buf());
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    apks.retain(|apk| seen.insert(apk.clone()));
+
+    Ok(apks)
+}
+
+/// Unpack `apks` with at most `jobs` running at once; `extractor_for` sets up the extractor of each APK
+///
+/// Outcomes are returned in the order of `apks`. Fails before starting anything if two APKs
+/// would unpack into the same directory.
+pub async fn unpack_all<F>(apks: Vec<PathBuf>, jobs: usize, extractor_for: F) -> Result<Vec<BatchOutcome>>
+where
+    F: Fn(&Path) -> Extractor,
+{
+    let extractors: Vec<(PathBuf, Extractor)> = apks
+        .into_iter()
+        .map(|apk| {
+            let extractor = extractor_for(&apk);
+            (apk, extractor)
+        })
+        .collect();
+
+    let mut targets: HashMap<PathBuf, &Path> = HashMap::new();
+    for (apk, extractor) in &extractors {
+        if let Some(other) = targets.insert(extractor.extract_dir(apk), apk) {
+            anyhow::bail!(
+                "'{}' and '{}' would both unpack into '{}'",
+                other.display(),
+                apk.display(),
+                extractor.extract_dir(apk).display()
+            );
+        }
+    }
+
+    let permits = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, (apk, extractor)) in extractors.into_iter().enumerate() {
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("Semaphore is never closed");
+            let result = extractor.unpack(&apk.to_string_lossy()).await;
+            (index, BatchOutcome { apk, result })
+        });
+    }
+
+    let mut outcomes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        outcomes.push(joined?);
+    }
+    outcomes.sort_by_key(|(index, _)| *index);
+
+    Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+}
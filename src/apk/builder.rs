@@ -1,35 +1,64 @@
-use crate::{config::Config, tools::ToolManager, assets::AssetManager, Result};
-use std::path::Path;
+use crate::aab;
+use crate::{config::Config, tools::{ProgressHandler, ToolManager}, assets::AssetManager, zipalign, ApkextError, Result};
+use crate::signing::{SignOptions, Signer};
+use super::report::{timed, PackReport, SigningReport};
+use super::splits::{self, ContainerKind, SplitSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 pub struct Builder {
     tool_manager: ToolManager,
     asset_manager: AssetManager,
+    signing: Option<SignOptions>,
 }
 
 impl Builder {
-    pub fn new(config: Config) -> Self {
-        let tool_manager = ToolManager::new(config);
-        let asset_manager = AssetManager::new().expect("Failed to create asset manager");
-        Self { tool_manager, asset_manager }
+    /// The built APK is signed with the debug key unless `with_signing` says otherwise
+    pub fn new(config: Config, asset_manager: AssetManager) -> Self {
+        let tool_manager = ToolManager::new(config, asset_manager.clone());
+        Self { tool_manager, asset_manager, signing: Some(SignOptions::default()) }
     }
 
-    pub async fn pack(&self, unpacked_dir: &str, output_apk: &str) -> Result<()> {
+    /// Deliver step, message and tool output events to `progress` instead of printing them
+    pub fn with_progress(mut self, progress: Option<ProgressHandler>) -> Self {
+        self.tool_manager = self.tool_manager.with_progress(progress);
+        self
+    }
+
+    /// Set how `pack` signs its output; `None` leaves the APK unsigned
+    pub fn with_signing(mut self, signing: Option<SignOptions>) -> Self {
+        self.signing = signing;
+        self
+    }
+
+    pub async fn pack(&self, unpacked_dir: &str, output_apk: &str) -> Result<PackReport, ApkextError> {
+        let started = Instant::now();
         let unpacked_dir = Path::new(unpacked_dir);
         let output_apk = Path::new(output_apk);
 
         // Validate input
         if !unpacked_dir.exists() {
-            return Err(anyhow::anyhow!("Unpacked directory does not exist: {}", unpacked_dir.display()));
+            return Err(invalid_project(unpacked_dir, "directory does not exist".to_string()));
         }
 
-        if output_apk.extension().and_then(|s| s.to_str()) != Some("apk") {
-            return Err(anyhow::anyhow!("Output file must have .apk extension"));
+        if let Some(set) = SplitSet::load(unpacked_dir)? {
+            return self.pack_split_set(unpacked_dir, output_apk, set, started).await;
+        }
+
+        let bundle_dir = unpacked_dir.join(aab::BUNDLE_DIR);
+        if bundle_dir.join(aab::BUNDLE_CONFIG).exists() {
+            return self.pack_bundle(unpacked_dir, &bundle_dir, output_apk, started).await;
+        }
+        if unpacked_dir.join(aab::BUNDLE_CONFIG).exists() {
+            return self.pack_bundle(unpacked_dir, unpacked_dir, output_apk, started).await;
         }
 
-        println!("[+] Building APK from '{}' to '{}'", unpacked_dir.display(), output_apk.display());
+        if output_apk.extension().and_then(|s| s.to_str()) != Some("apk") {
+            return Err(anyhow::anyhow!("Output file must have .apk extension").into());
+        }
 
-        // Get platform-specific aapt path through asset manager
-        let aapt_path = self.get_aapt_path();
+        self.tool_manager.report(&format!("[+] Building APK from '{}' to '{}'", unpacked_dir.display(), output_apk.display()));
+        let mut report = PackReport::new(unpacked_dir, output_apk);
 
         // Run apktool to build APK
         let unpacked_path = unpacked_dir.join("unpacked");
@@ -38,17 +67,167 @@ impl Builder {
         } else if unpacked_dir.join("apktool.yml").exists() {
             unpacked_dir.to_path_buf()
         } else {
-            return Err(anyhow::anyhow!("apktool.yml not found in {} or {}", unpacked_dir.display(), unpacked_path.display()));
+            return Err(invalid_project(unpacked_dir, format!("apktool.yml not found in it or in {}", unpacked_path.display())));
         };
 
+        let plan: &[&str] = if self.signing.is_some() { &["build", "align", "sign"] } else { &["build", "align"] };
+
+        self.tool_manager.step(plan, "build");
+        timed(&mut report.steps, "build", self.build_apk(&final_unpacked_path, output_apk)).await?;
+
+        // Signing keeps the alignment, so this also holds for the signed APK
+        self.tool_manager.step(plan, "align");
+        self.tool_manager.report("[+] Aligning APK");
+        timed(&mut report.steps, "align", async { zipalign::align_file(output_apk) }).await?;
+
+        if let Some(options) = &self.signing {
+            self.tool_manager.report(&format!("[+] Signing APK ({})", options.scheme_names().join(", ")));
+            self.tool_manager.step(plan, "sign");
+            let signing = timed(&mut report.steps, "sign", async { self.sign(options, &[output_apk.to_path_buf()]) }).await?;
+            report.signing = Some(signing);
+        }
+
+        report.total_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Rebuild the base and every split, sign them all with the same key and package them like the original container
+    async fn pack_split_set(&self, unpacked_dir: &Path, output: &Path, set: SplitSet, started: Instant) -> Result<PackReport, ApkextError> {
+        let as_container = ContainerKind::from_path(output).is_some();
+        if !as_container && output.extension().is_some() {
+            return Err(anyhow::anyhow!("A split set is packed into an .apks, .xapk or .apkm file, or into a directory").into());
+        }
+
+        self.tool_manager.report(&format!("[+] Building base and {} splits from '{}' to '{}'", set.splits.len(), unpacked_dir.display(), output.display()));
+        let mut report = PackReport::new(unpacked_dir, output);
+        report.splits = set.splits.iter().map(|split| split.name.clone()).collect();
+
+        // Each project directory and the path of its APK as laid out in the container
+        let apks_dir = tempfile::tempdir()?;
+        let mut projects = vec![(unpacked_dir.join("unpacked"), apks_dir.path().join(&set.base))];
+        for split in &set.splits {
+            projects.push((unpacked_dir.join(splits::SPLITS_DIR).join(&split.name), apks_dir.path().join(&split.entry)));
+        }
+        for (project, _) in &projects {
+            if !project.join("apktool.yml").exists() {
+                return Err(invalid_project(unpacked_dir, format!("apktool.yml not found in {}", project.display())));
+            }
+        }
+        let apks: Vec<PathBuf> = projects.iter().map(|(_, apk)| apk.clone()).collect();
+
+        let mut plan = vec!["build", "align"];
+        if self.signing.is_some() {
+            plan.push("sign");
+        }
+        plan.push("package");
+
+        self.tool_manager.step(&plan, "build");
+        timed(&mut report.steps, "build", async {
+            for (project, apk) in &projects {
+                if let Some(parent) = apk.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                self.build_apk(project, apk).await?;
+            }
+            Ok::<_, ApkextError>(())
+        }).await?;
+
+        self.tool_manager.step(&plan, "align");
+        self.tool_manager.report("[+] Aligning APKs");
+        timed(&mut report.steps, "align", async { apks.iter().try_for_each(|apk| zipalign::align_file(apk)) }).await?;
+
+        // Android installs a split set only if every APK carries the same certificate
+        if let Some(options) = &self.signing {
+            self.tool_manager.report(&format!("[+] Signing APKs ({})", options.scheme_names().join(", ")));
+            self.tool_manager.step(&plan, "sign");
+            let signing = timed(&mut report.steps, "sign", async { self.sign(options, &apks) }).await?;
+            report.signing = Some(signing);
+        }
+
+        self.tool_manager.step(&plan, "package");
+        timed(&mut report.steps, "package", async {
+            if as_container {
+                return splits::write_container(&set, apks_dir.path(), &unpacked_dir.join(splits::CONTAINER_DIR), output);
+            }
+
+            // A directory of APKs, ready for `adb install-multiple`
+            std::fs::create_dir_all(output)?;
+            for apk in &apks {
+                std::fs::copy(apk, output.join(apk.file_name().unwrap_or_default()))?;
+            }
+            Ok(())
+        }).await?;
+
+        report.total_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Zip the bundle directory back into an `.aab`; `decoded/` is for reading only and must be unchanged
+    async fn pack_bundle(&self, unpacked_dir: &Path, bundle_dir: &Path, output: &Path, started: Instant) -> Result<PackReport, ApkextError> {
+        if output.extension().and_then(|s| s.to_str()) != Some("aab") {
+            return Err(anyhow::anyhow!("An app bundle is packed into an .aab file").into());
+        }
+
+        // The XML is not compiled back to protobuf, so edits there would silently be lost
+        let changes = aab::decoded_changes(bundle_dir, &unpacked_dir.join(aab::DECODED_DIR))?;
+        if !changes.is_empty() {
+            let changes: Vec<String> = changes.iter().map(|change| format!("{}/{}", aab::DECODED_DIR, change.display())).collect();
+            return Err(anyhow::anyhow!(
+                "Edits in {}/ cannot be packed into an app bundle, only the protobuf files in {}/ are; undo them or make them in {}/: {}",
+                aab::DECODED_DIR,
+                aab::BUNDLE_DIR,
+                aab::BUNDLE_DIR,
+                changes.join(", ")
+            )
+            .into());
+        }
+
+        self.tool_manager.report(&format!("[+] Packaging app bundle from '{}' to '{}'", bundle_dir.display(), output.display()));
+        let mut report = PackReport::new(unpacked_dir, output);
+
+        let plan: &[&str] = if self.signing.is_some() { &["package", "sign"] } else { &["package"] };
+
+        self.tool_manager.step(plan, "package");
+        timed(&mut report.steps, "package", async { aab::pack(bundle_dir, output) }).await?;
+
+        // Bundles are uploaded to a store, which only checks the JAR signature
+        if let Some(options) = &self.signing {
+            let options = SignOptions { key: options.key.clone(), v1: true, v2: false, v3: false };
+            self.tool_manager.report("[+] Signing app bundle (v1)");
+            self.tool_manager.step(plan, "sign");
+            let signing = timed(&mut report.steps, "sign", async { self.sign(&options, &[output.to_path_buf()]) }).await?;
+            report.signing = Some(signing);
+        }
+
+        report.total_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Run `apktool b` on the project in `project_dir`
+    async fn build_apk(&self, project_dir: &Path, output_apk: &Path) -> Result<(), ApkextError> {
+        // Get platform-specific aapt path through asset manager
+        let aapt_path = self.get_aapt_path();
+
         self.tool_manager.run_apktool(&[
             "b",
             "-aapt", &aapt_path,
-            &final_unpacked_path.to_string_lossy(),
+            &project_dir.to_string_lossy(),
             "-o", &output_apk.to_string_lossy()
         ]).await
     }
 
+    fn sign(&self, options: &SignOptions, apks: &[PathBuf]) -> Result<SigningReport> {
+        let signer = Signer::new(options, &self.asset_manager.get_keys_path())?;
+        for apk in apks {
+            signer.sign_file(apk)?;
+        }
+
+        Ok(SigningReport {
+            schemes: options.scheme_names(),
+            certificate_sha256: signer.key().certificate_sha256(),
+        })
+    }
+
     fn get_aapt_path(&self) -> String {
         use std::env;
 
@@ -63,4 +242,8 @@ impl Builder {
 
         self.asset_manager.get_script_path(relative_path).to_string_lossy().to_string()
     }
-}
\ No newline at end of file
+}
+
+fn invalid_project(path: &Path, reason: String) -> ApkextError {
+    ApkextError::InvalidProject { path: path.to_path_buf(), reason }
+}
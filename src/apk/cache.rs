@@ -0,0 +1,203 @@
+use super::info::format_size;
+use crate::tools::Decompiler;
+use crate::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+/// Size limit of the cache unless `APKEXT_CACHE_MAX_SIZE` sets another one
+pub const DEFAULT_MAX_SIZE: u64 = 2 << 30;
+
+/// Decompiled sources keyed by the bytecode they came from, shared by every unpack
+///
+/// A source file is stored under the SHA-256 of its class and nested classes, separately for
+/// each decompiler version, so a library class already seen in another APK is not decompiled
+/// again. The least recently used sources are evicted once the cache exceeds its size limit.
+#[derive(Debug, Clone)]
+pub struct DecompileCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+/// Number and total size of the cached sources
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub dir: PathBuf,
+    pub entries: usize,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+/// Sources evicted from the cache
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheEviction {
+    pub removed: usize,
+    pub freed: u64,
+}
+
+impl DecompileCache {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self { dir: dir.into(), max_size }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cached source for `key`; marks it as recently used
+    pub fn get(&self, decompiler: Decompiler, key: &str) -> Option<String> {
+        let path = self.entry_path(decompiler, key);
+        let source = fs::read_to_string(&path).ok()?;
+
+        // Eviction goes by modification time, so a hit counts as a use
+        if let Ok(file) = File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(source)
+    }
+
+    /// Store the source `decompiler` produced for `key`
+    pub fn put(&self, decompiler: Decompiler, key: &str, source: &str) -> Result<()> {
+        let path = self.entry_path(decompiler, key);
+        let parent = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent)?;
+
+        // Concurrent unpacks may store the same class; a rename never leaves a partial file
+        let temp = tempfile::NamedTempFile::new_in(parent)?;
+        fs::write(temp.path(), source)?;
+        temp.persist(&path)?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let entries = self.entries()?;
+        Ok(CacheStats {
+            dir: self.dir.clone(),
+            entries: entries.len(),
+            size: entries.iter().map(|(_, size, _)| size).sum(),
+            max_size: self.max_size,
+        })
+    }
+
+    /// Evict the least recently used sources until the cache fits its size limit
+    pub fn trim(&self) -> Result<CacheEviction> {
+        self.trim_to(self.max_size)
+    }
+
+    /// Evict the least recently used sources until the cache holds at most `size` bytes
+    pub fn trim_to(&self, size: u64) -> Result<CacheEviction> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        let mut eviction = CacheEviction::default();
+        entries.sort_by_key(|(_, _, used)| *used);
+
+        for (path, entry_size, _) in entries {
+            if total <= size {
+                break;
+            }
+            // Another unpack may have evicted it already
+            if fs::remove_file(&path).is_ok() {
+                eviction.removed += 1;
+                eviction.freed += entry_size;
+            }
+            total -= entry_size;
+        }
+        Ok(eviction)
+    }
+
+    /// Remove every cached source
+    pub fn clear(&self) -> Result<CacheEviction> {
+        let eviction = self.trim_to(0)?;
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(eviction)
+    }
+
+    /// `<decompiler jar>/<first two hex digits>/<key>.java`; a new decompiler version starts afresh
+    fn entry_path(&self, decompiler: Decompiler, key: &str) -> PathBuf {
+        let version = decompiler.jar_name().trim_end_matches(".jar");
+        self.dir.join(version).join(key.get(..2).unwrap_or(key)).join(format!("{}.java", key))
+    }
+
+    /// Path, size and last use of every cached source
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        if !self.dir.exists() {
+            return Ok(entries);
+        }
+
+        for entry in WalkDir::new(&self.dir) {
+            let entry = entry?;
+            if !entry.file_type().is_file() || entry.path().extension() != Some("java".as_ref()) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            entries.push((entry.into_path(), metadata.len(), metadata.modified()?));
+        }
+        Ok(entries)
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Location: {}", self.dir.display())?;
+        writeln!(f, "Sources:  {}", self.entries)?;
+        writeln!(f, "Size:     {} of {}", format_size(self.size), format_size(self.max_size))
+    }
+}
+
+/// Cache key of every top-level source in `jar`: source path → SHA-256 of its classes
+///
+/// The key covers the names and bytecode of the class and all of its nested classes, which
+/// the decompilers write into the same source file.
+pub fn source_keys(jar: &Path) -> Result<BTreeMap<String, String>> {
+    let mut archive = ZipArchive::new(File::open(jar)?)?;
+
+    let mut names: Vec<String> = archive.file_names().filter(|name| name.ends_with(".class")).map(str::to_string).collect();
+    names.sort();
+
+    let mut hashers: BTreeMap<String, Sha256> = BTreeMap::new();
+    for name in names {
+        let class = name.trim_end_matches(".class");
+        let outer = class.split('$').next().unwrap_or(class);
+        if outer.ends_with("package-info") || outer.ends_with("module-info") {
+            continue;
+        }
+
+        let mut bytecode = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut bytecode)?;
+
+        let hasher = hashers.entry(format!("{}.java", outer)).or_default();
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((bytecode.len() as u64).to_le_bytes());
+        hasher.update(&bytecode);
+    }
+
+    Ok(hashers
+        .into_iter()
+        .map(|(source, hasher)| (source, hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()))
+        .collect())
+}
+
+/// Parse a size such as `500M`, `2G`, `64k` or a plain number of bytes
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match text[digits.len()..].to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return None,
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(unit)
+}
@@ -0,0 +1,95 @@
+use crate::tools::Decompiler;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::File;
+use std::path::Path;
+use zip::{ZipArchive, ZipWriter};
+
+/// Per-file decompiler report written next to `src/`
+pub const REPORT_FILE: &str = "decompilers.json";
+
+/// Comments and stubs the decompilers leave behind when they give up on a method or class
+const FAILURE_MARKERS: &[&str] = &[
+    // Procyon
+    "This method could not be decompiled",
+    "An error occurred while decompiling this method",
+    // CFR
+    "Exception decompiling",
+    "Decompilation failed",
+    // Fernflower and Vineflower
+    "Couldn't be decompiled",
+    // JADX
+    "Method dump skipped",
+    "Code decompiled incorrectly",
+];
+
+/// Which decompiler produced each file under `src/`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecompilerReport {
+    pub primary: Decompiler,
+    pub fallback: Option<Decompiler>,
+    /// Source path relative to `src/` → decompiler whose output was kept
+    pub files: BTreeMap<String, Decompiler>,
+    /// Sources that are still missing or incomplete after the fallback
+    pub failed: Vec<String>,
+}
+
+impl DecompilerReport {
+    /// The report `unpack` left in `dir`, if any
+    pub fn load(dir: &Path) -> Option<Self> {
+        let data = std::fs::read_to_string(dir.join(REPORT_FILE)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Drop every entry that is not one of `sources`
+    pub fn retain_sources(&mut self, sources: &BTreeSet<String>) {
+        self.files.retain(|source, _| sources.contains(source));
+        self.failed.retain(|source| sources.contains(source));
+    }
+}
+
+/// Whether decompiled source is missing parts of the class
+pub fn looks_failed(source: &str) -> bool {
+    source.trim().is_empty() || FAILURE_MARKERS.iter().any(|marker| source.contains(marker))
+}
+
+/// Top-level classes in a JAR as source paths, e.g. `com/example/Foo.java`
+///
+/// Nested classes are left out: they end up in the file of their outer class.
+pub fn top_level_sources(jar: &Path) -> Result<Vec<String>> {
+    let archive = ZipArchive::new(File::open(jar)?)?;
+
+    let mut sources: Vec<String> = archive
+        .file_names()
+        .filter_map(|name| name.strip_suffix(".class"))
+        .filter(|class| !class.contains('$') && !class.ends_with("package-info") && !class.ends_with("module-info"))
+        .map(|class| format!("{}.java", class))
+        .collect();
+    sources.sort();
+
+    Ok(sources)
+}
+
+/// Copy the classes behind `sources`, including their nested classes, into a new JAR
+pub fn write_subset_jar(jar: &Path, sources: &[String], output: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(jar)?)?;
+    let mut writer = ZipWriter::new(File::create(output)?);
+
+    let prefixes: HashSet<&str> = sources.iter().filter_map(|source| source.strip_suffix(".java")).collect();
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let keep = match file.name().strip_suffix(".class") {
+            Some(class) => prefixes.contains(class.split('$').next().unwrap_or(class)),
+            None => false,
+        };
+
+        if keep {
+            writer.raw_copy_file(file)?;
+        }
+    }
+
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,583 @@
+//! What changed between two versions of an app, given as two APKs or two `unpack` output directories
+//!
+//! Both sides are read into a `Snapshot` first: the manifest, a digest of every class and
+//! method in the DEX files (or in apktool's smali for directories), every resource value per
+//! configuration, digests of resource files, native libraries and assets, and the signing
+//! certificates.
+
+use super::extractor::{dex_index, smali_dirs};
+use crate::arsc::{ResourceTable, RESOURCES_ENTRY};
+use crate::dex::{self, DexFile};
+use crate::manifest::{axml, xml, Component, Element, Manifest, MANIFEST_ENTRY};
+use crate::signing::{self, CertificateInfo};
+use crate::{ApkextError, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+/// What `apkext diff` reports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApkDiff {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    /// Package, version, SDK levels and `debuggable`, where they differ
+    pub manifest: Vec<FieldChange>,
+    /// Requested permissions, and the app's own ones marked `(declared)`
+    pub permissions: Changes,
+    /// `activity com.example.MainActivity`; changed when `exported`, the permission,
+    /// the authorities or the intent filters differ
+    pub components: Changes,
+    /// Java class names; changed when the superclass, interfaces, access flags or any method differ
+    pub classes: Changes,
+    /// `com.example.MainActivity.onCreate(Landroid/os/Bundle;)V`, for classes on both sides
+    pub methods: Changes,
+    /// Values as `string/app_name (de, fr)`, listing the configurations that differ, and files as `res/layout/main.xml`
+    pub resources: Changes,
+    /// `lib/arm64-v8a/libapp.so`
+    pub native_libraries: Changes,
+    /// `assets/config.json`
+    pub assets: Changes,
+    /// `subject (SHA-256 fingerprint)`
+    pub certificates: Changes,
+    /// Parts that could not be compared, e.g. because one side has no DEX files
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// `Manifest` field name, e.g. `version_name`
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Compare two maps key by key; keys come out sorted
+    fn between<V: PartialEq>(old: &BTreeMap<String, V>, new: &BTreeMap<String, V>) -> Self {
+        let mut changes = Changes::default();
+        for (key, value) in new {
+            match old.get(key) {
+                None => changes.added.push(key.clone()),
+                Some(old_value) if old_value != value => changes.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = old.keys().filter(|key| !new.contains_key(*key)).cloned().collect();
+        changes
+    }
+}
+
+impl ApkDiff {
+    /// Compare two APKs, or two directories written by `unpack` (or by apktool)
+    pub fn compare(old: &Path, new: &Path) -> Result<Self, ApkextError> {
+        if old.is_dir() != new.is_dir() {
+            return Err(ApkextError::Other(anyhow::anyhow!(
+                "Cannot compare '{}' with '{}': give two APKs or two unpacked directories",
+                old.display(),
+                new.display()
+            )));
+        }
+
+        let read = |path: &Path| if path.is_dir() { Snapshot::from_dir(path) } else { Snapshot::from_apk(path) };
+        let (old_snapshot, new_snapshot) = (read(old)?, read(new)?);
+
+        let mut diff = ApkDiff { old: old.to_path_buf(), new: new.to_path_buf(), ..ApkDiff::default() };
+        diff.compare_manifests(&old_snapshot.manifest, &new_snapshot.manifest);
+
+        match (&old_snapshot.classes, &new_snapshot.classes) {
+            (Some(old_classes), Some(new_classes)) if old_snapshot.code == new_snapshot.code => diff.compare_classes(old_classes, new_classes),
+            (Some(_), Some(_)) => diff.notes.push(format!(
+                "Classes of '{}' come from {} but those of '{}' from {}; classes and methods were not compared",
+                old.display(),
+                old_snapshot.code,
+                new.display(),
+                new_snapshot.code
+            )),
+            _ => {
+                let missing = if old_snapshot.classes.is_none() { old } else { new };
+                diff.notes.push(format!("No DEX files or smali in '{}'; classes and methods were not compared", missing.display()));
+            }
+        }
+
+        diff.resources = Changes::between(&old_snapshot.resources, &new_snapshot.resources);
+        for name in &mut diff.resources.changed {
+            let configs: Vec<&str> = changed_configs(&old_snapshot.resources[name.as_str()], &new_snapshot.resources[name.as_str()]);
+            *name = format!("{} ({})", name, configs.iter().map(|config| if config.is_empty() { "default" } else { config }).collect::<Vec<_>>().join(", "));
+        }
+        let files = |prefix: &str, snapshot: &Snapshot| -> BTreeMap<String, [u8; 32]> {
+            snapshot.files.iter().filter(|(path, _)| path.starts_with(prefix)).map(|(path, digest)| (path.clone(), *digest)).collect()
+        };
+        let resource_files = Changes::between(&files("res/", &old_snapshot), &files("res/", &new_snapshot));
+        diff.resources.added.extend(resource_files.added);
+        diff.resources.removed.extend(resource_files.removed);
+        diff.resources.changed.extend(resource_files.changed);
+        diff.native_libraries = Changes::between(&files("lib/", &old_snapshot), &files("lib/", &new_snapshot));
+        diff.assets = Changes::between(&files("assets/", &old_snapshot), &files("assets/", &new_snapshot));
+
+        let certificates = |snapshot: &Snapshot| -> BTreeMap<String, ()> {
+            snapshot.certificates.iter().map(|certificate| (format!("{} (SHA-256 {})", certificate.subject, certificate.sha256), ())).collect()
+        };
+        diff.certificates = Changes::between(&certificates(&old_snapshot), &certificates(&new_snapshot));
+
+        Ok(diff)
+    }
+
+    /// Whether nothing that was compared differs
+    pub fn is_empty(&self) -> bool {
+        self.manifest.is_empty() && self.sections().iter().all(|(_, changes)| changes.is_empty())
+    }
+
+    fn sections(&self) -> [(&'static str, &Changes); 8] {
+        [
+            ("Permissions", &self.permissions),
+            ("Components", &self.components),
+            ("Classes", &self.classes),
+            ("Methods", &self.methods),
+            ("Resources", &self.resources),
+            ("Native libraries", &self.native_libraries),
+            ("Assets", &self.assets),
+            ("Certificates", &self.certificates),
+        ]
+    }
+
+    fn compare_manifests(&mut self, old: &Manifest, new: &Manifest) {
+        let fields = [
+            ("package", Some(old.package.clone()), Some(new.package.clone())),
+            ("version_code", old.version_code.map(|code| code.to_string()), new.version_code.map(|code| code.to_string())),
+            ("version_name", old.version_name.clone(), new.version_name.clone()),
+            ("min_sdk", old.min_sdk.map(|sdk| sdk.to_string()), new.min_sdk.map(|sdk| sdk.to_string())),
+            ("target_sdk", old.target_sdk.map(|sdk| sdk.to_string()), new.target_sdk.map(|sdk| sdk.to_string())),
+            ("max_sdk", old.max_sdk.map(|sdk| sdk.to_string()), new.max_sdk.map(|sdk| sdk.to_string())),
+            ("debuggable", Some(old.debuggable.to_string()), Some(new.debuggable.to_string())),
+        ];
+        for (field, old_value, new_value) in fields {
+            if old_value != new_value {
+                self.manifest.push(FieldChange { field: field.to_string(), old: old_value, new: new_value });
+            }
+        }
+
+        let permissions = |manifest: &Manifest| -> BTreeMap<String, ()> {
+            let requested = manifest.permissions.iter().cloned();
+            let declared = manifest.declared_permissions.iter().map(|permission| format!("{} (declared)", permission));
+            requested.chain(declared).map(|permission| (permission, ())).collect()
+        };
+        self.permissions = Changes::between(&permissions(old), &permissions(new));
+
+        let components = |manifest: &Manifest| -> BTreeMap<String, Component> {
+            [("activity", &manifest.activities), ("service", &manifest.services), ("receiver", &manifest.receivers), ("provider", &manifest.providers)]
+                .into_iter()
+                .flat_map(|(kind, components)| components.iter().map(move |component| (format!("{} {}", kind, component.name), component.clone())))
+                .collect()
+        };
+        self.components = Changes::between(&components(old), &components(new));
+    }
+
+    fn compare_classes(&mut self, old: &BTreeMap<String, ClassSummary>, new: &BTreeMap<String, ClassSummary>) {
+        self.classes = Changes::between(old, new);
+        for name in &self.classes.changed {
+            let methods = Changes::between(&old[name].methods, &new[name].methods);
+            let qualify = |signatures: Vec<String>| signatures.into_iter().map(|signature| format!("{}.{}", name, signature));
+            self.methods.added.extend(qualify(methods.added));
+            self.methods.removed.extend(qualify(methods.removed));
+            self.methods.changed.extend(qualify(methods.changed));
+        }
+    }
+}
+
+/// Configurations whose value was added, removed or changed
+fn changed_configs<'a>(old: &'a BTreeMap<String, String>, new: &'a BTreeMap<String, String>) -> Vec<&'a str> {
+    let mut configs: Vec<&str> = old.keys().chain(new.keys()).filter(|config| old.get(*config) != new.get(*config)).map(String::as_str).collect();
+    configs.sort();
+    configs.dedup();
+    configs
+}
+
+/// One side of the comparison
+struct Snapshot {
+    manifest: Manifest,
+    /// By Java class name; `None` without DEX files or smali
+    classes: Option<BTreeMap<String, ClassSummary>>,
+    /// Where the classes were read from, `DEX files` or `smali`; digests only compare within one
+    code: &'static str,
+    /// `type/name` to configuration to value
+    resources: BTreeMap<String, BTreeMap<String, String>>,
+    /// SHA-256 of the files under `res/` (other than decoded values), `lib/` and `assets/`
+    files: BTreeMap<String, [u8; 32]>,
+    certificates: Vec<CertificateInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ClassSummary {
+    /// Digest of the access flags, superclass and interfaces
+    header: [u8; 32],
+    /// Digest of each method's access flags and code, by `name(args)return`
+    methods: BTreeMap<String, [u8; 32]>,
+}
+
+impl Snapshot {
+    fn from_apk(apk_path: &Path) -> Result<Self, ApkextError> {
+        let invalid = |reason: String| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason };
+
+        if !apk_path.is_file() {
+            return Err(invalid("file does not exist".to_string()));
+        }
+        let data = std::fs::read(apk_path)?;
+        let mut archive = ZipArchive::new(Cursor::new(data.as_slice())).map_err(|e| invalid(format!("not a ZIP file: {}", e)))?;
+
+        let mut entries: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut contents)?;
+            entries.insert(entry.name().to_string(), contents);
+        }
+
+        let manifest_data = entries.get(MANIFEST_ENTRY).ok_or_else(|| invalid(format!("{} not found", MANIFEST_ENTRY)))?;
+        let manifest = Manifest::parse(manifest_data).map_err(|e| invalid(format!("cannot decode {}: {}", MANIFEST_ENTRY, e)))?;
+
+        let mut dex_entries: Vec<&String> = entries.keys().filter(|name| dex_index(name).is_some()).collect();
+        dex_entries.sort_by_key(|name| dex_index(name));
+        let dex_files: Vec<&[u8]> = dex_entries.iter().map(|name| entries[*name].as_slice()).collect();
+        let classes = read_classes(&dex_files).map_err(|e| invalid(e.to_string()))?;
+
+        let mut resources: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        if let Some(table_data) = entries.get(RESOURCES_ENTRY) {
+            let table = ResourceTable::parse(table_data).map_err(|e| invalid(format!("cannot decode {}: {}", RESOURCES_ENTRY, e)))?;
+            for package in &table.packages {
+                for resource_type in &package.types {
+                    for config in &resource_type.configs {
+                        for entry in &config.entries {
+                            resources
+                                .entry(format!("{}/{}", resource_type.name, entry.name))
+                                .or_default()
+                                .insert(config.qualifiers.clone(), table.format(&entry.value));
+                        }
+                    }
+                }
+            }
+        }
+
+        let files = entries
+            .iter()
+            .filter(|(name, _)| ["res/", "lib/", "assets/"].iter().any(|prefix| name.starts_with(prefix)))
+            .map(|(name, contents)| (name.clone(), Sha256::digest(contents).into()))
+            .collect();
+
+        let certificates = signing::inspect(&data).map_err(|e| invalid(format!("cannot read signatures: {}", e)))?.certificates;
+
+        Ok(Self { manifest, classes, code: "DEX files", resources, files, certificates })
+    }
+
+    /// An `unpack` output directory, with apktool's project in `unpacked/`, or an apktool project itself
+    fn from_dir(dir: &Path) -> Result<Self, ApkextError> {
+        let invalid = |reason: String| ApkextError::InvalidProject { path: dir.to_path_buf(), reason };
+        let project = if dir.join("unpacked").is_dir() { dir.join("unpacked") } else { dir.to_path_buf() };
+
+        let manifest_path = [project.join(MANIFEST_ENTRY), dir.join(MANIFEST_ENTRY)]
+            .into_iter()
+            .find(|path| path.is_file())
+            .ok_or_else(|| invalid(format!("{} not found", MANIFEST_ENTRY)))?;
+        let manifest_data = std::fs::read(&manifest_path)?;
+        let mut manifest = read_manifest(&manifest_data).map_err(|e| invalid(format!("cannot read {}: {}", manifest_path.display(), e)))?;
+        // apktool moves the version and SDK levels out of the manifest into apktool.yml
+        if let Ok(yml) = std::fs::read_to_string(project.join("apktool.yml")) {
+            let level = |key: &str| yml_value(&yml, key).and_then(|value| value.parse().ok());
+            manifest.version_code = manifest.version_code.or_else(|| level("versionCode"));
+            manifest.version_name = manifest.version_name.or_else(|| yml_value(&yml, "versionName"));
+            manifest.min_sdk = manifest.min_sdk.or_else(|| level("minSdkVersion").map(|level: i64| level as u32));
+            manifest.target_sdk = manifest.target_sdk.or_else(|| level("targetSdkVersion").map(|level: i64| level as u32));
+            manifest.max_sdk = manifest.max_sdk.or_else(|| level("maxSdkVersion").map(|level: i64| level as u32));
+        }
+
+        // unpack removes the DEX files once they are converted, so the smali is what is left;
+        // apktool keeps the DEX files in the project instead when told not to disassemble them
+        let (classes, code) = match read_smali(&project)? {
+            Some(classes) => (Some(classes), "smali"),
+            None => {
+                let mut dex_paths = Vec::new();
+                let directories = if project == dir { vec![dir] } else { vec![project.as_path(), dir] };
+                for directory in directories {
+                    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)?
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "dex"))
+                        .collect();
+                    paths.sort_by_key(|path| {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                        (dex_index(&name).unwrap_or(u32::MAX), name)
+                    });
+                    dex_paths.extend(paths);
+                }
+                let dex_data = dex_paths.iter().map(std::fs::read).collect::<std::io::Result<Vec<_>>>()?;
+                let dex_files: Vec<&[u8]> = dex_data.iter().map(Vec::as_slice).collect();
+                (read_classes(&dex_files).map_err(|e| invalid(e.to_string()))?, "DEX files")
+            }
+        };
+
+        let mut resources: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        let mut files = BTreeMap::new();
+        for directory in ["res", "lib", "assets"] {
+            for entry in WalkDir::new(project.join(directory)).sort_by_file_name() {
+                let Ok(entry) = entry else { continue };
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(&project).unwrap_or(entry.path());
+                let name = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                let contents = std::fs::read(entry.path())?;
+
+                if let Some(config) = values_config(&name) {
+                    if !name.ends_with("/public.xml") {
+                        let text = String::from_utf8_lossy(&contents);
+                        let root = xml::parse(&text).map_err(|e| invalid(format!("cannot read {}: {}", name, e)))?;
+                        for (resource, value) in values(&root) {
+                            resources.entry(resource).or_default().insert(config.to_string(), value);
+                        }
+                    }
+                    continue;
+                }
+                files.insert(name, Sha256::digest(&contents).into());
+            }
+        }
+
+        let mut certificates: Vec<CertificateInfo> = Vec::new();
+        if let Ok(meta_inf) = std::fs::read_dir(project.join("original").join("META-INF")) {
+            let mut paths: Vec<PathBuf> = meta_inf.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+            paths.sort();
+            for path in paths {
+                let name = format!("META-INF/{}", path.file_name().unwrap_or_default().to_string_lossy());
+                if !signing::is_signature_block(&name) {
+                    continue;
+                }
+                let block = std::fs::read(&path)?;
+                let ders = signing::pkcs7_certificates(&block).map_err(|e| invalid(format!("cannot read {}: {}", name, e)))?;
+                for der in ders {
+                    let certificate = CertificateInfo::from_der(&der).map_err(|e| invalid(format!("cannot read {}: {}", name, e)))?;
+                    if !certificates.contains(&certificate) {
+                        certificates.push(certificate);
+                    }
+                }
+            }
+        }
+
+        Ok(Self { manifest, classes, code, resources, files, certificates })
+    }
+}
+
+/// A binary manifest, as apktool leaves it with `--no-res`, or a decoded text one
+fn read_manifest(data: &[u8]) -> Result<Manifest> {
+    let root: Element = if data.starts_with(&[0x03, 0x00]) {
+        axml::parse(data)?
+    } else {
+        xml::parse(&String::from_utf8_lossy(data))?
+    };
+    Manifest::from_element(&root)
+}
+
+/// `key: value` anywhere in apktool.yml, without quotes
+fn yml_value(yml: &str, key: &str) -> Option<String> {
+    yml.lines()
+        .filter_map(|line| line.trim().strip_prefix(key)?.strip_prefix(':'))
+        .map(|value| value.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+        .find(|value| !value.is_empty())
+}
+
+/// Qualifiers of a decoded values file, e.g. `de` for `res/values-de/strings.xml`
+fn values_config(name: &str) -> Option<&str> {
+    let mut parts = name.split('/');
+    let (Some("res"), Some(directory), Some(file), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    if !file.ends_with(".xml") {
+        return None;
+    }
+    match directory.strip_prefix("values") {
+        Some("") => Some(""),
+        Some(qualifiers) => qualifiers.strip_prefix('-'),
+        None => None,
+    }
+}
+
+/// `type/name` and a comparable value for each resource in a `<resources>` file
+fn values(root: &Element) -> Vec<(String, String)> {
+    root.children
+        .iter()
+        .filter_map(|child| {
+            let name = child.attr("name")?.to_string();
+            let resource_type = match child.name.as_str() {
+                "item" => child.attr("type").map(|kind| kind.to_string()).unwrap_or_else(|| "item".to_string()),
+                "string-array" | "integer-array" => "array".to_string(),
+                "declare-styleable" => "styleable".to_string(),
+                other => other.to_string(),
+            };
+            Some((format!("{}/{}", resource_type, name), child.to_xml()))
+        })
+        .collect()
+}
+
+/// Summaries of every class in the DEX files, the first definition winning like the runtime's
+/// class loader; `None` if there are no DEX files
+fn read_classes(dex_files: &[&[u8]]) -> Result<Option<BTreeMap<String, ClassSummary>>> {
+    if dex_files.is_empty() {
+        return Ok(None);
+    }
+
+    let mut classes = BTreeMap::new();
+    for data in dex_files {
+        let dex = DexFile::parse(data)?;
+        for class in &dex.classes {
+            let name = dex::java_name(&class.name);
+            if classes.contains_key(&name) {
+                continue;
+            }
+
+            let mut header = Sha256::new();
+            header.update(class.access_flags.to_le_bytes());
+            for type_name in class.superclass.iter().chain(&class.interfaces) {
+                header.update(type_name.as_bytes());
+                header.update([0]);
+            }
+
+            let methods = class
+                .methods
+                .iter()
+                .map(|method| {
+                    let mut digest = Sha256::new();
+                    digest.update(method.access_flags.to_le_bytes());
+                    if let Some(code) = &method.code {
+                        digest.update(dex.code_digest(code));
+                    }
+                    (dex.method_signature(method.method), digest.finalize().into())
+                })
+                .collect();
+            classes.insert(name, ClassSummary { header: header.finalize().into(), methods });
+        }
+    }
+    Ok(Some(classes))
+}
+
+/// Directives baksmali writes for debug information, which changes with unrelated edits
+const SMALI_DEBUG_DIRECTIVES: [&str; 7] = [".line", ".local", ".end local", ".restart local", ".prologue", ".param", ".end param"];
+
+/// Summaries of the classes in apktool's `smali/`, `smali_classes2/`, … directories, the
+/// first definition winning as for DEX files; `None` if there are none
+fn read_smali(project: &Path) -> Result<Option<BTreeMap<String, ClassSummary>>> {
+    let roots = smali_dirs(project);
+    if roots.is_empty() {
+        return Ok(None);
+    }
+
+    let mut classes = BTreeMap::new();
+    for root in roots {
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() || entry.path().extension() != Some("smali".as_ref()) {
+                continue;
+            }
+            if let Some((name, summary)) = parse_smali(&std::fs::read_to_string(entry.path())?) {
+                classes.entry(name).or_insert(summary);
+            }
+        }
+    }
+    Ok(Some(classes))
+}
+
+/// The class name and summary of one smali file; `None` without a `.class` line
+fn parse_smali(text: &str) -> Option<(String, ClassSummary)> {
+    let mut name = None;
+    let mut header = Sha256::new();
+    let mut methods = BTreeMap::new();
+    let mut method: Option<(String, Sha256)> = None;
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if let Some((signature, digest)) = &mut method {
+            if line == ".end method" {
+                methods.insert(std::mem::take(signature), std::mem::take(digest).finalize().into());
+                method = None;
+            } else if !SMALI_DEBUG_DIRECTIVES.iter().any(|directive| line == *directive || line.starts_with(&format!("{} ", directive))) {
+                digest.update(line.as_bytes());
+                digest.update([b'\n']);
+            }
+            continue;
+        }
+
+        let directive = line.split_whitespace().next().unwrap_or_default();
+        match directive {
+            ".class" | ".super" | ".implements" => {
+                if directive == ".class" {
+                    name = line.split_whitespace().last().map(dex::java_name);
+                }
+                header.update(line.as_bytes());
+                header.update([b'\n']);
+            }
+            ".method" => {
+                let signature = line.split_whitespace().last().unwrap_or_default().to_string();
+                let mut digest = Sha256::new();
+                // The access flags come before the signature
+                digest.update(line.as_bytes());
+                digest.update([b'\n']);
+                method = Some((signature, digest));
+            }
+            _ => {}
+        }
+    }
+
+    Some((name?, ClassSummary { header: header.finalize().into(), methods }))
+}
+
+impl fmt::Display for ApkDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Old:          {}", self.old.display())?;
+        writeln!(f, "New:          {}", self.new.display())?;
+        for note in &self.notes {
+            writeln!(f, "Note:         {}", note)?;
+        }
+
+        if self.is_empty() {
+            writeln!(f)?;
+            return writeln!(f, "No differences");
+        }
+
+        if !self.manifest.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Manifest:")?;
+            let width = self.manifest.iter().map(|change| change.field.len()).max().unwrap_or(0) + 1;
+            for change in &self.manifest {
+                let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+                writeln!(f, "  {:<width$} {} -> {}", format!("{}:", change.field), value(&change.old), value(&change.new), width = width)?;
+            }
+        }
+
+        for (title, changes) in self.sections() {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f)?;
+            writeln!(f, "{} (+{} -{} ~{}):", title, changes.added.len(), changes.removed.len(), changes.changed.len())?;
+            for (marker, items) in [('+', &changes.added), ('-', &changes.removed), ('~', &changes.changed)] {
+                for item in items {
+                    writeln!(f, "  {} {}", marker, item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
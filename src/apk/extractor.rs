@@ -1,55 +1,404 @@
-use crate::{config::Config, tools::ToolManager, Result};
+use crate::aab;
+use crate::kotlin::{self, KotlinReport};
+use crate::manifest;
+use crate::mapping::{self, Mapping};
+use crate::signing::{self, VerifyReport};
+use crate::{assets::AssetManager, config::Config, tools::{Decompiler, ProgressHandler, ToolManager}, ApkextError, Result};
+use super::cache::{self, DecompileCache};
+use super::decompile::{self, DecompilerReport};
+use super::output::{self, ApktoolInput, ExistingOutput, ReusedOutputs, StageInputs};
+use super::report::{timed, CacheSummary, DecompileSummary, DeobfuscationSummary, KotlinSummary, UnpackReport};
+use super::splits::{self, ContainerKind, SplitSet};
+use super::stages::UnpackStages;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::fs;
 
 pub struct Extractor {
     tool_manager: ToolManager,
+    decompiler: Decompiler,
+    fallback_decompiler: Option<Decompiler>,
+    output_dir: Option<PathBuf>,
+    existing_output: ExistingOutput,
+    stages: UnpackStages,
+    cache: Option<DecompileCache>,
+    kotlin: bool,
+    mapping: Option<PathBuf>,
 }
 
 impl Extractor {
-    pub fn new(config: Config) -> Self {
-        let tool_manager = ToolManager::new(config);
-        Self { tool_manager }
+    /// Unpacks next to the APK and refuses to touch an existing output directory by default
+    pub fn new(config: Config, asset_manager: AssetManager) -> Self {
+        let decompiler = config.decompiler;
+        let fallback_decompiler = config.fallback_decompiler();
+        let cache_max_size = config.cache_max_size;
+        let use_cache = config.cache;
+        let kotlin = config.kotlin;
+        let tool_manager = ToolManager::new(config, asset_manager);
+        let cache = use_cache.then(|| DecompileCache::new(tool_manager.cache_dir(), cache_max_size));
+        Self {
+            tool_manager,
+            decompiler,
+            fallback_decompiler,
+            output_dir: None,
+            existing_output: ExistingOutput::default(),
+            stages: UnpackStages::default(),
+            cache,
+            kotlin,
+            mapping: None,
+        }
+    }
+
+    /// Take decompiled sources from `cache` where possible; `None` always runs the decompiler
+    pub fn with_cache(mut self, cache: Option<DecompileCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Run only some of the pipeline stages
+    pub fn with_stages(mut self, stages: UnpackStages) -> Self {
+        self.stages = stages;
+        self
+    }
+
+    /// Deliver step, message and tool output events to `progress` instead of printing them
+    pub fn with_progress(mut self, progress: Option<ProgressHandler>) -> Self {
+        self.tool_manager = self.tool_manager.with_progress(progress);
+        self
+    }
+
+    /// Unpack into `output_dir` instead of a directory named after the APK
+    pub fn with_output_dir(mut self, output_dir: Option<PathBuf>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Set what happens when the output directory already exists
+    pub fn with_existing_output(mut self, existing_output: ExistingOutput) -> Self {
+        self.existing_output = existing_output;
+        self
+    }
+
+    /// Rename classes, methods and fields in the JARs and smali back to their names in a ProGuard or R8 `mapping.txt`
+    pub fn with_mapping(mut self, mapping: Option<PathBuf>) -> Self {
+        self.mapping = mapping;
+        self
     }
 
-    pub async fn unpack(&self, apk_path: &str) -> Result<()> {
+    pub async fn unpack(&self, apk_path: &str) -> Result<UnpackReport, ApkextError> {
+        let started = Instant::now();
         let apk_path = Path::new(apk_path);
 
         // Validate input
-        if apk_path.extension().and_then(|s| s.to_str()) != Some("apk") {
-            return Err(anyhow::anyhow!("File must have .apk extension"));
+        let container = ContainerKind::from_path(apk_path);
+        let bundle = apk_path.extension().and_then(|s| s.to_str()) == Some("aab");
+        if apk_path.extension().and_then(|s| s.to_str()) != Some("apk") && container.is_none() && !bundle {
+            return Err(invalid_apk(apk_path, "file must have .apk, .apks, .xapk, .apkm or .aab extension"));
         }
 
         if !apk_path.exists() {
-            return Err(anyhow::anyhow!("APK file does not exist: {}", apk_path.display()));
+            return Err(invalid_apk(apk_path, "file does not exist"));
+        }
+
+        self.stages.validate()?;
+
+        let mapping = match &self.mapping {
+            Some(path) => Some((Mapping::load(path)?, output::file_sha256(path)?)),
+            None => None,
+        };
+
+        let extract_dir = self.extract_dir(apk_path);
+        let mut report = UnpackReport::new(apk_path, &extract_dir, self.decompiler, self.stages);
+
+        // When merging, the pipeline runs in a staging directory that is merged in at the end
+        let staging = self.prepare_output_dir(&extract_dir).await?;
+        let work_dir = match &staging {
+            Some(staging) => staging.path().to_path_buf(),
+            None => extract_dir.clone(),
+        };
+
+        // Stages whose input is the same as in the last unpack keep their output when merging,
+        // unless the last unpack was deobfuscated with another mapping file or without one
+        let mapping_hash = mapping.as_ref().map(|(_, hash)| hash.clone());
+        let previous = match &staging {
+            Some(_) => Some(StageInputs::load(&extract_dir)).filter(|previous| previous.mapping == mapping_hash).unwrap_or_default(),
+            None => StageInputs::default(),
+        };
+        let mut inputs = StageInputs { mapping: mapping_hash, ..StageInputs::default() };
+        let mut reused = ReusedOutputs::default();
+
+        self.tool_manager.report(&format!("[+] Extracting under '{}'", extract_dir.display()));
+        let plan = self.planned_steps(container.is_some(), bundle, staging.is_some());
+
+        // Step 0: Take split containers apart; the base APK goes through the whole pipeline
+        let apks_dir = match container {
+            Some(_) => Some(tempfile::tempdir()?),
+            None => None,
+        };
+        let base_apk = match &apks_dir {
+            Some(apks_dir) => {
+                self.tool_manager.step(&plan, "extract_splits");
+                let set = timed(&mut report.steps, "extract_splits", self.extract_splits(apk_path, apks_dir.path(), &work_dir)).await?;
+                let base_apk = apks_dir.path().join(&set.base);
+                report.splits = Some(set);
+                base_apk
+            }
+            None => apk_path.to_path_buf(),
+        };
+        let apk_path = base_apk.as_path();
+
+        // Who signed the APK, and whether it was modified since; a bad signature does not stop the unpack
+        if !bundle {
+            self.tool_manager.step(&plan, "verify_signature");
+            report.signature = Some(timed(&mut report.steps, "verify_signature", self.verify_signature(apk_path)).await?);
+        }
+
+        // App bundles keep their protobuf files; the modules take the place of the APK
+        if bundle {
+            self.tool_manager.step(&plan, "extract_bundle");
+            report.modules = timed(&mut report.steps, "extract_bundle", self.extract_bundle(apk_path, &work_dir)).await?;
+        }
+
+        // Step 1: Extract resources using apktool, or decode the protobuf XML of each bundle module
+        if self.stages.apktool && bundle {
+            self.tool_manager.step(&plan, "decode_bundle");
+            timed(&mut report.steps, "decode_bundle", self.decode_bundle(&work_dir, &report.modules)).await?;
+        } else if self.stages.apktool {
+            self.tool_manager.step(&plan, "extract_resources");
+            let input = ApktoolInput {
+                sha256: output::file_sha256(apk_path)?,
+                decode_resources: self.stages.decode_resources,
+                decode_smali: self.stages.decode_smali,
+            };
+            if previous.apktool.as_ref() == Some(&input) && extract_dir.join("unpacked").is_dir() {
+                self.tool_manager.report("[+] APK unchanged, keeping 'unpacked'");
+                reused.unpacked = true;
+            } else {
+                timed(&mut report.steps, "extract_resources", self.extract_resources(apk_path, &work_dir.join("unpacked"))).await?;
+            }
+            inputs.apktool = Some(input);
+        } else if !bundle {
+            // Without apktool, the manifest is still decoded natively
+            self.tool_manager.step(&plan, "decode_manifest");
+            timed(&mut report.steps, "decode_manifest", self.decode_manifest(apk_path, &work_dir)).await?;
+        }
+        if !self.stages.apktool {
+            inputs.apktool = previous.apktool.clone();
+        }
+
+        // Name and SHA-256 of each DEX file, in the order of `report.jars`
+        let mut dex_hashes = Vec::new();
+        let mut converted = Vec::new();
+        if self.stages.jar {
+            // Step 2: Extract classes.dex and any classes2.dex … classesN.dex
+            self.tool_manager.step(&plan, "extract_dex");
+            let dex_files = if bundle {
+                timed(&mut report.steps, "extract_dex", self.extract_bundle_dex(&work_dir, &report.modules)).await?
+            } else {
+                timed(&mut report.steps, "extract_dex", self.extract_dex(apk_path, &work_dir)).await?
+            };
+
+            // A DEX file that did not change since the last unpack keeps its JAR
+            let mut convert = Vec::with_capacity(dex_files.len());
+            for dex_path in &dex_files {
+                let dex_name = dex_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let hash = output::file_sha256(dex_path)?;
+                let jar_name = Path::new(&dex_name).with_extension("jar").to_string_lossy().to_string();
+
+                if previous.jars.get(&dex_name) == Some(&hash) && extract_dir.join(&jar_name).is_file() {
+                    self.tool_manager.report(&format!("[+] {} unchanged, keeping {}", dex_name, jar_name));
+                    fs::remove_file(dex_path).await?;
+                    report.jars.push(extract_dir.join(&jar_name));
+                    reused.jars.push(jar_name);
+                } else {
+                    report.jars.push(dex_path.with_extension("jar"));
+                    convert.push(dex_path.clone());
+                }
+                dex_hashes.push((dex_name, hash));
+            }
+            inputs.jars = dex_hashes.iter().cloned().collect();
+
+            // Step 3: Convert each DEX to its own JAR
+            self.tool_manager.step(&plan, "dex2jar");
+            converted = timed(&mut report.steps, "dex2jar", self.convert_dex_to_jar(&convert)).await?;
+        } else {
+            inputs.jars = previous.jars.clone();
         }
 
-        let extract_dir = self.get_extract_dir(apk_path);
-        if extract_dir.exists() {
-            println!("[+] Removing existing directory '{}'", extract_dir.display());
-            fs::remove_dir_all(&extract_dir).await
-                .map_err(|e| anyhow::anyhow!("Failed to remove existing directory: {}", e))?;
+        // Step 4: Give classes and members their original names before anything reads them
+        if let Some((mapping, _)) = &mapping {
+            if self.deobfuscates(bundle) {
+                self.tool_manager.step(&plan, "deobfuscate");
+                let smali = (self.stages.apktool && self.stages.decode_smali && !bundle && !reused.unpacked).then(|| work_dir.join("unpacked"));
+                report.deobfuscation = Some(timed(&mut report.steps, "deobfuscate", self.deobfuscate(mapping, &converted, smali.as_deref())).await?);
+            }
         }
 
-        println!("[+] Extracting under '{}'", extract_dir.display());
+        if self.stages.decompile {
+            // Sources decompiled from the same DEX file by the same decompilers are kept
+            let decompilers = (self.decompiler, self.fallback_decompiler);
+            let mut decompile = Vec::with_capacity(report.jars.len());
+            for ((dex_name, hash), jar_path) in dex_hashes.into_iter().zip(&report.jars) {
+                let jar_name = jar_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+                if previous.decompilers == Some(decompilers)
+                    && previous.sources.get(&dex_name) == Some(&hash)
+                    && extract_dir.join("src").is_dir()
+                {
+                    self.tool_manager.report(&format!("[+] {} unchanged, keeping its sources", jar_name));
+                    reused.source_files.extend(decompile::top_level_sources(jar_path)?);
+                    reused.sources.push(jar_name);
+                } else {
+                    decompile.push(jar_path.clone());
+                }
+                inputs.sources.insert(dex_name, hash);
+            }
+            inputs.decompilers = Some(decompilers);
 
-        // Step 1: Extract resources using apktool
-        self.extract_resources(apk_path, &extract_dir).await?;
+            let kept = match DecompilerReport::load(&extract_dir) {
+                Some(mut kept) if !reused.sources.is_empty() => {
+                    kept.retain_sources(&reused.source_files);
+                    Some(kept)
+                }
+                _ => None,
+            };
 
-        // Step 2: Extract classes.dex
-        self.extract_dex(apk_path, &extract_dir).await?;
+            // Step 5: Decompile all JARs to source
+            self.tool_manager.step(&plan, "decompile");
+            report.cache = timed(&mut report.steps, "decompile", self.decompile_jar(&work_dir, &decompile)).await?;
 
-        // Step 3: Convert DEX to JAR
-        self.convert_dex_to_jar(&extract_dir).await?;
+            // Step 6: Retry classes the decompiler failed on and record who produced each file
+            self.tool_manager.step(&plan, "decompile_fallback");
+            report.fallback = timed(&mut report.steps, "decompile_fallback", self.retry_failed_classes(&work_dir, &decompile, kept)).await?;
 
-        // Step 4: Decompile JAR to source
-        self.decompile_jar(&extract_dir).await?;
+            // Step 7: Recover Kotlin declarations from the metadata of every class
+            if self.kotlin {
+                self.tool_manager.step(&plan, "kotlin_metadata");
+                report.kotlin = timed(&mut report.steps, "kotlin_metadata", self.recover_kotlin(&work_dir, &report.jars)).await?;
+            }
+        } else {
+            inputs.sources = previous.sources.clone();
+            inputs.decompilers = previous.decompilers;
+        }
 
-        println!("");
-        println!("[+] Resources and smali are in '{}/unpacked'", extract_dir.display());
-        println!("[+] Decompiled classes in '{}/src'", extract_dir.display());
+        // Step 8: Decode every split next to the base and merge their resources for browsing
+        if let (Some(set), Some(apks_dir)) = (&report.splits, &apks_dir) {
+            if self.stages.apktool {
+                self.tool_manager.step(&plan, "decode_splits");
+                let base_dir = if reused.unpacked { &extract_dir } else { &work_dir };
+                timed(&mut report.steps, "decode_splits", self.decode_splits(set, apks_dir.path(), base_dir, &work_dir)).await?;
+            }
+        }
 
-        Ok(())
+        // Step 9: Remember what was generated, merging into the existing directory if asked to
+        match &staging {
+            Some(staging) => {
+                self.tool_manager.report(&format!("[+] Merging into '{}'", extract_dir.display()));
+                self.tool_manager.step(&plan, "merge");
+                let merge = timed(&mut report.steps, "merge", async { output::merge(staging.path(), &extract_dir, &self.stages, &reused) }).await?;
+
+                for kept in &merge.kept {
+                    self.tool_manager.report(&format!("[+] Keeping modified '{}'", kept.display()));
+                }
+
+                report.jars = report.jars.iter()
+                    .map(|jar| jar.strip_prefix(staging.path()).map(|relative| extract_dir.join(relative)).unwrap_or_else(|_| jar.clone()))
+                    .collect();
+                report.merge = Some(merge);
+                report.reused = Some(reused);
+            }
+            None => output::write_manifest(&extract_dir)?,
+        }
+        inputs.save(&extract_dir)?;
+
+        self.tool_manager.report("");
+        if self.stages.apktool && bundle {
+            self.tool_manager.report(&format!("[+] Decoded manifests and resources are in '{}/{}'", extract_dir.display(), aab::DECODED_DIR));
+        } else if self.stages.apktool {
+            self.tool_manager.report(&format!("[+] Resources and smali are in '{}/unpacked'", extract_dir.display()));
+            if report.splits.is_some() {
+                self.tool_manager.report(&format!("[+] Splits are in '{}/splits', merged resources in '{}/merged'", extract_dir.display(), extract_dir.display()));
+            }
+        }
+        if self.stages.decompile {
+            self.tool_manager.report(&format!("[+] Decompiled classes in '{}/src'", extract_dir.display()));
+        } else if self.stages.jar {
+            self.tool_manager.report(&format!("[+] JAR files are in '{}'", extract_dir.display()));
+        }
+
+        report.total_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Apply the existing-output policy; returns the staging directory when merging
+    async fn prepare_output_dir(&self, extract_dir: &Path) -> Result<Option<tempfile::TempDir>> {
+        let occupied = match std::fs::read_dir(extract_dir) {
+            Ok(mut entries) => entries.next().is_some(),
+            Err(_) => extract_dir.exists(),
+        };
+
+        if !occupied {
+            return Ok(None);
+        }
+
+        match self.existing_output {
+            ExistingOutput::Refuse => Err(ApkextError::OutputExists(extract_dir.to_path_buf()).into()),
+            ExistingOutput::Force => {
+                self.tool_manager.report(&format!("[+] Removing existing directory '{}'", extract_dir.display()));
+                fs::remove_dir_all(extract_dir).await
+                    .map_err(|e| anyhow::anyhow!("Failed to remove existing directory: {}", e))?;
+                Ok(None)
+            }
+            ExistingOutput::Merge => {
+                if !extract_dir.is_dir() {
+                    anyhow::bail!("Output path '{}' exists and is not a directory", extract_dir.display());
+                }
+                Ok(Some(tempfile::tempdir()?))
+            }
+        }
+    }
+
+    /// Names of the timed steps this run goes through, in order
+    fn planned_steps(&self, container: bool, bundle: bool, merging: bool) -> Vec<&'static str> {
+        let mut plan = Vec::new();
+        if container {
+            plan.push("extract_splits");
+        }
+        if bundle {
+            plan.push("extract_bundle");
+        } else {
+            plan.push("verify_signature");
+        }
+        if self.stages.apktool {
+            plan.push(if bundle { "decode_bundle" } else { "extract_resources" });
+        } else if !bundle {
+            plan.push("decode_manifest");
+        }
+        if self.stages.jar {
+            plan.extend(["extract_dex", "dex2jar"]);
+        }
+        if self.mapping.is_some() && self.deobfuscates(bundle) {
+            plan.push("deobfuscate");
+        }
+        if self.stages.decompile {
+            plan.extend(["decompile", "decompile_fallback"]);
+            if self.kotlin {
+                plan.push("kotlin_metadata");
+            }
+        }
+        if container && self.stages.apktool {
+            plan.push("decode_splits");
+        }
+        if merging {
+            plan.push("merge");
+        }
+        plan
+    }
+
+    /// Directory `unpack` writes `apk_path` into
+    pub fn extract_dir(&self, apk_path: &Path) -> PathBuf {
+        self.output_dir.clone().unwrap_or_else(|| self.get_extract_dir(apk_path))
     }
 
     fn get_extract_dir(&self, apk_path: &Path) -> PathBuf {
@@ -58,53 +407,209 @@ impl Extractor {
         extract_dir
     }
 
-    async fn extract_resources(&self, apk_path: &Path, extract_dir: &Path) -> Result<()> {
-        println!("[+] Extracting resources");
+    async fn verify_signature(&self, apk_path: &Path) -> Result<VerifyReport> {
+        self.tool_manager.report("[+] Verifying signatures");
+
+        // A signing block that cannot be read is reported like any other signature problem
+        let report = match signing::verify_file(apk_path) {
+            Ok(report) => report,
+            Err(e) => VerifyReport { errors: vec![format!("{:#}", e)], ..VerifyReport::default() },
+        };
+        for scheme in &report.schemes {
+            match scheme.signers.first() {
+                Some(signer) if scheme.verified => self.tool_manager.report(&format!("[+] {} signed by {} (SHA-256 {})", scheme.scheme, signer.subject, signer.sha256)),
+                _ => self.tool_manager.report(&format!("[!] {} signature does not verify: {}", scheme.scheme, scheme.errors.join("; "))),
+            }
+        }
+        for error in &report.errors {
+            self.tool_manager.report(&format!("[!] {}", error));
+        }
+        Ok(report)
+    }
+
+    /// Write `AndroidManifest.xml` as text without running apktool
+    async fn decode_manifest(&self, apk_path: &Path, extract_dir: &Path) -> Result<()> {
+        self.tool_manager.report("[+] Decoding AndroidManifest.xml");
 
-        let unpacked_dir = extract_dir.join("unpacked");
-        self.tool_manager.run_apktool(&["d", "-f", &apk_path.to_string_lossy(), "-o", &unpacked_dir.to_string_lossy()]).await
+        let data = manifest::read_entry(apk_path, manifest::MANIFEST_ENTRY)
+            .map_err(|e| invalid_apk(apk_path, &e.to_string()))?;
+        let xml = manifest::axml::decode(&data).map_err(|e| invalid_apk(apk_path, &format!("cannot decode {}: {}", manifest::MANIFEST_ENTRY, e)))?;
+
+        fs::create_dir_all(extract_dir).await?;
+        fs::write(extract_dir.join(manifest::MANIFEST_ENTRY), xml).await?;
+        Ok(())
+    }
+
+    /// Copy the bundle unchanged to `bundle/`; returns its modules
+    async fn extract_bundle(&self, aab_path: &Path, extract_dir: &Path) -> Result<Vec<String>> {
+        self.tool_manager.report("[+] Extracting app bundle");
+
+        let modules = aab::extract(aab_path, &extract_dir.join(aab::BUNDLE_DIR))
+            .map_err(|e| invalid_apk(aab_path, &e.to_string()))?;
+
+        self.tool_manager.report(&format!("[+] Modules: {}", modules.join(", ")));
+        Ok(modules)
     }
 
-    async fn extract_dex(&self, apk_path: &Path, extract_dir: &Path) -> Result<()> {
-        println!("[+] Extracting classes.dex");
+    async fn decode_bundle(&self, extract_dir: &Path, modules: &[String]) -> Result<()> {
+        for module in modules {
+            self.tool_manager.report(&format!("[+] Decoding manifest and resources of module {}", module));
+            aab::decode_module(
+                &extract_dir.join(aab::BUNDLE_DIR).join(module),
+                &extract_dir.join(aab::DECODED_DIR).join(module),
+            )?;
+        }
+        Ok(())
+    }
 
-        // Try to extract classes.dex first
-        let classes_dex_path = extract_dir.join("classes.dex");
-        if let Err(_) = self.unzip_file(apk_path, extract_dir, "classes.dex").await {
-            // If classes.dex doesn't exist, try class.dex and rename it
-            if let Err(e) = self.unzip_file(apk_path, extract_dir, "class.dex").await {
-                return Err(anyhow::anyhow!("Failed to extract DEX file: {}", e));
+    /// Copy `<module>/dex/classesN.dex` to `<module>-classesN.dex`, so each becomes its own JAR
+    async fn extract_bundle_dex(&self, extract_dir: &Path, modules: &[String]) -> Result<Vec<PathBuf>> {
+        self.tool_manager.report("[+] Extracting DEX files");
+
+        let mut dex_files = Vec::new();
+        for module in modules {
+            let dex_dir = extract_dir.join(aab::BUNDLE_DIR).join(module).join("dex");
+            let Ok(entries) = std::fs::read_dir(&dex_dir) else { continue };
+
+            let mut names: Vec<(u32, String)> = entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    dex_index(&name).map(|index| (index, name))
+                })
+                .collect();
+            names.sort();
+
+            for (_, name) in names {
+                let target = extract_dir.join(format!("{}-{}", module, name));
+                fs::copy(dex_dir.join(&name), &target).await?;
+                dex_files.push(target);
+            }
+        }
+
+        if dex_files.is_empty() {
+            anyhow::bail!("The bundle has no DEX files");
+        }
+        Ok(dex_files)
+    }
+
+    /// Split APKs to a temporary directory, the rest of the container to `container/`
+    async fn extract_splits(&self, container: &Path, apks_dir: &Path, extract_dir: &Path) -> Result<SplitSet> {
+        self.tool_manager.report("[+] Extracting split APKs");
+
+        let set = splits::extract_container(container, apks_dir, &extract_dir.join(splits::CONTAINER_DIR))
+            .map_err(|e| invalid_apk(container, &e.to_string()))?;
+        set.save(extract_dir)?;
+
+        self.tool_manager.report(&format!("[+] Base APK '{}' with {} splits", set.base, set.splits.len()));
+        Ok(set)
+    }
+
+    /// Decode the splits into `extract_dir` and merge them with the base decoded under `base_dir`
+    async fn decode_splits(&self, set: &SplitSet, apks_dir: &Path, base_dir: &Path, extract_dir: &Path) -> Result<()> {
+        let mut split_dirs = Vec::with_capacity(set.splits.len());
+
+        for split in &set.splits {
+            let split_dir = extract_dir.join(splits::SPLITS_DIR).join(&split.name);
+            self.extract_resources(&apks_dir.join(&split.entry), &split_dir).await?;
+            split_dirs.push(split_dir);
+        }
+
+        self.tool_manager.report("[+] Merging base and split resources");
+        splits::merge_resources(&base_dir.join("unpacked"), &split_dirs, &extract_dir.join(splits::MERGED_DIR))
+    }
+
+    /// Decode `apk_path` with apktool into `unpacked_dir`
+    async fn extract_resources(&self, apk_path: &Path, unpacked_dir: &Path) -> Result<()> {
+        self.tool_manager.report(&format!("[+] Extracting resources of {}", apk_path.file_name().unwrap_or_default().to_string_lossy()));
+
+        let apk_path = apk_path.to_string_lossy();
+        let unpacked_dir = unpacked_dir.to_string_lossy().to_string();
+
+        let mut args = vec!["d", "-f"];
+        if !self.stages.decode_resources {
+            args.push("-r");
+        }
+        if !self.stages.decode_smali {
+            args.push("-s");
+        }
+        args.extend_from_slice(&[&apk_path, "-o", &unpacked_dir]);
+
+        Ok(self.tool_manager.run_apktool(&args).await?)
+    }
+
+    async fn extract_dex(&self, apk_path: &Path, extract_dir: &Path) -> Result<Vec<PathBuf>> {
+        self.tool_manager.report("[+] Extracting DEX files");
+
+        let dex_names = self.list_dex_entries(apk_path)?;
+
+        if dex_names.is_empty() {
+            // Some old APKs ship a single class.dex; rename it so later steps see classes.dex
+            if self.unzip_file(apk_path, extract_dir, "class.dex").await.is_err() {
+                return Err(invalid_apk(apk_path, "no classes.dex or class.dex entry").into());
             }
 
-            // Rename class.dex to classes.dex
             let old_path = extract_dir.join("class.dex");
+            let classes_dex_path = extract_dir.join("classes.dex");
             fs::rename(&old_path, &classes_dex_path).await
                 .map_err(|e| anyhow::anyhow!("Failed to rename class.dex to classes.dex: {}", e))?;
+
+            return Ok(vec![classes_dex_path]);
         }
 
-        Ok(())
+        let mut dex_files = Vec::with_capacity(dex_names.len());
+        for name in dex_names {
+            self.unzip_file(apk_path, extract_dir, &name).await?;
+            dex_files.push(extract_dir.join(name));
+        }
+
+        Ok(dex_files)
     }
 
-    async fn convert_dex_to_jar(&self, extract_dir: &Path) -> Result<()> {
-        println!("[+] Converting classes.dex to jar");
+    /// Names of all `classes.dex`, `classes2.dex` … `classesN.dex` entries, in load order
+    fn list_dex_entries(&self, apk_path: &Path) -> Result<Vec<String>> {
+        use std::fs::File;
+        use zip::ZipArchive;
 
-        let dex_path = extract_dir.join("classes.dex");
-        let jar_path = extract_dir.join("classes.jar");
+        let file = File::open(apk_path)?;
+        let archive = ZipArchive::new(file).map_err(|e| invalid_apk(apk_path, &e.to_string()))?;
 
-        self.tool_manager.run_dex2jar(&[&dex_path.to_string_lossy(), "-o", &jar_path.to_string_lossy()]).await?;
+        let mut entries: Vec<(u32, String)> = archive
+            .file_names()
+            .filter_map(|name| dex_index(name).map(|index| (index, name.to_string())))
+            .collect();
+        entries.sort();
 
-        // Remove the DEX file after conversion
-        fs::remove_file(&dex_path).await
-            .map_err(|e| anyhow::anyhow!("Failed to remove DEX file: {}", e))?;
+        Ok(entries.into_iter().map(|(_, name)| name).collect())
+    }
 
-        Ok(())
+    async fn convert_dex_to_jar(&self, dex_files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut jar_files = Vec::with_capacity(dex_files.len());
+
+        for dex_path in dex_files {
+            let dex_name = dex_path.file_name().unwrap_or_default().to_string_lossy();
+            self.tool_manager.report(&format!("[+] Converting {} to jar", dex_name));
+
+            let jar_path = dex_path.with_extension("jar");
+            self.tool_manager.run_dex2jar(&[&dex_path.to_string_lossy(), "-o", &jar_path.to_string_lossy()]).await?;
+
+            // Remove the DEX file after conversion
+            fs::remove_file(dex_path).await
+                .map_err(|e| anyhow::anyhow!("Failed to remove DEX file: {}", e))?;
+
+            jar_files.push(jar_path);
+        }
+
+        Ok(jar_files)
     }
 
-    async fn decompile_jar(&self, extract_dir: &Path) -> Result<()> {
-        println!("[+] Decompiling jar files");
+    async fn decompile_jar(&self, extract_dir: &Path, jar_files: &[PathBuf]) -> Result<Option<CacheSummary>> {
+        if jar_files.is_empty() {
+            return Ok(None);
+        }
+        self.tool_manager.report(&format!("[+] Decompiling jar files with {}", self.decompiler));
 
         let src_dir = extract_dir.join("src");
-        let jar_path = extract_dir.join("classes.jar");
 
         // Remove existing src directory
         if src_dir.exists() {
@@ -116,8 +621,220 @@ impl Extractor {
         fs::create_dir_all(&src_dir).await
             .map_err(|e| anyhow::anyhow!("Failed to create src directory: {}", e))?;
 
-        // Run Procyon decompiler
-        self.tool_manager.run_procyon(&["-jar", &jar_path.to_string_lossy(), "-o", &src_dir.to_string_lossy()]).await
+        let Some(cache) = &self.cache else {
+            // Run the decompiler on every JAR; all of them share the same source tree
+            for jar_path in jar_files {
+                self.tool_manager.run_decompiler(self.decompiler, jar_path, &src_dir).await?;
+            }
+            return Ok(None);
+        };
+
+        let mut summary = CacheSummary::default();
+        let work_dir = tempfile::tempdir()?;
+
+        for (i, jar_path) in jar_files.iter().enumerate() {
+            let keys = cache::source_keys(jar_path)?;
+            let (hits, missing) = self.take_cached(cache, self.decompiler, &keys, &src_dir).await?;
+            summary.hits += hits;
+            summary.decompiled += missing.len();
+
+            // Only classes the cache does not have go through the decompiler
+            if missing.is_empty() {
+                continue;
+            } else if missing.len() == keys.len() {
+                self.tool_manager.run_decompiler(self.decompiler, jar_path, &src_dir).await?;
+            } else {
+                let subset_jar = work_dir.path().join(format!("missing{}.jar", i));
+                decompile::write_subset_jar(jar_path, &missing, &subset_jar)?;
+                self.tool_manager.run_decompiler(self.decompiler, &subset_jar, &src_dir).await?;
+            }
+
+            self.store_cached(cache, self.decompiler, &keys, &missing, &src_dir).await;
+        }
+
+        self.tool_manager.report(&format!("[+] {} classes from the decompilation cache, {} decompiled", summary.hits, summary.decompiled));
+        match cache.trim() {
+            Ok(eviction) => summary.evicted = eviction.removed,
+            Err(e) => self.tool_manager.report(&format!("[!] Could not trim the decompilation cache: {}", e)),
+        }
+
+        Ok(Some(summary))
+    }
+
+    /// Copy the cached sources of `keys` into `src_dir`; returns the number copied and the sources not cached
+    async fn take_cached(&self, cache: &DecompileCache, decompiler: Decompiler, keys: &BTreeMap<String, String>, src_dir: &Path) -> Result<(usize, Vec<String>)> {
+        let mut hits = 0;
+        let mut missing = Vec::new();
+
+        for (source, key) in keys {
+            match cache.get(decompiler, key) {
+                Some(text) => {
+                    let target = src_dir.join(source);
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent).await?;
+                    }
+                    fs::write(&target, text).await?;
+                    hits += 1;
+                }
+                None => missing.push(source.clone()),
+            }
+        }
+        Ok((hits, missing))
+    }
+
+    /// Add the complete sources among `sources` to the cache; a cache that cannot be written only warns
+    async fn store_cached(&self, cache: &DecompileCache, decompiler: Decompiler, keys: &BTreeMap<String, String>, sources: &[String], src_dir: &Path) {
+        for source in sources {
+            let Ok(text) = fs::read_to_string(src_dir.join(source)).await else { continue };
+            if decompile::looks_failed(&text) {
+                continue;
+            }
+            if let Err(e) = cache.put(decompiler, &keys[source], &text) {
+                self.tool_manager.report(&format!("[!] Could not write to the decompilation cache: {}", e));
+                return;
+            }
+        }
+    }
+
+    /// `kept` holds the entries of sources kept from the last unpack
+    async fn retry_failed_classes(&self, extract_dir: &Path, jar_files: &[PathBuf], kept: Option<DecompilerReport>) -> Result<Option<DecompileSummary>> {
+        let src_dir = extract_dir.join("src");
+        let (files, failed) = kept.map(|kept| (kept.files, kept.failed)).unwrap_or_default();
+        let mut report = DecompilerReport {
+            primary: self.decompiler,
+            fallback: self.fallback_decompiler,
+            files,
+            failed,
+        };
+
+        // Sources the primary decompiler did not produce completely, grouped by JAR
+        let mut failed_by_jar = Vec::new();
+        for jar_path in jar_files {
+            let mut failed = Vec::new();
+            for source in decompile::top_level_sources(jar_path)? {
+                if source_ok(&src_dir.join(&source)).await {
+                    report.files.insert(source, self.decompiler);
+                } else {
+                    failed.push(source);
+                }
+            }
+            failed_by_jar.push((jar_path, failed));
+        }
+
+        let retried: usize = failed_by_jar.iter().map(|(_, failed)| failed.len()).sum();
+        let mut summary = None;
+
+        if let Some(fallback) = self.fallback_decompiler.filter(|_| retried > 0) {
+            self.tool_manager.report(&format!("[+] Retrying {} classes with {}", retried, fallback));
+
+            let work_dir = tempfile::tempdir()?;
+            let mut recovered = 0;
+
+            for (i, (jar_path, failed)) in failed_by_jar.iter_mut().enumerate() {
+                if failed.is_empty() {
+                    continue;
+                }
+
+                // Classes the fallback decompiled before come from the cache
+                let mut keys = BTreeMap::new();
+                if let Some(cache) = &self.cache {
+                    keys = cache::source_keys(jar_path)?;
+                    keys.retain(|source, _| failed.contains(source));
+
+                    let (_, missing) = self.take_cached(cache, fallback, &keys, &src_dir).await?;
+                    for source in failed.iter().filter(|source| !missing.contains(source)) {
+                        report.files.insert(source.clone(), fallback);
+                        recovered += 1;
+                    }
+                    *failed = missing;
+                    if failed.is_empty() {
+                        continue;
+                    }
+                }
+
+                let subset_jar = work_dir.path().join(format!("retry{}.jar", i));
+                let retry_dir = work_dir.path().join(format!("src{}", i));
+                decompile::write_subset_jar(jar_path, failed, &subset_jar)?;
+
+                // A crashing fallback must not lose the primary output, so only report it
+                if let Err(e) = self.tool_manager.run_decompiler(fallback, &subset_jar, &retry_dir).await {
+                    self.tool_manager.report(&format!("[!] {}", e));
+                    continue;
+                }
+
+                let mut still_failed = Vec::new();
+                let mut fixed = Vec::new();
+                for source in failed.drain(..) {
+                    let retried_path = retry_dir.join(&source);
+                    if source_ok(&retried_path).await {
+                        let target = src_dir.join(&source);
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent).await?;
+                        }
+                        fs::copy(&retried_path, &target).await?;
+                        report.files.insert(source.clone(), fallback);
+                        fixed.push(source);
+                        recovered += 1;
+                    } else {
+                        still_failed.push(source);
+                    }
+                }
+                *failed = still_failed;
+
+                if let Some(cache) = &self.cache {
+                    self.store_cached(cache, fallback, &keys, &fixed, &retry_dir).await;
+                }
+            }
+
+            summary = Some(DecompileSummary { decompiler: fallback, retried, recovered });
+        }
+
+        // Whatever is still broken keeps the primary output, if there is any
+        for (_, failed) in failed_by_jar {
+            for source in failed {
+                if src_dir.join(&source).exists() {
+                    report.files.insert(source.clone(), self.decompiler);
+                }
+                report.failed.push(source);
+            }
+        }
+
+        fs::write(extract_dir.join(decompile::REPORT_FILE), serde_json::to_string_pretty(&report)?).await?;
+
+        Ok(summary)
+    }
+
+    /// Whether this run produces JARs or smali for a mapping file to apply to
+    fn deobfuscates(&self, bundle: bool) -> bool {
+        self.stages.jar || (self.stages.apktool && self.stages.decode_smali && !bundle)
+    }
+
+    /// Apply `mapping` to freshly converted JARs and to the smali of `project`
+    async fn deobfuscate(&self, mapping: &Mapping, jar_files: &[PathBuf], project: Option<&Path>) -> Result<DeobfuscationSummary> {
+        let classes = mapping::remap_jars(jar_files, mapping)?;
+        let smali = match project {
+            Some(project) => mapping::smali::remap_project(project, mapping)?,
+            None => 0,
+        };
+        self.tool_manager.report(&format!("[+] Restored the names of {} classes in the JARs and {} in smali from {} mapped classes", classes, smali, mapping.len()));
+        Ok(DeobfuscationSummary { classes, smali })
+    }
+
+    /// Write `kotlin.json` and put the Kotlin declarations into the sources; `None` if there is no Kotlin
+    async fn recover_kotlin(&self, extract_dir: &Path, jar_files: &[PathBuf]) -> Result<Option<KotlinSummary>> {
+        let report = KotlinReport::from_jars(jar_files)?;
+        if report.is_empty() {
+            return Ok(None);
+        }
+
+        let annotated = kotlin::annotate_sources(&report, &extract_dir.join("src"))?;
+        fs::write(extract_dir.join(kotlin::REPORT_FILE), serde_json::to_string_pretty(&report)?).await?;
+        if !report.errors.is_empty() {
+            self.tool_manager.report(&format!("[!] Could not read the Kotlin metadata of {} classes, see {}", report.errors.len(), kotlin::REPORT_FILE));
+        }
+        self.tool_manager.report(&format!("[+] Recovered {} Kotlin classes and {} file facades, annotated {} sources", report.classes.len(), report.facades.len(), annotated));
+
+        Ok(Some(KotlinSummary { classes: report.classes.len(), facades: report.facades.len(), annotated }))
     }
 
     async fn unzip_file(&self, zip_path: &Path, extract_dir: &Path, file_name: &str) -> Result<()> {
@@ -144,4 +861,49 @@ impl Extractor {
 
         Err(anyhow::anyhow!("File {} not found in archive", file_name))
     }
-}
\ No newline at end of file
+}
+
+fn invalid_apk(path: &Path, reason: &str) -> ApkextError {
+    ApkextError::InvalidApk { path: path.to_path_buf(), reason: reason.to_string() }
+}
+
+/// Whether a decompiled source file exists and looks complete
+async fn source_ok(path: &Path) -> bool {
+    match fs::read_to_string(path).await {
+        Ok(source) => !decompile::looks_failed(&source),
+        Err(_) => false,
+    }
+}
+
+/// Load order of a multidex entry: 1 for `classes.dex`, N for `classesN.dex`
+pub fn dex_index(name: &str) -> Option<u32> {
+    let suffix = name.strip_prefix("classes")?.strip_suffix(".dex")?;
+    if suffix.is_empty() {
+        return Some(1);
+    }
+
+    match suffix.parse::<u32>() {
+        Ok(index) if index >= 2 && !suffix.starts_with('0') => Some(index),
+        _ => None,
+    }
+}
+
+/// apktool's `smali/`, `smali_classes2/`, … of a project in load order, then `smali_assets/`
+/// and any other `smali_*` directory
+pub fn smali_dirs(project: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(project) else { return Vec::new() };
+    let mut dirs: Vec<(u32, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let dex = match name.as_str() {
+                "smali" => "classes.dex".to_string(),
+                _ => format!("{}.dex", name.strip_prefix("smali_")?),
+            };
+            Some((dex_index(&dex).unwrap_or(u32::MAX), path))
+        })
+        .collect();
+    dirs.sort();
+    dirs.into_iter().map(|(_, path)| path).collect()
+}
@@ -0,0 +1,226 @@
+use super::extractor::dex_index;
+use crate::arsc::{ResourceTable, ResourceValue, RESOURCES_ENTRY};
+use crate::manifest::{axml, Component, Manifest, Value, MANIFEST_ENTRY};
+use crate::signing::{self, SignatureInfo};
+use crate::{ApkextError, Result};
+use serde::Serialize;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// What `apkext info` reports: everything that can be read from the ZIP without apktool or Java
+#[derive(Debug, Clone, Serialize)]
+pub struct ApkInfo {
+    pub apk: PathBuf,
+    pub file_size: u64,
+    pub manifest: Manifest,
+    /// `android:label` of the application, resolved from `resources.arsc` if it is a reference
+    pub label: Option<String>,
+    /// Locales the resources are translated to, e.g. `de` and `fr-rCA`
+    pub locales: Vec<String>,
+    /// ABIs with native libraries under `lib/`, e.g. `arm64-v8a`
+    pub abis: Vec<String>,
+    /// `classes.dex`, `classes2.dex`, … in load order
+    pub dex_files: Vec<String>,
+    pub signing: SignatureInfo,
+    /// Entry sizes grouped by what the entries are, largest first
+    pub sizes: Vec<SizeBreakdown>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SizeBreakdown {
+    /// `dex`, `native`, `resources`, `assets`, `signature` or `other`
+    pub category: String,
+    pub files: usize,
+    pub compressed: u64,
+    pub uncompressed: u64,
+}
+
+impl ApkInfo {
+    /// Read the metadata of the APK at `apk_path`
+    pub fn read(apk_path: &Path) -> Result<Self, ApkextError> {
+        let invalid = |reason: String| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason };
+
+        if !apk_path.is_file() {
+            return Err(invalid("file does not exist".to_string()));
+        }
+        let data = std::fs::read(apk_path)?;
+        let mut archive = ZipArchive::new(Cursor::new(data.as_slice())).map_err(|e| invalid(format!("not a ZIP file: {}", e)))?;
+
+        let mut manifest_data = Vec::new();
+        archive
+            .by_name(MANIFEST_ENTRY)
+            .map_err(|_| invalid(format!("{} not found", MANIFEST_ENTRY)))?
+            .read_to_end(&mut manifest_data)?;
+        let root = axml::parse(&manifest_data).map_err(|e| invalid(format!("cannot decode {}: {}", MANIFEST_ENTRY, e)))?;
+        let manifest = Manifest::from_element(&root).map_err(|e| invalid(format!("cannot decode {}: {}", MANIFEST_ENTRY, e)))?;
+
+        // A missing or unreadable resource table only costs the label and locales
+        let mut table_data = Vec::new();
+        let table = match archive.by_name(RESOURCES_ENTRY) {
+            Ok(mut entry) => entry.read_to_end(&mut table_data).ok().and_then(|_| ResourceTable::parse(&table_data).ok()),
+            Err(_) => None,
+        };
+        let label = match root.children_named("application").next().and_then(|application| application.attr("label")) {
+            Some(Value::Reference(id)) => table.as_ref().and_then(|table| match table.resolve(*id, "") {
+                Some((_, ResourceValue::Item { value })) => Some(value.to_string()),
+                _ => None,
+            }),
+            Some(value) => Some(value.to_string()),
+            None => None,
+        };
+        let locales = table.as_ref().map(ResourceTable::locales).unwrap_or_default();
+
+        let mut abis = Vec::new();
+        let mut dex_files = Vec::new();
+        let mut sizes: Vec<SizeBreakdown> = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name();
+
+            if let Some(abi) = name.strip_prefix("lib/").and_then(|rest| rest.split_once('/')).map(|(abi, _)| abi) {
+                if !abis.iter().any(|known| known == abi) {
+                    abis.push(abi.to_string());
+                }
+            }
+            if dex_index(name).is_some() {
+                dex_files.push(name.to_string());
+            }
+
+            let category = category(name);
+            let index = match sizes.iter().position(|size| size.category == category) {
+                Some(index) => index,
+                None => {
+                    sizes.push(SizeBreakdown { category: category.to_string(), files: 0, compressed: 0, uncompressed: 0 });
+                    sizes.len() - 1
+                }
+            };
+            sizes[index].files += 1;
+            sizes[index].compressed += entry.compressed_size();
+            sizes[index].uncompressed += entry.size();
+        }
+        abis.sort();
+        dex_files.sort_by_key(|name| dex_index(name));
+        sizes.sort_by_key(|size| std::cmp::Reverse(size.compressed));
+
+        let signing = signing::inspect(&data).map_err(|e| invalid(format!("cannot read signatures: {}", e)))?;
+
+        Ok(Self {
+            apk: apk_path.to_path_buf(),
+            file_size: data.len() as u64,
+            manifest,
+            label,
+            locales,
+            abis,
+            dex_files,
+            signing,
+            sizes,
+        })
+    }
+}
+
+fn category(name: &str) -> &'static str {
+    if dex_index(name).is_some() {
+        "dex"
+    } else if name.starts_with("lib/") {
+        "native"
+    } else if name.starts_with("res/") || name == "resources.arsc" {
+        "resources"
+    } else if name.starts_with("assets/") {
+        "assets"
+    } else if name.starts_with("META-INF/") {
+        "signature"
+    } else {
+        "other"
+    }
+}
+
+/// `12.3 KB` style sizes for people
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+impl fmt::Display for ApkInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let manifest = &self.manifest;
+        let optional = |value: Option<u32>| value.map_or("-".to_string(), |value| value.to_string());
+
+        writeln!(f, "Package:      {}", manifest.package)?;
+        if let Some(label) = &self.label {
+            writeln!(f, "Label:        {}", label)?;
+        }
+        writeln!(
+            f,
+            "Version:      {} ({})",
+            manifest.version_name.as_deref().unwrap_or("-"),
+            manifest.version_code.map_or("-".to_string(), |code| code.to_string())
+        )?;
+        writeln!(f, "SDK:          min {}, target {}, max {}", optional(manifest.min_sdk), optional(manifest.target_sdk), optional(manifest.max_sdk))?;
+        if manifest.debuggable {
+            writeln!(f, "Debuggable:   yes")?;
+        }
+        writeln!(f, "File size:    {}", format_size(self.file_size))?;
+        writeln!(f, "DEX files:    {}", self.dex_files.len())?;
+        writeln!(f, "Locales:      {}", if self.locales.is_empty() { "default only".to_string() } else { self.locales.join(", ") })?;
+        writeln!(f, "Native ABIs:  {}", if self.abis.is_empty() { "none".to_string() } else { self.abis.join(", ") })?;
+        writeln!(f, "Signatures:   {}", if self.signing.schemes.is_empty() { "unsigned".to_string() } else { self.signing.schemes.join(", ") })?;
+        for certificate in &self.signing.certificates {
+            writeln!(f, "Certificate:  {}", certificate.subject)?;
+            writeln!(f, "  Issuer:     {}", certificate.issuer)?;
+            writeln!(f, "  SHA-256:    {}", certificate.sha256)?;
+            writeln!(f, "  Valid:      {} to {}", certificate.not_before, certificate.not_after)?;
+        }
+
+        write_list(f, "Permissions", &manifest.permissions)?;
+        write_components(f, "Activities", &manifest.activities)?;
+        write_components(f, "Services", &manifest.services)?;
+        write_components(f, "Receivers", &manifest.receivers)?;
+        write_components(f, "Providers", &manifest.providers)?;
+
+        writeln!(f)?;
+        writeln!(f, "{:<10} {:>6} {:>12} {:>12}", "Contents", "Files", "Compressed", "Size")?;
+        for size in &self.sizes {
+            writeln!(f, "{:<10} {:>6} {:>12} {:>12}", size.category, size.files, format_size(size.compressed), format_size(size.uncompressed))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, title: &str, items: &[String]) -> fmt::Result {
+    writeln!(f)?;
+    writeln!(f, "{} ({}):", title, items.len())?;
+    for item in items {
+        writeln!(f, "  {}", item)?;
+    }
+    Ok(())
+}
+
+fn write_components(f: &mut fmt::Formatter<'_>, title: &str, components: &[Component]) -> fmt::Result {
+    if components.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = components
+        .iter()
+        .map(|component| match component.exported {
+            Some(true) => format!("{} (exported)", component.name),
+            _ => component.name.clone(),
+        })
+        .collect();
+    write_list(f, title, &lines)
+}
@@ -1,5 +1,25 @@
 pub mod extractor;
 pub mod builder;
+pub mod report;
+pub mod decompile;
+pub mod output;
+pub mod stages;
+pub mod batch;
+pub mod splits;
+pub mod info;
+pub mod diff;
+pub mod cache;
+pub mod search;
 
 pub use extractor::Extractor;
-pub use builder::Builder;
\ No newline at end of file
+pub use builder::Builder;
+pub use report::{CacheSummary, DecompileSummary, DeobfuscationSummary, KotlinSummary, PackReport, SigningReport, StepTiming, UnpackReport};
+pub use decompile::DecompilerReport;
+pub use output::{ExistingOutput, MergeSummary, ReusedOutputs, StageInputs};
+pub use stages::UnpackStages;
+pub use batch::BatchOutcome;
+pub use splits::{ContainerKind, SplitSet};
+pub use info::ApkInfo;
+pub use diff::ApkDiff;
+pub use cache::DecompileCache;
+pub use search::{SearchQuery, SearchReport};
@@ -0,0 +1,223 @@
+use super::stages::UnpackStages;
+use crate::tools::Decompiler;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Hashes of the generated files, used by `--merge` to tell user edits apart
+pub const MANIFEST_FILE: &str = ".apkext-files.json";
+
+/// Inputs each stage last ran on, used by `--merge` to skip stages whose input did not change
+pub const STAGES_FILE: &str = ".apkext-stages.json";
+
+/// What `unpack` does when the output directory already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExistingOutput {
+    /// Fail without touching the directory
+    #[default]
+    Refuse,
+    /// Delete the directory and unpack from scratch
+    Force,
+    /// Refresh generated files, keep the ones the user modified
+    Merge,
+}
+
+/// Outcome of merging a fresh unpack into an existing directory
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeSummary {
+    /// Generated files written or refreshed
+    pub updated: usize,
+    /// Files the user modified since the last unpack, left as they are
+    pub kept: Vec<PathBuf>,
+    /// Unmodified files from the last unpack that are no longer generated
+    pub removed: usize,
+}
+
+/// Outputs of the last unpack that a merge keeps because their inputs did not change
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReusedOutputs {
+    /// `unpacked/` was not decoded again
+    pub unpacked: bool,
+    /// JARs that were not converted again, e.g. `classes2.jar`
+    pub jars: Vec<String>,
+    /// JARs whose sources in `src/` were not decompiled again
+    pub sources: Vec<String>,
+    /// Those sources, relative to `src/`
+    #[serde(skip)]
+    pub source_files: BTreeSet<String>,
+}
+
+impl ReusedOutputs {
+    /// Whether the file at `relative` (`/`-separated, relative to the output directory) is kept
+    pub fn contains(&self, relative: &str) -> bool {
+        (self.unpacked && relative.starts_with("unpacked/"))
+            || self.jars.iter().any(|jar| jar == relative)
+            || relative.strip_prefix("src/").is_some_and(|source| self.source_files.contains(source))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.unpacked && self.jars.is_empty() && self.sources.is_empty()
+    }
+}
+
+/// What the outputs of the last unpack were generated from (`.apkext-stages.json`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageInputs {
+    /// Input of `unpacked/`; `None` if apktool never ran
+    pub apktool: Option<ApktoolInput>,
+    /// DEX name → SHA-256 of the DEX file its JAR was converted from
+    pub jars: BTreeMap<String, String>,
+    /// DEX name → SHA-256 of the DEX file its sources were decompiled from
+    pub sources: BTreeMap<String, String>,
+    /// Primary and fallback decompiler that produced `src/`
+    pub decompilers: Option<(Decompiler, Option<Decompiler>)>,
+    /// SHA-256 of the mapping file the JARs and smali were deobfuscated with
+    pub mapping: Option<String>,
+}
+
+/// What apktool decoded into `unpacked/`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApktoolInput {
+    /// SHA-256 of the APK, or of the base APK of a split container
+    pub sha256: String,
+    pub decode_resources: bool,
+    pub decode_smali: bool,
+}
+
+impl StageInputs {
+    /// Inputs recorded in `dir`; empty when there are none or they cannot be read
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join(STAGES_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(STAGES_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FileManifest {
+    /// Path relative to the output directory → SHA-256 of the generated content
+    files: BTreeMap<String, String>,
+}
+
+impl FileManifest {
+    fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Record the hashes of everything under a freshly unpacked directory
+pub fn write_manifest(dir: &Path) -> Result<()> {
+    let mut manifest = FileManifest::default();
+    for (relative, path) in generated_files(dir)? {
+        manifest.files.insert(relative, file_sha256(&path)?);
+    }
+    manifest.save(dir)
+}
+
+/// Copy a fresh unpack from `staging` over `output`, keeping files the user changed
+///
+/// A file counts as user-modified when its content differs from what the last unpack
+/// recorded in the manifest; without a manifest any existing, differing file is kept.
+/// Files belonging to stages that did not run this time, and `reused` outputs, are left untouched.
+pub fn merge(staging: &Path, output: &Path, stages: &UnpackStages, reused: &ReusedOutputs) -> Result<MergeSummary> {
+    let previous = FileManifest::load(output);
+    let mut manifest = FileManifest::default();
+    let mut summary = MergeSummary::default();
+
+    for (relative, source) in generated_files(staging)? {
+        let target = output.join(&relative);
+        let new_hash = file_sha256(&source)?;
+
+        if target.exists() {
+            let current_hash = file_sha256(&target)?;
+            let unmodified = previous.files.get(&relative) == Some(&current_hash);
+
+            if current_hash != new_hash && !unmodified {
+                summary.kept.push(PathBuf::from(&relative));
+                // Keep tracking the generated content so the file stays "modified" next time
+                manifest.files.insert(relative, new_hash);
+                continue;
+            }
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&source, &target)?;
+        manifest.files.insert(relative, new_hash);
+        summary.updated += 1;
+    }
+
+    for (relative, old_hash) in &previous.files {
+        if manifest.files.contains_key(relative) {
+            continue;
+        }
+
+        if !stages.produces(relative) || reused.contains(relative) {
+            manifest.files.insert(relative.clone(), old_hash.clone());
+            continue;
+        }
+
+        let target = output.join(relative);
+        if target.is_file() && &file_sha256(&target)? == old_hash {
+            fs::remove_file(&target)?;
+            summary.removed += 1;
+        }
+    }
+
+    manifest.save(output)?;
+    Ok(summary)
+}
+
+/// Regular files under `dir` keyed by their `/`-separated relative path
+fn generated_files(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir)?;
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if relative != MANIFEST_FILE && relative != STAGES_FILE {
+            files.push((relative, entry.into_path()));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Hex SHA-256 of a file's content
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
@@ -0,0 +1,169 @@
+use super::decompile::REPORT_FILE;
+use super::output::{MergeSummary, ReusedOutputs};
+use super::splits::SplitSet;
+use super::stages::UnpackStages;
+use crate::signing::VerifyReport;
+use crate::tools::Decompiler;
+use crate::Result;
+use serde::Serialize;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Wall-clock duration of a single pipeline step
+#[derive(Debug, Clone, Serialize)]
+pub struct StepTiming {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+/// Result of a successful `Extractor::unpack`
+#[derive(Debug, Clone, Serialize)]
+pub struct UnpackReport {
+    pub apk: PathBuf,
+    pub output_dir: PathBuf,
+    pub unpacked_dir: PathBuf,
+    /// One JAR per DEX file: `classes.jar`, `classes2.jar`, …
+    pub jars: Vec<PathBuf>,
+    pub src_dir: PathBuf,
+    pub stages: UnpackStages,
+    /// Layout of the split container; `None` for a plain APK
+    pub splits: Option<SplitSet>,
+    /// Modules of an Android App Bundle, `base` first; empty for APKs
+    pub modules: Vec<String>,
+    /// Signature verification of the APK, or of the base APK of a split container; `None` for bundles
+    pub signature: Option<VerifyReport>,
+    pub decompiler: Decompiler,
+    /// Which decompiler produced each source file (`decompilers.json`)
+    pub decompiler_report: PathBuf,
+    /// `None` if no fallback decompiler ran
+    pub fallback: Option<DecompileSummary>,
+    /// `None` if the decompilation cache is off or nothing was decompiled
+    pub cache: Option<CacheSummary>,
+    /// `None` if no class carries Kotlin metadata or Kotlin recovery is off
+    pub kotlin: Option<KotlinSummary>,
+    /// `None` unless a mapping file was given
+    pub deobfuscation: Option<DeobfuscationSummary>,
+    /// `None` unless the unpack was merged into an existing directory
+    pub merge: Option<MergeSummary>,
+    /// Outputs kept from the last unpack because their inputs did not change; `None` unless merging
+    pub reused: Option<ReusedOutputs>,
+    pub steps: Vec<StepTiming>,
+    pub total_ms: u64,
+}
+
+/// Outcome of retrying failed classes with the fallback decompiler
+#[derive(Debug, Clone, Serialize)]
+pub struct DecompileSummary {
+    pub decompiler: Decompiler,
+    /// Classes retried because the primary output was missing or incomplete
+    pub retried: usize,
+    /// Retried classes whose fallback output replaced the primary one
+    pub recovered: usize,
+}
+
+/// How many sources the decompilation cache provided
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheSummary {
+    /// Sources taken from the cache instead of running the decompiler
+    pub hits: usize,
+    /// Sources the decompiler had to produce
+    pub decompiled: usize,
+    /// Least recently used sources evicted to stay under the size limit
+    pub evicted: usize,
+}
+
+/// What the `@kotlin.Metadata` annotations gave back (`kotlin.json`)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KotlinSummary {
+    /// Classes, interfaces and objects with Kotlin declarations
+    pub classes: usize,
+    /// Classes holding top-level functions and properties
+    pub facades: usize,
+    /// Sources that got Kotlin declarations as comments
+    pub annotated: usize,
+}
+
+/// What a ProGuard or R8 mapping file renamed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeobfuscationSummary {
+    /// Classes that got their original name back in the JARs
+    pub classes: usize,
+    /// Classes that got their original name back in the smali
+    pub smali: usize,
+}
+
+impl UnpackReport {
+    pub fn new(apk: &Path, output_dir: &Path, decompiler: Decompiler, stages: UnpackStages) -> Self {
+        Self {
+            apk: apk.to_path_buf(),
+            output_dir: output_dir.to_path_buf(),
+            unpacked_dir: output_dir.join("unpacked"),
+            jars: Vec::new(),
+            src_dir: output_dir.join("src"),
+            stages,
+            splits: None,
+            modules: Vec::new(),
+            signature: None,
+            decompiler,
+            decompiler_report: output_dir.join(REPORT_FILE),
+            fallback: None,
+            cache: None,
+            kotlin: None,
+            deobfuscation: None,
+            merge: None,
+            reused: None,
+            steps: Vec::new(),
+            total_ms: 0,
+        }
+    }
+}
+
+/// Result of a successful `Builder::pack`
+#[derive(Debug, Clone, Serialize)]
+pub struct PackReport {
+    pub input_dir: PathBuf,
+    pub output_apk: PathBuf,
+    /// Splits rebuilt next to the base APK; empty for a single APK
+    pub splits: Vec<String>,
+    /// `None` when signing was disabled
+    pub signing: Option<SigningReport>,
+    pub steps: Vec<StepTiming>,
+    pub total_ms: u64,
+}
+
+impl PackReport {
+    pub fn new(input_dir: &Path, output_apk: &Path) -> Self {
+        Self {
+            input_dir: input_dir.to_path_buf(),
+            output_apk: output_apk.to_path_buf(),
+            splits: Vec::new(),
+            signing: None,
+            steps: Vec::new(),
+            total_ms: 0,
+        }
+    }
+}
+
+/// Signatures applied by `Builder::pack`
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningReport {
+    /// Enabled schemes, e.g. `["v1", "v2", "v3"]`
+    pub schemes: Vec<String>,
+    /// SHA-256 fingerprint of the signing certificate
+    pub certificate_sha256: String,
+}
+
+/// Run a pipeline step and record how long it took
+pub(crate) async fn timed<T, E, F>(steps: &mut Vec<StepTiming>, name: &str, step: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    let output = step.await?;
+    steps.push(StepTiming {
+        name: name.to_string(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+    Ok(output)
+}
@@ -0,0 +1,285 @@
+//! Regex search over everything `unpack` wrote: decompiled Java, smali, decoded XML,
+//! string resources and assets
+
+use crate::aab::DECODED_DIR;
+use crate::apk::extractor::smali_dirs;
+use crate::apk::splits::SPLITS_DIR;
+use crate::error::ApkextError;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Longest part of a matching line that is reported, in characters
+const MAX_LINE_LENGTH: usize = 200;
+
+/// Assets with a NUL byte in their first few kilobytes are binary and not searched
+const BINARY_PROBE_SIZE: usize = 8192;
+
+/// Where a match was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// Decompiled sources in `src/`
+    Java,
+    /// apktool's `smali*/` directories
+    Smali,
+    /// The manifest and XML resources other than strings
+    Xml,
+    /// `strings.xml`, `plurals.xml` and `arrays.xml` of every `values*/` directory
+    Strings,
+    /// Text files under `assets/`
+    Assets,
+}
+
+/// What to search for and where
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pattern: String,
+    ignore_case: bool,
+    fixed_strings: bool,
+    origins: Vec<Origin>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub origin: Origin,
+    /// Relative to the output directory
+    pub path: PathBuf,
+    /// 1-based
+    pub line: usize,
+    /// The matching line without leading whitespace, shortened to 200 characters
+    pub text: String,
+    /// The string resource the line belongs to, e.g. `string/app_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    /// For smali, the decompiled source of the same class, relative to the output directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchReport {
+    pub pattern: String,
+    /// Number of files read
+    pub files: usize,
+    /// Matches in order of origin, then path and line
+    pub hits: Vec<SearchHit>,
+}
+
+impl Origin {
+    pub const ALL: [Origin; 5] = [Origin::Java, Origin::Smali, Origin::Xml, Origin::Strings, Origin::Assets];
+
+    fn title(self) -> &'static str {
+        match self {
+            Origin::Java => "Java sources",
+            Origin::Smali => "Smali",
+            Origin::Xml => "XML",
+            Origin::Strings => "String resources",
+            Origin::Assets => "Assets",
+        }
+    }
+}
+
+impl SearchQuery {
+    /// A case-sensitive regular expression searched in every origin
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self { pattern: pattern.into(), ignore_case: false, fixed_strings: false, origins: Origin::ALL.to_vec() }
+    }
+
+    pub fn with_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Match the pattern as plain text rather than as a regular expression
+    pub fn with_fixed_strings(mut self, fixed_strings: bool) -> Self {
+        self.fixed_strings = fixed_strings;
+        self
+    }
+
+    /// Only search these origins; all of them when empty
+    pub fn with_origins(mut self, origins: Vec<Origin>) -> Self {
+        self.origins = if origins.is_empty() { Origin::ALL.to_vec() } else { origins };
+        self
+    }
+
+    /// Search the output directory of an unpack
+    pub fn run(&self, dir: &Path) -> Result<SearchReport, ApkextError> {
+        let invalid = |reason: String| ApkextError::InvalidProject { path: dir.to_path_buf(), reason };
+        let pattern = if self.fixed_strings { regex::escape(&self.pattern) } else { self.pattern.clone() };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|e| ApkextError::Other(anyhow::anyhow!("Invalid pattern '{}': {}", self.pattern, e)))?;
+
+        let projects = project_dirs(dir);
+        if projects.is_empty() && !dir.join("src").is_dir() {
+            return Err(invalid("no src/, unpacked/ or decoded/ directory; not an unpack output".to_string()));
+        }
+
+        let mut report = SearchReport { pattern: self.pattern.clone(), files: 0, hits: Vec::new() };
+        let mut files = Vec::new();
+        if self.origins.contains(&Origin::Java) {
+            files.extend(walk(&dir.join("src")).filter(|path| has_extension(path, "java")).map(|path| (Origin::Java, path)));
+        }
+        for project in &projects {
+            files.extend(self.project_files(project));
+        }
+        if self.origins.contains(&Origin::Xml) && dir.join("AndroidManifest.xml").is_file() {
+            files.push((Origin::Xml, dir.join("AndroidManifest.xml")));
+        }
+        files.sort();
+
+        for (origin, path) in files {
+            let Some(text) = read_text(&path, origin) else { continue };
+            report.files += 1;
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+            let source = match origin {
+                Origin::Smali => decompiled_source(dir, &relative),
+                _ => None,
+            };
+            search_text(&regex, &text, |line, text, resource| {
+                report.hits.push(SearchHit { origin, path: relative.clone(), line, text, resource, source: source.clone() });
+            });
+        }
+        Ok(report)
+    }
+
+    /// Smali, XML, string resource and asset files of one apktool project, as asked for
+    fn project_files(&self, project: &Path) -> Vec<(Origin, PathBuf)> {
+        let mut files = Vec::new();
+        let wanted = |origin| self.origins.contains(&origin);
+
+        if wanted(Origin::Smali) {
+            for smali_dir in smali_dirs(project) {
+                files.extend(walk(&smali_dir).filter(|path| has_extension(path, "smali")).map(|path| (Origin::Smali, path)));
+            }
+        }
+        if wanted(Origin::Xml) && project.join("AndroidManifest.xml").is_file() {
+            files.push((Origin::Xml, project.join("AndroidManifest.xml")));
+        }
+        for path in walk(&project.join("res")).filter(|path| has_extension(path, "xml")) {
+            let origin = if is_string_resource(&path) { Origin::Strings } else { Origin::Xml };
+            if wanted(origin) {
+                files.push((origin, path));
+            }
+        }
+        if wanted(Origin::Assets) {
+            files.extend(walk(&project.join("assets")).map(|path| (Origin::Assets, path)));
+        }
+        files
+    }
+}
+
+/// apktool projects in an output directory: `unpacked/`, every split in `splits/` and every
+/// module of an app bundle in `decoded/`
+fn project_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut projects = Vec::new();
+    if dir.join("unpacked").is_dir() {
+        projects.push(dir.join("unpacked"));
+    }
+    for parent in [SPLITS_DIR, DECODED_DIR] {
+        let Ok(entries) = fs::read_dir(dir.join(parent)) else { continue };
+        let mut dirs: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_dir()).collect();
+        dirs.sort();
+        projects.extend(dirs);
+    }
+    projects
+}
+
+/// Files under `dir`, or none if it does not exist
+fn walk(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()).filter(|entry| entry.file_type().is_file()).map(|entry| entry.into_path())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension() == Some(extension.as_ref())
+}
+
+/// `res/values*/strings.xml`, `plurals.xml` or `arrays.xml`
+fn is_string_resource(path: &Path) -> bool {
+    let in_values = path.parent().and_then(Path::file_name).is_some_and(|dir| dir.to_string_lossy().starts_with("values"));
+    let name = path.file_name().unwrap_or_default();
+    in_values && (name == "strings.xml" || name == "plurals.xml" || name == "arrays.xml")
+}
+
+/// The text of a file, or `None` for an asset that looks binary
+fn read_text(path: &Path, origin: Origin) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    if origin == Origin::Assets && data[..data.len().min(BINARY_PROBE_SIZE)].contains(&0) {
+        return None;
+    }
+    Some(match String::from_utf8(data) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    })
+}
+
+/// `src/com/example/Foo.java` for `unpacked/smali_classes2/com/example/Foo$1.smali`, if it exists
+fn decompiled_source(dir: &Path, smali: &Path) -> Option<PathBuf> {
+    // Skip the project and smali directories: unpacked/smali, splits/<name>/smali, …
+    let mut components = smali.components().map(|component| component.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>();
+    let smali_dir = components.iter().position(|component| component == "smali" || component.starts_with("smali_"))?;
+    components.drain(..=smali_dir);
+
+    let class = components.join("/");
+    let class = class.strip_suffix(".smali")?;
+    let outer = class.split('$').next().unwrap_or(class);
+    let source = PathBuf::from("src").join(format!("{}.java", outer));
+    dir.join(&source).is_file().then_some(source)
+}
+
+/// Call `hit` with the line number, shortened text and string resource of each matching line
+fn search_text(regex: &Regex, text: &str, mut hit: impl FnMut(usize, String, Option<String>)) {
+    // The resource a line of a values file belongs to; values can span several lines
+    let mut resource = None;
+    for (i, line) in text.lines().enumerate() {
+        if let Some(name) = resource_name(line) {
+            resource = Some(name);
+        }
+        if regex.is_match(line) {
+            let trimmed = line.trim_start();
+            let text = match trimmed.char_indices().nth(MAX_LINE_LENGTH) {
+                Some((end, _)) => format!("{}...", &trimmed[..end]),
+                None => trimmed.to_string(),
+            };
+            hit(i + 1, text, resource.clone());
+        }
+    }
+}
+
+/// `string/app_name` for a line opening `<string name="app_name">`
+fn resource_name(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let (kind, rest) = [("string", "<string "), ("plurals", "<plurals "), ("array", "<string-array "), ("array", "<integer-array "), ("array", "<array ")]
+        .into_iter()
+        .find_map(|(kind, tag)| line.strip_prefix(tag).map(|rest| (kind, rest)))?;
+    let name = rest.split("name=\"").nth(1)?.split('"').next()?;
+    Some(format!("{}/{}", kind, name))
+}
+
+impl fmt::Display for SearchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} matches of '{}' in {} files", self.hits.len(), self.pattern, self.files)?;
+        for origin in Origin::ALL {
+            let hits: Vec<&SearchHit> = self.hits.iter().filter(|hit| hit.origin == origin).collect();
+            if hits.is_empty() {
+                continue;
+            }
+
+            writeln!(f)?;
+            writeln!(f, "{} ({}):", origin.title(), hits.len())?;
+            for hit in hits {
+                let resource = hit.resource.as_deref().map(|resource| format!(" [{}]", resource)).unwrap_or_default();
+                writeln!(f, "  {}:{}{}: {}", hit.path.display(), hit.line, resource, hit.text)?;
+                if let Some(source) = &hit.source {
+                    writeln!(f, "    source: {}", source.display())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Describes the split set an output directory was unpacked from; `pack` rebuilds the set from it
+pub const SPLITS_FILE: &str = "splits.json";
+
+/// Files of the container that are not APKs (`toc.pb`, `manifest.json`, icons, OBBs)
+pub const CONTAINER_DIR: &str = "container";
+
+/// Split APKs, each decoded by apktool into `splits/<name>/`
+pub const SPLITS_DIR: &str = "splits";
+
+/// Resources, native libraries and assets of the base and all splits, copied together for browsing
+pub const MERGED_DIR: &str = "merged";
+
+/// Archive formats that bundle a base APK with its splits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerKind {
+    /// `bundletool build-apks` output
+    Apks,
+    /// APKPure and similar stores
+    Xapk,
+    /// APKMirror
+    Apkm,
+}
+
+impl ContainerKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("apks") => Some(ContainerKind::Apks),
+            Some("xapk") => Some(ContainerKind::Xapk),
+            Some("apkm") => Some(ContainerKind::Apkm),
+            _ => None,
+        }
+    }
+}
+
+/// One split APK of the set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitEntry {
+    /// Directory under `splits/`, e.g. `config.arm64_v8a`
+    pub name: String,
+    /// Path of the APK inside the container
+    pub entry: String,
+}
+
+/// Layout of a split container, recorded in `splits.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitSet {
+    pub container: ContainerKind,
+    /// Path of the base APK inside the container
+    pub base: String,
+    pub splits: Vec<SplitEntry>,
+}
+
+impl SplitSet {
+    /// The split set recorded in `dir`, if it was unpacked from a container
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(SPLITS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(SPLITS_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Extract `container` into `apks_dir` (the APKs) and `metadata_dir` (everything else)
+///
+/// APKs keep their container paths below `apks_dir`.
+pub fn extract_container(container: &Path, apks_dir: &Path, metadata_dir: &Path) -> Result<SplitSet> {
+    let kind = ContainerKind::from_path(container)
+        .ok_or_else(|| anyhow::anyhow!("Not a split APK container: {}", container.display()))?;
+    let mut archive = ZipArchive::new(File::open(container)?)?;
+
+    let mut apks = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(relative) = file.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+
+        let name = file.name().to_string();
+        let target = if name.ends_with(".apk") {
+            // bundletool also ships standalone APKs for old devices; they duplicate the splits
+            if name.starts_with("standalones/") {
+                continue;
+            }
+            apks.push(name);
+            apks_dir.join(&relative)
+        } else {
+            metadata_dir.join(&relative)
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&target)?)?;
+    }
+
+    let base = base_entry(&apks)
+        .ok_or_else(|| anyhow::anyhow!("Could not find the base APK among {}", apks.join(", ")))?
+        .to_string();
+
+    let mut splits: Vec<SplitEntry> = apks
+        .iter()
+        .filter(|entry| **entry != base)
+        .map(|entry| SplitEntry { name: split_name(entry), entry: entry.clone() })
+        .collect();
+    splits.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(SplitSet { container: kind, base, splits })
+}
+
+/// The base APK: `base.apk` or `base-master.apk`, otherwise the only APK that is not a config split
+pub fn base_entry(entries: &[String]) -> Option<&str> {
+    let stem = |entry: &str| Path::new(entry).file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+    if let Some(base) = entries.iter().find(|entry| matches!(stem(entry).as_str(), "base" | "base-master")) {
+        return Some(base);
+    }
+
+    let mut candidates = entries.iter().filter(|entry| {
+        let stem = stem(entry);
+        !stem.starts_with("config.") && !stem.starts_with("split_") && !stem.starts_with("base-")
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(base), None) => Some(base),
+        _ => None,
+    }
+}
+
+/// Directory name of a split: its file name without `.apk` and without the `split_` prefix
+pub fn split_name(entry: &str) -> String {
+    let stem = Path::new(entry).file_stem().unwrap_or_default().to_string_lossy();
+    stem.strip_prefix("split_").unwrap_or(&stem).to_string()
+}
+
+/// Copy `res/`, `lib/` and `assets/` of the base and then of every split into `merged`
+///
+/// Config splits only add qualified resources (`values-fr`, `drawable-xxhdpi`) and ABI
+/// directories, so the copies rarely collide; where they do, the later split wins.
+pub fn merge_resources(base_dir: &Path, split_dirs: &[PathBuf], merged: &Path) -> Result<()> {
+    for dir in std::iter::once(base_dir).chain(split_dirs.iter().map(PathBuf::as_path)) {
+        for subdir in ["res", "lib", "assets"] {
+            let source = dir.join(subdir);
+            if !source.is_dir() {
+                continue;
+            }
+
+            for entry in WalkDir::new(&source) {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let target = merged.join(subdir).join(entry.path().strip_prefix(&source)?);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(entry.path(), &target)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the APKs in `apks_dir` (laid out as in the container) and the files of `metadata_dir` into a new container
+pub fn write_container(set: &SplitSet, apks_dir: &Path, metadata_dir: &Path, output: &Path) -> Result<()> {
+    let mut writer = ZipWriter::new(File::create(output)?);
+    // APKs are compressed already, and installers may read them straight from the container
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for entry in std::iter::once(&set.base).chain(set.splits.iter().map(|split| &split.entry)) {
+        writer.start_file(entry.as_str(), stored)?;
+        writer.write_all(&fs::read(apks_dir.join(entry))?)?;
+    }
+
+    if metadata_dir.is_dir() {
+        for entry in WalkDir::new(metadata_dir).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(metadata_dir)?;
+            let name = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            writer.start_file(name, deflated)?;
+            io::copy(&mut File::open(entry.path())?, &mut writer)?;
+        }
+    }
+
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use super::splits;
+use crate::aab;
+use crate::kotlin;
+use crate::manifest;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+
+/// Which parts of the unpack pipeline run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnpackStages {
+    /// Run apktool into `unpacked/`
+    pub apktool: bool,
+    /// Let apktool decode resources; `false` passes `-r`
+    pub decode_resources: bool,
+    /// Let apktool disassemble DEX to smali; `false` passes `-s`
+    pub decode_smali: bool,
+    /// Extract the DEX files and convert them to JARs
+    pub jar: bool,
+    /// Decompile the JARs into `src/`
+    pub decompile: bool,
+}
+
+impl Default for UnpackStages {
+    fn default() -> Self {
+        Self {
+            apktool: true,
+            decode_resources: true,
+            decode_smali: true,
+            jar: true,
+            decompile: true,
+        }
+    }
+}
+
+impl UnpackStages {
+    /// Only apktool: resources and smali
+    pub fn only_resources() -> Self {
+        Self { jar: false, decompile: false, ..Self::default() }
+    }
+
+    /// Only DEX to JAR conversion, no apktool and no decompiler
+    pub fn only_jar() -> Self {
+        Self { apktool: false, decompile: false, ..Self::default() }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.decompile && !self.jar {
+            anyhow::bail!("Decompiling requires the JAR stage");
+        }
+        if !self.apktool && !self.jar {
+            anyhow::bail!("No unpack stage selected");
+        }
+        Ok(())
+    }
+
+    /// Whether this run regenerates the file at `relative` (`/`-separated, relative to the output directory)
+    pub fn produces(&self, relative: &str) -> bool {
+        let top_level = relative.split('/').next().unwrap_or(relative);
+
+        match top_level {
+            "unpacked" | splits::SPLITS_DIR | splits::MERGED_DIR | aab::DECODED_DIR => self.apktool,
+            splits::CONTAINER_DIR | splits::SPLITS_FILE | aab::BUNDLE_DIR => true,
+            "src" | super::decompile::REPORT_FILE | kotlin::REPORT_FILE => self.decompile,
+            manifest::MANIFEST_ENTRY => !self.apktool,
+            _ if !relative.contains('/') && relative.ends_with(".jar") => self.jar,
+            _ => false,
+        }
+    }
+}
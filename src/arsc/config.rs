@@ -0,0 +1,103 @@
+//! `ResTable_config` to resource qualifiers, e.g. `de-rDE-land-xhdpi-v21`
+//!
+//! Qualifiers come out in the order aapt uses for `res/` directory names.
+
+use crate::aab::resources::{density_qualifier, locale_qualifier};
+use crate::manifest::chunk::read_u32;
+
+/// Qualifiers of a binary `ResTable_config`; empty for the default configuration
+///
+/// Older tables write shorter configs; fields past the recorded size read as unset.
+pub fn qualifiers(config: &[u8]) -> String {
+    let size = read_u32(config, 0).unwrap_or(0) as usize;
+    let config = &config[..size.min(config.len())];
+    let byte = |offset: usize| config.get(offset).copied().unwrap_or(0);
+    let short = |offset: usize| u16::from_le_bytes([byte(offset), byte(offset + 1)]);
+    let mut parts = Vec::new();
+
+    if short(4) != 0 {
+        parts.push(format!("mcc{}", short(4)));
+    }
+    if short(6) != 0 {
+        parts.push(format!("mnc{:02}", short(6)));
+    }
+    if let Some(locale) = locale(config) {
+        parts.push(locale_qualifier(&locale));
+    }
+
+    let screen_layout = byte(28);
+    let ui_mode = byte(29);
+    let input_flags = byte(18);
+    let screen_layout2 = byte(48);
+    let color_mode = byte(49);
+
+    push_named(&mut parts, (screen_layout >> 6) as usize, &["", "ldltr", "ldrtl"]);
+    if short(30) != 0 {
+        parts.push(format!("sw{}dp", short(30)));
+    }
+    if short(32) != 0 {
+        parts.push(format!("w{}dp", short(32)));
+    }
+    if short(34) != 0 {
+        parts.push(format!("h{}dp", short(34)));
+    }
+    push_named(&mut parts, (screen_layout & 0x0f) as usize, &["", "small", "normal", "large", "xlarge"]);
+    push_named(&mut parts, ((screen_layout >> 4) & 0x03) as usize, &["", "notlong", "long"]);
+    push_named(&mut parts, (screen_layout2 & 0x03) as usize, &["", "notround", "round"]);
+    push_named(&mut parts, (color_mode & 0x03) as usize, &["", "nowidecg", "widecg"]);
+    push_named(&mut parts, ((color_mode >> 2) & 0x03) as usize, &["", "lowdr", "highdr"]);
+    push_named(&mut parts, byte(12) as usize, &["", "port", "land", "square"]);
+    push_named(&mut parts, (ui_mode & 0x0f) as usize, &["", "", "desk", "car", "television", "appliance", "watch", "vrheadset"]);
+    push_named(&mut parts, ((ui_mode >> 4) & 0x03) as usize, &["", "notnight", "night"]);
+    if short(14) != 0 {
+        parts.push(density_qualifier(short(14) as u32));
+    }
+    push_named(&mut parts, byte(13) as usize, &["", "notouch", "stylus", "finger"]);
+    push_named(&mut parts, (input_flags & 0x03) as usize, &["", "keysexposed", "keyshidden", "keyssoft"]);
+    push_named(&mut parts, byte(16) as usize, &["", "nokeys", "qwerty", "12key"]);
+    push_named(&mut parts, ((input_flags >> 2) & 0x03) as usize, &["", "navexposed", "navhidden"]);
+    push_named(&mut parts, byte(17) as usize, &["", "nonav", "dpad", "trackball", "wheel"]);
+    if short(20) != 0 && short(22) != 0 {
+        parts.push(format!("{}x{}", short(20).max(short(22)), short(20).min(short(22))));
+    }
+    if short(24) != 0 {
+        parts.push(format!("v{}", short(24)));
+    }
+
+    parts.join("-")
+}
+
+fn push_named(parts: &mut Vec<String>, value: usize, names: &[&str]) {
+    if let Some(name) = names.get(value).filter(|name| !name.is_empty()) {
+        parts.push(name.to_string());
+    }
+}
+
+/// BCP-47 style `language-Script-REGION-variant`, or `None` for no locale
+fn locale(config: &[u8]) -> Option<String> {
+    let language = unpack_language_or_region(config.get(8..10)?, b'a');
+    if language.is_empty() {
+        return None;
+    }
+
+    let text = |bytes: Option<&[u8]>| {
+        let bytes = bytes.unwrap_or_default();
+        String::from_utf8_lossy(&bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]).into_owned()
+    };
+    let region = config.get(10..12).map(|region| unpack_language_or_region(region, b'0')).unwrap_or_default();
+    let parts = [language, text(config.get(36..40)), region, text(config.get(40..48))];
+    Some(parts.into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-"))
+}
+
+/// Two ASCII letters, or three 5-bit letters packed into two bytes with the high bit set
+fn unpack_language_or_region(packed: &[u8], base: u8) -> String {
+    let (first, second) = (packed[0], packed[1]);
+    if first & 0x80 == 0 {
+        return [first, second].iter().take_while(|&&b| b != 0).map(|&b| b as char).collect();
+    }
+
+    [second & 0x1f, ((second & 0xe0) >> 5) | ((first & 0x03) << 3), (first & 0x7c) >> 2]
+        .iter()
+        .map(|&letter| (letter + base) as char)
+        .collect()
+}
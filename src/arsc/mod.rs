@@ -0,0 +1,405 @@
+//! `resources.arsc` read straight from an APK, without apktool or Java
+//!
+//! The resource table maps IDs like `0x7f0b0001` to a type and name (`string/app_name`)
+//! and to one value per configuration (`de-rDE`, `land`, `night-v29`, …).
+
+use crate::manifest::axml::{self, Value};
+use crate::manifest::chunk::{self, read_u16, read_u32, truncated, Chunk, NO_INDEX, RES_STRING_POOL_TYPE, RES_TABLE_TYPE};
+use crate::Result;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+pub mod config;
+
+pub const RESOURCES_ENTRY: &str = "resources.arsc";
+
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+
+/// `ResTable_type` up to its `ResTable_config`: chunk header, id, flags, reserved, entry count and entries start
+const TYPE_HEADER_SIZE: usize = 20;
+
+/// `ResTable_type.flags`: entries are `(index, offset / 4)` pairs instead of one offset per index
+const FLAG_SPARSE: u8 = 0x01;
+/// `ResTable_type.flags`: offsets are 16-bit and in units of 4 bytes
+const FLAG_OFFSET16: u8 = 0x02;
+
+/// `ResTable_entry.flags`
+const FLAG_COMPLEX: u16 = 0x0001;
+const FLAG_COMPACT: u16 = 0x0008;
+
+const TYPE_STRING: u8 = 0x03;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResourceTable {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Package {
+    /// `0x7f` for apps
+    pub id: u8,
+    pub name: String,
+    pub types: Vec<ResourceType>,
+}
+
+/// `string`, `drawable`, `layout`, … with its entries in every configuration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceType {
+    pub id: u8,
+    pub name: String,
+    /// One per configuration, in table order; the default configuration has no qualifiers
+    pub configs: Vec<TypeConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeConfig {
+    /// e.g. `de-rDE` or `night-v29`; empty for the default configuration
+    pub qualifiers: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    pub id: u32,
+    pub name: String,
+    pub value: ResourceValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResourceValue {
+    /// A plain value: string, color, dimension, file path, reference, …
+    Item { value: Value },
+    /// Styles, arrays, plurals and attribute definitions: a parent and keyed values
+    Bag { parent: Option<u32>, items: Vec<BagItem> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BagItem {
+    /// Attribute ID for styles, or an index/quantity code for arrays and plurals
+    pub key: u32,
+    pub value: Value,
+}
+
+/// A resource and its values, as `apkext resource` prints them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceValues {
+    /// `0x7f0b0001`
+    pub id: String,
+    /// `string/app_name`
+    pub name: String,
+    pub values: Vec<ConfigValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigValue {
+    /// Qualifiers of the configuration the value comes from; empty for the default
+    pub config: String,
+    /// The value with references resolved to names where the table has them
+    pub value: String,
+}
+
+impl ResourceTable {
+    /// Read and parse `resources.arsc` from the APK at `apk_path`
+    pub fn from_apk(apk_path: &Path) -> Result<Self> {
+        Self::parse(&crate::manifest::read_entry(apk_path, RESOURCES_ENTRY)?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let table = Chunk::parse(data)?;
+        if table.kind != RES_TABLE_TYPE {
+            anyhow::bail!("Not a resource table: chunk type 0x{:04x}", table.kind);
+        }
+
+        let mut strings = Vec::new();
+        let mut packages = Vec::new();
+        for child in table.children()? {
+            match child.kind {
+                RES_STRING_POOL_TYPE => strings = chunk::string_pool(&child)?,
+                RES_TABLE_PACKAGE_TYPE => packages.push(parse_package(&child, &strings)?),
+                _ => {}
+            }
+        }
+
+        Ok(Self { packages })
+    }
+
+    /// `type/name` of a resource ID, e.g. `string/app_name`
+    pub fn name(&self, id: u32) -> Option<String> {
+        let resource_type = self.resource_type(id)?;
+        let entry = resource_type.configs.iter().flat_map(|config| &config.entries).find(|entry| entry.id == id)?;
+        Some(format!("{}/{}", resource_type.name, entry.name))
+    }
+
+    /// The ID of a resource given as `string/app_name`, `@string/app_name`, `R.string.app_name`,
+    /// `package:string/app_name` or `0x7f0b0001`
+    pub fn id(&self, name: &str) -> Option<u32> {
+        if let Some(hex) = name.strip_prefix("0x") {
+            return u32::from_str_radix(hex, 16).ok().filter(|&id| self.name(id).is_some());
+        }
+
+        // R.style.Theme_App names the resource style/Theme.App
+        let (name, r_class) = match name.strip_prefix("R.") {
+            Some(rest) => (rest.replacen('.', "/", 1), true),
+            None => (name.trim_start_matches('@').to_string(), false),
+        };
+        let (package, name) = match name.split_once(':') {
+            Some((package, name)) => (Some(package), name),
+            None => (None, name.as_str()),
+        };
+        let (type_name, entry_name) = name.split_once('/')?;
+
+        self.packages
+            .iter()
+            .filter(|p| package.is_none() || package == Some(p.name.as_str()))
+            .flat_map(|p| &p.types)
+            .filter(|t| t.name == type_name)
+            .flat_map(|t| &t.configs)
+            .flat_map(|config| &config.entries)
+            .find(|entry| entry.name == entry_name || (r_class && entry.name.replace('.', "_") == entry_name))
+            .map(|entry| entry.id)
+    }
+
+    /// Every value of a resource with the qualifiers of its configuration
+    pub fn values(&self, id: u32) -> Vec<(&str, &ResourceValue)> {
+        let Some(resource_type) = self.resource_type(id) else {
+            return Vec::new();
+        };
+        resource_type
+            .configs
+            .iter()
+            .flat_map(|config| config.entries.iter().filter(|entry| entry.id == id).map(|entry| (config.qualifiers.as_str(), &entry.value)))
+            .collect()
+    }
+
+    /// The value a device with `qualifiers` would see, e.g. `de-rDE`
+    ///
+    /// Picks the configuration with the most qualifiers among those whose qualifiers are all
+    /// in `qualifiers`, so `de-rDE` falls back to `de` and then to the default. A `vN` qualifier
+    /// matches any platform version from N up, or every version when `qualifiers` names none.
+    pub fn resolve(&self, id: u32, qualifiers: &str) -> Option<(&str, &ResourceValue)> {
+        let parts = |qualifiers: &str| qualifiers.split('-').filter(|part| !part.is_empty()).map(str::to_string).collect::<Vec<_>>();
+        let wanted = parts(qualifiers);
+        let wanted_sdk = wanted.iter().find_map(|part| sdk_version(part));
+        let matches = |part: &String| match sdk_version(part) {
+            Some(sdk) => !matches!(wanted_sdk, Some(wanted_sdk) if wanted_sdk < sdk),
+            None => wanted.contains(part),
+        };
+
+        self.values(id)
+            .into_iter()
+            .filter(|(config, _)| parts(config).iter().all(matches))
+            .max_by_key(|(config, _)| parts(config).len())
+    }
+
+    /// Locales the table has values for, e.g. `de` and `fr-rCA`
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = Vec::new();
+        for config in self.packages.iter().flat_map(|p| &p.types).flat_map(|t| &t.configs) {
+            let Some(locale) = config.qualifiers.split('-').next().filter(|first| is_locale(first)) else { continue };
+            let locale = match config.qualifiers.split('-').nth(1).filter(|second| second.len() == 3 && second.starts_with('r')) {
+                Some(region) => format!("{}-{}", locale, region),
+                None => locale.to_string(),
+            };
+            if !locales.contains(&locale) {
+                locales.push(locale);
+            }
+        }
+        locales.sort();
+        locales
+    }
+
+    /// A resource by name or ID with its values, or only the one a device with `qualifiers` would see
+    pub fn lookup(&self, name: &str, qualifiers: Option<&str>) -> Option<ResourceValues> {
+        let id = self.id(name)?;
+        let values = match qualifiers {
+            Some(qualifiers) => self.resolve(id, qualifiers).into_iter().collect(),
+            None => self.values(id),
+        };
+
+        Some(ResourceValues {
+            id: format!("0x{:08x}", id),
+            name: self.name(id)?,
+            values: values
+                .into_iter()
+                .map(|(config, value)| ConfigValue { config: config.to_string(), value: self.format(value) })
+                .collect(),
+        })
+    }
+
+    /// Text form of a value, with `@type/name` for references the table can resolve
+    pub fn format(&self, value: &ResourceValue) -> String {
+        match value {
+            ResourceValue::Item { value } => self.format_value(value),
+            // Array items are keyed by their index
+            ResourceValue::Bag { parent: None, items } if !items.is_empty() && items.iter().all(|item| item.key >> 16 == 0x0200) => {
+                let items: Vec<String> = items.iter().map(|item| self.format_value(&item.value)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            ResourceValue::Bag { parent, items } => {
+                let mut parts = Vec::new();
+                if let Some(parent) = parent {
+                    parts.push(format!("parent={}", self.format_value(&Value::Reference(*parent))));
+                }
+                for item in items {
+                    parts.push(format!("{}={}", self.bag_key(item.key), self.format_value(&item.value)));
+                }
+                format!("{{{}}}", parts.join(", "))
+            }
+        }
+    }
+
+    /// Attribute names for styles; the reserved keys of attribute definitions and plurals by name
+    fn bag_key(&self, key: u32) -> String {
+        const RESERVED: [&str; 10] = ["type", "min", "max", "l10n", "other", "zero", "one", "two", "few", "many"];
+
+        match key.checked_sub(0x0100_0000).and_then(|index| RESERVED.get(index as usize)) {
+            Some(name) => name.to_string(),
+            None => self.name(key).unwrap_or_else(|| format!("0x{:08x}", key)),
+        }
+    }
+
+    fn format_value(&self, value: &Value) -> String {
+        match value {
+            Value::Reference(id) if *id != 0 => self.name(*id).map_or_else(|| value.to_string(), |name| format!("@{}", name)),
+            Value::Attribute(id) => self.name(*id).map_or_else(|| value.to_string(), |name| format!("?{}", name)),
+            _ => value.to_string(),
+        }
+    }
+
+    fn resource_type(&self, id: u32) -> Option<&ResourceType> {
+        let package = self.packages.iter().find(|package| package.id as u32 == id >> 24)?;
+        package.types.iter().find(|resource_type| resource_type.id as u32 == (id >> 16) & 0xff)
+    }
+}
+
+impl fmt::Display for ResourceValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({})", self.name, self.id)?;
+        for value in &self.values {
+            let config = if value.config.is_empty() { "default" } else { &value.config };
+            writeln!(f, "  {:<12} {}", format!("{}:", config), value.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The platform version of a `v21` qualifier
+fn sdk_version(qualifier: &str) -> Option<u32> {
+    qualifier.strip_prefix('v')?.parse().ok()
+}
+
+/// `de`, `fil` or `b+sr+Latn`, as opposed to `mcc310`, `land` or `v21`
+fn is_locale(qualifier: &str) -> bool {
+    qualifier.starts_with("b+") || ((2..=3).contains(&qualifier.len()) && qualifier.chars().all(|c| c.is_ascii_lowercase()) && qualifier != "car")
+}
+
+fn parse_package(package: &Chunk<'_>, strings: &[String]) -> Result<Package> {
+    let id = read_u32(package.header, 8)? as u8;
+    let name_units: Vec<u16> = (0..128).map(|i| read_u16(package.header, 12 + i * 2)).collect::<Result<_>>()?;
+    let name_len = name_units.iter().position(|&unit| unit == 0).unwrap_or(name_units.len());
+    let name = String::from_utf16_lossy(&name_units[..name_len]);
+
+    // The type names come first, then the entry names
+    let children = package.children()?;
+    let mut pools = children.iter().filter(|child| child.kind == RES_STRING_POOL_TYPE);
+    let type_names = pools.next().map(chunk::string_pool).transpose()?.unwrap_or_default();
+    let key_names = pools.next().map(chunk::string_pool).transpose()?.unwrap_or_default();
+
+    let mut types: Vec<ResourceType> = Vec::new();
+    for child in children.iter().filter(|child| child.kind == RES_TABLE_TYPE_TYPE) {
+        if child.header.len() < TYPE_HEADER_SIZE {
+            anyhow::bail!("Truncated resource type chunk");
+        }
+        let type_id = child.header[8];
+        let config = TypeConfig {
+            qualifiers: config::qualifiers(child.header.get(20..).unwrap_or_default()),
+            entries: parse_entries(child, id, type_id, strings, &key_names)?,
+        };
+
+        match types.iter_mut().find(|resource_type| resource_type.id == type_id) {
+            Some(resource_type) => resource_type.configs.push(config),
+            None => types.push(ResourceType {
+                id: type_id,
+                name: (type_id as usize).checked_sub(1).and_then(|index| type_names.get(index)).cloned().unwrap_or_else(|| format!("type{}", type_id)),
+                configs: vec![config],
+            }),
+        }
+    }
+
+    Ok(Package { id, name, types })
+}
+
+fn parse_entries(chunk: &Chunk<'_>, package_id: u8, type_id: u8, strings: &[String], keys: &[String]) -> Result<Vec<Entry>> {
+    let flags = *chunk.header.get(9).ok_or_else(truncated)?;
+    let entry_count = read_u32(chunk.header, 12)? as usize;
+    let entries_start = read_u32(chunk.header, 16)? as usize;
+    let offsets_start = chunk.header.len();
+
+    // (entry index, offset from entries_start)
+    let mut offsets = Vec::with_capacity(entry_count.min(chunk.data.len() / 2));
+    for i in 0..entry_count {
+        if flags & FLAG_SPARSE != 0 {
+            let index = read_u16(chunk.data, offsets_start + i * 4)? as usize;
+            offsets.push((index, read_u16(chunk.data, offsets_start + i * 4 + 2)? as usize * 4));
+        } else if flags & FLAG_OFFSET16 != 0 {
+            let offset = read_u16(chunk.data, offsets_start + i * 2)?;
+            if offset != 0xffff {
+                offsets.push((i, offset as usize * 4));
+            }
+        } else {
+            let offset = read_u32(chunk.data, offsets_start + i * 4)?;
+            if offset != NO_INDEX {
+                offsets.push((i, offset as usize));
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(offsets.len());
+    for (index, offset) in offsets {
+        let pos = entries_start + offset;
+        let size = read_u16(chunk.data, pos)? as usize;
+        let entry_flags = read_u16(chunk.data, pos + 2)?;
+
+        let (key, value) = if entry_flags & FLAG_COMPACT != 0 {
+            // The key index sits in the size field and the value type in the high byte of the flags
+            let value = item(strings, (entry_flags >> 8) as u8, read_u32(chunk.data, pos + 4)?);
+            (size as u32, ResourceValue::Item { value })
+        } else if entry_flags & FLAG_COMPLEX != 0 {
+            let parent = read_u32(chunk.data, pos + 8)?;
+            let count = read_u32(chunk.data, pos + 12)? as usize;
+            let mut items = Vec::with_capacity(count.min(chunk.data.len() / 12));
+            for i in 0..count {
+                let map = pos + size + i * 12;
+                items.push(BagItem {
+                    key: read_u32(chunk.data, map)?,
+                    value: item(strings, *chunk.data.get(map + 7).ok_or_else(truncated)?, read_u32(chunk.data, map + 8)?),
+                });
+            }
+            (read_u32(chunk.data, pos + 4)?, ResourceValue::Bag { parent: (parent != 0).then_some(parent), items })
+        } else {
+            let value_pos = pos + size;
+            let data_type = *chunk.data.get(value_pos + 3).ok_or_else(truncated)?;
+            (read_u32(chunk.data, pos + 4)?, ResourceValue::Item { value: item(strings, data_type, read_u32(chunk.data, value_pos + 4)?) })
+        };
+
+        entries.push(Entry {
+            id: (package_id as u32) << 24 | (type_id as u32) << 16 | index as u32,
+            name: keys.get(key as usize).cloned().unwrap_or_default(),
+            value,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// A `Res_value`; strings index the table's global string pool
+fn item(strings: &[String], data_type: u8, data: u32) -> Value {
+    if data_type == TYPE_STRING {
+        Value::String(strings.get(data as usize).cloned().unwrap_or_default())
+    } else {
+        axml::typed_value(data_type, data)
+    }
+}
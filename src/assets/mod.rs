@@ -1,3 +1,4 @@
+use crate::tools::decompiler::{CFR_JAR, JADX_JAR, PROCYON_JAR, VINEFLOWER_JAR};
 use crate::Result;
 use include_dir::{include_dir, Dir};
 use std::path::{Path, PathBuf};
@@ -5,11 +6,19 @@ use std::fs;
 
 // Embedded JAR files - downloaded during build.rs
 static APKTOOL_JAR: &[u8] = include_bytes!("../../assets/jars/apktool.jar");
-static PROCYON_JAR: &[u8] = include_bytes!("../../assets/jars/procyon-decompiler-v0.6.1.jar");
+
+/// Decompiler JARs, keyed by their file name under `jars/`
+static DECOMPILER_JARS: &[(&str, &[u8])] = &[
+    (PROCYON_JAR, include_bytes!("../../assets/jars/procyon-decompiler-v0.6.1.jar")),
+    (CFR_JAR, include_bytes!("../../assets/jars/cfr-0.152.jar")),
+    (VINEFLOWER_JAR, include_bytes!("../../assets/jars/vineflower-1.10.1.jar")),
+    (JADX_JAR, include_bytes!("../../assets/jars/jadx-1.5.1-all.jar")),
+];
 
 // Embedded tools directory
 static TOOLS_DIR: Dir = include_dir!("assets/tools");
 
+#[derive(Debug, Clone)]
 pub struct AssetManager {
     tools_path: PathBuf,
 }
@@ -41,13 +50,13 @@ impl AssetManager {
         // Check if all required files exist
         let jars_dir = self.tools_path.join("jars");
         let apktool_jar = jars_dir.join("apktool.jar");
-        let procyon_jar = jars_dir.join("procyon-decompiler-v0.6.1.jar");
+        let decompilers_exist = DECOMPILER_JARS.iter().all(|(name, _)| jars_dir.join(name).exists());
 
         // Check if version file exists and matches current version
         let version_file = self.tools_path.join(".version");
         let current_version = env!("CARGO_PKG_VERSION");
 
-        if !apktool_jar.exists() || !procyon_jar.exists() || !version_file.exists() {
+        if !apktool_jar.exists() || !decompilers_exist || !version_file.exists() {
             return Ok(true);
         }
 
@@ -59,11 +68,11 @@ impl AssetManager {
     }
 
     fn extract_all(&self) -> Result<()> {
-        println!("Extracting assets to config directory...");
+        eprintln!("Extracting assets to config directory...");
         self.extract_jars()?;
         self.extract_tools()?;
         self.write_version_file()?;
-        println!("Assets extracted successfully.");
+        eprintln!("Assets extracted successfully.");
         Ok(())
     }
 
@@ -80,7 +89,9 @@ impl AssetManager {
 
         // Extract JAR files
         fs::write(jars_dir.join("apktool.jar"), APKTOOL_JAR)?;
-        fs::write(jars_dir.join("procyon-decompiler-v0.6.1.jar"), PROCYON_JAR)?;
+        for (name, contents) in DECOMPILER_JARS {
+            fs::write(jars_dir.join(name), contents)?;
+        }
 
         Ok(())
     }
@@ -157,4 +168,14 @@ impl AssetManager {
     pub fn get_tools_path(&self) -> &Path {
         &self.tools_path
     }
+
+    /// Directory of the decompilation cache
+    pub fn get_cache_path(&self) -> PathBuf {
+        self.tools_path.join("cache")
+    }
+
+    /// Directory holding the auto-generated debug signing key
+    pub fn get_keys_path(&self) -> PathBuf {
+        self.tools_path.join("keys")
+    }
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+//! The constant pool and annotations of JVM class files, as read by the Kotlin metadata
+//! reader and the mapping remapper
+//!
+//! See <https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4>.
+
+use crate::Result;
+
+pub const MAGIC: u32 = 0xCAFE_BABE;
+
+/// A constant pool entry, with the indices it refers to
+#[derive(Debug, Clone, Copy)]
+pub enum Constant<'a> {
+    /// Modified UTF-8 as it is in the file
+    Utf8(&'a [u8]),
+    Integer(i32),
+    Class(u16),
+    NameAndType(u16, u16),
+    /// Fieldref, Methodref or InterfaceMethodref
+    Member { tag: u8, class: u16, name_and_type: u16 },
+    MethodType,
+    /// Dynamic or InvokeDynamic, with their name and type
+    Dynamic(u16),
+    Other,
+}
+
+/// The constant pool of a class file, with where each entry starts
+pub struct ConstantPool<'a> {
+    pub entries: Vec<Constant<'a>>,
+    /// Offset of each entry's contents, after its tag
+    pub offsets: Vec<usize>,
+    /// Offset of the access flags that follow the pool
+    pub end: usize,
+}
+
+pub fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    let bytes = data.get(pos..pos + 2).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl<'a> ConstantPool<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if u32_at(data, 0)? != MAGIC {
+            anyhow::bail!("Not a class file");
+        }
+        let count = u16_at(data, 8)? as usize;
+        let mut entries = vec![Constant::Other; count];
+        let mut offsets = vec![0; count];
+        let mut pos = 10;
+        let mut index = 1;
+        while index < count {
+            let tag = *data.get(pos).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+            pos += 1;
+            offsets[index] = pos;
+            let (entry, len) = match tag {
+                1 => {
+                    let len = u16_at(data, pos)? as usize;
+                    let bytes = data.get(pos + 2..pos + 2 + len).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+                    (Constant::Utf8(bytes), 2 + len)
+                }
+                3 => (Constant::Integer(u32_at(data, pos)? as i32), 4),
+                7 => (Constant::Class(u16_at(data, pos)?), 2),
+                12 => (Constant::NameAndType(u16_at(data, pos)?, u16_at(data, pos + 2)?), 4),
+                9..=11 => (Constant::Member { tag, class: u16_at(data, pos)?, name_and_type: u16_at(data, pos + 2)? }, 4),
+                16 => (Constant::MethodType, 2),
+                17 | 18 => (Constant::Dynamic(u16_at(data, pos + 2)?), 4),
+                8 | 19 | 20 => (Constant::Other, 2),
+                15 => (Constant::Other, 3),
+                4 => (Constant::Other, 4),
+                5 | 6 => (Constant::Other, 8),
+                tag => anyhow::bail!("Unknown constant pool tag {}", tag),
+            };
+            entries[index] = entry;
+            pos += len;
+            // Longs and doubles take two entries
+            index += if matches!(tag, 5 | 6) { 2 } else { 1 };
+        }
+        Ok(Self { entries, offsets, end: pos })
+    }
+
+    pub fn get(&self, index: u16) -> Option<Constant<'a>> {
+        self.entries.get(index as usize).copied()
+    }
+
+    /// The string at `index` if it is plain UTF-8, which every name in a mapping is
+    pub fn text(&self, index: u16) -> Option<&'a str> {
+        match self.get(index) {
+            Some(Constant::Utf8(bytes)) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+
+    /// The string at `index` as Java chars
+    pub fn chars(&self, index: u16) -> Option<Vec<u16>> {
+        match self.get(index) {
+            Some(Constant::Utf8(bytes)) => Some(decode_modified_utf8(bytes)),
+            _ => None,
+        }
+    }
+
+    /// The string at `index`, with anything that is not valid UTF-16 replaced
+    pub fn string(&self, index: u16) -> Option<String> {
+        self.chars(index).map(|chars| String::from_utf16_lossy(&chars))
+    }
+
+    pub fn class_name(&self, index: u16) -> Option<&'a str> {
+        match self.get(index) {
+            Some(Constant::Class(name)) => self.text(name),
+            _ => None,
+        }
+    }
+
+    pub fn name_and_type(&self, index: u16) -> Option<(&'a str, &'a str)> {
+        match self.get(index) {
+            Some(Constant::NameAndType(name, descriptor)) => Some((self.text(name)?, self.text(descriptor)?)),
+            _ => None,
+        }
+    }
+}
+
+/// Offset after the annotation at `pos`
+pub fn skip_annotation(data: &[u8], pos: usize) -> Result<usize> {
+    let mut pos = pos + 4;
+    for _ in 0..u16_at(data, pos - 2)? {
+        pos = skip_element_value(data, pos + 2)?;
+    }
+    Ok(pos)
+}
+
+/// Offset after the annotation argument value at `pos`
+pub fn skip_element_value(data: &[u8], pos: usize) -> Result<usize> {
+    let tag = *data.get(pos).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+    let pos = pos + 1;
+    match tag {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' | b'c' => {
+            u16_at(data, pos)?;
+            Ok(pos + 2)
+        }
+        b'e' => {
+            u32_at(data, pos)?;
+            Ok(pos + 4)
+        }
+        b'@' => skip_annotation(data, pos),
+        b'[' => {
+            let mut at = pos + 2;
+            for _ in 0..u16_at(data, pos)? {
+                at = skip_element_value(data, at)?;
+            }
+            Ok(at)
+        }
+        tag => anyhow::bail!("Unknown annotation element tag {}", tag),
+    }
+}
+
+/// Java chars of a constant pool string; NUL is two bytes and supplementary characters are
+/// surrogate pairs of three bytes each
+pub fn decode_modified_utf8(bytes: &[u8]) -> Vec<u16> {
+    let mut chars = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i] as u16;
+        let continuation = |offset: usize| bytes.get(i + offset).map(|&b| b as u16 & 0x3f).unwrap_or(0);
+        let (char, width) = match byte {
+            0x00..=0x7f => (byte, 1),
+            0xc0..=0xdf => ((byte & 0x1f) << 6 | continuation(1), 2),
+            0xe0..=0xef => ((byte & 0x0f) << 12 | continuation(1) << 6 | continuation(2), 3),
+            _ => (0xfffd, 1),
+        };
+        chars.push(char);
+        i += width;
+    }
+    chars
+}
+
+/// Java's modified UTF-8: characters outside the BMP as two encoded surrogates
+pub fn encode_modified_utf8(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for unit in text.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => bytes.extend_from_slice(&[0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]),
+            _ => bytes.extend_from_slice(&[0xE0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8]),
+        }
+    }
+    bytes
+}
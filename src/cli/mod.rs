@@ -1,4 +1,9 @@
+use crate::apk::search::Origin;
+use crate::tools::Decompiler;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+pub mod progress;
 
 #[derive(Parser)]
 #[command(
@@ -17,27 +22,313 @@ pub enum Commands {
     /// Unpack APK file to source code
     #[command(
         about = "Unpack APK file to source code",
-        long_about = "Unpack APK file by extracting resources, converting DEX to JAR,\nand decompiling Java classes to source code."
+        long_about = "Unpack APK file by extracting resources, converting DEX to JAR,\nand decompiling Java classes to source code. Classes the decompiler\nfails on are retried with a fallback decompiler; decompilers.json\nrecords which decompiler produced each source file. Sources of classes\ndecompiled before, e.g. shared libraries, come from the decompilation cache.\nKotlin declarations recovered from @kotlin.Metadata go to kotlin.json and\nare added to the sources as comments. With --mapping, the names in a\nProGuard or R8 mapping.txt are restored in the JARs and smali, so the\nsources come out with their original classes, packages and members.\nAn existing output directory is left alone unless --force or --merge is given.\nSeveral APKs, directories of APKs or glob patterns are unpacked concurrently.\nSplit containers (.apks, .xapk, .apkm) are unpacked with every split in splits/.\nApp bundles (.aab) keep their modules in bundle/, with readable XML in decoded/."
     )]
     Unpack {
-        /// Path to the APK file to unpack
-        #[arg(value_name = "APK_FILE")]
-        apk_file: String,
+        /// APK files, split containers (.apks, .xapk, .apkm) or app bundles (.aab), directories containing APK files, or glob patterns such as 'apks/*.apk'
+        #[arg(value_name = "APK_FILE", required = true)]
+        apk_files: Vec<String>,
+
+        /// Number of APKs unpacked at the same time (default: half the CPU cores)
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+
+        /// Decompiler used to produce the Java sources
+        #[arg(long, value_enum, value_name = "DECOMPILER")]
+        decompiler: Option<Decompiler>,
+
+        /// Decompiler that retries classes the first one failed on (default: vineflower, or cfr when the first one is vineflower)
+        #[arg(long, value_enum, value_name = "DECOMPILER")]
+        fallback_decompiler: Option<Decompiler>,
+
+        /// Keep failed classes as the first decompiler left them
+        #[arg(long, conflicts_with = "fallback_decompiler")]
+        no_fallback: bool,
+
+        /// Decompile every class, without reading or filling the decompilation cache
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Leave the sources as decompiled, without recovering Kotlin declarations into kotlin.json
+        #[arg(long)]
+        no_kotlin: bool,
+
+        /// ProGuard or R8 mapping.txt whose original class, method and field names replace the obfuscated ones
+        #[arg(long, value_name = "FILE")]
+        mapping: Option<PathBuf>,
+
+        /// Output directory (default: the APK path without its extension); with several APKs, the directory holding one output directory per APK
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        /// Replace the output directory if it already exists
+        #[arg(long, conflicts_with = "merge")]
+        force: bool,
+
+        /// Refresh generated files in an existing output directory, keeping files you modified
+        #[arg(long)]
+        merge: bool,
+
+        /// Stop after producing the JAR files, without decompiling them
+        #[arg(long)]
+        no_src: bool,
+
+        /// Do not decode resources (apktool -r)
+        #[arg(long)]
+        no_res: bool,
+
+        /// Do not disassemble DEX files to smali (apktool -s)
+        #[arg(long)]
+        no_smali: bool,
+
+        /// Only convert DEX files to JAR files, skipping apktool and the decompiler
+        #[arg(long, conflicts_with_all = ["no_src", "no_res", "no_smali", "only_res"])]
+        only_jar: bool,
+
+        /// Only run apktool, skipping DEX to JAR conversion and the decompiler
+        #[arg(long, conflicts_with = "no_src")]
+        only_res: bool,
     },
 
     /// Pack source code back to APK
     #[command(
         about = "Pack source code back to APK",
-        long_about = "Pack the unpacked source code directory back into an APK file."
+        long_about = "Pack the unpacked source code directory back into an APK file.\nThe APK is signed with a debug key generated on first use, unless\na keystore or a key and certificate are given.\nA directory unpacked from a split container is rebuilt as a whole set:\nOUTPUT_APK is then an .apks, .xapk or .apkm file, or a directory.\nA directory unpacked from an app bundle is packed into an .aab file."
     )]
     Pack {
         /// Path to the unpacked directory
         #[arg(value_name = "UNPACKED_DIR")]
         unpacked_dir: String,
 
-        /// Output APK file path
+        /// Output APK file path (for split sets: a container file or a directory)
         #[arg(value_name = "OUTPUT_APK")]
         output_apk: String,
+
+        /// Leave the APK unsigned
+        #[arg(long, conflicts_with_all = ["keystore", "key", "cert", "schemes"])]
+        no_sign: bool,
+
+        /// PKCS#12 keystore to sign with
+        #[arg(long, value_name = "KEYSTORE", conflicts_with_all = ["key", "cert"])]
+        keystore: Option<PathBuf>,
+
+        /// Keystore password
+        #[arg(long, value_name = "PASSWORD", requires = "keystore")]
+        ks_pass: Option<String>,
+
+        /// Alias of the key in the keystore (default: the first private key)
+        #[arg(long, value_name = "ALIAS", requires = "keystore")]
+        ks_key_alias: Option<String>,
+
+        /// Private key to sign with (PKCS#8 or PKCS#1, PEM or DER)
+        #[arg(long, value_name = "KEY_FILE", requires = "cert")]
+        key: Option<PathBuf>,
+
+        /// X.509 certificate matching --key (PEM or DER)
+        #[arg(long, value_name = "CERT_FILE", requires = "key")]
+        cert: Option<PathBuf>,
+
+        /// Signature schemes to apply
+        #[arg(long, value_name = "SCHEMES", value_delimiter = ',', default_value = "v1,v2,v3")]
+        schemes: Vec<String>,
+    },
+
+    /// Check zipalign alignment of an APK
+    #[command(
+        about = "Check zipalign alignment of an APK",
+        long_about = "Check that uncompressed entries of an APK start at 4-byte boundaries\nand uncompressed native libraries at 16 KB page boundaries."
+    )]
+    VerifyAlign {
+        /// Path to the APK file to check
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+    },
+
+    /// Show package, version, components and signers of an APK
+    #[command(
+        about = "Show package, version, components and signers of an APK",
+        long_about = "Print the package name, version, SDK levels, permissions, components,\nnative ABIs, DEX files, signing certificates and a size breakdown of an APK.\nReads the ZIP directly, so it needs neither apktool nor Java."
+    )]
+    Info {
+        /// Path to the APK file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the values of a resource in each configuration
+    #[command(
+        about = "Show the values of a resource in each configuration",
+        long_about = "Look up a resource in resources.arsc by name (string/app_name, @string/app_name,\nR.string.app_name) or ID (0x7f050002) and print its value in every configuration,\nor with --config only the value a device with those qualifiers would see.\nReads the resource table directly, so it needs neither apktool nor Java."
+    )]
+    Resource {
+        /// Path to the APK file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Resource name or ID
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Device qualifiers to resolve the value for, e.g. de-rDE or night-xxhdpi
+        #[arg(long, value_name = "QUALIFIERS")]
+        config: Option<String>,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the classes of an APK
+    #[command(
+        about = "List the classes of an APK",
+        long_about = "List every class defined in the DEX files of an APK, or in a single .dex file,\nwith its modifiers, superclass and interfaces. Reads the DEX files directly,\nso it needs neither dex2jar nor Java."
+    )]
+    Classes {
+        /// Path to the APK or DEX file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Only list classes whose name contains this text, e.g. a package name
+        #[arg(long, value_name = "TEXT")]
+        filter: Option<String>,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the fields and methods of a class
+    #[command(
+        about = "Show the fields and methods of a class",
+        long_about = "Print the declaration, fields and methods of a class with their modifiers and\ntypes, as read from the DEX files of an APK. The class is given by its Java name\n(com.example.Foo$Inner) or type descriptor (Lcom/example/Foo$Inner;).\nNeeds neither dex2jar nor Java."
+    )]
+    Methods {
+        /// Path to the APK or DEX file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Class name or descriptor
+        #[arg(value_name = "CLASS")]
+        class: String,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Find where a method, field or string is used
+    #[command(
+        about = "Find where a method, field or string is used",
+        long_about = "Search the DEX bytecode of an APK for the callers of a method, the calls a method\nmakes, the reads and writes of a field, or the instructions loading a string.\nMethods and fields are given as javax.crypto.Cipher.getInstance, Cipher.getInstance\nor Ljavax/crypto/Cipher;->getInstance, optionally with a descriptor to pick one\noverload. When the APK was unpacked, each usage is mapped to its line in the\nsmali and in the decompiled source. Needs neither dex2jar nor Java.",
+        group(clap::ArgGroup::new("query").required(true).args(["callers", "callees", "field", "string"]))
+    )]
+    Xref {
+        /// Path to the APK or DEX file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Find the instructions that call this method
+        #[arg(long, value_name = "METHOD")]
+        callers: Option<String>,
+
+        /// Find the methods this method calls
+        #[arg(long, value_name = "METHOD")]
+        callees: Option<String>,
+
+        /// Find the instructions that read or write this field
+        #[arg(long, value_name = "FIELD")]
+        field: Option<String>,
+
+        /// Find the instructions that load a string containing this text
+        #[arg(long, value_name = "TEXT")]
+        string: Option<String>,
+
+        /// Output directory of unpack, for smali and source locations (default: the APK path without its extension, if it exists)
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Search the sources, smali, resources and assets of an unpacked APK
+    #[command(
+        about = "Search the sources, smali, resources and assets of an unpacked APK",
+        long_about = "Search a directory produced by unpack for a regular expression: the decompiled\nJava in src/, the smali, the manifest and XML resources, the string resources\n(values*/strings.xml, plurals.xml, arrays.xml) and the text files in assets/.\nMatches are grouped by origin. A smali match also names the decompiled source of\nits class, and a string resource match the resource it belongs to."
+    )]
+    Search {
+        /// Output directory of unpack
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Regular expression to search for
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Treat the pattern as plain text instead of a regular expression
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+
+        /// Only search these origins (default: all)
+        #[arg(long, value_enum, value_name = "ORIGIN", value_delimiter = ',')]
+        only: Vec<Origin>,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare two versions of an app
+    #[command(
+        about = "Compare two versions of an app",
+        long_about = "Compare two APKs, or two directories produced by unpack, and report changes to\nthe manifest (version, SDK levels, permissions, components), added, removed and\nchanged classes and methods, resource values and files, native libraries,\nassets and signing certificates. Reads DEX and resources directly, so it needs\nneither apktool nor Java."
+    )]
+    Diff {
+        /// Old APK file or unpacked directory
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        /// New APK file or unpacked directory
+        #[arg(value_name = "NEW")]
+        new: String,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Verify the signatures of an APK and show its signers
+    #[command(
+        about = "Verify the signatures of an APK and show its signers",
+        long_about = "Verify the v1 (JAR), v2, v3 and v3.1 signatures of an APK, and the v4 signature\nin <APK_FILE>.idsig if there is one. Checks every signature, the digests of\nthe signed contents and that no scheme was stripped, and prints the subject,\nissuer, SHA-256 fingerprint and validity of each signer's certificate.\nExits with code 8 if the APK is unsigned or any signature does not verify."
+    )]
+    Verify {
+        /// Path to the APK file
+        #[arg(value_name = "APK_FILE")]
+        apk_file: String,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show or clean the decompilation cache
+    #[command(
+        about = "Show or clean the decompilation cache",
+        long_about = "The decompilation cache in the config directory keeps the sources of classes\ndecompiled before, keyed by the SHA-256 of their bytecode, so libraries shared\nby many apps are decompiled once. Its size limit is 2 GB unless the\nAPKEXT_CACHE_MAX_SIZE environment variable sets another, e.g. 500M; the least\nrecently used sources are evicted first."
+    )]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
     },
 
     /// Start MCP (Model Context Protocol) server
@@ -48,8 +339,36 @@ pub enum Commands {
     Mcp,
 }
 
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Show where the cache is, how many sources it holds and its size
+    Info {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Remove cached sources
+    Clean {
+        /// Only evict the least recently used sources until the cache fits this size, e.g. 500M
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+}
+
+fn parse_size(text: &str) -> Result<u64, String> {
+    crate::apk::cache::parse_size(text).ok_or_else(|| format!("'{}' is not a size such as 500M or 2G", text))
+}
+
 impl Cli {
     pub fn parse_args() -> Self {
         Self::parse()
     }
+}
+
+impl Commands {
+    /// Whether the command runs apktool, dex2jar or a decompiler
+    pub fn needs_java(&self) -> bool {
+        matches!(self, Commands::Unpack { .. } | Commands::Pack { .. } | Commands::Mcp)
+    }
 }
\ No newline at end of file
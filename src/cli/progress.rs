@@ -0,0 +1,90 @@
+use crate::tools::{Progress, ProgressHandler, Stream};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Progress bar on stderr showing the current step and the latest tool output line
+///
+/// Returns `None` when stderr is not a terminal; the pipeline then prints plain lines as before.
+pub fn progress_bar() -> Option<(ProgressBar, ProgressHandler)> {
+    if !std::io::stderr().is_terminal() {
+        return None;
+    }
+
+    let bar = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stderr());
+    let handler = bar_handler(&bar, None);
+    Some((bar, handler))
+}
+
+/// Progress for a batch: one bar per APK on a terminal, labelled lines otherwise
+pub enum BatchProgress {
+    Bars(MultiProgress),
+    Lines,
+}
+
+impl BatchProgress {
+    pub fn new() -> Self {
+        if std::io::stderr().is_terminal() {
+            BatchProgress::Bars(MultiProgress::with_draw_target(ProgressDrawTarget::stderr()))
+        } else {
+            BatchProgress::Lines
+        }
+    }
+
+    /// Handler for the APK shown as `label`, plus its bar if there is one
+    pub fn add(&self, label: &str) -> (Option<ProgressBar>, ProgressHandler) {
+        match self {
+            BatchProgress::Bars(multi) => {
+                let bar = multi.add(ProgressBar::new(0));
+                let handler = bar_handler(&bar, Some(label.to_string()));
+                (Some(bar), handler)
+            }
+            BatchProgress::Lines => {
+                let label = label.to_string();
+                // Concurrent runs interleave, so every line names its APK
+                let handler: ProgressHandler = Arc::new(move |event: &Progress| match event {
+                    Progress::Message { text } if !text.is_empty() => println!("[{}] {}", label, text),
+                    Progress::Output { stream: Stream::Stdout, line, .. } => println!("[{}] {}", label, line),
+                    _ => {}
+                });
+                (None, handler)
+            }
+        }
+    }
+}
+
+impl Default for BatchProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bar_handler(bar: &ProgressBar, label: Option<String>) -> ProgressHandler {
+    bar.set_style(
+        ProgressStyle::with_template("{spinner} [{pos}/{len}] {prefix:.bold} {wide_msg:.dim}")
+            .expect("Valid progress template"),
+    );
+    bar.enable_steady_tick(Duration::from_millis(120));
+
+    let bar = bar.clone();
+    Arc::new(move |event: &Progress| match event {
+        Progress::Step { name, index, total } => {
+            bar.set_length(*total as u64);
+            bar.set_position(index.saturating_sub(1) as u64);
+            bar.set_prefix(match &label {
+                Some(label) => format!("{} {}", label, name),
+                None => name.clone(),
+            });
+            bar.set_message("");
+        }
+        // In a batch the status messages of every APK would flood the screen; the bars say enough
+        Progress::Message { .. } if label.is_some() => {}
+        Progress::Message { text } => {
+            if !text.is_empty() {
+                bar.println(text);
+            }
+        }
+        Progress::Output { line, .. } => bar.set_message(line.clone()),
+    })
+}
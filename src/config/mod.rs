@@ -1,4 +1,6 @@
-use crate::Result;
+use crate::apk::cache;
+use crate::tools::decompiler::{self, Decompiler};
+use crate::{ApkextError, Result};
 use serde::{Deserialize, Serialize};
 use std::env;
 
@@ -6,6 +8,27 @@ use std::env;
 pub struct Config {
     pub java: JavaConfig,
     pub tools: ToolsConfig,
+    /// Backend used to decompile the converted JARs
+    #[serde(default)]
+    pub decompiler: Decompiler,
+    /// Retry classes the decompiler failed on with a second backend
+    #[serde(default = "default_fallback")]
+    pub fallback: bool,
+    /// Backend for the retry; `None` picks `Decompiler::fallback` of the primary one
+    #[serde(default)]
+    pub fallback_decompiler: Option<Decompiler>,
+    /// Reuse the sources of classes decompiled before from the decompilation cache
+    #[serde(default = "default_cache")]
+    pub cache: bool,
+    /// Size limit of the decompilation cache in bytes
+    #[serde(default = "default_cache_max_size")]
+    pub cache_max_size: u64,
+    /// Recover Kotlin declarations from `@kotlin.Metadata` and annotate the decompiled sources
+    #[serde(default = "default_kotlin")]
+    pub kotlin: bool,
+    /// Suppress progress messages on stdout (used when stdout carries the MCP protocol)
+    #[serde(default)]
+    pub quiet: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +56,32 @@ impl Config {
         let java = JavaConfig::detect();
         let tools = ToolsConfig::default();
 
-        Self { java, tools }
+        Self {
+            java,
+            tools,
+            decompiler: Decompiler::default(),
+            fallback: true,
+            fallback_decompiler: None,
+            cache: true,
+            cache_max_size: default_cache_max_size(),
+            kotlin: true,
+            quiet: false,
+        }
+    }
+
+    /// Backend that retries failed classes, if any
+    pub fn fallback_decompiler(&self) -> Option<Decompiler> {
+        if !self.fallback {
+            return None;
+        }
+
+        let fallback = self.fallback_decompiler.unwrap_or_else(|| self.decompiler.fallback());
+        (fallback != self.decompiler).then_some(fallback)
     }
 
     pub fn update_tool_paths(&mut self, tools_dir: &std::path::Path) {
         self.tools.apktool_jar = tools_dir.join("jars/apktool.jar").to_string_lossy().to_string();
-        self.tools.procyon_jar = tools_dir.join("jars/procyon-decompiler-v0.6.1.jar").to_string_lossy().to_string();
+        self.tools.procyon_jar = tools_dir.join("jars").join(decompiler::PROCYON_JAR).to_string_lossy().to_string();
 
         // Platform-specific aapt paths
         self.tools.aapt_path = self.get_aapt_path(tools_dir);
@@ -72,6 +115,26 @@ impl Config {
     }
 }
 
+fn default_fallback() -> bool {
+    true
+}
+
+fn default_cache() -> bool {
+    true
+}
+
+fn default_kotlin() -> bool {
+    true
+}
+
+/// `APKEXT_CACHE_MAX_SIZE` (e.g. `500M`), or 2 GB
+fn default_cache_max_size() -> u64 {
+    env::var("APKEXT_CACHE_MAX_SIZE")
+        .ok()
+        .and_then(|size| cache::parse_size(&size))
+        .unwrap_or(cache::DEFAULT_MAX_SIZE)
+}
+
 impl JavaConfig {
     pub fn detect() -> Self {
         let java_path = Self::find_java().unwrap_or_else(|| "java".to_string());
@@ -98,14 +161,17 @@ impl JavaConfig {
         None
     }
 
-    pub fn check_java(&self) -> Result<()> {
+    pub fn check_java(&self) -> Result<(), ApkextError> {
         let output = std::process::Command::new(&self.java_path)
             .arg("-version")
             .output()
-            .map_err(|e| anyhow::anyhow!("Failed to run java: {}", e))?;
+            .map_err(|source| ApkextError::JavaNotFound { java: self.java_path.clone(), source })?;
 
         if !output.status.success() {
-            anyhow::bail!("Java is not working properly");
+            return Err(ApkextError::JavaNotWorking {
+                java: self.java_path.clone(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim_end().to_string(),
+            });
         }
 
         Ok(())
@@ -116,7 +182,7 @@ impl Default for ToolsConfig {
     fn default() -> Self {
         Self {
             apktool_jar: "apktool.jar".to_string(),
-            procyon_jar: "procyon-decompiler-v0.6.1.jar".to_string(),
+            procyon_jar: decompiler::PROCYON_JAR.to_string(),
             aapt_path: "aapt".to_string(),
             dex2jar_path: "d2j-dex2jar.sh".to_string(),
         }
@@ -0,0 +1,109 @@
+//! Access flags of classes, fields and methods
+//!
+//! Several bits mean different things depending on what they belong to, e.g. `0x40` is
+//! `volatile` on a field and `bridge` on a method.
+
+pub const ACC_PUBLIC: u32 = 0x1;
+pub const ACC_PRIVATE: u32 = 0x2;
+pub const ACC_PROTECTED: u32 = 0x4;
+pub const ACC_STATIC: u32 = 0x8;
+pub const ACC_FINAL: u32 = 0x10;
+pub const ACC_SYNCHRONIZED: u32 = 0x20;
+pub const ACC_VOLATILE: u32 = 0x40;
+pub const ACC_BRIDGE: u32 = 0x40;
+pub const ACC_TRANSIENT: u32 = 0x80;
+pub const ACC_VARARGS: u32 = 0x80;
+pub const ACC_NATIVE: u32 = 0x100;
+pub const ACC_INTERFACE: u32 = 0x200;
+pub const ACC_ABSTRACT: u32 = 0x400;
+pub const ACC_STRICT: u32 = 0x800;
+pub const ACC_SYNTHETIC: u32 = 0x1000;
+pub const ACC_ANNOTATION: u32 = 0x2000;
+pub const ACC_ENUM: u32 = 0x4000;
+pub const ACC_CONSTRUCTOR: u32 = 0x1_0000;
+pub const ACC_DECLARED_SYNCHRONIZED: u32 = 0x2_0000;
+
+/// What access flags belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    Class,
+    Field,
+    Method,
+}
+
+use MemberKind::{Class, Field, Method};
+
+/// Every flag with the kinds it applies to, in the order smali writes them
+const FLAGS: [(u32, &str, &[MemberKind]); 18] = [
+    (ACC_PUBLIC, "public", &[Class, Field, Method]),
+    (ACC_PRIVATE, "private", &[Class, Field, Method]),
+    (ACC_PROTECTED, "protected", &[Class, Field, Method]),
+    (ACC_STATIC, "static", &[Class, Field, Method]),
+    (ACC_FINAL, "final", &[Class, Field, Method]),
+    (ACC_SYNCHRONIZED, "synchronized", &[Method]),
+    (ACC_VOLATILE, "volatile", &[Field]),
+    (ACC_BRIDGE, "bridge", &[Method]),
+    (ACC_TRANSIENT, "transient", &[Field]),
+    (ACC_VARARGS, "varargs", &[Method]),
+    (ACC_NATIVE, "native", &[Method]),
+    (ACC_INTERFACE, "interface", &[Class]),
+    (ACC_ABSTRACT, "abstract", &[Class, Method]),
+    (ACC_STRICT, "strictfp", &[Method]),
+    (ACC_SYNTHETIC, "synthetic", &[Class, Field, Method]),
+    (ACC_ANNOTATION, "annotation", &[Class]),
+    (ACC_ENUM, "enum", &[Class, Field]),
+    (ACC_CONSTRUCTOR, "constructor", &[Method]),
+];
+
+/// Names of the flags set in `flags`, as smali writes them, e.g. `["public", "static", "constructor"]`
+pub fn names(flags: u32, kind: MemberKind) -> Vec<&'static str> {
+    let mut names: Vec<&str> = FLAGS
+        .iter()
+        .filter(|(bit, _, kinds)| flags & bit != 0 && kinds.contains(&kind))
+        .map(|(_, name, _)| *name)
+        .collect();
+    if kind == Method && flags & ACC_DECLARED_SYNCHRONIZED != 0 {
+        names.push("declared-synchronized");
+    }
+    names
+}
+
+/// The modifiers Java source would declare, in the order Java writes them
+///
+/// `abstract` is left out for interfaces, where it is implied, and both ways of marking a
+/// method `synchronized` count.
+pub fn java_modifiers(flags: u32, kind: MemberKind) -> Vec<&'static str> {
+    let synchronized = ACC_SYNCHRONIZED | ACC_DECLARED_SYNCHRONIZED;
+    let interface = kind == Class && flags & ACC_INTERFACE != 0;
+
+    [
+        (ACC_PUBLIC, "public", true),
+        (ACC_PROTECTED, "protected", true),
+        (ACC_PRIVATE, "private", true),
+        (ACC_ABSTRACT, "abstract", kind != Field && !interface),
+        (ACC_STATIC, "static", true),
+        (ACC_FINAL, "final", !(kind == Class && flags & ACC_ENUM != 0)),
+        (ACC_TRANSIENT, "transient", kind == Field),
+        (ACC_VOLATILE, "volatile", kind == Field),
+        (synchronized, "synchronized", kind == Method),
+        (ACC_NATIVE, "native", kind == Method),
+        (ACC_STRICT, "strictfp", kind == Method),
+    ]
+    .into_iter()
+    .filter(|(bits, _, applies)| *applies && flags & bits != 0)
+    .map(|(_, name, _)| name)
+    .collect()
+}
+
+/// `class`, `interface`, `@interface` or `enum`
+pub fn class_kind(flags: u32) -> &'static str {
+    if flags & ACC_ANNOTATION != 0 {
+        "@interface"
+    } else if flags & ACC_INTERFACE != 0 {
+        "interface"
+    } else if flags & ACC_ENUM != 0 {
+        "enum"
+    } else {
+        "class"
+    }
+}
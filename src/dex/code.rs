@@ -0,0 +1,197 @@
+//! Dalvik instructions: how many code units each takes and which table entries it refers to
+//!
+//! See <https://source.android.com/docs/core/runtime/dalvik-bytecode> and
+//! <https://source.android.com/docs/core/runtime/instruction-formats>.
+
+/// A table entry an instruction refers to by index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reference {
+    String(u32),
+    Type(u32),
+    Field(u32),
+    Method(u32),
+    Proto(u32),
+    CallSite(u32),
+    MethodHandle(u32),
+}
+
+/// One instruction, or one switch or array payload
+#[derive(Debug, Clone, Copy)]
+pub struct Instruction<'a> {
+    /// Position in code units from the start of the method
+    pub offset: usize,
+    pub opcode: u8,
+    pub units: &'a [u16],
+}
+
+/// What the index operand of an opcode refers to
+#[derive(Debug, Clone, Copy)]
+enum IndexKind {
+    String,
+    Type,
+    Field,
+    Method,
+    Proto,
+    CallSite,
+    MethodHandle,
+}
+
+/// Pseudo-instructions that hold data, marked by opcode `nop` with a non-zero high byte
+const PACKED_SWITCH_PAYLOAD: u16 = 0x0100;
+const SPARSE_SWITCH_PAYLOAD: u16 = 0x0200;
+const FILL_ARRAY_DATA_PAYLOAD: u16 = 0x0300;
+
+const CONST_STRING_JUMBO: u8 = 0x1b;
+
+/// Opcodes 0x52 to 0x6d in order
+const FIELD_OPS: [&str; 28] = [
+    "iget", "iget-wide", "iget-object", "iget-boolean", "iget-byte", "iget-char", "iget-short",
+    "iput", "iput-wide", "iput-object", "iput-boolean", "iput-byte", "iput-char", "iput-short",
+    "sget", "sget-wide", "sget-object", "sget-boolean", "sget-byte", "sget-char", "sget-short",
+    "sput", "sput-wide", "sput-object", "sput-boolean", "sput-byte", "sput-char", "sput-short",
+];
+const INVOKE_POLYMORPHIC: u8 = 0xfa;
+const INVOKE_POLYMORPHIC_RANGE: u8 = 0xfb;
+
+impl Instruction<'_> {
+    /// Whether this is a switch or array payload rather than an instruction
+    pub fn is_payload(&self) -> bool {
+        self.opcode == 0 && self.units[0] != 0
+    }
+
+    /// The table entries the instruction refers to: none, one, or for `invoke-polymorphic`
+    /// the method and then the proto
+    pub fn references(&self) -> Vec<Reference> {
+        let Some(kind) = self.index_kind() else { return Vec::new() };
+        let index = match self.opcode {
+            CONST_STRING_JUMBO => self.units[1] as u32 | (self.units[2] as u32) << 16,
+            _ => self.units[1] as u32,
+        };
+
+        let mut references = vec![match kind {
+            IndexKind::String => Reference::String(index),
+            IndexKind::Type => Reference::Type(index),
+            IndexKind::Field => Reference::Field(index),
+            IndexKind::Method => Reference::Method(index),
+            IndexKind::Proto => Reference::Proto(index),
+            IndexKind::CallSite => Reference::CallSite(index),
+            IndexKind::MethodHandle => Reference::MethodHandle(index),
+        }];
+        if matches!(self.opcode, INVOKE_POLYMORPHIC | INVOKE_POLYMORPHIC_RANGE) {
+            references.push(Reference::Proto(self.units[3] as u32));
+        }
+        references
+    }
+
+    /// Which of `units` hold table indices rather than opcodes, registers or literals
+    pub fn index_units(&self) -> &'static [usize] {
+        match (self.opcode, self.index_kind()) {
+            (_, None) => &[],
+            (CONST_STRING_JUMBO, _) => &[1, 2],
+            (INVOKE_POLYMORPHIC | INVOKE_POLYMORPHIC_RANGE, _) => &[1, 3],
+            _ => &[1],
+        }
+    }
+
+    /// Name of an instruction that refers to a table entry, e.g. `invoke-virtual` or
+    /// `iget-object`; `None` for the others
+    pub fn mnemonic(&self) -> Option<&'static str> {
+        self.index_kind()?;
+        let name = match self.opcode {
+            0x1a => "const-string",
+            0x1b => "const-string/jumbo",
+            0x1c => "const-class",
+            0x1f => "check-cast",
+            0x20 => "instance-of",
+            0x22 => "new-instance",
+            0x23 => "new-array",
+            0x24 => "filled-new-array",
+            0x25 => "filled-new-array/range",
+            0x52..=0x6d => FIELD_OPS[(self.opcode - 0x52) as usize],
+            0x6e => "invoke-virtual",
+            0x6f => "invoke-super",
+            0x70 => "invoke-direct",
+            0x71 => "invoke-static",
+            0x72 => "invoke-interface",
+            0x74 => "invoke-virtual/range",
+            0x75 => "invoke-super/range",
+            0x76 => "invoke-direct/range",
+            0x77 => "invoke-static/range",
+            0x78 => "invoke-interface/range",
+            0xfa => "invoke-polymorphic",
+            0xfb => "invoke-polymorphic/range",
+            0xfc => "invoke-custom",
+            0xfd => "invoke-custom/range",
+            0xfe => "const-method-handle",
+            _ => "const-method-type",
+        };
+        Some(name)
+    }
+
+    fn index_kind(&self) -> Option<IndexKind> {
+        if self.is_payload() {
+            return None;
+        }
+        format(self.opcode).1
+    }
+}
+
+/// The instructions of a method body in order; stops at a truncated instruction
+pub fn instructions(insns: &[u16]) -> impl Iterator<Item = Instruction<'_>> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let width = width(insns, offset)?;
+        let units = insns.get(offset..offset + width)?;
+        let instruction = Instruction { offset, opcode: (units[0] & 0xff) as u8, units };
+        offset += width;
+        Some(instruction)
+    })
+}
+
+/// Width in code units of the instruction or payload at `offset`
+fn width(insns: &[u16], offset: usize) -> Option<usize> {
+    let first = *insns.get(offset)?;
+    let unit = |i: usize| insns.get(offset + i).map(|&unit| unit as usize);
+
+    let width = match first {
+        PACKED_SWITCH_PAYLOAD => unit(1)? * 2 + 4,
+        SPARSE_SWITCH_PAYLOAD => unit(1)? * 4 + 2,
+        FILL_ARRAY_DATA_PAYLOAD => {
+            let element_width = unit(1)?;
+            let size = unit(2)? | unit(3)? << 16;
+            (size * element_width).div_ceil(2) + 4
+        }
+        _ => format((first & 0xff) as u8).0,
+    };
+    Some(width)
+}
+
+/// Width in code units and index kind of each opcode
+fn format(opcode: u8) -> (usize, Option<IndexKind>) {
+    use IndexKind::*;
+
+    match opcode {
+        // const-string, const-string/jumbo
+        0x1a => (2, Some(String)),
+        0x1b => (3, Some(String)),
+        // const-class, check-cast, instance-of, new-instance, new-array
+        0x1c | 0x1f | 0x20 | 0x22 | 0x23 => (2, Some(Type)),
+        // filled-new-array, filled-new-array/range
+        0x24 | 0x25 => (3, Some(Type)),
+        // iget*, iput*, sget*, sput*
+        0x52..=0x6d => (2, Some(Field)),
+        // invoke-kind, invoke-kind/range
+        0x6e..=0x72 | 0x74..=0x78 => (3, Some(Method)),
+        0xfa | 0xfb => (4, Some(Method)),
+        0xfc | 0xfd => (3, Some(CallSite)),
+        0xfe => (2, Some(MethodHandle)),
+        0xff => (2, Some(Proto)),
+
+        0x18 => (5, None),
+        0x03 | 0x06 | 0x09 | 0x14 | 0x17 | 0x26 | 0x2a | 0x2b | 0x2c => (3, None),
+        0x02 | 0x05 | 0x08 | 0x13 | 0x15 | 0x16 | 0x19 | 0x29 => (2, None),
+        // cmp*, if-*, aget*, aput*, binop, binop/lit16, binop/lit8
+        0x2d..=0x3d | 0x44..=0x51 | 0x90..=0xaf | 0xd0..=0xe2 => (2, None),
+        _ => (1, None),
+    }
+}
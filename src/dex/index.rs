@@ -0,0 +1,264 @@
+//! Classes, fields and methods of every DEX file in an APK, for listing and lookup
+
+use super::access::{self, MemberKind};
+use super::{java_name, read_dex_files, DexFile};
+use crate::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DexIndex {
+    /// DEX files read, in load order
+    pub dex_files: Vec<String>,
+    /// Every class sorted by name; a class defined twice keeps the definition loaded first
+    pub classes: Vec<ClassInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassInfo {
+    /// Java name, e.g. `com.example.MainActivity$1`
+    pub name: String,
+    /// Type descriptor, e.g. `Lcom/example/MainActivity$1;`
+    pub descriptor: String,
+    /// DEX file that defines the class
+    pub dex: String,
+    pub access_flags: u32,
+    /// Access flags as smali writes them
+    pub flags: Vec<&'static str>,
+    pub superclass: Option<String>,
+    pub interfaces: Vec<String>,
+    pub source_file: Option<String>,
+    /// Static fields followed by instance fields
+    pub fields: Vec<FieldInfo>,
+    /// Direct methods followed by virtual methods
+    pub methods: Vec<MethodInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldInfo {
+    pub name: String,
+    /// Java type, e.g. `java.lang.String`
+    pub field_type: String,
+    /// Type descriptor, e.g. `Ljava/lang/String;`
+    pub descriptor: String,
+    pub access_flags: u32,
+    pub flags: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodInfo {
+    pub name: String,
+    /// Proto descriptor, e.g. `(Landroid/os/Bundle;)V`
+    pub descriptor: String,
+    /// Java types of the return value and the parameters
+    pub return_type: String,
+    pub parameters: Vec<String>,
+    pub access_flags: u32,
+    pub flags: Vec<&'static str>,
+    /// Length of the body in 16-bit code units; `None` for abstract and native methods
+    pub code_units: Option<usize>,
+}
+
+/// A class without its members, as `apkext classes --json` lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassListing<'a> {
+    pub name: &'a str,
+    pub dex: &'a str,
+    pub flags: &'a [&'static str],
+    pub superclass: Option<&'a str>,
+    pub interfaces: &'a [String],
+    pub fields: usize,
+    pub methods: usize,
+}
+
+impl DexIndex {
+    /// Index the DEX files of an APK, or a single `.dex` file
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::from_dex_files(read_dex_files(path)?)
+    }
+
+    /// Index DEX files given as name and content, in load order
+    pub fn from_dex_files(files: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        let mut index = DexIndex::default();
+        let mut seen = HashSet::new();
+
+        for (name, data) in files {
+            let dex = DexFile::parse(&data).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+            for class in &dex.classes {
+                if !seen.insert(class.name.clone()) {
+                    continue;
+                }
+
+                let fields = class
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let id = &dex.fields[field.field as usize];
+                        FieldInfo {
+                            name: id.name.clone(),
+                            field_type: java_name(&id.field_type),
+                            descriptor: id.field_type.clone(),
+                            access_flags: field.access_flags,
+                            flags: access::names(field.access_flags, MemberKind::Field),
+                        }
+                    })
+                    .collect();
+                let methods = class
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        let id = &dex.methods[method.method as usize];
+                        let proto = &dex.protos[id.proto as usize];
+                        MethodInfo {
+                            name: id.name.clone(),
+                            descriptor: proto.descriptor(),
+                            return_type: java_name(&proto.return_type),
+                            parameters: proto.parameters.iter().map(|parameter| java_name(parameter)).collect(),
+                            access_flags: method.access_flags,
+                            flags: access::names(method.access_flags, MemberKind::Method),
+                            code_units: method.code.as_ref().map(|code| code.insns.len()),
+                        }
+                    })
+                    .collect();
+
+                index.classes.push(ClassInfo {
+                    name: java_name(&class.name),
+                    descriptor: class.name.clone(),
+                    dex: name.clone(),
+                    access_flags: class.access_flags,
+                    flags: access::names(class.access_flags, MemberKind::Class),
+                    superclass: class.superclass.as_deref().map(java_name),
+                    interfaces: class.interfaces.iter().map(|interface| java_name(interface)).collect(),
+                    source_file: class.source_file.clone(),
+                    fields,
+                    methods,
+                });
+            }
+            index.dex_files.push(name);
+        }
+
+        index.classes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(index)
+    }
+
+    /// A class by Java name (`com.example.Foo$Bar`), descriptor (`Lcom/example/Foo$Bar;`)
+    /// or internal name (`com/example/Foo$Bar`)
+    pub fn class(&self, name: &str) -> Option<&ClassInfo> {
+        let name = if name.starts_with('L') && name.ends_with(';') { java_name(name) } else { name.replace('/', ".") };
+        self.classes.binary_search_by(|class| class.name.as_str().cmp(&name)).ok().map(|i| &self.classes[i])
+    }
+
+    /// Classes whose Java name contains `filter`
+    pub fn matching<'a>(&'a self, filter: &'a str) -> impl Iterator<Item = &'a ClassInfo> + 'a {
+        self.classes.iter().filter(move |class| class.name.contains(filter))
+    }
+}
+
+impl ClassInfo {
+    /// The class without its members
+    pub fn listing(&self) -> ClassListing<'_> {
+        ClassListing {
+            name: &self.name,
+            dex: &self.dex,
+            flags: &self.flags,
+            superclass: self.superclass.as_deref(),
+            interfaces: &self.interfaces,
+            fields: self.fields.len(),
+            methods: self.methods.len(),
+        }
+    }
+
+    /// `public final class com.example.Foo extends android.app.Activity implements java.lang.Runnable`
+    pub fn declaration(&self) -> String {
+        let mut declaration = join_modifiers(access::java_modifiers(self.access_flags, MemberKind::Class));
+        declaration.push_str(access::class_kind(self.access_flags));
+        declaration.push(' ');
+        declaration.push_str(&self.name);
+
+        let interface = self.access_flags & access::ACC_INTERFACE != 0;
+        if let Some(superclass) = self.superclass.as_deref().filter(|superclass| !interface && *superclass != "java.lang.Object" && *superclass != "java.lang.Enum") {
+            declaration.push_str(&format!(" extends {}", superclass));
+        }
+        let interfaces: Vec<&str> = self.interfaces.iter().map(String::as_str).filter(|name| *name != "java.lang.annotation.Annotation").collect();
+        if !interfaces.is_empty() {
+            declaration.push_str(if interface { " extends " } else { " implements " });
+            declaration.push_str(&interfaces.join(", "));
+        }
+        declaration
+    }
+
+    /// Name without the package, e.g. `MainActivity$1`
+    pub fn simple_name(&self) -> &str {
+        self.name.rsplit('.').next().unwrap_or(&self.name)
+    }
+}
+
+impl FieldInfo {
+    /// `private static final java.lang.String TAG`
+    pub fn declaration(&self) -> String {
+        format!("{}{} {}", join_modifiers(access::java_modifiers(self.access_flags, MemberKind::Field)), self.field_type, self.name)
+    }
+}
+
+impl MethodInfo {
+    /// `protected void onCreate(android.os.Bundle)`; constructors are named after `class_name`
+    /// and the static initializer is `static {}`
+    pub fn declaration(&self, class_name: &str) -> String {
+        if self.name == "<clinit>" {
+            return "static {}".to_string();
+        }
+
+        let mut parameters = self.parameters.clone();
+        if self.access_flags & access::ACC_VARARGS != 0 {
+            if let Some(last) = parameters.last_mut().filter(|last| last.ends_with("[]")) {
+                *last = format!("{}...", &last[..last.len() - 2]);
+            }
+        }
+
+        let modifiers = join_modifiers(access::java_modifiers(self.access_flags, MemberKind::Method));
+        match self.name.as_str() {
+            "<init>" => format!("{}{}({})", modifiers, class_name, parameters.join(", ")),
+            name => format!("{}{} {}({})", modifiers, self.return_type, name, parameters.join(", ")),
+        }
+    }
+}
+
+fn join_modifiers(modifiers: Vec<&str>) -> String {
+    modifiers.iter().map(|modifier| format!("{} ", modifier)).collect()
+}
+
+/// Flags that Java source does not spell out, as a trailing comment
+fn flag_comment(flags: &[&str]) -> String {
+    let hidden: Vec<&str> = flags.iter().copied().filter(|flag| matches!(*flag, "synthetic" | "bridge")).collect();
+    if hidden.is_empty() {
+        String::new()
+    } else {
+        format!("  // {}", hidden.join(", "))
+    }
+}
+
+impl fmt::Display for ClassInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}{}", self.declaration(), flag_comment(&self.flags))?;
+        match &self.source_file {
+            Some(source_file) => writeln!(f, "Source: {} ({})", source_file, self.dex)?,
+            None => writeln!(f, "Defined in {}", self.dex)?,
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Fields ({}):", self.fields.len())?;
+        for field in &self.fields {
+            writeln!(f, "  {}{}", field.declaration(), flag_comment(&field.flags))?;
+        }
+
+        // Nested classes are constructed under their own simple name
+        let class_name = self.simple_name().rsplit('$').next().unwrap_or_default();
+        writeln!(f, "Methods ({}):", self.methods.len())?;
+        for method in &self.methods {
+            writeln!(f, "  {}{}", method.declaration(class_name), flag_comment(&method.flags))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+//! DEX files read directly, without dex2jar or Java
+//!
+//! `DexFile` holds the header, the string, type, proto, field and method tables and the
+//! classes the file defines; `code` walks the Dalvik instructions of a method body, `access`
+//! spells out access flags and `index` lists the classes of all DEX files in an APK.
+//! See <https://source.android.com/docs/core/runtime/dex-format>.
+
+use crate::apk::extractor::dex_index;
+use crate::manifest::chunk::{read_u16, read_u32, NO_INDEX};
+use crate::Result;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+pub mod access;
+pub mod code;
+pub mod index;
+pub mod xref;
+
+pub use access::MemberKind;
+pub use code::{Instruction, Reference};
+pub use index::DexIndex;
+pub use xref::{XrefQuery, XrefReport, Xrefs};
+
+/// `dex\n` followed by a three-digit version and a NUL
+const DEX_MAGIC: &[u8] = b"dex\n";
+const HEADER_SIZE: usize = 0x70;
+const CLASS_DEF_SIZE: usize = 32;
+const ENDIAN_CONSTANT: u32 = 0x1234_5678;
+
+#[derive(Debug, Clone, Default)]
+pub struct DexFile {
+    pub header: Header,
+    pub strings: Vec<String>,
+    /// Type descriptors, e.g. `Ljava/lang/String;`
+    pub types: Vec<String>,
+    pub protos: Vec<Proto>,
+    pub fields: Vec<FieldId>,
+    pub methods: Vec<MethodId>,
+    /// Classes defined in this file, in `class_defs` order
+    pub classes: Vec<ClassDef>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    /// Format version, e.g. `035` or `039`
+    pub version: String,
+    /// Adler-32 of everything after the checksum itself
+    pub checksum: u32,
+    /// SHA-1 of everything after the signature, as hex
+    pub signature: String,
+    pub file_size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proto {
+    pub return_type: String,
+    pub parameters: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldId {
+    pub class: String,
+    pub name: String,
+    pub field_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodId {
+    pub class: String,
+    pub name: String,
+    pub proto: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDef {
+    /// Type descriptor, e.g. `Lcom/example/MainActivity;`
+    pub name: String,
+    pub access_flags: u32,
+    pub superclass: Option<String>,
+    pub interfaces: Vec<String>,
+    pub source_file: Option<String>,
+    /// Static fields followed by instance fields
+    pub fields: Vec<Field>,
+    /// Direct methods (constructors, static and private methods) followed by virtual methods
+    pub methods: Vec<Method>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Index into `DexFile::fields`
+    pub field: u32,
+    pub access_flags: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    /// Index into `DexFile::methods`
+    pub method: u32,
+    pub access_flags: u32,
+    /// `None` for abstract and native methods
+    pub code: Option<Code>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    pub registers: u16,
+    pub ins: u16,
+    pub outs: u16,
+    /// Instructions as 16-bit code units
+    pub insns: Vec<u16>,
+}
+
+impl Proto {
+    /// `(ILjava/lang/String;)V`
+    pub fn descriptor(&self) -> String {
+        format!("({}){}", self.parameters.concat(), self.return_type)
+    }
+}
+
+impl DexFile {
+    /// Parse a whole DEX file
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE || !data.starts_with(DEX_MAGIC) {
+            anyhow::bail!("Not a DEX file");
+        }
+        if read_u32(data, 0x28)? != ENDIAN_CONSTANT {
+            anyhow::bail!("Byte-swapped DEX files are not supported");
+        }
+        let table = |offset: usize| -> Result<(usize, usize)> { Ok((read_u32(data, offset)? as usize, read_u32(data, offset + 4)? as usize)) };
+
+        let header = Header {
+            version: String::from_utf8_lossy(&data[4..7]).to_string(),
+            checksum: read_u32(data, 8)?,
+            signature: data[12..32].iter().map(|b| format!("{:02x}", b)).collect(),
+            file_size: read_u32(data, 0x20)?,
+        };
+        let mut dex = DexFile { header, ..DexFile::default() };
+
+        let (count, offset) = table(0x38)?;
+        for i in 0..count {
+            let string_offset = read_u32(data, offset + i * 4)? as usize;
+            dex.strings.push(read_mutf8(data, string_offset)?);
+        }
+
+        let (count, offset) = table(0x40)?;
+        for i in 0..count {
+            let descriptor = dex.string(read_u32(data, offset + i * 4)?)?;
+            dex.types.push(descriptor);
+        }
+
+        let (count, offset) = table(0x48)?;
+        for i in 0..count {
+            let base = offset + i * 12;
+            let return_type = dex.type_name(read_u32(data, base + 4)?)?;
+            let parameters = dex.type_list(data, read_u32(data, base + 8)? as usize)?;
+            dex.protos.push(Proto { return_type, parameters });
+        }
+
+        let (count, offset) = table(0x50)?;
+        for i in 0..count {
+            let base = offset + i * 8;
+            dex.fields.push(FieldId {
+                class: dex.type_name(read_u16(data, base)? as u32)?,
+                field_type: dex.type_name(read_u16(data, base + 2)? as u32)?,
+                name: dex.string(read_u32(data, base + 4)?)?,
+            });
+        }
+
+        let (count, offset) = table(0x58)?;
+        for i in 0..count {
+            let base = offset + i * 8;
+            let proto = read_u16(data, base + 2)? as u32;
+            if proto as usize >= dex.protos.len() {
+                anyhow::bail!("Malformed DEX: method {} has proto {} of {}", i, proto, dex.protos.len());
+            }
+            dex.methods.push(MethodId {
+                class: dex.type_name(read_u16(data, base)? as u32)?,
+                name: dex.string(read_u32(data, base + 4)?)?,
+                proto,
+            });
+        }
+
+        let (count, offset) = table(0x60)?;
+        for i in 0..count {
+            let class = dex.class_def(data, offset + i * CLASS_DEF_SIZE)?;
+            dex.classes.push(class);
+        }
+
+        Ok(dex)
+    }
+
+    /// `name(args)return` of a method, e.g. `onCreate(Landroid/os/Bundle;)V`
+    pub fn method_signature(&self, method: u32) -> String {
+        match self.methods.get(method as usize) {
+            Some(id) => format!("{}{}", id.name, self.protos[id.proto as usize].descriptor()),
+            None => format!("method@{}", method),
+        }
+    }
+
+    /// What an instruction refers to, as smali writes it: `"text"`, `Lcom/example/Foo;`,
+    /// `Lcom/example/Foo;->bar:I` or `Lcom/example/Foo;->run(I)V`
+    pub fn describe(&self, reference: Reference) -> String {
+        let described = match reference {
+            Reference::String(index) => self.strings.get(index as usize).map(|text| format!("{:?}", text)),
+            Reference::Type(index) => self.types.get(index as usize).cloned(),
+            Reference::Field(index) => self.fields.get(index as usize).map(|field| format!("{}->{}:{}", field.class, field.name, field.field_type)),
+            Reference::Method(index) => self.methods.get(index as usize).map(|method| format!("{}->{}", method.class, self.method_signature(index))),
+            Reference::Proto(index) => self.protos.get(index as usize).map(Proto::descriptor),
+            Reference::CallSite(_) | Reference::MethodHandle(_) => None,
+        };
+        described.unwrap_or_else(|| format!("{:?}", reference))
+    }
+
+    /// SHA-256 of a method body that stays the same when only the table indices in it move,
+    /// as they do whenever strings, types or methods are added elsewhere in the file
+    pub fn code_digest(&self, code: &Code) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for value in [code.registers, code.ins, code.outs] {
+            hasher.update(value.to_le_bytes());
+        }
+
+        for instruction in code::instructions(&code.insns) {
+            let index_units = instruction.index_units();
+            for (i, unit) in instruction.units.iter().enumerate() {
+                let unit = if index_units.contains(&i) { 0 } else { *unit };
+                hasher.update(unit.to_le_bytes());
+            }
+            for reference in instruction.references() {
+                hasher.update(self.describe(reference).as_bytes());
+                hasher.update([0]);
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    fn string(&self, index: u32) -> Result<String> {
+        self.strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Malformed DEX: string index {} of {}", index, self.strings.len()))
+    }
+
+    fn type_name(&self, index: u32) -> Result<String> {
+        self.types
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Malformed DEX: type index {} of {}", index, self.types.len()))
+    }
+
+    /// A `type_list`; offset 0 stands for an empty list
+    fn type_list(&self, data: &[u8], offset: usize) -> Result<Vec<String>> {
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let size = read_u32(data, offset)? as usize;
+        (0..size).map(|i| self.type_name(read_u16(data, offset + 4 + i * 2)? as u32)).collect()
+    }
+
+    fn class_def(&self, data: &[u8], base: usize) -> Result<ClassDef> {
+        let optional_string = |index: u32| if index == NO_INDEX { Ok(None) } else { self.string(index).map(Some) };
+        let superclass = match read_u32(data, base + 8)? {
+            NO_INDEX => None,
+            index => Some(self.type_name(index)?),
+        };
+
+        let mut class = ClassDef {
+            name: self.type_name(read_u32(data, base)?)?,
+            access_flags: read_u32(data, base + 4)?,
+            superclass,
+            interfaces: self.type_list(data, read_u32(data, base + 12)? as usize)?,
+            source_file: optional_string(read_u32(data, base + 16)?)?,
+            fields: Vec::new(),
+            methods: Vec::new(),
+        };
+
+        let class_data = read_u32(data, base + 24)? as usize;
+        if class_data != 0 {
+            (class.fields, class.methods) = self.class_members(data, class_data)?;
+        }
+        Ok(class)
+    }
+
+    /// Fields and methods of a `class_data_item`
+    fn class_members(&self, data: &[u8], offset: usize) -> Result<(Vec<Field>, Vec<Method>)> {
+        let mut pos = offset;
+        let mut sizes = [0usize; 4];
+        for size in &mut sizes {
+            *size = read_uleb128(data, &mut pos)? as usize;
+        }
+        let [static_fields, instance_fields, direct_methods, virtual_methods] = sizes;
+
+        let mut fields = Vec::with_capacity(static_fields + instance_fields);
+        for count in [static_fields, instance_fields] {
+            // Like method indices below, field indices are differences from the previous one
+            let mut field = 0u32;
+            for _ in 0..count {
+                field = field.wrapping_add(read_uleb128(data, &mut pos)?);
+                let access_flags = read_uleb128(data, &mut pos)?;
+                if field as usize >= self.fields.len() {
+                    anyhow::bail!("Malformed DEX: field index {} of {}", field, self.fields.len());
+                }
+                fields.push(Field { field, access_flags });
+            }
+        }
+
+        let mut methods = Vec::with_capacity(direct_methods + virtual_methods);
+        for count in [direct_methods, virtual_methods] {
+            // Method indices are stored as differences from the previous one in the list
+            let mut method = 0u32;
+            for _ in 0..count {
+                method = method.wrapping_add(read_uleb128(data, &mut pos)?);
+                let access_flags = read_uleb128(data, &mut pos)?;
+                let code_offset = read_uleb128(data, &mut pos)? as usize;
+                if method as usize >= self.methods.len() {
+                    anyhow::bail!("Malformed DEX: method index {} of {}", method, self.methods.len());
+                }
+                let code = if code_offset == 0 { None } else { Some(read_code(data, code_offset)?) };
+                methods.push(Method { method, access_flags, code });
+            }
+        }
+        Ok((fields, methods))
+    }
+}
+
+fn read_code(data: &[u8], offset: usize) -> Result<Code> {
+    let size = read_u32(data, offset + 12)? as usize;
+    let start = offset + 16;
+    let bytes = start
+        .checked_add(size * 2)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| anyhow::anyhow!("Malformed DEX: code at 0x{:x} runs past the end of the file", offset))?;
+
+    Ok(Code {
+        registers: read_u16(data, offset)?,
+        ins: read_u16(data, offset + 2)?,
+        outs: read_u16(data, offset + 4)?,
+        insns: bytes.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect(),
+    })
+}
+
+fn read_uleb128(data: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*pos).ok_or_else(|| anyhow::anyhow!("Malformed DEX: LEB128 value at 0x{:x} runs past the end of the file", *pos))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("Malformed DEX: LEB128 value longer than 5 bytes at 0x{:x}", *pos)
+}
+
+/// A `string_data_item`: the UTF-16 length, then Modified UTF-8 up to a NUL
+///
+/// Modified UTF-8 writes NUL as two bytes and characters outside the BMP as two
+/// three-byte surrogates, so it decodes to UTF-16 first.
+fn read_mutf8(data: &[u8], offset: usize) -> Result<String> {
+    let mut pos = offset;
+    let length = read_uleb128(data, &mut pos)? as usize;
+    let mut units = Vec::with_capacity(length);
+    let mut next = || {
+        let byte = data.get(pos).copied().ok_or_else(|| anyhow::anyhow!("Malformed DEX: string at 0x{:x} runs past the end of the file", offset));
+        pos += 1;
+        byte
+    };
+
+    loop {
+        let first = next()?;
+        let unit = match first {
+            0 => break,
+            0x01..=0x7f => first as u16,
+            0xc0..=0xdf => ((first as u16 & 0x1f) << 6) | (next()? as u16 & 0x3f),
+            0xe0..=0xef => ((first as u16 & 0x0f) << 12) | ((next()? as u16 & 0x3f) << 6) | (next()? as u16 & 0x3f),
+            _ => anyhow::bail!("Malformed DEX: invalid Modified UTF-8 in string at 0x{:x}", offset),
+        };
+        units.push(unit);
+    }
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Name and content of the DEX files of an APK in load order, or of a single `.dex` file
+pub fn read_dex_files(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    if path.extension() == Some("dex".as_ref()) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        return Ok(vec![(name, std::fs::read(path)?)]);
+    }
+
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut names: Vec<String> = archive.file_names().filter(|name| dex_index(name).is_some()).map(str::to_string).collect();
+    names.sort_by_key(|name| dex_index(name));
+    if names.is_empty() {
+        anyhow::bail!("No classes.dex in {}", path.display());
+    }
+
+    let mut files = Vec::with_capacity(names.len());
+    for name in names {
+        let mut data = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut data)?;
+        files.push((name, data));
+    }
+    Ok(files)
+}
+
+/// `com.example.Foo$Bar` for `Lcom/example/Foo$Bar;`; arrays and primitives as Java writes them
+pub fn java_name(descriptor: &str) -> String {
+    if let Some(element) = descriptor.strip_prefix('[') {
+        return format!("{}[]", java_name(element));
+    }
+    if let Some(class) = descriptor.strip_prefix('L').and_then(|rest| rest.strip_suffix(';')) {
+        return class.replace('/', ".");
+    }
+
+    match descriptor {
+        "V" => "void",
+        "Z" => "boolean",
+        "B" => "byte",
+        "S" => "short",
+        "C" => "char",
+        "I" => "int",
+        "J" => "long",
+        "F" => "float",
+        "D" => "double",
+        other => other,
+    }
+    .to_string()
+}
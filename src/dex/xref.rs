@@ -0,0 +1,393 @@
+//! Cross-references found in DEX bytecode: callers and callees of a method, readers and
+//! writers of a field, and instructions that load a string constant
+//!
+//! Every usage is found by its instruction in the DEX files, then mapped to the smali and
+//! decompiled source files an unpack wrote for its class.
+
+use super::code::{self, Reference};
+use super::{java_name, read_dex_files, DexFile};
+use crate::apk::extractor::dex_index;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to look up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "target", rename_all = "snake_case")]
+pub enum XrefQuery {
+    /// Instructions that invoke a method
+    Callers(String),
+    /// Methods invoked by a method defined in the APK
+    Callees(String),
+    /// Instructions that read or write a field
+    Field(String),
+    /// `const-string` instructions whose string contains this text
+    String(String),
+}
+
+/// Whether a field instruction reads or writes the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldAccess {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct XrefHit {
+    /// Method containing the instruction, e.g. `Lcom/example/Api;->connect()V`
+    pub method: String,
+    /// Java name of the class defining that method
+    pub class: String,
+    /// DEX file that defines the class
+    pub dex: String,
+    /// Position of the instruction in 16-bit code units from the start of the method
+    pub offset: usize,
+    /// e.g. `invoke-static`, `iget-object` or `const-string`
+    pub instruction: &'static str,
+    /// What the instruction refers to, as smali writes it
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access: Option<FieldAccess>,
+    /// Where the instruction is in apktool's smali, when the APK was unpacked
+    pub smali: Option<Location>,
+    /// Where it most likely is in the decompiled source, when the APK was unpacked
+    pub source: Option<Location>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub path: PathBuf,
+    /// 1-based; `None` when the file exists but the usage was not found in it
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct XrefReport {
+    pub query: XrefQuery,
+    pub hits: Vec<XrefHit>,
+}
+
+/// The parsed DEX files of an APK, searched for each query
+#[derive(Debug, Clone, Default)]
+pub struct Xrefs {
+    files: Vec<(String, DexFile)>,
+}
+
+/// A method or field given as `com.example.Foo.bar`, `Foo.bar` or `Lcom/example/Foo;->bar`,
+/// optionally followed by a descriptor: `(Ljava/lang/String;)V`, `(java.lang.String)` or `:I`
+#[derive(Debug, Clone)]
+struct MemberPattern {
+    class: String,
+    name: String,
+    descriptor: Option<String>,
+}
+
+impl Xrefs {
+    /// Read the DEX files of an APK, or a single `.dex` file
+    pub fn from_path(path: &Path) -> Result<Self> {
+        Self::from_dex_files(read_dex_files(path)?)
+    }
+
+    /// Parse DEX files given as name and content, in load order
+    pub fn from_dex_files(files: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        let files = files
+            .into_iter()
+            .map(|(name, data)| {
+                let dex = DexFile::parse(&data).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+                Ok((name, dex))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    /// Every usage matching `query`, in load order of the classes and instruction order
+    /// within each method
+    pub fn search(&self, query: &XrefQuery) -> Result<XrefReport> {
+        let pattern = match query {
+            XrefQuery::Callers(method) | XrefQuery::Callees(method) => Some(MemberPattern::parse(method, '(')?),
+            XrefQuery::Field(field) => Some(MemberPattern::parse(field, ':')?),
+            XrefQuery::String(_) => None,
+        };
+
+        let mut hits = Vec::new();
+        let mut seen = HashSet::new();
+        for (name, dex) in &self.files {
+            // Which entries of this file's tables the query is about
+            let targets: HashSet<Reference> = match (query, &pattern) {
+                (XrefQuery::Callers(_), Some(pattern)) => (0..dex.methods.len() as u32)
+                    .filter(|&index| pattern.matches_method(dex, index))
+                    .map(Reference::Method)
+                    .collect(),
+                (XrefQuery::Field(_), Some(pattern)) => (0..dex.fields.len() as u32)
+                    .filter(|&index| pattern.matches_field(dex, index))
+                    .map(Reference::Field)
+                    .collect(),
+                (XrefQuery::String(text), _) => (0..dex.strings.len() as u32)
+                    .filter(|&index| dex.strings[index as usize].contains(text.as_str()))
+                    .map(Reference::String)
+                    .collect(),
+                _ => HashSet::new(),
+            };
+
+            for class in &dex.classes {
+                // A class defined twice runs the definition loaded first
+                if !seen.insert(class.name.as_str()) {
+                    continue;
+                }
+
+                for method in &class.methods {
+                    let Some(code) = &method.code else { continue };
+                    if let (XrefQuery::Callees(_), Some(pattern)) = (query, &pattern) {
+                        if !pattern.matches_method(dex, method.method) {
+                            continue;
+                        }
+                    }
+
+                    for instruction in code::instructions(&code.insns) {
+                        let Some(mnemonic) = instruction.mnemonic() else { continue };
+                        let Some(&reference) = instruction.references().first() else { continue };
+                        let found = match query {
+                            XrefQuery::Callees(_) => matches!(reference, Reference::Method(_)),
+                            _ => targets.contains(&reference),
+                        };
+                        if !found {
+                            continue;
+                        }
+
+                        hits.push(XrefHit {
+                            method: format!("{}->{}", class.name, dex.method_signature(method.method)),
+                            class: java_name(&class.name),
+                            dex: name.clone(),
+                            offset: instruction.offset,
+                            instruction: mnemonic,
+                            target: match reference {
+                                Reference::String(index) => smali_literal(&dex.strings[index as usize]),
+                                _ => dex.describe(reference),
+                            },
+                            access: match mnemonic.get(1..4) {
+                                Some("get") => Some(FieldAccess::Read),
+                                Some("put") => Some(FieldAccess::Write),
+                                _ => None,
+                            },
+                            smali: None,
+                            source: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(XrefReport { query: query.clone(), hits })
+    }
+}
+
+impl MemberPattern {
+    /// `separator` starts the descriptor: `(` for methods, `:` for fields
+    fn parse(spec: &str, separator: char) -> Result<Self> {
+        let spec = spec.trim();
+        let (head, descriptor) = match spec.find(separator) {
+            Some(i) => (&spec[..i], Some(&spec[i..])),
+            None => (spec, None),
+        };
+        let descriptor = descriptor.map(|descriptor| {
+            let descriptor = descriptor.strip_prefix(':').unwrap_or(descriptor);
+            descriptor.chars().filter(|c| !c.is_whitespace()).collect()
+        });
+
+        let (class, name) = match head.split_once("->") {
+            Some((class, name)) => (java_name(class).replace('/', "."), name.to_string()),
+            None => match head.rsplit_once('.') {
+                Some((class, name)) => (class.replace('/', "."), name.to_string()),
+                None => anyhow::bail!("Expected Class.member or Lcom/example/Class;->member, got '{}'", spec),
+            },
+        };
+        if class.is_empty() || name.is_empty() {
+            anyhow::bail!("Expected Class.member or Lcom/example/Class;->member, got '{}'", spec);
+        }
+        Ok(Self { class, name, descriptor })
+    }
+
+    /// The full Java name, or a simple name matching after a `.` or `$`
+    fn matches_class(&self, descriptor: &str) -> bool {
+        let class = java_name(descriptor);
+        class == self.class
+            || class.strip_suffix(self.class.as_str()).is_some_and(|prefix| prefix.ends_with('.') || prefix.ends_with('$'))
+    }
+
+    fn matches_method(&self, dex: &DexFile, index: u32) -> bool {
+        let id = &dex.methods[index as usize];
+        if id.name != self.name || !self.matches_class(&id.class) {
+            return false;
+        }
+        let Some(descriptor) = &self.descriptor else { return true };
+
+        let proto = &dex.protos[id.proto as usize];
+        let parameters = format!("({})", proto.parameters.concat());
+        let java_parameters = format!("({})", proto.parameters.iter().map(|parameter| java_name(parameter)).collect::<Vec<_>>().join(","));
+        *descriptor == proto.descriptor() || *descriptor == parameters || *descriptor == java_parameters
+    }
+
+    fn matches_field(&self, dex: &DexFile, index: u32) -> bool {
+        let id = &dex.fields[index as usize];
+        if id.name != self.name || !self.matches_class(&id.class) {
+            return false;
+        }
+        match &self.descriptor {
+            Some(descriptor) => *descriptor == id.field_type || *descriptor == java_name(&id.field_type),
+            None => true,
+        }
+    }
+}
+
+impl XrefReport {
+    /// Fill in the smali and source locations from the directory an unpack wrote
+    ///
+    /// The smali line is exact: the n-th usage of a target in a method is the n-th line of
+    /// that method referring to it. Decompiled sources keep no offsets, so the source line is
+    /// the n-th line of the file that names the target, which is usually right.
+    pub fn locate(&mut self, output_dir: &Path) {
+        let mut files: HashMap<PathBuf, Option<Vec<String>>> = HashMap::new();
+        let mut lines = |path: &Path| -> Option<Vec<String>> {
+            files.entry(path.to_path_buf()).or_insert_with(|| fs::read_to_string(path).ok().map(|text| text.lines().map(str::to_string).collect())).clone()
+        };
+
+        let mut smali_seen: HashMap<(String, String), usize> = HashMap::new();
+        let mut source_seen: HashMap<(PathBuf, String), usize> = HashMap::new();
+        for hit in &mut self.hits {
+            let internal = hit.class.replace('.', "/");
+
+            if let Some(smali_dir) = dex_index(&hit.dex).map(|index| if index == 1 { "smali".to_string() } else { format!("smali_classes{}", index) }) {
+                let path = output_dir.join("unpacked").join(smali_dir).join(format!("{}.smali", internal));
+                if let Some(text) = lines(&path) {
+                    let nth = smali_seen.entry((hit.method.clone(), hit.target.clone())).or_default();
+                    let line = smali_line(&text, &hit.method, &hit.target, *nth);
+                    *nth += 1;
+                    hit.smali = Some(Location { path, line });
+                }
+            }
+
+            let outer = internal.split('$').next().unwrap_or(&internal);
+            let path = output_dir.join("src").join(format!("{}.java", outer));
+            if let Some(text) = lines(&path) {
+                let needle = source_needle(&hit.target);
+                let line = needle.as_ref().and_then(|needle| {
+                    let nth = source_seen.entry((path.clone(), needle.clone())).or_default();
+                    let matching: Vec<usize> = text.iter().enumerate().filter(|(_, line)| line.contains(needle.as_str())).map(|(i, _)| i + 1).collect();
+                    let line = matching.get(*nth).or(matching.last()).copied();
+                    *nth += 1;
+                    line
+                });
+                hit.source = Some(Location { path, line });
+            }
+        }
+    }
+}
+
+/// Line of the `nth` reference to `target` inside `method` of a smali file
+fn smali_line(lines: &[String], method: &str, target: &str, nth: usize) -> Option<usize> {
+    let signature = method.split_once("->").map(|(_, signature)| signature)?;
+    let start = lines.iter().position(|line| {
+        let line = line.trim();
+        line.starts_with(".method ") && line.ends_with(&format!(" {}", signature))
+    })?;
+
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| line.trim() != ".end method")
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim_start();
+            !line.starts_with('#') && !line.starts_with('.') && line.contains(target)
+        })
+        .nth(nth)
+        .map(|(i, _)| start + i + 2)
+}
+
+/// Text a decompiled source most likely has where it uses `target`: `name(` for a method,
+/// `new Foo(` for a constructor, the name of a field or the longest plain part of a string
+fn source_needle(target: &str) -> Option<String> {
+    if let Some(literal) = target.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        // Decompilers escape strings their own way, so only match text between escapes
+        let mut parts = literal.split('\\');
+        let first = parts.next().unwrap_or_default();
+        let rest = parts.map(|part| if part.starts_with('u') { part.get(5..).unwrap_or_default() } else { part.get(1..).unwrap_or_default() });
+        return std::iter::once(first).chain(rest).max_by_key(|part| part.len()).filter(|part| part.len() > 1).map(str::to_string);
+    }
+
+    let (class, member) = target.split_once("->")?;
+    if let Some((name, _)) = member.split_once('(') {
+        return match name {
+            "<clinit>" => None,
+            "<init>" => {
+                let class = java_name(class);
+                let simple = class.rsplit(['.', '$']).next().unwrap_or(&class);
+                Some(format!("{}(", simple))
+            }
+            name => Some(format!("{}(", name)),
+        };
+    }
+    member.split_once(':').map(|(name, _)| name.to_string())
+}
+
+/// A string constant as smali writes it: quoted, with quotes, backslashes and everything
+/// outside printable ASCII escaped
+pub fn smali_literal(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for unit in text.encode_utf16() {
+        match unit {
+            0x27 | 0x22 | 0x5c => {
+                literal.push('\\');
+                literal.push(unit as u8 as char);
+            }
+            0x20..=0x7e => literal.push(unit as u8 as char),
+            0x0a => literal.push_str("\\n"),
+            0x0d => literal.push_str("\\r"),
+            0x09 => literal.push_str("\\t"),
+            _ => literal.push_str(&format!("\\u{:04x}", unit)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+impl fmt::Display for XrefQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XrefQuery::Callers(method) => write!(f, "Callers of {}", method),
+            XrefQuery::Callees(method) => write!(f, "Calls made by {}", method),
+            XrefQuery::Field(field) => write!(f, "Reads and writes of {}", field),
+            XrefQuery::String(text) => write!(f, "Uses of strings containing {:?}", text),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.path.display(), line),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+impl fmt::Display for XrefReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({}):", self.query, self.hits.len())?;
+        for hit in &self.hits {
+            writeln!(f, "  {} @{:04x}", hit.method, hit.offset)?;
+            writeln!(f, "    {} {}", hit.instruction, hit.target)?;
+            if let Some(smali) = &hit.smali {
+                writeln!(f, "    smali:  {}", smali)?;
+            }
+            if let Some(source) = &hit.source {
+                writeln!(f, "    source: {}", source)?;
+            }
+        }
+        Ok(())
+    }
+}
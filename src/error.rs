@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Failures of the unpack and pack pipelines that callers may want to tell apart
+#[derive(Debug, Error)]
+pub enum ApkextError {
+    /// The Java runtime could not be started
+    #[error("Failed to run Java at '{java}': {source}")]
+    JavaNotFound {
+        java: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// `java -version` ran but did not succeed
+    #[error("Java at '{java}' is not working properly: {stderr}")]
+    JavaNotWorking { java: String, stderr: String },
+
+    /// The input is not an APK this tool can unpack
+    #[error("Invalid APK '{}': {reason}", path.display())]
+    InvalidApk { path: PathBuf, reason: String },
+
+    /// The directory given to `pack` is not an unpacked APK
+    #[error("Invalid unpacked directory '{}': {reason}", path.display())]
+    InvalidProject { path: PathBuf, reason: String },
+
+    /// The output directory exists and the caller did not say what to do with it
+    #[error("Output directory '{}' already exists; use --force to replace it or --merge to refresh generated files and keep your changes", .0.display())]
+    OutputExists(PathBuf),
+
+    /// A signature of the APK does not verify, or the APK is not signed
+    #[error("Signature verification of '{}' failed: {reason}", path.display())]
+    SignatureInvalid { path: PathBuf, reason: String },
+
+    /// An external tool (apktool, dex2jar, a decompiler) exited with an error
+    #[error("{tool} failed{}: {stderr}", exit_code_suffix(*.code))]
+    ToolFailed {
+        tool: String,
+        /// `None` when the process was killed by a signal
+        code: Option<i32>,
+        stderr: String,
+    },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl ApkextError {
+    /// Process exit code for the CLI; 2 is left to argument parsing errors
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ApkextError::Other(_) => 1,
+            ApkextError::ToolFailed { .. } => 3,
+            ApkextError::JavaNotFound { .. } | ApkextError::JavaNotWorking { .. } => 4,
+            ApkextError::InvalidApk { .. } | ApkextError::InvalidProject { .. } => 5,
+            ApkextError::OutputExists(_) => 6,
+            ApkextError::Io(_) | ApkextError::Zip(_) => 7,
+            ApkextError::SignatureInvalid { .. } => 8,
+        }
+    }
+}
+
+/// Keeps typed errors that travelled through an `anyhow::Error`
+impl From<anyhow::Error> for ApkextError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ApkextError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(error) => return ApkextError::Io(error),
+            Err(error) => error,
+        };
+        match error.downcast::<zip::result::ZipError>() {
+            Ok(error) => ApkextError::Zip(error),
+            Err(error) => ApkextError::Other(error),
+        }
+    }
+}
+
+fn exit_code_suffix(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!(" with exit code {}", code),
+        None => String::new(),
+    }
+}
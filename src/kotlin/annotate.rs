@@ -0,0 +1,251 @@
+//! Kotlin declarations as comments in decompiled Java
+//!
+//! Every class, constructor, method and field that the metadata describes gets the Kotlin
+//! declaration above it, e.g. `// Kotlin: data class User(name: String, age: Int?)`.
+
+use super::{FacadeKind, KotlinReport};
+use crate::Result;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Prefix of every comment this module writes; a file that has one was annotated before
+pub const COMMENT_PREFIX: &str = "// Kotlin: ";
+
+/// What to write above the declaration of one class and its members
+#[derive(Default)]
+struct Target {
+    comment: Option<String>,
+    members: Vec<Member>,
+}
+
+struct Member {
+    /// Name in the Java source; constructors are named after their class
+    java_name: String,
+    /// Parameters in the JVM descriptor; `None` for fields
+    parameters: Option<usize>,
+    comment: String,
+}
+
+/// Insert Kotlin declarations into the sources under `src_dir`; returns how many files changed
+pub fn annotate_sources(report: &KotlinReport, src_dir: &Path) -> Result<usize> {
+    let mut files: BTreeMap<PathBuf, HashMap<String, Target>> = BTreeMap::new();
+
+    for class in &report.classes {
+        let simple_name = simple_name(&class.jvm_class).to_string();
+        let target = target(&mut files, &class.jvm_class);
+        target.comment = Some(class.declaration.clone());
+        for constructor in &class.constructors {
+            target.members.push(Member {
+                java_name: simple_name.clone(),
+                parameters: Some(constructor.jvm_signature.as_deref().map_or(constructor.parameters, descriptor_parameters)),
+                comment: constructor.declaration.clone(),
+            });
+        }
+        add_members(target, &class.functions, &class.properties);
+    }
+    for facade in &report.facades {
+        let source_file = facade.source_file.as_deref().unwrap_or("?");
+        let target = target(&mut files, &facade.jvm_class);
+        target.comment = Some(match facade.kind {
+            FacadeKind::File => format!("top-level declarations of {}", source_file),
+            FacadeKind::MultiFileFacade => format!("multi-file class of {}", facade.parts.join(", ")),
+            FacadeKind::MultiFilePart => format!("part of multi-file class {}, from {}", facade.facade.as_deref().unwrap_or("?"), source_file),
+        });
+        add_members(target, &facade.functions, &facade.properties);
+    }
+
+    let mut annotated = 0;
+    for (source, targets) in files {
+        let path = src_dir.join(&source);
+        let Ok(text) = fs::read_to_string(&path) else { continue };
+        if text.contains(COMMENT_PREFIX) {
+            continue;
+        }
+        let package = source.parent().map(|parent| parent.to_string_lossy().replace('/', ".")).filter(|package| !package.is_empty());
+        let annotated_text = annotate(&text, package.as_deref(), &targets);
+        if annotated_text != text {
+            fs::write(&path, annotated_text)?;
+            annotated += 1;
+        }
+    }
+    Ok(annotated)
+}
+
+/// The comments for `jvm_class`, in the source file of its outer class
+fn target<'a>(files: &'a mut BTreeMap<PathBuf, HashMap<String, Target>>, jvm_class: &str) -> &'a mut Target {
+    let outer = jvm_class.split('$').next().unwrap_or(jvm_class);
+    let source = PathBuf::from(format!("{}.java", outer.replace('.', "/")));
+    files.entry(source).or_default().entry(jvm_class.to_string()).or_default()
+}
+
+fn add_members(target: &mut Target, functions: &[super::KotlinFunction], properties: &[super::KotlinProperty]) {
+    for function in functions {
+        let (java_name, parameters) = match function.jvm_signature.as_deref() {
+            Some(signature) => (signature.split('(').next().unwrap_or(&function.name).to_string(), descriptor_parameters(signature)),
+            None => (function.name.clone(), function.parameters),
+        };
+        target.members.push(Member { java_name, parameters: Some(parameters), comment: function.declaration.clone() });
+    }
+    for property in properties {
+        if let Some(field) = &property.field {
+            let java_name = field.split(':').next().unwrap_or(&property.name).to_string();
+            target.members.push(Member { java_name, parameters: None, comment: property.declaration.clone() });
+        }
+        for accessor in [&property.getter, &property.setter].into_iter().flatten() {
+            let java_name = accessor.split('(').next().unwrap_or_default().to_string();
+            target.members.push(Member { java_name, parameters: Some(descriptor_parameters(accessor)), comment: property.declaration.clone() });
+        }
+    }
+}
+
+/// `Bar` for `com.example.Foo$Bar`
+fn simple_name(jvm_class: &str) -> &str {
+    jvm_class.rsplit(['.', '$']).next().unwrap_or(jvm_class)
+}
+
+/// Number of parameters of a method descriptor such as `greet(Ljava/lang/String;[II)V`
+fn descriptor_parameters(signature: &str) -> usize {
+    let Some(parameters) = signature.split_once('(').and_then(|(_, rest)| rest.split_once(')')).map(|(parameters, _)| parameters) else { return 0 };
+    let mut count = 0;
+    let mut chars = parameters.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '[' => continue,
+            'L' => {
+                chars.by_ref().find(|&char| char == ';');
+            }
+            _ => {}
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Number of parameters in a Java parameter list, ignoring commas inside generics
+fn java_parameters(parameters: &str) -> usize {
+    if parameters.trim().is_empty() {
+        return 0;
+    }
+    let mut depth = 0;
+    let mut count = 1;
+    for char in parameters.chars() {
+        match char {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+fn class_declaration() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^\s*(?:[\w@]+\s+)*(?:class|interface|enum|@interface)\s+(\w+)\b").unwrap())
+}
+
+fn method_declaration() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^\s*((?:[\w<>\[\],.?@]+\s+)*)(\w+)\s*\((.*)\)\s*(?:throws\s+[\w.,\s]+)?[{;]\s*$").unwrap())
+}
+
+fn field_declaration() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^\s*(?:[\w<>\[\],.?@]+\s+)+(\w+)\s*(?:=.*)?;\s*$").unwrap())
+}
+
+/// The comment for a member declared on `line` of class `jvm_class`
+fn member_comment<'a>(line: &str, jvm_class: &str, target: &'a Target) -> Option<&'a str> {
+    if let Some(captures) = method_declaration().captures(line) {
+        let name = &captures[2];
+        // Only constructors are declared without a return type
+        if captures[1].trim().is_empty() && name != simple_name(jvm_class) {
+            return None;
+        }
+        let parameters = java_parameters(&captures[3]);
+        let candidates: Vec<&Member> = target.members.iter().filter(|member| member.parameters.is_some() && member.java_name == name).collect();
+        // Procyon hides the synthetic parameters of enum and inner class constructors, so a
+        // single candidate is taken whatever its parameter count
+        let member = match candidates[..] {
+            [member] => Some(member),
+            _ => candidates.into_iter().find(|member| member.parameters == Some(parameters)),
+        };
+        return member.map(|member| member.comment.as_str());
+    }
+    let name = field_declaration().captures(line)?.get(1)?.as_str();
+    target.members.iter().find(|member| member.parameters.is_none() && member.java_name == name).map(|member| member.comment.as_str())
+}
+
+/// `text` with comments inserted above the declarations in `targets`
+fn annotate(text: &str, package: Option<&str>, targets: &HashMap<String, Target>) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // JVM names of the classes whose body the line is in, and the depth of their declaration
+    let mut classes: Vec<(String, i32)> = Vec::new();
+    let mut depth = 0;
+
+    for line in text.split('\n') {
+        let comment = if let Some(captures) = class_declaration().captures(line) {
+            let jvm_class = match (classes.last(), package) {
+                (Some((outer, _)), _) => format!("{}${}", outer, &captures[1]),
+                (None, Some(package)) => format!("{}.{}", package, &captures[1]),
+                (None, None) => captures[1].to_string(),
+            };
+            let comment = targets.get(&jvm_class).and_then(|target| target.comment.as_deref());
+            classes.push((jvm_class, depth));
+            comment
+        } else {
+            match classes.last() {
+                // Declarations directly in a class body, not statements in a method
+                Some((jvm_class, class_depth)) if depth == class_depth + 1 => {
+                    targets.get(jvm_class).and_then(|target| member_comment(line, jvm_class, target))
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(comment) = comment {
+            // Above the annotations of the declaration, not between them and it
+            let mut at = lines.len();
+            while at > 0 && lines[at - 1].trim_start().starts_with('@') {
+                at -= 1;
+            }
+            let indent = &line[..line.len() - line.trim_start().len()];
+            lines.insert(at, format!("{}{}{}", indent, COMMENT_PREFIX, comment));
+        }
+        lines.push(line.to_string());
+
+        let (opened, closed) = braces(line);
+        depth += opened - closed;
+        if closed > 0 {
+            while classes.last().is_some_and(|(_, class_depth)| *class_depth >= depth) {
+                classes.pop();
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Opening and closing braces on a line, outside string and char literals
+fn braces(line: &str) -> (i32, i32) {
+    let (mut opened, mut closed) = (0, 0);
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(char) = chars.next() {
+        match (quote, char) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(open), char) if char == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(char),
+            (None, '{') => opened += 1,
+            (None, '}') => closed += 1,
+            (None, '/') if chars.as_str().starts_with('/') => break,
+            _ => {}
+        }
+    }
+    (opened, closed)
+}
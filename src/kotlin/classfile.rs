@@ -0,0 +1,191 @@
+//! Just enough of the JVM class file format to find a class's name, source file and
+//! `kotlin.Metadata` annotation
+//!
+//! See <https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html>.
+
+use crate::classfile::{self, Constant, ConstantPool};
+use crate::Result;
+
+const METADATA_DESCRIPTOR: &str = "Lkotlin/Metadata;";
+
+/// What a class file says about itself
+#[derive(Debug, Clone, Default)]
+pub struct ClassFile {
+    /// Internal name, e.g. `com/example/Foo$Bar`
+    pub name: String,
+    /// `SourceFile` attribute, e.g. `Foo.kt`
+    pub source_file: Option<String>,
+    pub metadata: Option<MetadataAnnotation>,
+}
+
+/// The arguments of `@kotlin.Metadata`, under their names in the annotation
+#[derive(Debug, Clone, Default)]
+pub struct MetadataAnnotation {
+    /// `k`: 1 class, 2 file facade, 3 synthetic class, 4 multi-file class facade, 5 multi-file class part
+    pub kind: i32,
+    /// `mv`
+    pub version: Vec<i32>,
+    /// `d1`: the protobuf message, as strings of Java chars
+    pub data1: Vec<Vec<u16>>,
+    /// `d2`: the string table
+    pub data2: Vec<String>,
+    /// `xs`: for a multi-file class part, the facade's internal name
+    pub extra_string: Option<String>,
+    /// `pn`
+    pub package_name: Option<String>,
+    /// `xi`
+    pub extra_int: i32,
+}
+
+/// An annotation argument
+#[derive(Debug, Clone)]
+enum ElementValue {
+    Int(i32),
+    String(Vec<u16>),
+    Array(Vec<ElementValue>),
+    Other,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into()?))
+    }
+}
+
+impl ClassFile {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let pool = ConstantPool::parse(data)?;
+        let utf8 = |index: u16| pool.string(index);
+        let mut reader = Reader { data, pos: pool.end };
+
+        reader.bytes(2)?; // access flags
+        let this_class = reader.u16()?;
+        let name = match pool.get(this_class) {
+            Some(Constant::Class(name)) => utf8(name).unwrap_or_default(),
+            _ => anyhow::bail!("Malformed class file: this_class is not a class"),
+        };
+        reader.bytes(2)?; // superclass
+        let interfaces = reader.u16()? as usize;
+        reader.bytes(interfaces * 2)?;
+
+        // Fields, then methods
+        for _ in 0..2 {
+            for _ in 0..reader.u16()? {
+                reader.bytes(6)?;
+                skip_attributes(&mut reader)?;
+            }
+        }
+
+        let mut class = ClassFile { name, ..Default::default() };
+        for _ in 0..reader.u16()? {
+            let attribute = utf8(reader.u16()?).unwrap_or_default();
+            let len = reader.u32()? as usize;
+            let mut body = Reader { data: reader.bytes(len)?, pos: 0 };
+            match attribute.as_str() {
+                "SourceFile" => class.source_file = utf8(body.u16()?),
+                "RuntimeVisibleAnnotations" => {
+                    for _ in 0..body.u16()? {
+                        let descriptor = utf8(body.u16()?).unwrap_or_default();
+                        let arguments = annotation_arguments(&mut body, &pool)?;
+                        if descriptor == METADATA_DESCRIPTOR {
+                            class.metadata = Some(metadata(arguments, &utf8));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(class)
+    }
+}
+
+fn skip_attributes(reader: &mut Reader<'_>) -> Result<()> {
+    for _ in 0..reader.u16()? {
+        reader.bytes(2)?;
+        let len = reader.u32()? as usize;
+        reader.bytes(len)?;
+    }
+    Ok(())
+}
+
+/// Name and value of each argument of the annotation at the reader
+fn annotation_arguments(reader: &mut Reader<'_>, pool: &ConstantPool<'_>) -> Result<Vec<(u16, ElementValue)>> {
+    let mut arguments = Vec::new();
+    for _ in 0..reader.u16()? {
+        let name = reader.u16()?;
+        arguments.push((name, element_value(reader, pool)?));
+    }
+    Ok(arguments)
+}
+
+fn element_value(reader: &mut Reader<'_>, pool: &ConstantPool<'_>) -> Result<ElementValue> {
+    let start = reader.pos;
+    let value = match reader.u8()? {
+        b'I' => match pool.get(reader.u16()?) {
+            Some(Constant::Integer(value)) => ElementValue::Int(value),
+            _ => ElementValue::Other,
+        },
+        b's' => match pool.chars(reader.u16()?) {
+            Some(chars) => ElementValue::String(chars),
+            None => ElementValue::Other,
+        },
+        b'[' => {
+            let count = reader.u16()?;
+            let mut values = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                values.push(element_value(reader, pool)?);
+            }
+            ElementValue::Array(values)
+        }
+        _ => {
+            reader.pos = classfile::skip_element_value(reader.data, start)?;
+            ElementValue::Other
+        }
+    };
+    Ok(value)
+}
+
+fn metadata(arguments: Vec<(u16, ElementValue)>, utf8: &impl Fn(u16) -> Option<String>) -> MetadataAnnotation {
+    let strings = |values: Vec<ElementValue>| -> Vec<Vec<u16>> {
+        values.into_iter().filter_map(|value| if let ElementValue::String(chars) = value { Some(chars) } else { None }).collect()
+    };
+
+    let mut metadata = MetadataAnnotation { kind: 1, ..Default::default() };
+    for (name, value) in arguments {
+        match (utf8(name).as_deref(), value) {
+            (Some("k"), ElementValue::Int(kind)) => metadata.kind = kind,
+            (Some("xi"), ElementValue::Int(extra_int)) => metadata.extra_int = extra_int,
+            (Some("mv"), ElementValue::Array(values)) => {
+                metadata.version = values.into_iter().filter_map(|value| if let ElementValue::Int(part) = value { Some(part) } else { None }).collect();
+            }
+            (Some("d1"), ElementValue::Array(values)) => metadata.data1 = strings(values),
+            (Some("d2"), ElementValue::Array(values)) => {
+                metadata.data2 = strings(values).iter().map(|chars| String::from_utf16_lossy(chars)).collect();
+            }
+            (Some("xs"), ElementValue::String(chars)) => metadata.extra_string = Some(String::from_utf16_lossy(&chars)),
+            (Some("pn"), ElementValue::String(chars)) => metadata.package_name = Some(String::from_utf16_lossy(&chars)),
+            _ => {}
+        }
+    }
+    metadata
+}
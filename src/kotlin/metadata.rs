@@ -0,0 +1,506 @@
+//! The protobuf messages inside `@kotlin.Metadata`, turned into Kotlin declarations
+//!
+//! `d1` holds a `StringTableTypes` message followed by a `Class` or `Package` message from
+//! the Kotlin compiler's `metadata.proto` and `jvm_metadata.proto`; `d2` is the string table
+//! their names point into.
+
+use super::classfile::MetadataAnnotation;
+use super::{FacadeKind, FileFacade, KotlinClass, KotlinFunction, KotlinProperty};
+use crate::aab::proto::{self, field, repeated, repeated_varints, Value};
+use crate::Result;
+use std::collections::HashMap;
+
+/// `k` of a class
+pub const KIND_CLASS: i32 = 1;
+/// `k` of the `FooKt` class holding the top-level declarations of `Foo.kt`
+pub const KIND_FILE_FACADE: i32 = 2;
+/// `k` of lambdas, `$WhenMappings`, `$DefaultImpls` and other compiler-generated classes
+pub const KIND_SYNTHETIC_CLASS: i32 = 3;
+/// `k` of a `@JvmMultifileClass` facade
+pub const KIND_MULTI_FILE_FACADE: i32 = 4;
+/// `k` of one file's part of a `@JvmMultifileClass` facade
+pub const KIND_MULTI_FILE_PART: i32 = 5;
+
+/// Marks `d1` as written one byte per char, rather than in the older 8-to-7-bit encoding
+const UTF8_MODE_MARKER: u16 = 0;
+const EIGHT_TO_SEVEN_MARKER: u16 = 0xffff;
+
+/// Strings a `StringTableTypes` record can name by index instead of repeating them in `d2`
+const PREDEFINED_STRINGS: [&str; 44] = [
+    "kotlin/Any", "kotlin/Nothing", "kotlin/Unit", "kotlin/Throwable", "kotlin/Number",
+    "kotlin/Byte", "kotlin/Double", "kotlin/Float", "kotlin/Int", "kotlin/Long", "kotlin/Short", "kotlin/Boolean", "kotlin/Char",
+    "kotlin/CharSequence", "kotlin/String", "kotlin/Comparable", "kotlin/Enum",
+    "kotlin/Array", "kotlin/ByteArray", "kotlin/DoubleArray", "kotlin/FloatArray", "kotlin/IntArray",
+    "kotlin/LongArray", "kotlin/ShortArray", "kotlin/BooleanArray", "kotlin/CharArray",
+    "kotlin/Cloneable", "kotlin/Annotation",
+    "kotlin/collections/Iterable", "kotlin/collections/MutableIterable",
+    "kotlin/collections/Collection", "kotlin/collections/MutableCollection",
+    "kotlin/collections/List", "kotlin/collections/MutableList",
+    "kotlin/collections/Set", "kotlin/collections/MutableSet",
+    "kotlin/collections/Map", "kotlin/collections/MutableMap",
+    "kotlin/collections/Map.Entry", "kotlin/collections/MutableMap.MutableEntry",
+    "kotlin/collections/Iterator", "kotlin/collections/MutableIterator",
+    "kotlin/collections/ListIterator", "kotlin/collections/MutableListIterator",
+];
+
+const VISIBILITIES: [&str; 6] = ["internal", "private", "protected", "public", "private", "local"];
+const MODALITIES: [&str; 4] = ["final", "open", "abstract", "sealed"];
+const CLASS_KINDS: [&str; 7] = ["class", "interface", "enum class", "enum entry", "annotation class", "object", "companion object"];
+
+type Fields<'a> = Vec<(u32, Value<'a>)>;
+
+/// The bytes of `d1`
+pub fn decode_data1(data1: &[Vec<u16>]) -> Vec<u8> {
+    let mut chars: Vec<u16> = data1.concat();
+    match chars.first() {
+        Some(&UTF8_MODE_MARKER) => return chars[1..].iter().map(|&char| char as u8).collect(),
+        Some(&EIGHT_TO_SEVEN_MARKER) => {
+            chars.remove(0);
+        }
+        _ => {}
+    }
+
+    // Each char holds 7 bits, shifted so that no byte is zero
+    let bytes: Vec<u8> = chars.iter().map(|&char| ((char as u8 as u32 + 0x7f) & 0x7f) as u8).collect();
+    let len = 7 * bytes.len() / 8;
+    let mut result = Vec::with_capacity(len);
+    let (mut index, mut bit) = (0, 0);
+    for _ in 0..len {
+        let first = bytes[index] as u32 >> bit;
+        index += 1;
+        let second = (bytes[index] as u32 & ((1 << (bit + 1)) - 1)) << (7 - bit);
+        result.push((first + second) as u8);
+        if bit == 6 {
+            index += 1;
+            bit = 0;
+        } else {
+            bit += 1;
+        }
+    }
+    result
+}
+
+/// Names in `d2`, after applying what `StringTableTypes` says about each
+struct NameResolver {
+    strings: Vec<String>,
+}
+
+impl NameResolver {
+    fn new(types: &[u8], data2: &[String]) -> Result<Self> {
+        let mut strings = Vec::with_capacity(data2.len());
+        for record in repeated(&proto::fields(types)?, 1) {
+            let record = proto::fields(record.as_bytes())?;
+            let range = field(&record, 1).map_or(1, |value| value.as_u64() as usize);
+            for _ in 0..range {
+                let index = strings.len();
+                let Some(plain) = data2.get(index) else { break };
+                strings.push(resolve(&record, plain)?);
+            }
+        }
+        // Strings past the last record are used as they are
+        strings.extend(data2.iter().skip(strings.len()).cloned());
+        Ok(Self { strings })
+    }
+
+    fn string(&self, index: u64) -> String {
+        self.strings.get(index as usize).cloned().unwrap_or_else(|| format!("<{}>", index))
+    }
+
+    /// A class as Kotlin names it: `String` for the standard types, `com.example.Outer.Inner` otherwise
+    fn class_name(&self, index: u64) -> String {
+        let name = self.string(index);
+        let name = name.strip_prefix('.').unwrap_or(&name);
+        match name.rsplit_once('/') {
+            Some((package, simple)) if package == "kotlin" || package.starts_with("kotlin/") => simple.to_string(),
+            _ => name.replace('/', "."),
+        }
+    }
+}
+
+fn resolve(record: &Fields<'_>, plain: &str) -> Result<String> {
+    let mut string = match (field(record, 6), field(record, 2)) {
+        (Some(string), _) => string.as_str(),
+        (None, Some(index)) => PREDEFINED_STRINGS.get(index.as_u64() as usize).map_or_else(|| plain.to_string(), |string| string.to_string()),
+        (None, None) => plain.to_string(),
+    };
+
+    let substring = repeated_varints(record, 4)?;
+    if let [begin, end, ..] = substring[..] {
+        let chars: Vec<u16> = string.encode_utf16().collect();
+        if begin <= end && end as usize <= chars.len() {
+            string = String::from_utf16_lossy(&chars[begin as usize..end as usize]);
+        }
+    }
+    let replace = repeated_varints(record, 5)?;
+    if let [from, to, ..] = replace[..] {
+        if let (Some(from), Some(to)) = (char::from_u32(from as u32), char::from_u32(to as u32)) {
+            string = string.replace(from, &to.to_string());
+        }
+    }
+
+    Ok(match field(record, 3).map(|value| value.as_u64()) {
+        // INTERNAL_TO_CLASS_ID
+        Some(1) => string.replace('$', "."),
+        // DESC_TO_CLASS_ID: `Lcom/example/Foo$Bar;`
+        Some(2) => match string.strip_prefix('L').and_then(|name| name.strip_suffix(';')) {
+            Some(name) => name.replace('$', "."),
+            None => string,
+        },
+        _ => string,
+    })
+}
+
+/// Types referred to by index from the members of one class or package
+struct TypeTable<'a> {
+    types: Vec<&'a [u8]>,
+    first_nullable: Option<usize>,
+}
+
+impl<'a> TypeTable<'a> {
+    fn new(message: &Fields<'a>) -> Result<Self> {
+        let Some(table) = field(message, 30) else { return Ok(Self { types: Vec::new(), first_nullable: None }) };
+        let table = proto::fields(table.as_bytes())?;
+        Ok(Self {
+            types: repeated(&table, 1).map(|value| value.as_bytes()).collect(),
+            first_nullable: field(&table, 2).map(|value| value.as_u64() as i32).filter(|first| *first >= 0).map(|first| first as usize),
+        })
+    }
+}
+
+/// Renders the declarations of one class or package
+struct Renderer<'a> {
+    names: NameResolver,
+    types: TypeTable<'a>,
+    /// Type parameter names by id, of the class and the member being rendered
+    type_parameters: HashMap<u64, String>,
+}
+
+impl<'a> Renderer<'a> {
+    /// A type given inline in `field_number` or by index into the type table in `id_number`
+    fn type_of(&self, message: &Fields<'_>, field_number: u32, id_number: u32) -> Option<String> {
+        if let Some(inline) = field(message, field_number) {
+            return self.render_type(inline.as_bytes(), false).ok();
+        }
+        let id = field(message, id_number)?.as_u64() as usize;
+        let nullable = self.types.first_nullable.is_some_and(|first| id >= first);
+        self.render_type(self.types.types.get(id)?, nullable).ok()
+    }
+
+    fn render_type(&self, data: &[u8], nullable: bool) -> Result<String> {
+        let message = proto::fields(data)?;
+        let mut arguments = Vec::new();
+        for argument in repeated(&message, 2) {
+            let argument = proto::fields(argument.as_bytes())?;
+            // IN, OUT, INV, STAR
+            let projection = field(&argument, 1).map_or(2, |value| value.as_u64());
+            let rendered = match self.type_of(&argument, 2, 3) {
+                _ if projection == 3 => "*".to_string(),
+                Some(rendered) if projection == 0 => format!("in {}", rendered),
+                Some(rendered) if projection == 1 => format!("out {}", rendered),
+                Some(rendered) => rendered,
+                None => "*".to_string(),
+            };
+            arguments.push(rendered);
+        }
+
+        let name = if let Some(class) = field(&message, 6).or_else(|| field(&message, 12)) {
+            self.names.class_name(class.as_u64())
+        } else if let Some(name) = field(&message, 9) {
+            self.names.string(name.as_u64())
+        } else if let Some(id) = field(&message, 7) {
+            self.type_parameters.get(&id.as_u64()).cloned().unwrap_or_else(|| format!("T{}", id.as_u64()))
+        } else {
+            "?".to_string()
+        };
+
+        let nullable = nullable || field(&message, 3).is_some_and(|value| value.as_bool());
+        let mut rendered = match name.strip_prefix("Function").filter(|arity| arity.parse::<u32>().is_ok()) {
+            // kotlin/Function2<A, B, R> is (A, B) -> R
+            Some(_) if !arguments.is_empty() => {
+                let result = arguments.pop().unwrap_or_default();
+                let function = format!("({}) -> {}", arguments.join(", "), result);
+                if nullable { format!("({})", function) } else { function }
+            }
+            _ if arguments.is_empty() => name,
+            _ => format!("{}<{}>", name, arguments.join(", ")),
+        };
+        if nullable {
+            rendered.push('?');
+        }
+        Ok(rendered)
+    }
+
+    /// Remember the type parameters in `field_number` and render them as `<T, R : Any>`
+    fn type_parameters(&mut self, message: &Fields<'_>, field_number: u32) -> Result<String> {
+        let mut rendered = Vec::new();
+        for parameter in repeated(message, field_number) {
+            let parameter = proto::fields(parameter.as_bytes())?;
+            let id = field(&parameter, 1).map_or(0, |value| value.as_u64());
+            let name = field(&parameter, 2).map_or_else(String::new, |value| self.names.string(value.as_u64()));
+            self.type_parameters.insert(id, name.clone());
+
+            let reified = if field(&parameter, 3).is_some_and(|value| value.as_bool()) { "reified " } else { "" };
+            let variance = match field(&parameter, 4).map(|value| value.as_u64()) {
+                Some(0) => "in ",
+                Some(1) => "out ",
+                _ => "",
+            };
+            rendered.push(format!("{}{}{}", reified, variance, name));
+        }
+        Ok(if rendered.is_empty() { String::new() } else { format!("<{}>", rendered.join(", ")) })
+    }
+
+    fn value_parameters(&self, message: &Fields<'_>, field_number: u32) -> Result<Vec<String>> {
+        let mut rendered = Vec::new();
+        for parameter in repeated(message, field_number) {
+            let parameter = proto::fields(parameter.as_bytes())?;
+            let flags = field(&parameter, 1).map_or(0, |value| value.as_u64());
+            let name = field(&parameter, 2).map_or_else(String::new, |value| self.names.string(value.as_u64()));
+            let default = if flags & 0x2 != 0 { " = ..." } else { "" };
+
+            let mut modifiers = String::new();
+            if flags & 0x4 != 0 {
+                modifiers.push_str("crossinline ");
+            }
+            if flags & 0x8 != 0 {
+                modifiers.push_str("noinline ");
+            }
+            match self.type_of(&parameter, 4, 6) {
+                Some(element) => rendered.push(format!("{}vararg {}: {}{}", modifiers, name, element, default)),
+                None => rendered.push(format!("{}{}: {}{}", modifiers, name, self.type_of(&parameter, 3, 5).unwrap_or_default(), default)),
+            }
+        }
+        Ok(rendered)
+    }
+
+    /// `name(desc)` from a `JvmMethodSignature`, defaulting the name
+    fn jvm_method(&self, signature: Option<Value<'_>>, default_name: &str) -> Result<Option<String>> {
+        let Some(signature) = signature else { return Ok(None) };
+        let signature = proto::fields(signature.as_bytes())?;
+        let name = field(&signature, 1).map_or_else(|| default_name.to_string(), |value| self.names.string(value.as_u64()));
+        Ok(field(&signature, 2).map(|desc| format!("{}{}", name, self.names.string(desc.as_u64()))))
+    }
+
+    fn function(&mut self, data: &[u8], in_interface: bool) -> Result<KotlinFunction> {
+        let message = proto::fields(data)?;
+        let flags = field(&message, 9).map_or(6, |value| value.as_u64());
+        let name = field(&message, 2).map_or_else(String::new, |value| self.names.string(value.as_u64()));
+
+        let outer = self.type_parameters.clone();
+        let type_parameters = self.type_parameters(&message, 4)?;
+        let receiver = self.type_of(&message, 5, 8).map(|receiver| format!("{}.", receiver)).unwrap_or_default();
+        let parameters = self.value_parameters(&message, 6)?;
+        let result = self.type_of(&message, 3, 7).filter(|result| result != "Unit").map(|result| format!(": {}", result)).unwrap_or_default();
+        self.type_parameters = outer;
+
+        let mut modifiers = member_modifiers(flags, in_interface);
+        for (bit, modifier) in [(12, "external"), (13, "suspend"), (10, "inline"), (11, "tailrec"), (8, "operator"), (9, "infix")] {
+            if flags >> bit & 1 != 0 {
+                modifiers.push(modifier);
+            }
+        }
+        let type_parameters = if type_parameters.is_empty() { type_parameters } else { format!("{} ", type_parameters) };
+
+        Ok(KotlinFunction {
+            declaration: format!("{}fun {}{}{}({}){}", prefix(&modifiers), type_parameters, receiver, name, parameters.join(", "), result),
+            jvm_signature: self.jvm_method(field(&message, 100), &name)?,
+            parameters: parameters.len() + usize::from(!receiver.is_empty()),
+            name,
+        })
+    }
+
+    fn property(&mut self, data: &[u8], in_interface: bool) -> Result<KotlinProperty> {
+        let message = proto::fields(data)?;
+        let flags = field(&message, 11).map_or(518, |value| value.as_u64());
+        let name = field(&message, 2).map_or_else(String::new, |value| self.names.string(value.as_u64()));
+
+        let outer = self.type_parameters.clone();
+        let type_parameters = self.type_parameters(&message, 4)?;
+        let receiver = self.type_of(&message, 5, 10).map(|receiver| format!("{}.", receiver)).unwrap_or_default();
+        let property_type = self.type_of(&message, 3, 9).unwrap_or_default();
+        self.type_parameters = outer;
+
+        let mut modifiers = member_modifiers(flags, in_interface);
+        for (bit, modifier) in [(14, "external"), (11, "const"), (12, "lateinit")] {
+            if flags >> bit & 1 != 0 {
+                modifiers.push(modifier);
+            }
+        }
+        let keyword = if flags >> 8 & 1 != 0 { "var" } else { "val" };
+        let delegated = if flags >> 15 & 1 != 0 { " by ..." } else { "" };
+        let type_parameters = if type_parameters.is_empty() { type_parameters } else { format!("{} ", type_parameters) };
+
+        let mut property = KotlinProperty {
+            declaration: format!("{}{} {}{}{}: {}{}", prefix(&modifiers), keyword, type_parameters, receiver, name, property_type, delegated),
+            name,
+            field: None,
+            getter: None,
+            setter: None,
+        };
+        if let Some(signature) = field(&message, 100) {
+            let signature = proto::fields(signature.as_bytes())?;
+            if let Some(jvm_field) = field(&signature, 1) {
+                let jvm_field = proto::fields(jvm_field.as_bytes())?;
+                let name = field(&jvm_field, 1).map_or_else(|| property.name.clone(), |value| self.names.string(value.as_u64()));
+                property.field = field(&jvm_field, 2).map(|desc| format!("{}:{}", name, self.names.string(desc.as_u64())));
+            }
+            property.getter = self.jvm_method(field(&signature, 3), "")?;
+            property.setter = self.jvm_method(field(&signature, 4), "")?;
+        }
+        Ok(property)
+    }
+
+    fn members(&mut self, message: &Fields<'_>, functions: u32, properties: u32, in_interface: bool) -> Result<(Vec<KotlinFunction>, Vec<KotlinProperty>)> {
+        let functions = repeated(message, functions).map(|function| self.function(function.as_bytes(), in_interface)).collect::<Result<_>>()?;
+        let properties = repeated(message, properties).map(|property| self.property(property.as_bytes(), in_interface)).collect::<Result<_>>()?;
+        Ok((functions, properties))
+    }
+}
+
+/// Visibility unless public, and modality where it is not the default
+fn member_modifiers(flags: u64, in_interface: bool) -> Vec<&'static str> {
+    let mut modifiers = Vec::new();
+    let visibility = VISIBILITIES.get((flags >> 1 & 0x7) as usize).copied().unwrap_or("public");
+    if visibility != "public" {
+        modifiers.push(visibility);
+    }
+    let modality = MODALITIES[(flags >> 4 & 0x3) as usize];
+    if modality != "final" && !(in_interface && modality == "abstract") {
+        modifiers.push(modality);
+    }
+    modifiers
+}
+
+fn prefix(modifiers: &[&str]) -> String {
+    modifiers.iter().map(|modifier| format!("{} ", modifier)).collect()
+}
+
+/// Split `d1` into the name resolver and the message after it
+fn open(metadata: &MetadataAnnotation) -> Result<(Vec<u8>, NameResolver, usize)> {
+    let data = decode_data1(&metadata.data1);
+    let (types, message) = proto::delimited(&data)?;
+    let names = NameResolver::new(types, &metadata.data2)?;
+    let offset = data.len() - message.len();
+    Ok((data, names, offset))
+}
+
+/// A class's declaration and members from `k = 1` metadata
+pub fn class(jvm_class: &str, metadata: &MetadataAnnotation) -> Result<KotlinClass> {
+    let (data, names, offset) = open(metadata)?;
+    let message = proto::fields(&data[offset..])?;
+    let flags = field(&message, 1).map_or(6, |value| value.as_u64());
+    let kind = CLASS_KINDS.get((flags >> 6 & 0x7) as usize).copied().unwrap_or("class");
+    let interface = kind == "interface" || kind == "annotation class";
+
+    let mut renderer = Renderer { names, types: TypeTable::new(&message)?, type_parameters: HashMap::new() };
+    let name = field(&message, 3).map_or_else(String::new, |value| renderer.names.class_name(value.as_u64()));
+    let simple_name = name.rsplit('.').next().unwrap_or(&name).to_string();
+    let type_parameters = renderer.type_parameters(&message, 5)?;
+
+    let mut supertypes: Vec<String> = repeated(&message, 6).filter_map(|supertype| renderer.render_type(supertype.as_bytes(), false).ok()).collect();
+    for id in repeated_varints(&message, 2)? {
+        if let Some(supertype) = renderer.types.types.get(id as usize) {
+            supertypes.push(renderer.render_type(supertype, false)?);
+        }
+    }
+    // Implied by the kind of class
+    supertypes.retain(|supertype| supertype != "Any" && !(kind == "enum class" && supertype.starts_with("Enum<")));
+
+    let mut modifiers = Vec::new();
+    let visibility = VISIBILITIES.get((flags >> 1 & 0x7) as usize).copied().unwrap_or("public");
+    if visibility != "public" {
+        modifiers.push(visibility);
+    }
+    let modality = MODALITIES[(flags >> 4 & 0x3) as usize];
+    if modality != "final" && !(interface && modality == "abstract") {
+        modifiers.push(modality);
+    }
+    for (bit, modifier) in [(11, "external"), (12, "expect"), (9, "inner"), (10, "data"), (13, "value"), (14, "fun")] {
+        if flags >> bit & 1 != 0 {
+            modifiers.push(modifier);
+        }
+    }
+
+    let mut constructors = Vec::new();
+    let mut primary = None;
+    for constructor in repeated(&message, 8) {
+        let constructor = proto::fields(constructor.as_bytes())?;
+        let flags = field(&constructor, 1).map_or(6, |value| value.as_u64());
+        let value_parameters = renderer.value_parameters(&constructor, 2)?;
+        let parameters = value_parameters.join(", ");
+        let secondary = flags >> 4 & 1 != 0;
+        if !secondary {
+            primary = Some(parameters.clone());
+        }
+        constructors.push(KotlinFunction {
+            name: "<init>".to_string(),
+            declaration: format!("{}constructor({})", prefix(&member_modifiers(flags & 0xf, false)), parameters),
+            jvm_signature: renderer.jvm_method(field(&constructor, 100), "<init>")?,
+            parameters: value_parameters.len(),
+        });
+    }
+
+    let (functions, properties) = renderer.members(&message, 9, 10, interface)?;
+    let nested = |index: u64| format!("{}${}", jvm_class, renderer.names.string(index));
+
+    let mut declaration = format!("{}{} {}{}", prefix(&modifiers), kind, simple_name, type_parameters);
+    if let Some(primary) = primary.filter(|primary| !primary.is_empty() || modifiers.contains(&"data")) {
+        declaration.push_str(&format!("({})", primary));
+    }
+    if !supertypes.is_empty() {
+        declaration.push_str(&format!(" : {}", supertypes.join(", ")));
+    }
+
+    Ok(KotlinClass {
+        jvm_class: jvm_class.to_string(),
+        kind: kind.to_string(),
+        modifiers: modifiers.iter().map(|modifier| modifier.to_string()).collect(),
+        declaration,
+        source_file: None,
+        companion_object: field(&message, 4).map(|value| nested(value.as_u64())),
+        nested_classes: repeated_varints(&message, 7)?.into_iter().map(nested).collect(),
+        enum_entries: repeated(&message, 13)
+            .filter_map(|entry| proto::fields(entry.as_bytes()).ok())
+            .filter_map(|entry| field(&entry, 1).map(|value| renderer.names.string(value.as_u64())))
+            .collect(),
+        sealed_subclasses: repeated_varints(&message, 16)?.into_iter().map(|index| renderer.names.class_name(index)).collect(),
+        constructors,
+        functions,
+        properties,
+        name,
+    })
+}
+
+/// Top-level functions and properties of a file facade or multi-file class part, or the parts
+/// of a multi-file facade
+pub fn facade(jvm_class: &str, metadata: &MetadataAnnotation) -> Result<FileFacade> {
+    let mut facade = FileFacade {
+        jvm_class: jvm_class.to_string(),
+        kind: FacadeKind::File,
+        source_file: None,
+        facade: None,
+        parts: Vec::new(),
+        functions: Vec::new(),
+        properties: Vec::new(),
+    };
+
+    match metadata.kind {
+        // d1 is the list of parts, not a message
+        KIND_MULTI_FILE_FACADE => {
+            facade.kind = FacadeKind::MultiFileFacade;
+            facade.parts = metadata.data1.iter().map(|part| String::from_utf16_lossy(part).replace('/', ".")).collect();
+            return Ok(facade);
+        }
+        KIND_MULTI_FILE_PART => {
+            facade.kind = FacadeKind::MultiFilePart;
+            facade.facade = metadata.extra_string.as_ref().map(|name| name.replace('/', "."));
+        }
+        _ => {}
+    }
+
+    let (data, names, offset) = open(metadata)?;
+    let message = proto::fields(&data[offset..])?;
+    let mut renderer = Renderer { names, types: TypeTable::new(&message)?, type_parameters: HashMap::new() };
+    (facade.functions, facade.properties) = renderer.members(&message, 3, 4, false)?;
+    Ok(facade)
+}
@@ -0,0 +1,181 @@
+//! Kotlin declarations recovered from the `@kotlin.Metadata` annotations of compiled classes
+//!
+//! Decompilers see the JVM view of a Kotlin class: properties become fields and accessors,
+//! companion objects and file facades become plain classes and nullability is gone. The
+//! metadata the Kotlin compiler leaves on every class still has all of it.
+
+pub mod annotate;
+pub mod classfile;
+pub mod metadata;
+
+use crate::Result;
+use classfile::ClassFile;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+pub use annotate::annotate_sources;
+
+/// Kotlin declarations recovered from the JARs, written next to `src/`
+pub const REPORT_FILE: &str = "kotlin.json";
+
+/// Classes without these bytes cannot carry Kotlin metadata and are not parsed
+const METADATA_MARKER: &[u8] = b"Lkotlin/Metadata;";
+
+/// Everything recovered from the `@kotlin.Metadata` annotations of a set of JARs
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KotlinReport {
+    /// Classes, interfaces and objects, by JVM class name
+    pub classes: Vec<KotlinClass>,
+    /// Classes holding top-level functions and properties, e.g. `com.example.UtilsKt`
+    pub facades: Vec<FileFacade>,
+    /// Lambdas and other compiler-generated classes, which have no declarations of their own
+    pub synthetic_classes: usize,
+    /// Classes whose metadata could not be read, with the reason
+    pub errors: Vec<String>,
+}
+
+/// A class as declared in Kotlin
+#[derive(Debug, Clone, Serialize)]
+pub struct KotlinClass {
+    /// e.g. `com.example.User$Companion`
+    pub jvm_class: String,
+    /// e.g. `com.example.User.Companion`
+    pub name: String,
+    /// `class`, `interface`, `enum class`, `object`, `companion object`, …
+    pub kind: String,
+    /// `data`, `sealed`, `inner`, `private`, …
+    pub modifiers: Vec<String>,
+    /// e.g. `data class User(name: String, age: Int? = ...)`
+    pub declaration: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
+    /// JVM class name of the companion object
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub companion_object: Option<String>,
+    /// JVM class names
+    pub nested_classes: Vec<String>,
+    pub enum_entries: Vec<String>,
+    pub sealed_subclasses: Vec<String>,
+    pub constructors: Vec<KotlinFunction>,
+    pub functions: Vec<KotlinFunction>,
+    pub properties: Vec<KotlinProperty>,
+}
+
+/// What a file facade class is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FacadeKind {
+    /// `FooKt` for the top-level declarations of `Foo.kt`
+    File,
+    /// A `@JvmMultifileClass` facade, which delegates to its parts
+    MultiFileFacade,
+    /// One file's declarations of a `@JvmMultifileClass` facade
+    MultiFilePart,
+}
+
+/// A class holding top-level declarations
+#[derive(Debug, Clone, Serialize)]
+pub struct FileFacade {
+    pub jvm_class: String,
+    pub kind: FacadeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
+    /// For a multi-file part, the facade it belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facade: Option<String>,
+    /// For a multi-file facade, its parts
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+    pub functions: Vec<KotlinFunction>,
+    pub properties: Vec<KotlinProperty>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KotlinFunction {
+    pub name: String,
+    /// e.g. `suspend fun String.greet(times: Int = ...): String?`
+    pub declaration: String,
+    /// JVM name and descriptor, e.g. `greet(Ljava/lang/String;I)Ljava/lang/String;`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jvm_signature: Option<String>,
+    /// Parameters of the JVM method, counting an extension receiver
+    #[serde(skip)]
+    pub parameters: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KotlinProperty {
+    pub name: String,
+    /// e.g. `lateinit var token: String`
+    pub declaration: String,
+    /// Backing field name and descriptor, e.g. `token:Ljava/lang/String;`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub getter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setter: Option<String>,
+}
+
+impl KotlinReport {
+    /// Read the metadata of every class in `jars`
+    pub fn from_jars(jars: &[PathBuf]) -> Result<Self> {
+        let mut report = Self::default();
+        for jar in jars {
+            report.add_jar(jar)?;
+        }
+        report.classes.sort_by(|a, b| a.jvm_class.cmp(&b.jvm_class));
+        report.facades.sort_by(|a, b| a.jvm_class.cmp(&b.jvm_class));
+        Ok(report)
+    }
+
+    fn add_jar(&mut self, jar: &Path) -> Result<()> {
+        let mut archive = ZipArchive::new(File::open(jar)?)?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            if let Err(e) = self.add_class(&data) {
+                self.errors.push(format!("{}: {}", file.name(), e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add one class file; classes without Kotlin metadata are ignored
+    pub fn add_class(&mut self, data: &[u8]) -> Result<()> {
+        if !data.windows(METADATA_MARKER.len()).any(|window| window == METADATA_MARKER) {
+            return Ok(());
+        }
+        let class = ClassFile::parse(data)?;
+        let Some(annotation) = &class.metadata else { return Ok(()) };
+        let jvm_class = class.name.replace('/', ".");
+
+        match annotation.kind {
+            metadata::KIND_CLASS => {
+                let mut kotlin_class = metadata::class(&jvm_class, annotation)?;
+                kotlin_class.source_file = class.source_file;
+                self.classes.push(kotlin_class);
+            }
+            metadata::KIND_FILE_FACADE | metadata::KIND_MULTI_FILE_FACADE | metadata::KIND_MULTI_FILE_PART => {
+                let mut facade = metadata::facade(&jvm_class, annotation)?;
+                facade.source_file = class.source_file;
+                self.facades.push(facade);
+            }
+            metadata::KIND_SYNTHETIC_CLASS => self.synthetic_classes += 1,
+            kind => anyhow::bail!("Unknown Kotlin metadata kind {}", kind),
+        }
+        Ok(())
+    }
+
+    /// Whether no Kotlin class was found
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty() && self.facades.is_empty() && self.synthetic_classes == 0
+    }
+}
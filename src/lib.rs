@@ -1,10 +1,21 @@
+pub mod aab;
 pub mod apk;
+pub mod arsc;
 pub mod assets;
+pub mod classfile;
 pub mod cli;
 pub mod config;
+pub mod dex;
+pub mod error;
+pub mod kotlin;
+pub mod mapping;
+pub mod manifest;
 pub mod mcp;
+pub mod signing;
 pub mod tools;
+pub mod zipalign;
 
 pub use anyhow::{Context, Result};
+pub use error::ApkextError;
 
 pub type Error = anyhow::Error;
\ No newline at end of file
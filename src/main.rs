@@ -1,30 +1,295 @@
-use apkext::cli::{Cli, Commands};
-use apkext::{apk, assets, config, mcp, Result};
+use apkext::apk::{BatchOutcome, ExistingOutput, UnpackStages};
+use apkext::cli::{progress, CacheAction, Cli, Commands};
+use apkext::signing::{self, KeySource, SignOptions};
+use apkext::{apk, arsc, assets, config, dex, mcp, zipalign, ApkextError, Result};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse_args();
 
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            // Typed failures get their own exit codes so scripts can react to them
+            let code = error.downcast_ref::<ApkextError>().map_or(1, ApkextError::exit_code);
+            ExitCode::from(code)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
     // Initialize asset manager and config
     let asset_manager = assets::AssetManager::new()?;
     let mut config = config::Config::load();
     config.update_tool_paths(asset_manager.get_tools_path());
 
     // Check Java installation
-    config.java.check_java()?;
+    if cli.command.needs_java() {
+        config.java.check_java()?;
+    }
 
     match cli.command {
-        Commands::Unpack { apk_file } => {
-            let extractor = apk::Extractor::new(config);
-            extractor.unpack(&apk_file).await?;
+        Commands::Unpack {
+            apk_files,
+            jobs,
+            decompiler,
+            fallback_decompiler,
+            no_fallback,
+            no_cache,
+            no_kotlin,
+            mapping,
+            output,
+            force,
+            merge,
+            no_src,
+            no_res,
+            no_smali,
+            only_jar,
+            only_res,
+        } => {
+            if let Some(decompiler) = decompiler {
+                config.decompiler = decompiler;
+            }
+            if fallback_decompiler.is_some() {
+                config.fallback_decompiler = fallback_decompiler;
+            }
+            config.fallback = !no_fallback;
+            config.cache &= !no_cache;
+            config.kotlin &= !no_kotlin;
+
+            let existing_output = if force {
+                ExistingOutput::Force
+            } else if merge {
+                ExistingOutput::Merge
+            } else {
+                ExistingOutput::Refuse
+            };
+
+            let mut stages = if only_jar {
+                UnpackStages::only_jar()
+            } else if only_res {
+                UnpackStages::only_resources()
+            } else {
+                UnpackStages::default()
+            };
+            stages.decompile &= !no_src;
+            stages.decode_resources &= !no_res;
+            stages.decode_smali &= !no_smali;
+
+            // A single APK path keeps the plain behaviour, including what --output means
+            let apks = apk::batch::collect_apks(&apk_files)?;
+            if apk_files.len() > 1 || apks.len() != 1 || apks[0] != Path::new(&apk_files[0]) {
+                let jobs = jobs.map_or_else(default_jobs, usize::from);
+                let quiet = config.quiet;
+                // `output`, if given, is the parent of the per-APK output directories
+                return unpack_batch(apks, jobs, quiet, |apk| {
+                    apk::Extractor::new(config.clone(), asset_manager.clone())
+                        .with_output_dir(output.as_ref().map(|root| root.join(apk.file_stem().unwrap_or_default())))
+                        .with_existing_output(existing_output)
+                        .with_stages(stages)
+                        .with_mapping(mapping.clone())
+                })
+                .await;
+            }
+
+            let progress = (!config.quiet).then(progress::progress_bar).flatten();
+
+            let extractor = apk::Extractor::new(config, asset_manager)
+                .with_output_dir(output)
+                .with_existing_output(existing_output)
+                .with_stages(stages)
+                .with_mapping(mapping)
+                .with_progress(progress.as_ref().map(|(_, handler)| handler.clone()));
+            let result = extractor.unpack(&apk_files[0]).await;
+
+            if let Some((bar, _)) = progress {
+                bar.finish_and_clear();
+            }
+            result?;
         }
 
         Commands::Pack {
             unpacked_dir,
             output_apk,
+            no_sign,
+            keystore,
+            ks_pass,
+            ks_key_alias,
+            key,
+            cert,
+            schemes,
         } => {
-            let builder = apk::Builder::new(config);
-            builder.pack(&unpacked_dir, &output_apk).await?;
+            let signing = if no_sign {
+                None
+            } else {
+                let key = KeySource::select(keystore, ks_pass, ks_key_alias, key, cert)?;
+                Some(SignOptions::with_schemes(key, &schemes)?)
+            };
+
+            let progress = (!config.quiet).then(progress::progress_bar).flatten();
+
+            let builder = apk::Builder::new(config, asset_manager)
+                .with_signing(signing)
+                .with_progress(progress.as_ref().map(|(_, handler)| handler.clone()));
+            let result = builder.pack(&unpacked_dir, &output_apk).await;
+
+            if let Some((bar, _)) = progress {
+                bar.finish_and_clear();
+            }
+            result?;
+        }
+
+        Commands::VerifyAlign { apk_file } => {
+            let misaligned = zipalign::verify_file(Path::new(&apk_file))?;
+            if !misaligned.is_empty() {
+                for entry in &misaligned {
+                    eprintln!("{} (offset {}, needs {}-byte alignment)", entry.name, entry.data_offset, entry.alignment);
+                }
+                anyhow::bail!("{} entries in {} are not aligned", misaligned.len(), apk_file);
+            }
+            println!("[+] All uncompressed entries in '{}' are aligned", apk_file);
+        }
+
+        Commands::Info { apk_file, json } => {
+            let info = apk::ApkInfo::read(Path::new(&apk_file))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                print!("{}", info);
+            }
+        }
+
+        Commands::Classes { apk_file, filter, json } => {
+            let apk_path = Path::new(&apk_file);
+            let index = dex::DexIndex::from_path(apk_path)
+                .map_err(|e| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason: e.to_string() })?;
+            let classes = index.matching(filter.as_deref().unwrap_or_default());
+            if json {
+                let listings: Vec<_> = classes.map(|class| class.listing()).collect();
+                println!("{}", serde_json::to_string_pretty(&listings)?);
+            } else {
+                for class in classes {
+                    println!("{}", class.declaration());
+                }
+            }
+        }
+
+        Commands::Methods { apk_file, class, json } => {
+            let apk_path = Path::new(&apk_file);
+            let index = dex::DexIndex::from_path(apk_path)
+                .map_err(|e| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason: e.to_string() })?;
+            let Some(info) = index.class(&class) else {
+                anyhow::bail!("Class '{}' not found in '{}'", class, apk_file);
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(info)?);
+            } else {
+                print!("{}", info);
+            }
+        }
+
+        Commands::Xref { apk_file, callers, callees, field, string, output, json } => {
+            let query = match (callers, callees, field, string) {
+                (Some(method), _, _, _) => dex::XrefQuery::Callers(method),
+                (_, Some(method), _, _) => dex::XrefQuery::Callees(method),
+                (_, _, Some(field), _) => dex::XrefQuery::Field(field),
+                (_, _, _, Some(text)) => dex::XrefQuery::String(text),
+                _ => unreachable!("clap requires one query"),
+            };
+
+            let apk_path = Path::new(&apk_file);
+            let xrefs = dex::Xrefs::from_path(apk_path)
+                .map_err(|e| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason: e.to_string() })?;
+            let mut report = xrefs.search(&query)?;
+            if let Some(dir) = output.or_else(|| Some(apk_path.with_extension("")).filter(|dir| dir.is_dir())) {
+                report.locate(&dir);
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+
+        Commands::Search { dir, pattern, ignore_case, fixed_strings, only, json } => {
+            let report = apk::SearchQuery::new(pattern)
+                .with_ignore_case(ignore_case)
+                .with_fixed_strings(fixed_strings)
+                .with_origins(only)
+                .run(&dir)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+        }
+
+        Commands::Diff { old, new, json } => {
+            let diff = apk::ApkDiff::compare(Path::new(&old), Path::new(&new))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print!("{}", diff);
+            }
+        }
+
+        Commands::Resource { apk_file, name, config, json } => {
+            let apk_path = Path::new(&apk_file);
+            let table = arsc::ResourceTable::from_apk(apk_path)
+                .map_err(|e| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason: e.to_string() })?;
+            let Some(resource) = table.lookup(&name, config.as_deref()) else {
+                anyhow::bail!("Resource '{}' not found in '{}'", name, apk_file);
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&resource)?);
+            } else {
+                print!("{}", resource);
+            }
+        }
+
+        Commands::Verify { apk_file, json } => {
+            let apk_path = Path::new(&apk_file);
+            let report = signing::verify_file(apk_path)
+                .map_err(|e| ApkextError::InvalidApk { path: apk_path.to_path_buf(), reason: e.to_string() })?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+            if !report.verified {
+                let failed: Vec<&str> = report.schemes.iter().filter(|scheme| !scheme.verified).map(|scheme| scheme.scheme.as_str()).collect();
+                let reason = if failed.is_empty() {
+                    report.errors.join("; ")
+                } else {
+                    format!("{} did not verify", failed.join(", "))
+                };
+                return Err(ApkextError::SignatureInvalid { path: apk_path.to_path_buf(), reason }.into());
+            }
+        }
+
+        Commands::Cache { action } => {
+            let cache = apk::DecompileCache::new(asset_manager.get_cache_path(), config.cache_max_size);
+            match action {
+                CacheAction::Info { json } => {
+                    let stats = cache.stats()?;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&stats)?);
+                    } else {
+                        print!("{}", stats);
+                    }
+                }
+                CacheAction::Clean { max_size } => {
+                    let eviction = match max_size {
+                        Some(size) => cache.trim_to(size)?,
+                        None => cache.clear()?,
+                    };
+                    println!("[+] Removed {} cached sources ({})", eviction.removed, apk::info::format_size(eviction.freed));
+                }
+            }
         }
 
         Commands::Mcp => {
@@ -35,3 +300,61 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Unpack several APKs concurrently, then print one line per APK
+///
+/// `extractor` sets up the extractor for each APK; progress reporting is added here.
+async fn unpack_batch(apks: Vec<PathBuf>, jobs: usize, quiet: bool, extractor: impl Fn(&Path) -> apk::Extractor) -> Result<()> {
+    let total = apks.len();
+    let progress = (!quiet).then(progress::BatchProgress::new);
+    let bars = RefCell::new(Vec::new());
+
+    let outcomes = apk::batch::unpack_all(apks, jobs, |apk| {
+        let name = apk.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let handler = progress.as_ref().map(|progress| {
+            let (bar, handler) = progress.add(&name);
+            bars.borrow_mut().extend(bar);
+            handler
+        });
+
+        extractor(apk).with_progress(handler)
+    })
+    .await?;
+
+    for bar in bars.into_inner() {
+        bar.finish_and_clear();
+    }
+
+    print_batch_summary(&outcomes);
+
+    let failed = outcomes.iter().filter(|outcome| outcome.result.is_err()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} APKs failed to unpack", failed, total);
+    }
+    Ok(())
+}
+
+fn print_batch_summary(outcomes: &[BatchOutcome]) {
+    let width = outcomes.iter().map(|outcome| outcome.apk.display().to_string().len()).max().unwrap_or(0).max(3);
+
+    println!();
+    println!("{:<width$}  {:<6}  {:>8}  Output or error", "APK", "Result", "Time", width = width);
+    for outcome in outcomes {
+        let apk = outcome.apk.display().to_string();
+        match &outcome.result {
+            Ok(report) => println!(
+                "{:<width$}  {:<6}  {:>7.1}s  {}",
+                apk, "ok", report.total_ms as f64 / 1000.0, report.output_dir.display(), width = width
+            ),
+            Err(error) => println!(
+                "{:<width$}  {:<6}  {:>8}  {}",
+                apk, "failed", "-", error.to_string().lines().next().unwrap_or_default(), width = width
+            ),
+        }
+    }
+}
+
+/// Half the CPU cores: every APK runs its own JVMs with up to 2 GB of heap each
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |cores| (cores.get() / 2).max(1))
+}
@@ -0,0 +1,308 @@
+//! Android binary XML (AXML), the compiled form of `AndroidManifest.xml` and `res/**/*.xml` in an APK
+
+use super::chunk::{self, read_u16, read_u32, Chunk, NO_INDEX};
+use crate::aab::resources::{escape_attr, escape_text, format_complex};
+use crate::Result;
+use serde::Serialize;
+use std::fmt;
+
+pub const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_CDATA_TYPE: u16 = 0x0104;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+
+/// Framework attributes by resource ID; shrinkers often blank the names in the string pool, the IDs stay
+const ANDROID_ATTRIBUTES: [(u32, &str); 17] = [
+    (0x0101_0000, "theme"),
+    (0x0101_0001, "label"),
+    (0x0101_0002, "icon"),
+    (0x0101_0003, "name"),
+    (0x0101_0006, "permission"),
+    (0x0101_0007, "readPermission"),
+    (0x0101_0008, "writePermission"),
+    (0x0101_000e, "enabled"),
+    (0x0101_000f, "debuggable"),
+    (0x0101_0010, "exported"),
+    (0x0101_0011, "process"),
+    (0x0101_0018, "authorities"),
+    (0x0101_020c, "minSdkVersion"),
+    (0x0101_021b, "versionCode"),
+    (0x0101_021c, "versionName"),
+    (0x0101_0270, "targetSdkVersion"),
+    (0x0101_0271, "maxSdkVersion"),
+];
+
+/// An element and everything below it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Element {
+    /// Namespace URI, if the element name is qualified
+    pub namespace: Option<String>,
+    pub name: String,
+    /// `(prefix, uri)` pairs declared on this element
+    pub namespaces: Vec<(String, String)>,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Element>,
+    /// Character data directly inside the element
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attribute {
+    pub namespace: Option<String>,
+    pub name: String,
+    /// Framework or app attribute ID from the resource map
+    pub resource_id: Option<u32>,
+    pub value: Value,
+}
+
+/// A typed attribute value (`Res_value`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Value {
+    Null,
+    String(String),
+    /// `@0x7f…` resource reference
+    Reference(u32),
+    /// `?0x7f…` theme attribute reference
+    Attribute(u32),
+    Int(i32),
+    Hex(u32),
+    Bool(bool),
+    Float(f32),
+    /// Complex dimension as stored, e.g. `16.0dp`
+    Dimension(u32),
+    Fraction(u32),
+    /// ARGB
+    Color(u32),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Integers, and strings holding an integer (some tools write `versionCode` as text)
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value as i64),
+            Value::Hex(value) => Some(*value as i64),
+            Value::String(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            Value::String(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "@null"),
+            Value::String(text) => write!(f, "{}", text),
+            Value::Reference(0) => write!(f, "@null"),
+            Value::Reference(id) => write!(f, "@0x{:08x}", id),
+            Value::Attribute(id) => write!(f, "?0x{:08x}", id),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Hex(value) => write!(f, "0x{:x}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Dimension(data) => write!(f, "{}", format_complex(*data, &["px", "dp", "sp", "pt", "in", "mm"])),
+            Value::Fraction(data) => write!(f, "{}", format_complex(*data, &["%", "%p"])),
+            Value::Color(argb) => write!(f, "#{:08x}", argb),
+        }
+    }
+}
+
+impl Element {
+    /// Value of the attribute with this local name, in any namespace
+    pub fn attr(&self, name: &str) -> Option<&Value> {
+        self.attributes.iter().find(|attribute| attribute.name == name).map(|attribute| &attribute.value)
+    }
+
+    /// Direct children with this name
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The element as an XML document
+    pub fn to_xml(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        let mut namespaces = Vec::new();
+        self.write_xml(0, &mut namespaces, &mut out);
+        out
+    }
+
+    fn write_xml(&self, depth: usize, namespaces: &mut Vec<(String, String)>, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        let scope = namespaces.len();
+        namespaces.extend(self.namespaces.iter().cloned());
+
+        let name = qualified(namespaces, self.namespace.as_deref(), &self.name);
+        out.push_str(&format!("{}<{}", indent, name));
+        for (prefix, uri) in &self.namespaces {
+            out.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape_attr(uri)));
+        }
+        for attribute in &self.attributes {
+            let attribute_name = qualified(namespaces, attribute.namespace.as_deref(), &attribute.name);
+            out.push_str(&format!(" {}=\"{}\"", attribute_name, escape_attr(&attribute.value.to_string())));
+        }
+
+        let text = self.text.trim();
+        if self.children.is_empty() && text.is_empty() {
+            out.push_str(" />\n");
+        } else {
+            out.push_str(">\n");
+            if !text.is_empty() {
+                out.push_str(&format!("{}    {}\n", indent, escape_text(text)));
+            }
+            for child in &self.children {
+                child.write_xml(depth + 1, namespaces, out);
+            }
+            out.push_str(&format!("{}</{}>\n", indent, name));
+        }
+
+        namespaces.truncate(scope);
+    }
+}
+
+fn qualified(namespaces: &[(String, String)], uri: Option<&str>, name: &str) -> String {
+    let Some(uri) = uri else { return name.to_string() };
+
+    let prefix = namespaces
+        .iter()
+        .rev()
+        .find(|(_, declared)| declared == uri)
+        .map(|(prefix, _)| prefix.as_str())
+        .or((uri == ANDROID_NAMESPACE).then_some("android"));
+
+    match prefix {
+        Some(prefix) if !prefix.is_empty() => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Render a binary XML file as text
+pub fn decode(data: &[u8]) -> Result<String> {
+    Ok(parse(data)?.to_xml())
+}
+
+/// Parse a binary XML file into its root element
+pub fn parse(data: &[u8]) -> Result<Element> {
+    let document = Chunk::parse(data)?;
+    if document.kind != chunk::RES_XML_TYPE {
+        anyhow::bail!("Not a binary XML file (chunk type 0x{:04x})", document.kind);
+    }
+
+    let mut strings = Vec::new();
+    let mut resource_ids = Vec::new();
+    let mut pending_namespaces = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+
+    for node in document.children()? {
+        match node.kind {
+            chunk::RES_STRING_POOL_TYPE => strings = chunk::string_pool(&node)?,
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = node.body.chunks_exact(4).map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]])).collect();
+            }
+            RES_XML_START_NAMESPACE_TYPE => {
+                let prefix = string(&strings, read_u32(node.body, 0)?).unwrap_or_default();
+                let uri = string(&strings, read_u32(node.body, 4)?).unwrap_or_default();
+                pending_namespaces.push((prefix, uri));
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                let mut element = start_element(node.body, &strings, &resource_ids)?;
+                element.namespaces = std::mem::take(&mut pending_namespaces);
+                stack.push(element);
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                let element = stack.pop().ok_or_else(|| anyhow::anyhow!("Unbalanced end element in binary XML"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None if root.is_none() => root = Some(element),
+                    None => anyhow::bail!("More than one root element in binary XML"),
+                }
+            }
+            RES_XML_CDATA_TYPE => {
+                if let (Some(element), Some(text)) = (stack.last_mut(), string(&strings, read_u32(node.body, 0)?)) {
+                    element.text.push_str(&text);
+                }
+            }
+            // End namespace and unknown chunks carry nothing we keep
+            _ => {}
+        }
+    }
+
+    root.ok_or_else(|| anyhow::anyhow!("Binary XML has no root element"))
+}
+
+/// `ResXMLTree_attrExt` and the attributes that follow it
+fn start_element(ext: &[u8], strings: &[String], resource_ids: &[u32]) -> Result<Element> {
+    let namespace = string(strings, read_u32(ext, 0)?);
+    let name = string(strings, read_u32(ext, 4)?).unwrap_or_default();
+    let attribute_start = read_u16(ext, 8)? as usize;
+    let attribute_size = read_u16(ext, 10)? as usize;
+    let attribute_count = read_u16(ext, 12)? as usize;
+
+    let mut attributes = Vec::with_capacity(attribute_count);
+    for i in 0..attribute_count {
+        let pos = attribute_start + i * attribute_size;
+        let namespace = string(strings, read_u32(ext, pos)?);
+        let name_index = read_u32(ext, pos + 4)?;
+        let raw_value = read_u32(ext, pos + 8)?;
+        let data_type = *ext.get(pos + 15).ok_or_else(|| anyhow::anyhow!("Truncated binary XML attribute"))?;
+        let data = read_u32(ext, pos + 16)?;
+
+        let resource_id = resource_ids.get(name_index as usize).copied();
+        let known = resource_id.and_then(|id| ANDROID_ATTRIBUTES.iter().find(|(known, _)| *known == id)).map(|(_, name)| *name);
+        let name = match known {
+            Some(known) => known.to_string(),
+            None => string(strings, name_index).unwrap_or_default(),
+        };
+
+        let value = match data_type {
+            // Strings keep the raw text; everything else is decoded from the typed data
+            0x03 => Value::String(string(strings, data).or_else(|| string(strings, raw_value)).unwrap_or_default()),
+            _ => typed_value(data_type, data),
+        };
+
+        attributes.push(Attribute { namespace, name, resource_id, value });
+    }
+
+    Ok(Element { namespace, name, attributes, ..Element::default() })
+}
+
+/// `Res_value` data by its `dataType`
+pub fn typed_value(data_type: u8, data: u32) -> Value {
+    match data_type {
+        0x01 | 0x07 => Value::Reference(data),
+        0x02 | 0x08 => Value::Attribute(data),
+        0x04 => Value::Float(f32::from_bits(data)),
+        0x05 => Value::Dimension(data),
+        0x06 => Value::Fraction(data),
+        0x10 => Value::Int(data as i32),
+        0x11 => Value::Hex(data),
+        0x12 => Value::Bool(data != 0),
+        0x1c..=0x1f => Value::Color(data),
+        _ => Value::Null,
+    }
+}
+
+fn string(strings: &[String], index: u32) -> Option<String> {
+    if index == NO_INDEX {
+        return None;
+    }
+    strings.get(index as usize).cloned()
+}
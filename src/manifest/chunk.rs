@@ -0,0 +1,134 @@
+//! `ResChunk_header` framing and string pools, shared by binary XML and `resources.arsc`
+//!
+//! See `frameworks/base/libs/androidfw/include/androidfw/ResourceTypes.h`.
+
+use crate::Result;
+
+pub const RES_STRING_POOL_TYPE: u16 = 0x0001;
+pub const RES_TABLE_TYPE: u16 = 0x0002;
+pub const RES_XML_TYPE: u16 = 0x0003;
+
+/// Set in `ResStringPool_header.flags` when strings are UTF-8 rather than UTF-16
+const UTF8_FLAG: u32 = 1 << 8;
+
+/// Index used for "no string"
+pub const NO_INDEX: u32 = 0xffff_ffff;
+
+/// One chunk: its type, the header (including the 8 common bytes) and the body that follows it
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'a> {
+    pub kind: u16,
+    pub header: &'a [u8],
+    pub body: &'a [u8],
+    /// The whole chunk, header included; offsets inside a chunk are relative to its start
+    pub data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// The chunk at the start of `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let kind = read_u16(data, 0)?;
+        let header_size = read_u16(data, 2)? as usize;
+        let size = read_u32(data, 4)? as usize;
+
+        if header_size < 8 || header_size > size || size > data.len() {
+            anyhow::bail!("Malformed resource chunk 0x{:04x}: header {} bytes, size {} of {}", kind, header_size, size, data.len());
+        }
+
+        Ok(Self {
+            kind,
+            header: &data[..header_size],
+            body: &data[header_size..size],
+            data: &data[..size],
+        })
+    }
+
+    /// The chunks that follow this chunk's header, in order
+    pub fn children(&self) -> Result<Vec<Chunk<'a>>> {
+        chunks(self.body)
+    }
+}
+
+/// Consecutive chunks filling `data`
+pub fn chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    let mut chunks = Vec::new();
+
+    while data.len() >= 8 {
+        let chunk = Chunk::parse(data)?;
+        data = &data[chunk.data.len()..];
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Strings of a `RES_STRING_POOL_TYPE` chunk; styles are not read
+pub fn string_pool(chunk: &Chunk<'_>) -> Result<Vec<String>> {
+    let count = read_u32(chunk.header, 8)? as usize;
+    let flags = read_u32(chunk.header, 16)?;
+    let strings_start = read_u32(chunk.header, 20)? as usize;
+    let utf8 = flags & UTF8_FLAG != 0;
+
+    let offsets_start = chunk.header.len();
+    let mut strings = Vec::with_capacity(count.min(chunk.data.len() / 4));
+    for i in 0..count {
+        let offset = read_u32(chunk.data, offsets_start + i * 4)? as usize;
+        let start = strings_start.checked_add(offset).ok_or_else(|| anyhow::anyhow!("String offset overflows"))?;
+        strings.push(if utf8 { utf8_string(chunk.data, start)? } else { utf16_string(chunk.data, start)? });
+    }
+
+    Ok(strings)
+}
+
+fn utf8_string(data: &[u8], mut pos: usize) -> Result<String> {
+    // The length in UTF-16 units comes first, then the length in bytes
+    let length = |pos: &mut usize| -> Result<usize> {
+        let first = *data.get(*pos).ok_or_else(truncated)? as usize;
+        *pos += 1;
+        if first & 0x80 == 0 {
+            return Ok(first);
+        }
+        let second = *data.get(*pos).ok_or_else(truncated)? as usize;
+        *pos += 1;
+        Ok(((first & 0x7f) << 8) | second)
+    };
+    length(&mut pos)?;
+    let len = length(&mut pos)?;
+
+    let bytes = data.get(pos..pos + len).ok_or_else(truncated)?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn utf16_string(data: &[u8], mut pos: usize) -> Result<String> {
+    let first = read_u16(data, pos)? as usize;
+    pos += 2;
+    let len = if first & 0x8000 == 0 {
+        first
+    } else {
+        let second = read_u16(data, pos)? as usize;
+        pos += 2;
+        ((first & 0x7fff) << 16) | second
+    };
+
+    let units = (0..len).map(|i| read_u16(data, pos + i * 2)).collect::<Result<Vec<u16>>>()?;
+    Ok(String::from_utf16_lossy(&units))
+}
+
+pub fn read_u16(data: &[u8], pos: usize) -> Result<u16> {
+    let bytes = data.get(pos..pos + 2).ok_or_else(truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn read_u64(data: &[u8], pos: usize) -> Result<u64> {
+    let bytes = data.get(pos..pos + 8).ok_or_else(truncated)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+pub(crate) fn truncated() -> anyhow::Error {
+    anyhow::anyhow!("Truncated resource chunk")
+}
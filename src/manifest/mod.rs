@@ -0,0 +1,161 @@
+//! `AndroidManifest.xml` read straight from an APK, without apktool or Java
+//!
+//! `axml` decodes the compiled binary XML and `xml` reads the text form apktool writes;
+//! `Manifest` is the typed view of the parts that describe the app: identity, SDK levels,
+//! permissions and components.
+
+use crate::Result;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+pub mod axml;
+pub mod chunk;
+pub mod xml;
+
+pub use axml::{Element, Value};
+
+pub const MANIFEST_ENTRY: &str = "AndroidManifest.xml";
+
+/// What the manifest declares about the app
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Manifest {
+    pub package: String,
+    pub version_code: Option<i64>,
+    pub version_name: Option<String>,
+    pub min_sdk: Option<u32>,
+    pub target_sdk: Option<u32>,
+    pub max_sdk: Option<u32>,
+    /// `uses-permission` and `uses-permission-sdk-23`, in manifest order
+    pub permissions: Vec<String>,
+    /// Permissions the app defines itself
+    pub declared_permissions: Vec<String>,
+    pub debuggable: bool,
+    /// Activities, including `activity-alias`
+    pub activities: Vec<Component>,
+    pub services: Vec<Component>,
+    pub receivers: Vec<Component>,
+    pub providers: Vec<Component>,
+}
+
+/// An activity, service, receiver or provider
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Component {
+    /// Fully qualified class name
+    pub name: String,
+    /// `android:exported` if set; the default depends on the intent filters and target SDK
+    pub exported: Option<bool>,
+    pub permission: Option<String>,
+    /// Content provider authorities
+    pub authorities: Option<String>,
+    pub intent_filters: Vec<IntentFilter>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IntentFilter {
+    pub actions: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+impl Manifest {
+    /// Read and decode `AndroidManifest.xml` from the APK at `apk_path`
+    pub fn from_apk(apk_path: &Path) -> Result<Self> {
+        Self::parse(&read_entry(apk_path, MANIFEST_ENTRY)?)
+    }
+
+    /// Decode a binary `AndroidManifest.xml`
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::from_element(&axml::parse(data)?)
+    }
+
+    /// The typed view of a parsed `<manifest>` element
+    pub fn from_element(root: &Element) -> Result<Self> {
+        if root.name != "manifest" {
+            anyhow::bail!("Root element is <{}>, not <manifest>", root.name);
+        }
+
+        let package = root.attr("package").map(Value::to_string).unwrap_or_default();
+        let mut manifest = Manifest {
+            version_code: root.attr("versionCode").and_then(Value::as_int),
+            version_name: root.attr("versionName").map(Value::to_string),
+            package,
+            ..Manifest::default()
+        };
+
+        if let Some(uses_sdk) = root.children_named("uses-sdk").next() {
+            let level = |name: &str| uses_sdk.attr(name).and_then(Value::as_int).and_then(|level| u32::try_from(level).ok());
+            manifest.min_sdk = level("minSdkVersion");
+            manifest.target_sdk = level("targetSdkVersion");
+            manifest.max_sdk = level("maxSdkVersion");
+        }
+
+        for child in &root.children {
+            let name = child.attr("name").map(Value::to_string);
+            match (child.name.as_str(), name) {
+                ("uses-permission" | "uses-permission-sdk-23", Some(name)) => manifest.permissions.push(name),
+                ("permission", Some(name)) => manifest.declared_permissions.push(name),
+                _ => {}
+            }
+        }
+
+        if let Some(application) = root.children_named("application").next() {
+            manifest.debuggable = application.attr("debuggable").and_then(Value::as_bool).unwrap_or(false);
+
+            for child in &application.children {
+                let list = match child.name.as_str() {
+                    "activity" | "activity-alias" => &mut manifest.activities,
+                    "service" => &mut manifest.services,
+                    "receiver" => &mut manifest.receivers,
+                    "provider" => &mut manifest.providers,
+                    _ => continue,
+                };
+                list.push(component(child, &manifest.package));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+fn component(element: &Element, package: &str) -> Component {
+    let name = element.attr("name").map(Value::to_string).unwrap_or_default();
+
+    Component {
+        name: class_name(package, &name),
+        exported: element.attr("exported").and_then(Value::as_bool),
+        permission: element.attr("permission").map(Value::to_string),
+        authorities: element.attr("authorities").map(Value::to_string),
+        intent_filters: element
+            .children_named("intent-filter")
+            .map(|filter| IntentFilter {
+                actions: filter.children_named("action").filter_map(|action| action.attr("name")).map(Value::to_string).collect(),
+                categories: filter.children_named("category").filter_map(|category| category.attr("name")).map(Value::to_string).collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Component names may be relative to the package: `.MainActivity`, or `MainActivity` without any dot
+pub fn class_name(package: &str, name: &str) -> String {
+    if name.starts_with('.') {
+        format!("{}{}", package, name)
+    } else if !name.contains('.') && !package.is_empty() {
+        format!("{}.{}", package, name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Contents of one entry of the ZIP file at `zip_path`
+pub fn read_entry(zip_path: &Path, entry_name: &str) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+    let mut entry = archive
+        .by_name(entry_name)
+        .map_err(|_| anyhow::anyhow!("File {} not found in archive", entry_name))?;
+
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,240 @@
+//! Text XML, as apktool writes `AndroidManifest.xml` and `res/values*/*.xml`, read into the same
+//! `Element` tree as binary XML
+//!
+//! Every attribute value is kept as a string. Comments, processing instructions and the
+//! doctype are skipped; CDATA sections become text.
+
+use super::axml::{Attribute, Element, Value};
+use crate::Result;
+
+/// Parse a text XML document and return its root element
+pub fn parse(text: &str) -> Result<Element> {
+    let mut parser = Parser { text, pos: 0 };
+    let mut stack: Vec<Element> = Vec::new();
+    // Prefix to URI bindings in scope, with the depth of the element that declared them
+    let mut bindings: Vec<(usize, String, String)> = Vec::new();
+
+    loop {
+        parser.skip_misc()?;
+        if parser.at_end() {
+            anyhow::bail!("XML document ends inside <{}>", stack.last().map_or("", |element| element.name.as_str()));
+        }
+
+        if parser.eat("</") {
+            let name = parser.name()?;
+            parser.skip_whitespace();
+            parser.expect(">")?;
+
+            let element = stack.pop().ok_or_else(|| anyhow::anyhow!("Unexpected </{}>", name))?;
+            if name.rsplit(':').next() != Some(element.name.as_str()) {
+                anyhow::bail!("</{}> closes <{}>", name, element.name);
+            }
+            bindings.retain(|(depth, _, _)| *depth <= stack.len());
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => return Ok(element),
+            }
+        } else if !parser.rest().starts_with("<![CDATA[") && parser.eat("<") {
+            let (element, closed) = parser.element(stack.len() + 1, &mut bindings)?;
+            if !closed {
+                stack.push(element);
+                continue;
+            }
+            bindings.retain(|(depth, _, _)| *depth <= stack.len());
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => return Ok(element),
+            }
+        } else {
+            let text = parser.text()?;
+            match stack.last_mut() {
+                Some(element) => element.text.push_str(&text),
+                None if text.trim().is_empty() => {}
+                None => anyhow::bail!("Text outside the root element"),
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if !self.eat(token) {
+            anyhow::bail!("Expected '{}' at byte {} of the XML document", token, self.pos);
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start().len();
+        self.pos = self.text.len() - trimmed;
+    }
+
+    fn skip_past(&mut self, token: &str) -> Result<()> {
+        let end = self.rest().find(token).ok_or_else(|| anyhow::anyhow!("Unterminated markup at byte {} of the XML document", self.pos))?;
+        self.pos += end + token.len();
+        Ok(())
+    }
+
+    /// Skip comments, processing instructions and the doctype
+    fn skip_misc(&mut self) -> Result<()> {
+        loop {
+            if self.eat("<!--") {
+                self.skip_past("-->")?;
+            } else if self.eat("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!DOCTYPE") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let length = self.rest().find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=')).unwrap_or(self.rest().len());
+        if length == 0 {
+            anyhow::bail!("Expected a name at byte {} of the XML document", self.pos);
+        }
+        let name = self.rest()[..length].to_string();
+        self.pos += length;
+        Ok(name)
+    }
+
+    /// Character data up to the next markup, with entities and CDATA sections resolved
+    fn text(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            if self.eat("<![CDATA[") {
+                let end = self.rest().find("]]>").ok_or_else(|| anyhow::anyhow!("Unterminated CDATA section"))?;
+                text.push_str(&self.rest()[..end]);
+                self.pos += end + 3;
+                continue;
+            }
+            let end = self.rest().find('<').unwrap_or(self.rest().len());
+            if end == 0 {
+                return Ok(text);
+            }
+            text.push_str(&unescape(&self.rest()[..end]));
+            self.pos += end;
+            if self.at_end() {
+                return Ok(text);
+            }
+        }
+    }
+
+    /// A start tag after its `<`; returns the element and whether it was self-closing
+    fn element(&mut self, depth: usize, bindings: &mut Vec<(usize, String, String)>) -> Result<(Element, bool)> {
+        let name = self.name()?;
+        let mut raw_attributes = Vec::new();
+
+        let closed = loop {
+            self.skip_whitespace();
+            if self.eat("/>") {
+                break true;
+            }
+            if self.eat(">") {
+                break false;
+            }
+
+            let attribute = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.eat("\"") { "\"" } else { self.expect("'").map(|_| "'")? };
+            let end = self.rest().find(quote).ok_or_else(|| anyhow::anyhow!("Unterminated value of attribute {}", attribute))?;
+            let value = unescape(&self.rest()[..end]);
+            self.pos += end + 1;
+            raw_attributes.push((attribute, value));
+        };
+
+        let mut element = Element::default();
+        for (attribute, value) in &raw_attributes {
+            if let Some(prefix) = attribute.strip_prefix("xmlns:") {
+                bindings.push((depth, prefix.to_string(), value.clone()));
+                element.namespaces.push((prefix.to_string(), value.clone()));
+            }
+        }
+        let resolve = |qualified: &str| -> (Option<String>, String) {
+            match qualified.split_once(':') {
+                Some((prefix, local)) => {
+                    let uri = bindings.iter().rev().find(|(_, bound, _)| bound == prefix).map(|(_, _, uri)| uri.clone());
+                    (uri, local.to_string())
+                }
+                None => (None, qualified.to_string()),
+            }
+        };
+
+        (element.namespace, element.name) = resolve(&name);
+        for (attribute, value) in raw_attributes {
+            if attribute == "xmlns" || attribute.starts_with("xmlns:") {
+                continue;
+            }
+            let (namespace, name) = resolve(&attribute);
+            element.attributes.push(Attribute { namespace, name, resource_id: None, value: Value::String(value) });
+        }
+        Ok((element, closed))
+    }
+}
+
+/// Resolve the predefined and numeric character entities
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                out.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,378 @@
+//! Renaming classes, fields and methods inside JVM class files
+//!
+//! Renamed strings are appended to the constant pool and the entries and attributes that
+//! named the old ones are repointed; nothing else in the class moves, so bytecode offsets
+//! and constant indices in code stay valid. The old strings remain for `ldc` and friends.
+
+use super::{Mapping, Remapper};
+use crate::classfile::{encode_modified_utf8, skip_element_value, u16_at, u32_at, Constant, ConstantPool};
+use crate::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+fn set_u16(data: &mut [u8], pos: usize, value: u16) {
+    data[pos..pos + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+/// The internal name of a class and of its superclass and interfaces
+pub fn class_hierarchy(data: &[u8]) -> Result<(String, Vec<String>)> {
+    let pool = ConstantPool::parse(data)?;
+    let name = pool.class_name(u16_at(data, pool.end + 2)?).ok_or_else(|| anyhow::anyhow!("Malformed class file: this_class is not a class"))?;
+    let mut parents: Vec<String> = pool.class_name(u16_at(data, pool.end + 4)?).into_iter().map(str::to_string).collect();
+    let interfaces = u16_at(data, pool.end + 6)? as usize;
+    for i in 0..interfaces {
+        parents.extend(pool.class_name(u16_at(data, pool.end + 8 + i * 2)?).map(str::to_string));
+    }
+    Ok((name.to_string(), parents))
+}
+
+/// Rewrites one class file
+struct ClassRemapper<'a> {
+    remapper: &'a Remapper<'a>,
+    pool: ConstantPool<'a>,
+    /// Internal name of the class, before renaming
+    class: String,
+    /// Index of every string in the pool, to reuse rather than append equal ones
+    strings: HashMap<String, u16>,
+    name_and_types: HashMap<(u16, u16), u16>,
+    /// Entries appended to the pool
+    added: Vec<u8>,
+    count: usize,
+}
+
+impl ClassRemapper<'_> {
+    fn mapping(&self) -> &Mapping {
+        self.remapper.mapping()
+    }
+
+    fn utf8(&mut self, text: &str) -> Result<u16> {
+        if let Some(&index) = self.strings.get(text) {
+            return Ok(index);
+        }
+        let bytes = encode_modified_utf8(text);
+        let index = self.add_entry()?;
+        self.added.push(1);
+        self.added.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        self.added.extend_from_slice(&bytes);
+        self.strings.insert(text.to_string(), index);
+        Ok(index)
+    }
+
+    fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16> {
+        let key = (self.utf8(name)?, self.utf8(descriptor)?);
+        if let Some(&index) = self.name_and_types.get(&key) {
+            return Ok(index);
+        }
+        let index = self.add_entry()?;
+        self.added.push(12);
+        self.added.extend_from_slice(&key.0.to_be_bytes());
+        self.added.extend_from_slice(&key.1.to_be_bytes());
+        self.name_and_types.insert(key, index);
+        Ok(index)
+    }
+
+    fn add_entry(&mut self) -> Result<u16> {
+        if self.count >= u16::MAX as usize {
+            anyhow::bail!("Constant pool of {} overflows with the renamed names", self.class);
+        }
+        self.count += 1;
+        Ok((self.count - 1) as u16)
+    }
+
+    /// Repoint the string index at `pos` to `rename` of its text, if that differs
+    fn rename_at(&mut self, data: &mut [u8], pos: usize, rename: impl FnOnce(&Mapping, &str) -> String) -> Result<()> {
+        let index = u16_at(data, pos)?;
+        let Some(text) = self.pool.text(index) else { return Ok(()) };
+        let renamed = rename(self.mapping(), text);
+        if renamed != text {
+            let index = self.utf8(&renamed)?;
+            set_u16(data, pos, index);
+        }
+        Ok(())
+    }
+
+    /// The renamed member behind a name and type entry, or `None` if it keeps its name and type
+    fn member(&mut self, owner: Option<&str>, name_and_type: u16, field: bool) -> Result<Option<u16>> {
+        let Some((name, descriptor)) = self.pool.name_and_type(name_and_type) else { return Ok(None) };
+        let renamed = owner.and_then(|owner| match field {
+            true => self.remapper.field(owner, name, descriptor),
+            false => self.remapper.method(owner, name, descriptor),
+        });
+        let name = renamed.unwrap_or(name).to_string();
+        let mapped_descriptor = self.mapping().map_descriptor(descriptor);
+        if renamed.is_none() && mapped_descriptor == descriptor {
+            return Ok(None);
+        }
+        self.name_and_type(&name, &mapped_descriptor).map(Some)
+    }
+
+    fn remap_pool(&mut self, data: &mut [u8]) -> Result<()> {
+        for index in 1..self.pool.entries.len() {
+            let pos = self.pool.offsets[index];
+            match self.pool.entries[index] {
+                Constant::Class(_) => self.rename_at(data, pos, |mapping, name| match name.starts_with('[') {
+                    true => mapping.map_descriptor(name),
+                    false => mapping.map_class(name),
+                })?,
+                Constant::Member { tag, class, name_and_type } => {
+                    // Members are looked up under the old name of their class
+                    let owner = self.pool.class_name(class).map(str::to_string);
+                    if let Some(renamed) = self.member(owner.as_deref(), name_and_type, tag == 9)? {
+                        set_u16(data, pos + 2, renamed);
+                    }
+                }
+                Constant::MethodType => self.rename_at(data, pos, |mapping, descriptor| mapping.map_descriptor(descriptor))?,
+                Constant::Dynamic(name_and_type) => {
+                    if let Some(renamed) = self.member(None, name_and_type, false)? {
+                        set_u16(data, pos + 2, renamed);
+                    }
+                }
+                Constant::Utf8(_) | Constant::Integer(_) | Constant::NameAndType(..) | Constant::Other => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields, methods and attributes after the pool
+    fn remap_body(&mut self, data: &mut [u8]) -> Result<()> {
+        let interfaces = u16_at(data, self.pool.end + 6)? as usize;
+        let mut pos = self.pool.end + 8 + interfaces * 2;
+        for field in [true, false] {
+            let count = u16_at(data, pos)?;
+            pos += 2;
+            for _ in 0..count {
+                let (name, descriptor) = (u16_at(data, pos + 2)?, u16_at(data, pos + 4)?);
+                if let (Some(name), Some(descriptor)) = (self.pool.text(name), self.pool.text(descriptor)) {
+                    let renamed = match field {
+                        true => self.remapper.field(&self.class, name, descriptor),
+                        false => self.remapper.method(&self.class, name, descriptor),
+                    };
+                    if let Some(renamed) = renamed {
+                        let index = self.utf8(renamed)?;
+                        set_u16(data, pos + 2, index);
+                    }
+                }
+                self.rename_at(data, pos + 4, |mapping, descriptor| mapping.map_descriptor(descriptor))?;
+                pos = self.attributes(data, pos + 6)?;
+            }
+        }
+        self.attributes(data, pos)?;
+        Ok(())
+    }
+
+    /// Returns the offset after the attributes
+    fn attributes(&mut self, data: &mut [u8], mut pos: usize) -> Result<usize> {
+        let count = u16_at(data, pos)?;
+        pos += 2;
+        for _ in 0..count {
+            let name = self.pool.text(u16_at(data, pos)?).unwrap_or_default().to_string();
+            let start = pos + 6;
+            let end = start + u32_at(data, pos + 2)? as usize;
+            if end > data.len() {
+                anyhow::bail!("Truncated class file");
+            }
+            match name.as_str() {
+                "Signature" => self.rename_at(data, start, |mapping, signature| mapping.map_signature(signature))?,
+                "Code" => {
+                    let code = u32_at(data, start + 4)? as usize;
+                    let exceptions = u16_at(data, start + 8 + code)? as usize;
+                    self.attributes(data, start + 10 + code + exceptions * 8)?;
+                }
+                "LocalVariableTable" | "LocalVariableTypeTable" => {
+                    let generic = name == "LocalVariableTypeTable";
+                    for i in 0..u16_at(data, start)? as usize {
+                        self.rename_at(data, start + 2 + i * 10 + 6, |mapping, descriptor| match generic {
+                            true => mapping.map_signature(descriptor),
+                            false => mapping.map_descriptor(descriptor),
+                        })?;
+                    }
+                }
+                "InnerClasses" => {
+                    for i in 0..u16_at(data, start)? as usize {
+                        self.inner_class(data, start + 2 + i * 8)?;
+                    }
+                }
+                "EnclosingMethod" => {
+                    let owner = self.pool.class_name(u16_at(data, start)?).map(str::to_string);
+                    let name_and_type = u16_at(data, start + 2)?;
+                    if name_and_type != 0 {
+                        if let Some(renamed) = self.member(owner.as_deref(), name_and_type, false)? {
+                            set_u16(data, start + 2, renamed);
+                        }
+                    }
+                }
+                "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+                    let mut at = start + 2;
+                    for _ in 0..u16_at(data, start)? {
+                        at = self.annotation(data, at)?;
+                    }
+                }
+                "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                    let mut at = start + 1;
+                    for _ in 0..data[start] {
+                        let count = u16_at(data, at)?;
+                        at += 2;
+                        for _ in 0..count {
+                            at = self.annotation(data, at)?;
+                        }
+                    }
+                }
+                "AnnotationDefault" => {
+                    self.element_value(data, start)?;
+                }
+                _ => {}
+            }
+            pos = end;
+        }
+        Ok(pos)
+    }
+
+    /// Renames the simple name of an inner class along with the class
+    fn inner_class(&mut self, data: &mut [u8], pos: usize) -> Result<()> {
+        let Some(inner) = self.pool.class_name(u16_at(data, pos)?) else { return Ok(()) };
+        let Some(mapped) = self.mapping().class(inner) else { return Ok(()) };
+        if u16_at(data, pos + 4)? == 0 {
+            return Ok(());
+        }
+        let outer = self.pool.class_name(u16_at(data, pos + 2)?).map(|outer| format!("{}$", self.mapping().map_class(outer)));
+        let simple_name = match outer.as_deref().and_then(|outer| mapped.strip_prefix(outer)) {
+            Some(simple_name) => simple_name,
+            None => mapped.rsplit(['$', '/']).next().unwrap_or(mapped),
+        };
+        let simple_name = simple_name.to_string();
+        let index = self.utf8(&simple_name)?;
+        set_u16(data, pos + 4, index);
+        Ok(())
+    }
+
+    /// Returns the offset after the annotation
+    fn annotation(&mut self, data: &mut [u8], pos: usize) -> Result<usize> {
+        self.rename_at(data, pos, |mapping, descriptor| mapping.map_descriptor(descriptor))?;
+        let mut pos = pos + 4;
+        for _ in 0..u16_at(data, pos - 2)? {
+            pos = self.element_value(data, pos + 2)?;
+        }
+        Ok(pos)
+    }
+
+    /// Returns the offset after the value
+    fn element_value(&mut self, data: &mut [u8], pos: usize) -> Result<usize> {
+        let tag = *data.get(pos).ok_or_else(|| anyhow::anyhow!("Truncated class file"))?;
+        let value = pos + 1;
+        match tag {
+            b'e' => {
+                // The constant is a field of the enum, looked up under its old name
+                let descriptor = self.pool.text(u16_at(data, value)?).map(str::to_string);
+                let constant = self.pool.text(u16_at(data, value + 2)?).map(str::to_string);
+                if let (Some(descriptor), Some(constant)) = (descriptor, constant) {
+                    let owner = descriptor.strip_prefix('L').and_then(|owner| owner.strip_suffix(';')).unwrap_or_default();
+                    if let Some(renamed) = self.remapper.field(owner, &constant, &descriptor) {
+                        let index = self.utf8(renamed)?;
+                        set_u16(data, value + 2, index);
+                    }
+                }
+                self.rename_at(data, value, |mapping, descriptor| mapping.map_descriptor(descriptor))?;
+                Ok(value + 4)
+            }
+            b'c' => {
+                self.rename_at(data, value, |mapping, descriptor| mapping.map_descriptor(descriptor))?;
+                Ok(value + 2)
+            }
+            b'@' => self.annotation(data, value),
+            b'[' => {
+                let mut at = value + 2;
+                for _ in 0..u16_at(data, value)? {
+                    at = self.element_value(data, at)?;
+                }
+                Ok(at)
+            }
+            _ => skip_element_value(data, pos),
+        }
+    }
+}
+
+/// Rename the members of a class file and everything it refers to; returns the class's new
+/// internal name and bytes
+pub fn remap_class(data: &[u8], remapper: &Remapper<'_>) -> Result<(String, Vec<u8>)> {
+    let pool = ConstantPool::parse(data)?;
+    let class = pool.class_name(u16_at(data, pool.end + 2)?).ok_or_else(|| anyhow::anyhow!("Malformed class file: this_class is not a class"))?.to_string();
+    let strings = (1..pool.entries.len() as u16).filter_map(|index| pool.text(index).map(|text| (text.to_string(), index))).collect();
+    let name_and_types = pool.entries.iter().enumerate().filter_map(|(index, entry)| match entry {
+        Constant::NameAndType(name, descriptor) => Some(((*name, *descriptor), index as u16)),
+        _ => None,
+    });
+    let name_and_types = name_and_types.collect();
+    let count = pool.entries.len();
+    let end = pool.end;
+    let mut remapper = ClassRemapper { remapper, pool, class, strings, name_and_types, added: Vec::new(), count };
+
+    let mut remapped = data.to_vec();
+    remapper.remap_pool(&mut remapped)?;
+    remapper.remap_body(&mut remapped)?;
+    set_u16(&mut remapped, 8, remapper.count as u16);
+    remapped.splice(end..end, remapper.added);
+
+    let name = remapper.remapper.mapping().map_class(&remapper.class);
+    Ok((name, remapped))
+}
+
+/// Apply `mapping` to the classes of `jars` in place; returns how many classes were renamed
+pub fn remap_jars(jars: &[PathBuf], mapping: &Mapping) -> Result<usize> {
+    // Members are found through the superclasses and interfaces, which may be in another JAR
+    let mut parents = HashMap::new();
+    for jar in jars {
+        let mut archive = ZipArchive::new(File::open(jar)?)?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            if let Ok((name, class_parents)) = class_hierarchy(&data) {
+                parents.insert(name, class_parents);
+            }
+        }
+    }
+
+    let remapper = Remapper::new(mapping, parents);
+    let mut renamed = 0;
+    for jar in jars {
+        renamed += remap_jar(jar, &remapper)?;
+    }
+    Ok(renamed)
+}
+
+fn remap_jar(jar: &Path, remapper: &Remapper<'_>) -> Result<usize> {
+    let mut archive = ZipArchive::new(File::open(jar)?)?;
+    let parent = jar.parent().unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(parent)?;
+    let mut writer = ZipWriter::new(temp.reopen()?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut renamed = 0;
+    for i in 0..archive.len() {
+        if !archive.by_index_raw(i)?.name().ends_with(".class") {
+            writer.raw_copy_file(archive.by_index_raw(i)?)?;
+            continue;
+        }
+        let mut file = archive.by_index(i)?;
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+        let (name, data) = remap_class(&data, remapper).map_err(|e| anyhow::anyhow!("{}: {}", file.name(), e))?;
+        let path = format!("{}.class", name);
+        if path != file.name() {
+            renamed += 1;
+        }
+        writer.start_file(path, options)?;
+        writer.write_all(&data)?;
+    }
+
+    writer.finish()?;
+    temp.persist(jar)?;
+    Ok(renamed)
+}
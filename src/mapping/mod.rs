@@ -0,0 +1,391 @@
+//! ProGuard and R8 `mapping.txt`: the original names of obfuscated classes, fields and methods
+//!
+//! ```text
+//! com.example.Account -> a.b:
+//!     java.lang.String owner -> a
+//!     1:4:void deposit(long):22:25 -> a
+//! ```
+//!
+//! Names here are internal names (`com/example/Account`) and descriptors use the obfuscated
+//! types, as they appear in class files and smali.
+
+pub mod classfile;
+pub mod smali;
+
+use crate::{Context, Result};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+pub use classfile::remap_jars;
+
+/// The original names of one obfuscated class's members, by obfuscated name and descriptor
+#[derive(Debug, Clone, Default)]
+struct Members {
+    fields: HashMap<(String, String), String>,
+    methods: HashMap<(String, String), String>,
+}
+
+/// A parsed mapping file
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+    /// Obfuscated internal name → original internal name
+    classes: HashMap<String, String>,
+    /// Members by obfuscated internal name of their class
+    members: HashMap<String, Members>,
+}
+
+/// A member line before the types in it can be turned into obfuscated descriptors
+struct MemberLine {
+    java_type: String,
+    name: String,
+    /// `None` for fields
+    parameters: Option<Vec<String>>,
+    obfuscated: String,
+}
+
+impl Mapping {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read mapping file '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid mapping file '{}'", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        // Original class → obfuscated class and its member lines
+        let mut classes: Vec<(String, String, Vec<MemberLine>)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let Some((original, obfuscated)) = trimmed.split_once(" -> ") else {
+                anyhow::bail!("line {}: expected 'original -> obfuscated', found '{}'", number + 1, trimmed);
+            };
+
+            if !line.starts_with(char::is_whitespace) {
+                let obfuscated = obfuscated.strip_suffix(':').with_context(|| format!("line {}: class mapping must end with ':'", number + 1))?;
+                classes.push((internal(original.trim()), internal(obfuscated.trim()), Vec::new()));
+                continue;
+            }
+
+            let Some((_, _, members)) = classes.last_mut() else {
+                anyhow::bail!("line {}: member mapping before the first class", number + 1);
+            };
+            let member = member_line(original, obfuscated.trim()).with_context(|| format!("line {}: malformed member '{}'", number + 1, trimmed))?;
+            // Code R8 inlined from another class is named with its class; it is not a member here
+            if !member.name.contains('.') {
+                members.push(member);
+            }
+        }
+
+        // Descriptors in class files use the obfuscated names of the types
+        let obfuscated_names: HashMap<&str, &str> = classes.iter().map(|(original, obfuscated, _)| (original.as_str(), obfuscated.as_str())).collect();
+        let descriptor = |java_type: &str| java_descriptor(java_type, &obfuscated_names);
+
+        let mut mapping = Mapping::default();
+        for (original, obfuscated, lines) in &classes {
+            let mut members = Members::default();
+            for line in lines {
+                let key = match &line.parameters {
+                    None => (line.obfuscated.clone(), descriptor(&line.java_type)),
+                    Some(parameters) => {
+                        let parameters: String = parameters.iter().map(|parameter| descriptor(parameter)).collect();
+                        (line.obfuscated.clone(), format!("({}){}", parameters, descriptor(&line.java_type)))
+                    }
+                };
+                if line.name == line.obfuscated {
+                    continue;
+                }
+                // Of several lines for the same method, R8 writes the inlined frames first and the method itself last
+                match line.parameters {
+                    None => members.fields.insert(key, line.name.clone()),
+                    Some(_) => members.methods.insert(key, line.name.clone()),
+                };
+            }
+            if !members.fields.is_empty() || !members.methods.is_empty() {
+                mapping.members.insert(obfuscated.clone(), members);
+            }
+            if original != obfuscated {
+                mapping.classes.insert(obfuscated.clone(), original.clone());
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// Number of renamed classes
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty() && self.members.is_empty()
+    }
+
+    /// The original internal name of an obfuscated class, if it was renamed
+    pub fn class(&self, name: &str) -> Option<&str> {
+        self.classes.get(name).map(String::as_str)
+    }
+
+    /// The original internal name of a class, or the name itself
+    pub fn map_class(&self, name: &str) -> String {
+        self.class(name).unwrap_or(name).to_string()
+    }
+
+    /// A field or method descriptor with every class type renamed, e.g. `(La/b;I)La/c;`
+    pub fn map_descriptor(&self, descriptor: &str) -> String {
+        let mut mapped = String::with_capacity(descriptor.len());
+        let mut rest = descriptor;
+        while let Some(start) = rest.find('L') {
+            let Some(end) = rest[start..].find(';').map(|end| start + end) else { break };
+            mapped.push_str(&rest[..=start]);
+            mapped.push_str(self.class(&rest[start + 1..end]).unwrap_or(&rest[start + 1..end]));
+            mapped.push(';');
+            rest = &rest[end + 1..];
+        }
+        mapped.push_str(rest);
+        mapped
+    }
+
+    /// A generic signature (`Signature` attribute) with every class type renamed; unchanged if it cannot be parsed
+    pub fn map_signature(&self, signature: &str) -> String {
+        let mut mapper = SignatureMapper { mapping: self, chars: signature.chars().collect(), pos: 0, mapped: String::with_capacity(signature.len()) };
+        match mapper.signature() {
+            Some(()) => mapper.mapped,
+            None => signature.to_string(),
+        }
+    }
+
+    fn members(&self, class: &str) -> Option<&Members> {
+        self.members.get(class)
+    }
+}
+
+/// `com/example/Foo$Bar` for `com.example.Foo$Bar`
+fn internal(java_name: &str) -> String {
+    java_name.replace('.', "/")
+}
+
+/// `[1:4:]type name[(parameters)][:22[:25]]`
+fn member_line(original: &str, obfuscated: &str) -> Option<MemberLine> {
+    let mut original = original.trim();
+    // Line numbers of the obfuscated code
+    while let Some((number, rest)) = original.split_once(':') {
+        if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+            break;
+        }
+        original = rest;
+    }
+    let (java_type, rest) = original.split_once(' ')?;
+    let (name, parameters) = match rest.split_once('(') {
+        Some((name, rest)) => {
+            let (parameters, _) = rest.split_once(')')?;
+            let parameters = parameters.split(',').map(str::trim).filter(|parameter| !parameter.is_empty()).map(str::to_string).collect();
+            (name, Some(parameters))
+        }
+        None => (rest, None),
+    };
+    Some(MemberLine { java_type: java_type.to_string(), name: name.trim().to_string(), parameters, obfuscated: obfuscated.to_string() })
+}
+
+/// `[Ljava/lang/String;` for `java.lang.String[]`, with classes under their obfuscated names
+fn java_descriptor(java_type: &str, obfuscated_names: &HashMap<&str, &str>) -> String {
+    let mut element = java_type.trim();
+    let mut descriptor = String::new();
+    while let Some(inner) = element.strip_suffix("[]") {
+        descriptor.push('[');
+        element = inner;
+    }
+    let primitive = match element {
+        "void" => "V",
+        "boolean" => "Z",
+        "byte" => "B",
+        "char" => "C",
+        "short" => "S",
+        "int" => "I",
+        "long" => "J",
+        "float" => "F",
+        "double" => "D",
+        _ => {
+            let class = internal(element);
+            descriptor.push('L');
+            descriptor.push_str(obfuscated_names.get(class.as_str()).copied().unwrap_or(&class));
+            descriptor.push(';');
+            return descriptor;
+        }
+    };
+    descriptor.push_str(primitive);
+    descriptor
+}
+
+/// Renames the class types of a generic signature, following the grammar in JVMS 4.7.9.1
+struct SignatureMapper<'a> {
+    mapping: &'a Mapping,
+    chars: Vec<char>,
+    pos: usize,
+    mapped: String,
+}
+
+impl SignatureMapper<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn copy(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.mapped.push(char);
+        self.pos += 1;
+        Some(char)
+    }
+
+    /// Characters up to one of `stops`, not copied
+    fn identifier(&mut self, stops: &[char]) -> Option<String> {
+        let start = self.pos;
+        while !stops.contains(&self.peek()?) {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn signature(&mut self) -> Option<()> {
+        if self.peek() == Some('<') {
+            self.type_parameters()?;
+        }
+        if self.peek() == Some('(') {
+            self.copy();
+            while self.peek()? != ')' {
+                self.type_signature()?;
+            }
+            self.copy();
+            self.type_signature()?;
+            while self.peek() == Some('^') {
+                self.copy();
+                self.type_signature()?;
+            }
+        }
+        // Superclass and interfaces of a class, or the type of a field
+        while self.peek().is_some() {
+            self.type_signature()?;
+        }
+        Some(())
+    }
+
+    /// `<T:Ljava/lang/Object;U::Ljava/lang/Comparable<TU;>;>`
+    fn type_parameters(&mut self) -> Option<()> {
+        self.copy();
+        while self.peek()? != '>' {
+            let name = self.identifier(&[':'])?;
+            self.mapped.push_str(&name);
+            self.copy();
+            // The class bound may be empty
+            if self.peek()? != ':' {
+                self.type_signature()?;
+            }
+            while self.peek()? == ':' {
+                self.copy();
+                self.type_signature()?;
+            }
+        }
+        self.copy();
+        Some(())
+    }
+
+    fn type_signature(&mut self) -> Option<()> {
+        match self.peek()? {
+            'L' => self.class_type(),
+            'T' => {
+                while self.copy()? != ';' {}
+                Some(())
+            }
+            '[' | '+' | '-' => {
+                self.copy();
+                self.type_signature()
+            }
+            'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 'V' | '*' => {
+                self.copy();
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    /// `Lcom/a/b<TT;>.c;`, where `c` is the inner class `com/a/b$c`
+    fn class_type(&mut self) -> Option<()> {
+        self.copy();
+        let mut class = self.identifier(&['<', ';', '.'])?;
+        let mut mapped_class = self.mapping.map_class(&class);
+        self.mapped.push_str(&mapped_class);
+        loop {
+            match self.peek()? {
+                '<' => {
+                    self.copy();
+                    while self.peek()? != '>' {
+                        self.type_signature()?;
+                    }
+                    self.copy();
+                }
+                '.' => {
+                    self.copy();
+                    let inner = self.identifier(&['<', ';', '.'])?;
+                    class = format!("{}${}", class, inner);
+                    let mapped_inner = self.mapping.map_class(&class);
+                    let simple_name = match mapped_inner.strip_prefix(&format!("{}$", mapped_class)) {
+                        Some(simple_name) => simple_name.to_string(),
+                        None => mapped_inner.rsplit(['$', '/']).next().unwrap_or(&inner).to_string(),
+                    };
+                    self.mapped.push_str(&simple_name);
+                    mapped_class = mapped_inner;
+                }
+                ';' => {
+                    self.copy();
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Looks members up in the class that declares them, following superclasses and interfaces
+/// from the class a reference names
+pub struct Remapper<'a> {
+    mapping: &'a Mapping,
+    /// Obfuscated internal name → superclass and interfaces
+    parents: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Remapper<'a> {
+    pub fn new(mapping: &'a Mapping, parents: HashMap<String, Vec<String>>) -> Self {
+        Self { mapping, parents }
+    }
+
+    pub fn mapping(&self) -> &'a Mapping {
+        self.mapping
+    }
+
+    /// Original name of the field `name` with the obfuscated `descriptor`, referenced through `owner`
+    pub fn field(&self, owner: &str, name: &str, descriptor: &str) -> Option<&'a str> {
+        self.find(owner, |members| members.fields.get(&(name.to_string(), descriptor.to_string())))
+    }
+
+    /// Original name of the method `name` with the obfuscated `descriptor`, referenced through `owner`
+    pub fn method(&self, owner: &str, name: &str, descriptor: &str) -> Option<&'a str> {
+        if name.starts_with('<') {
+            return None;
+        }
+        self.find(owner, |members| members.methods.get(&(name.to_string(), descriptor.to_string())))
+    }
+
+    fn find(&self, owner: &str, lookup: impl Fn(&'a Members) -> Option<&'a String>) -> Option<&'a str> {
+        let mut queue = VecDeque::from([owner.to_string()]);
+        let mut seen = HashSet::new();
+        while let Some(class) = queue.pop_front() {
+            if let Some(name) = self.mapping.members(&class).and_then(&lookup) {
+                return Some(name);
+            }
+            if let Some(parents) = self.parents.get(&class) {
+                queue.extend(parents.iter().filter(|parent| seen.insert(parent.to_string())).cloned());
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,284 @@
+//! Renaming classes, fields and methods in apktool's smali
+//!
+//! Types are rewritten wherever smali names them, members in their declarations and in
+//! `Lowner;->name:type` references. String literals are left alone, except in the
+//! `dalvik.annotation.Signature` annotations that hold generic signatures.
+
+use super::{Mapping, Remapper};
+use crate::apk::extractor::smali_dirs;
+use crate::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+const SIGNATURE_ANNOTATION: &str = ".annotation system Ldalvik/annotation/Signature;";
+
+/// Apply `mapping` to the smali directories of an apktool project in place; returns how many
+/// classes were renamed
+pub fn remap_project(project: &Path, mapping: &Mapping) -> Result<usize> {
+    let dirs = smali_dirs(project);
+
+    // Members are found through the superclasses and interfaces, which may be in another directory
+    let mut parents = HashMap::new();
+    for dir in &dirs {
+        for path in smali_files(dir) {
+            let text = fs::read_to_string(&path)?;
+            if let Some((class, class_parents)) = class_hierarchy(&text) {
+                parents.insert(class, class_parents);
+            }
+        }
+    }
+
+    let remapper = Remapper::new(mapping, parents);
+    let mut renamed = 0;
+    for dir in &dirs {
+        renamed += remap_dir(dir, &remapper)?;
+    }
+    Ok(renamed)
+}
+
+fn smali_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.path().extension() == Some("smali".as_ref()))
+        .map(|entry| entry.into_path())
+}
+
+/// The internal name of the class in a smali file, with its superclass and interfaces
+fn class_hierarchy(text: &str) -> Option<(String, Vec<String>)> {
+    let mut class = None;
+    let mut parents = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let Some((directive, rest)) = line.split_once(' ') else { continue };
+        let name = || rest.rsplit(' ').next().and_then(|name| name.strip_prefix('L')).and_then(|name| name.strip_suffix(';')).map(str::to_string);
+        match directive {
+            ".class" => class = name(),
+            ".super" | ".implements" => parents.extend(name()),
+            ".field" | ".method" => break,
+            _ => {}
+        }
+    }
+    Some((class?, parents))
+}
+
+/// Rewrite one smali directory into a sibling and swap it in
+fn remap_dir(dir: &Path, remapper: &Remapper<'_>) -> Result<usize> {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let temp = dir.with_file_name(format!(".{}.remap", name));
+    if temp.exists() {
+        fs::remove_dir_all(&temp)?;
+    }
+
+    let mut renamed = 0;
+    for entry in WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()).filter(|entry| entry.file_type().is_file()) {
+        let path = entry.path();
+        let relative = path.strip_prefix(dir)?;
+        if path.extension() != Some("smali".as_ref()) {
+            let output = temp.join(relative);
+            fs::create_dir_all(output.parent().unwrap_or(&temp))?;
+            fs::copy(path, &output)?;
+            continue;
+        }
+
+        let text = fs::read_to_string(path)?;
+        let class = class_hierarchy(&text).map(|(class, _)| class).unwrap_or_default();
+        let output = match remapper.mapping().class(&class) {
+            Some(mapped) => {
+                renamed += 1;
+                temp.join(format!("{}.smali", mapped))
+            }
+            None => temp.join(relative),
+        };
+        fs::create_dir_all(output.parent().unwrap_or(&temp))?;
+        fs::write(&output, remap_text(&text, &class, remapper))?;
+    }
+
+    fs::remove_dir_all(dir)?;
+    fs::rename(&temp, dir)?;
+    Ok(renamed)
+}
+
+fn field_declaration() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^(\s*\.field\s+(?:\S+\s+)*?)([^\s:]+):(\S+)").unwrap())
+}
+
+fn method_declaration() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^(\s*\.method\s+(?:\S+\s+)*?)([^\s(]+)(\(\S*)").unwrap())
+}
+
+/// The smali of `class` with every name in `remapper`'s mapping replaced
+pub fn remap_text(text: &str, class: &str, remapper: &Remapper<'_>) -> String {
+    let mut remapped = String::with_capacity(text.len());
+    let mut in_signature = false;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed == SIGNATURE_ANNOTATION {
+            in_signature = true;
+        } else if trimmed == ".end annotation" {
+            in_signature = false;
+        }
+
+        // Declarations: the member's own name, then the rest as any other line
+        let declaration = field_declaration()
+            .captures(line)
+            .map(|captures| (remapper.field(class, &captures[2], &captures[3]), captures))
+            .or_else(|| method_declaration().captures(line).map(|captures| (remapper.method(class, &captures[2], &captures[3]), captures)));
+        match declaration {
+            Some((renamed, captures)) => {
+                let (name, descriptor) = (captures.get(2).unwrap(), captures.get(3).unwrap());
+                remapped.push_str(&captures[1]);
+                remapped.push_str(renamed.unwrap_or(name.as_str()));
+                remapped.push_str(&line[name.end()..descriptor.start()]);
+                remap_line(&line[descriptor.start()..], remapper, in_signature, &mut remapped);
+            }
+            None => remap_line(line, remapper, in_signature, &mut remapped),
+        }
+    }
+    remapped
+}
+
+/// Characters that cannot be in a class name as smali writes it
+fn ends_class_name(char: char) -> bool {
+    char.is_whitespace() || matches!(char, '(' | ')' | ':' | '"' | ',' | '{' | '}' | '<' | '>' | ';')
+}
+
+/// End of the type descriptor at `start`, e.g. `[La/b;` or `I`
+fn type_end(line: &str, start: usize) -> Option<usize> {
+    let rest = &line[start..];
+    let element = rest.trim_start_matches('[');
+    let at = start + rest.len() - element.len();
+    match element.chars().next()? {
+        'V' | 'Z' | 'B' | 'S' | 'C' | 'I' | 'J' | 'F' | 'D' => Some(at + 1),
+        'L' => {
+            let end = element.find(ends_class_name)?;
+            (element[end..].starts_with(';') && end > 1).then_some(at + end + 1)
+        }
+        _ => None,
+    }
+}
+
+/// End of the method descriptor at `start`, e.g. `(ILa/b;)V`
+fn method_descriptor_end(line: &str, start: usize) -> Option<usize> {
+    let mut at = start + 1;
+    while !line[at..].starts_with(')') {
+        at = type_end(line, at)?;
+    }
+    type_end(line, at + 1)
+}
+
+/// Whether a class type may start after `previous`, rather than `L` being part of a name
+fn starts_type(previous: Option<char>) -> bool {
+    match previous {
+        Some(char) => !(char.is_alphanumeric() || matches!(char, '_' | '$' | '-' | '/')),
+        None => true,
+    }
+}
+
+fn remap_line(line: &str, remapper: &Remapper<'_>, in_signature: bool, remapped: &mut String) {
+    let mapping = remapper.mapping();
+    let mut at = 0;
+    let mut previous = None;
+    while let Some(char) = line[at..].chars().next() {
+        match char {
+            '"' => {
+                let end = string_end(line, at);
+                match line[at + 1..end].strip_suffix('"') {
+                    Some(literal) if in_signature => {
+                        remapped.push('"');
+                        remapped.push_str(&signature_fragment(literal, mapping));
+                        remapped.push('"');
+                    }
+                    _ => remapped.push_str(&line[at..end]),
+                }
+                at = end;
+            }
+            '#' => {
+                remapped.push_str(&line[at..]);
+                return;
+            }
+            '(' => match method_descriptor_end(line, at) {
+                Some(end) => {
+                    remapped.push_str(&mapping.map_descriptor(&line[at..end]));
+                    at = end;
+                }
+                None => {
+                    remapped.push(char);
+                    at += 1;
+                }
+            },
+            'L' if starts_type(previous) => match type_end(line, at) {
+                Some(end) => {
+                    remapped.push_str(&mapping.map_descriptor(&line[at..end]));
+                    // An array's members are not the element class's
+                    let owner = (previous != Some('[')).then(|| &line[at + 1..end - 1]);
+                    at = end;
+                    if line[at..].starts_with("->") {
+                        at = remap_member(line, at + 2, owner, remapper, remapped);
+                    }
+                }
+                None => {
+                    remapped.push(char);
+                    at += 1;
+                }
+            },
+            _ => {
+                remapped.push(char);
+                at += char.len_utf8();
+            }
+        }
+        previous = line[..at].chars().next_back();
+    }
+}
+
+/// `->name:type` or `->name(descriptor)`, with `start` after the arrow; returns where it ends
+fn remap_member(line: &str, start: usize, owner: Option<&str>, remapper: &Remapper<'_>, remapped: &mut String) -> usize {
+    remapped.push_str("->");
+    let Some(name_end) = line[start..].find([':', '(']).map(|end| start + end) else { return start };
+    let name = &line[start..name_end];
+    let (end, renamed) = if line[name_end..].starts_with(':') {
+        let Some(end) = type_end(line, name_end + 1) else { return start };
+        (end, owner.and_then(|owner| remapper.field(owner, name, &line[name_end + 1..end])))
+    } else {
+        let Some(end) = method_descriptor_end(line, name_end) else { return start };
+        (end, owner.and_then(|owner| remapper.method(owner, name, &line[name_end..end])))
+    };
+    remapped.push_str(renamed.unwrap_or(name));
+    if line[name_end..].starts_with(':') {
+        remapped.push(':');
+        remapped.push_str(&remapper.mapping().map_descriptor(&line[name_end + 1..end]));
+    } else {
+        remapped.push_str(&remapper.mapping().map_descriptor(&line[name_end..end]));
+    }
+    end
+}
+
+/// Offset after the closing quote of the string literal at `start`
+fn string_end(line: &str, start: usize) -> usize {
+    let mut escaped = false;
+    for (offset, char) in line[start + 1..].char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return start + 1 + offset + 1,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+/// A piece of a generic signature as dx splits it, e.g. `Ljava/util/List<` or `La/b;`
+fn signature_fragment(fragment: &str, mapping: &Mapping) -> String {
+    let Some(class) = fragment.strip_prefix('L') else { return fragment.to_string() };
+    let Some(end) = class.find(['<', ';']) else { return fragment.to_string() };
+    if class[..end].contains(ends_class_name) {
+        return fragment.to_string();
+    }
+    format!("L{}{}", mapping.map_class(&class[..end]), &class[end..])
+}
@@ -1,22 +1,180 @@
+use crate::tools::{Progress, ProgressHandler};
 use crate::{assets::AssetManager, config::Config, Result};
+use jsonrpc_core::{Error as RpcError, IoHandler, Params, Value};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinSet;
+
+pub mod tools;
+
+/// Newest MCP revision we speak; older ones are accepted if the client asks for them
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
 
 pub struct Server {
     config: Config,
-    _asset_manager: AssetManager,
+    asset_manager: AssetManager,
+}
+
+#[derive(Deserialize)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+    #[serde(rename = "_meta", default)]
+    meta: Option<RequestMeta>,
+}
+
+#[derive(Deserialize)]
+struct RequestMeta {
+    #[serde(rename = "progressToken")]
+    progress_token: Option<Value>,
 }
 
 impl Server {
-    pub fn new(config: Config, asset_manager: AssetManager) -> Result<Self> {
+    pub fn new(mut config: Config, asset_manager: AssetManager) -> Result<Self> {
+        // Stdout carries the protocol, so tool progress output must not leak into it
+        config.quiet = true;
+
         Ok(Self {
             config,
-            _asset_manager: asset_manager,
+            asset_manager,
         })
     }
 
+    /// Serve newline-delimited JSON-RPC over stdin/stdout until stdin is closed
     pub async fn run(&self) -> Result<()> {
-        println!("MCP Server starting with Java: {}", self.config.java.java_path);
-        println!("MCP functionality will be implemented");
-        // TODO: Port from internal/mcp/server.go
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve newline-delimited JSON-RPC read from `input` until it is closed
+    pub async fn serve<R, W>(&self, input: R, mut output: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        // Responses and progress notifications share the output, so a single task writes both
+        let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            while let Some(message) = outgoing.recv().await {
+                output.write_all(message.as_bytes()).await?;
+                output.write_all(b"\n").await?;
+                output.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+
+        let io = self.handler(sender.clone());
+        let mut lines = BufReader::new(input).lines();
+        let mut requests = JoinSet::new();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Each request runs on its own so a long unpack does not hold up a ping sent after it
+            let response = io.handle_request(&line);
+            let sender = sender.clone();
+            requests.spawn(async move {
+                if let Some(response) = response.await {
+                    // Only fails once the writer has stopped, and that error is returned below
+                    let _ = sender.send(response);
+                }
+            });
+        }
+
+        // Answer what is still running before closing the output
+        while let Some(request) = requests.join_next().await {
+            request?;
+        }
+
+        drop(io);
+        drop(sender);
+        writer.await??;
+
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// The JSON-RPC methods; `handle_request` returns the response and progress notifications go to `sender`
+    pub fn handler(&self, sender: UnboundedSender<String>) -> IoHandler {
+        let mut io = IoHandler::new();
+
+        io.add_method("initialize", |params: Params| async move {
+            let requested = params.parse::<Value>().ok().and_then(|p| {
+                p.get("protocolVersion").and_then(Value::as_str).map(str::to_string)
+            });
+
+            let protocol_version = match requested {
+                Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version.as_str()) => version,
+                _ => PROTOCOL_VERSION.to_string(),
+            };
+
+            Ok(json!({
+                "protocolVersion": protocol_version,
+                "capabilities": {
+                    "tools": { "listChanged": false }
+                },
+                "serverInfo": {
+                    "name": "apkext",
+                    "version": env!("CARGO_PKG_VERSION")
+                }
+            }))
+        });
+
+        io.add_notification("notifications/initialized", |_params: Params| {});
+
+        io.add_method("ping", |_params: Params| async { Ok(json!({})) });
+
+        io.add_method("tools/list", |_params: Params| async {
+            Ok(json!({ "tools": tools::definitions() }))
+        });
+
+        let config = self.config.clone();
+        let asset_manager = self.asset_manager.clone();
+        io.add_method("tools/call", move |params: Params| {
+            let config = config.clone();
+            let asset_manager = asset_manager.clone();
+            let sender = sender.clone();
+            async move {
+                let params: CallToolParams = params.parse()?;
+                let progress = params.meta
+                    .and_then(|meta| meta.progress_token)
+                    .map(|token| progress_notifier(sender, token));
+                tools::call(config, asset_manager, &params.name, params.arguments, progress)
+                    .await
+                    .ok_or_else(|| RpcError::invalid_params(format!("Unknown tool: {}", params.name)))
+            }
+        });
+
+        io
+    }
+}
+
+/// Forward pipeline progress as `notifications/progress` for the request that sent `token`
+fn progress_notifier(sender: UnboundedSender<String>, token: Value) -> ProgressHandler {
+    let sent = AtomicU64::new(0);
+
+    Arc::new(move |event: &Progress| {
+        let message = match event {
+            Progress::Step { name, index, total } => format!("[{}/{}] {}", index, total, name),
+            Progress::Message { text } if text.is_empty() => return,
+            Progress::Message { text } => text.clone(),
+            Progress::Output { tool, line, .. } => format!("{}: {}", tool, line),
+        };
+
+        // The progress value only has to increase, so count the notifications
+        let progress = sent.fetch_add(1, Ordering::Relaxed) + 1;
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": { "progressToken": token, "progress": progress, "message": message }
+        });
+        // The client may already be gone; the tool result will tell it what happened
+        let _ = sender.send(notification.to_string());
+    })
+}
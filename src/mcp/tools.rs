@@ -0,0 +1,558 @@
+use crate::signing::{self, KeySource, SignOptions};
+use crate::tools::{Decompiler, ProgressHandler};
+use crate::apk::{ExistingOutput, UnpackStages};
+use crate::{apk, arsc, assets::AssetManager, config::Config, dex, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Tool descriptors returned by `tools/list`
+pub fn definitions() -> Value {
+    json!([
+        {
+            "name": "unpack",
+            "description": "Unpack an APK file: decode resources and smali with apktool, convert DEX to JAR and decompile the classes to Java source.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK file, an .apks, .xapk or .apkm split container, or an .aab app bundle to unpack"
+                    },
+                    "decompiler": {
+                        "type": "string",
+                        "enum": ["procyon", "cfr", "vineflower", "jadx"],
+                        "description": "Decompiler used to produce the Java sources (default: procyon)"
+                    },
+                    "fallback_decompiler": {
+                        "type": "string",
+                        "enum": ["procyon", "cfr", "vineflower", "jadx"],
+                        "description": "Decompiler that retries classes the first one failed on (default: vineflower, or cfr when the first one is vineflower)"
+                    },
+                    "fallback": {
+                        "type": "boolean",
+                        "description": "Retry failed classes with the fallback decompiler (default: true)"
+                    },
+                    "cache": {
+                        "type": "boolean",
+                        "description": "Take the sources of classes decompiled before from the decompilation cache (default: true)"
+                    },
+                    "kotlin": {
+                        "type": "boolean",
+                        "description": "Recover Kotlin declarations from @kotlin.Metadata into kotlin.json and comments in the sources (default: true)"
+                    },
+                    "mapping": {
+                        "type": "string",
+                        "description": "ProGuard or R8 mapping.txt whose original class, method and field names replace the obfuscated ones in the JARs and smali"
+                    },
+                    "output_dir": {
+                        "type": "string",
+                        "description": "Output directory (default: the APK path without its extension)"
+                    },
+                    "existing_output": {
+                        "type": "string",
+                        "enum": ["refuse", "force", "merge"],
+                        "description": "What to do if the output directory exists: fail, replace it, or refresh generated files while keeping modified ones (default: refuse)"
+                    },
+                    "stages": {
+                        "type": "object",
+                        "description": "Pipeline stages to run; omitted fields default to true",
+                        "properties": {
+                            "apktool": { "type": "boolean", "description": "Run apktool into unpacked/" },
+                            "decode_resources": { "type": "boolean", "description": "Decode resources (false passes apktool -r)" },
+                            "decode_smali": { "type": "boolean", "description": "Disassemble DEX to smali (false passes apktool -s)" },
+                            "jar": { "type": "boolean", "description": "Convert DEX files to JAR files" },
+                            "decompile": { "type": "boolean", "description": "Decompile the JAR files into src/" }
+                        }
+                    }
+                },
+                "required": ["apk_file"]
+            }
+        },
+        {
+            "name": "info",
+            "description": "Read package name, version, SDK levels, permissions, components, native ABIs, DEX files, signing certificates and a size breakdown of an APK. Fast: reads the ZIP directly without Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK file"
+                    }
+                },
+                "required": ["apk_file"]
+            }
+        },
+        {
+            "name": "resource",
+            "description": "Look up a resource in an APK's resources.arsc by name (string/app_name, R.string.app_name) or ID (0x7f050002). Returns its value in every configuration, or only the value for the given device qualifiers, e.g. \"what is R.string.app_name in de-rDE\". Fast: needs no Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK file"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Resource name or ID"
+                    },
+                    "config": {
+                        "type": "string",
+                        "description": "Device qualifiers such as de-rDE or night-xxhdpi; the best matching value is returned, falling back to less specific configurations"
+                    }
+                },
+                "required": ["apk_file", "name"]
+            }
+        },
+        {
+            "name": "classes",
+            "description": "List the classes defined in the DEX files of an APK with their DEX file, access flags, superclass, interfaces and member counts. Use filter to narrow down to a package. Fast: reads the DEX files directly without Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK or DEX file"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only list classes whose Java name contains this text, e.g. com.example.net"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most classes to return (default: 1000); total reports how many matched"
+                    }
+                },
+                "required": ["apk_file"]
+            }
+        },
+        {
+            "name": "methods",
+            "description": "Show one class from the DEX files of an APK: its declaration, source file, and every field and method with access flags, Java types and descriptors. Fast: reads the DEX files directly without Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK or DEX file"
+                    },
+                    "class": {
+                        "type": "string",
+                        "description": "Java name (com.example.Foo$Inner) or type descriptor (Lcom/example/Foo$Inner;) of the class"
+                    }
+                },
+                "required": ["apk_file", "class"]
+            }
+        },
+        {
+            "name": "xref",
+            "description": "Find where a method, field or string is used in the DEX bytecode of an APK: the callers of a method, the methods a method calls, the instructions reading or writing a field, or the instructions loading a string. Each usage names the method and code offset it is in and, when output_dir holds an unpack of the APK, the smali and decompiled source file and line. Fast: reads the DEX files directly without Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK or DEX file"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["callers", "callees", "field", "string"],
+                        "description": "callers or callees of a method, reads and writes of a field, or uses of strings containing the target text"
+                    },
+                    "target": {
+                        "type": "string",
+                        "description": "Method or field as javax.crypto.Cipher.getInstance, Cipher.getInstance or Ljavax/crypto/Cipher;->getInstance, optionally followed by a descriptor such as (Ljava/lang/String;); or the text to find in strings"
+                    },
+                    "output_dir": {
+                        "type": "string",
+                        "description": "Output directory of the unpack tool, to map usages to smali and source lines (default: the APK path without its extension, if it exists)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most usages to return (default: 1000); total reports how many were found"
+                    }
+                },
+                "required": ["apk_file", "kind", "target"]
+            }
+        },
+        {
+            "name": "search",
+            "description": "Search a directory produced by the unpack tool for a regular expression, across the decompiled Java sources, smali, manifest and XML resources, string resources and text assets. Each match has its origin, file, line and text; smali matches also name the decompiled source of their class, and string resource matches the resource they belong to.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "output_dir": {
+                        "type": "string",
+                        "description": "Output directory of the unpack tool"
+                    },
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression (Rust regex syntax) to search for"
+                    },
+                    "ignore_case": {
+                        "type": "boolean",
+                        "description": "Match case-insensitively (default: false)"
+                    },
+                    "fixed_strings": {
+                        "type": "boolean",
+                        "description": "Treat the pattern as plain text (default: false)"
+                    },
+                    "origins": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["java", "smali", "xml", "strings", "assets"] },
+                        "description": "Only search these origins (default: all)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most matches to return (default: 1000); total reports how many were found"
+                    }
+                },
+                "required": ["output_dir", "pattern"]
+            }
+        },
+        {
+            "name": "diff",
+            "description": "Compare two versions of an app, given as two APK files or two directories produced by the unpack tool. Reports manifest changes (version, SDK levels, permissions, components), added, removed and changed classes and methods, resource values and files, native libraries, assets and signing certificates. Fast: reads DEX and resources directly without Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "old": {
+                        "type": "string",
+                        "description": "Path to the old APK file or unpacked directory"
+                    },
+                    "new": {
+                        "type": "string",
+                        "description": "Path to the new APK file or unpacked directory"
+                    }
+                },
+                "required": ["old", "new"]
+            }
+        },
+        {
+            "name": "verify",
+            "description": "Verify the v1, v2, v3 and v3.1 signatures of an APK (and v4 from <apk_file>.idsig if present). Reports whether each scheme verified, why not, and the subject, issuer, SHA-256 fingerprint and validity of each signer's certificate. Fast: needs no Java.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "apk_file": {
+                        "type": "string",
+                        "description": "Path to the APK file"
+                    }
+                },
+                "required": ["apk_file"]
+            }
+        },
+        {
+            "name": "pack",
+            "description": "Build an APK from a directory previously produced by the unpack tool and sign it. Without a keystore or key/certificate, an auto-generated debug key is used.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "unpacked_dir": {
+                        "type": "string",
+                        "description": "Path to the unpacked directory (the one containing apktool.yml, or its parent)"
+                    },
+                    "output_apk": {
+                        "type": "string",
+                        "description": "Output APK file path; for a directory unpacked from a split container, an .apks, .xapk or .apkm file or a directory; for an unpacked app bundle, an .aab file"
+                    },
+                    "sign": {
+                        "type": "boolean",
+                        "description": "Sign the built APK (default: true)"
+                    },
+                    "keystore": {
+                        "type": "string",
+                        "description": "PKCS#12 keystore to sign with"
+                    },
+                    "keystore_password": {
+                        "type": "string",
+                        "description": "Keystore password"
+                    },
+                    "key_alias": {
+                        "type": "string",
+                        "description": "Alias of the key in the keystore (default: the first private key)"
+                    },
+                    "key": {
+                        "type": "string",
+                        "description": "Private key file (PKCS#8 or PKCS#1, PEM or DER), used together with cert"
+                    },
+                    "cert": {
+                        "type": "string",
+                        "description": "X.509 certificate file (PEM or DER), used together with key"
+                    },
+                    "schemes": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["v1", "v2", "v3"] },
+                        "description": "Signature schemes to apply (default: all)"
+                    }
+                },
+                "required": ["unpacked_dir", "output_apk"]
+            }
+        }
+    ])
+}
+
+#[derive(Deserialize)]
+struct UnpackArgs {
+    apk_file: String,
+    decompiler: Option<Decompiler>,
+    fallback_decompiler: Option<Decompiler>,
+    fallback: Option<bool>,
+    cache: Option<bool>,
+    kotlin: Option<bool>,
+    mapping: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    existing_output: ExistingOutput,
+    #[serde(default)]
+    stages: UnpackStages,
+}
+
+#[derive(Deserialize)]
+struct InfoArgs {
+    apk_file: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ResourceArgs {
+    apk_file: PathBuf,
+    name: String,
+    config: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClassesArgs {
+    apk_file: PathBuf,
+    #[serde(default)]
+    filter: String,
+    #[serde(default = "default_class_limit")]
+    limit: usize,
+}
+
+fn default_class_limit() -> usize {
+    1000
+}
+
+#[derive(Deserialize)]
+struct MethodsArgs {
+    apk_file: PathBuf,
+    class: String,
+}
+
+#[derive(Deserialize)]
+struct XrefArgs {
+    apk_file: PathBuf,
+    #[serde(flatten)]
+    query: dex::XrefQuery,
+    output_dir: Option<PathBuf>,
+    #[serde(default = "default_xref_limit")]
+    limit: usize,
+}
+
+fn default_xref_limit() -> usize {
+    1000
+}
+
+#[derive(Deserialize)]
+struct SearchArgs {
+    output_dir: PathBuf,
+    pattern: String,
+    #[serde(default)]
+    ignore_case: bool,
+    #[serde(default)]
+    fixed_strings: bool,
+    #[serde(default)]
+    origins: Vec<apk::search::Origin>,
+    #[serde(default = "default_search_limit")]
+    limit: usize,
+}
+
+fn default_search_limit() -> usize {
+    1000
+}
+
+#[derive(Deserialize)]
+struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct VerifyArgs {
+    apk_file: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct PackArgs {
+    unpacked_dir: String,
+    output_apk: String,
+    #[serde(default = "default_sign")]
+    sign: bool,
+    keystore: Option<PathBuf>,
+    keystore_password: Option<String>,
+    key_alias: Option<String>,
+    key: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    schemes: Option<Vec<String>>,
+}
+
+fn default_sign() -> bool {
+    true
+}
+
+/// Returns `None` if no tool with the given name exists
+pub async fn call(config: Config, asset_manager: AssetManager, name: &str, arguments: Value, progress: Option<ProgressHandler>) -> Option<Value> {
+    let result = match name {
+        "unpack" => unpack(config, asset_manager, arguments, progress).await,
+        "info" => info(arguments),
+        "resource" => resource(arguments),
+        "classes" => classes(arguments),
+        "methods" => methods(arguments),
+        "xref" => xref(arguments),
+        "search" => search(arguments),
+        "diff" => diff(arguments),
+        "verify" => verify(arguments),
+        "pack" => pack(config, asset_manager, arguments, progress).await,
+        _ => return None,
+    };
+
+    Some(tool_result(result))
+}
+
+async fn unpack(mut config: Config, asset_manager: AssetManager, arguments: Value, progress: Option<ProgressHandler>) -> Result<Value> {
+    let args: UnpackArgs = serde_json::from_value(arguments)?;
+    if let Some(decompiler) = args.decompiler {
+        config.decompiler = decompiler;
+    }
+    if args.fallback_decompiler.is_some() {
+        config.fallback_decompiler = args.fallback_decompiler;
+    }
+    if let Some(fallback) = args.fallback {
+        config.fallback = fallback;
+    }
+    if let Some(cache) = args.cache {
+        config.cache = cache;
+    }
+    if let Some(kotlin) = args.kotlin {
+        config.kotlin = kotlin;
+    }
+
+    let extractor = apk::Extractor::new(config, asset_manager)
+        .with_output_dir(args.output_dir)
+        .with_existing_output(args.existing_output)
+        .with_stages(args.stages)
+        .with_mapping(args.mapping)
+        .with_progress(progress);
+    let report = extractor.unpack(&args.apk_file).await?;
+    Ok(serde_json::to_value(report)?)
+}
+
+fn info(arguments: Value) -> Result<Value> {
+    let args: InfoArgs = serde_json::from_value(arguments)?;
+    let info = apk::ApkInfo::read(&args.apk_file)?;
+    Ok(serde_json::to_value(info)?)
+}
+
+fn resource(arguments: Value) -> Result<Value> {
+    let args: ResourceArgs = serde_json::from_value(arguments)?;
+    let table = arsc::ResourceTable::from_apk(&args.apk_file)?;
+    let resource = table
+        .lookup(&args.name, args.config.as_deref())
+        .ok_or_else(|| anyhow::anyhow!("Resource '{}' not found in '{}'", args.name, args.apk_file.display()))?;
+    Ok(serde_json::to_value(resource)?)
+}
+
+fn classes(arguments: Value) -> Result<Value> {
+    let args: ClassesArgs = serde_json::from_value(arguments)?;
+    let index = dex::DexIndex::from_path(&args.apk_file)?;
+    let matching: Vec<_> = index.matching(&args.filter).collect();
+    let listings: Vec<_> = matching.iter().take(args.limit).map(|class| class.listing()).collect();
+    Ok(json!({ "total": matching.len(), "classes": listings }))
+}
+
+fn methods(arguments: Value) -> Result<Value> {
+    let args: MethodsArgs = serde_json::from_value(arguments)?;
+    let index = dex::DexIndex::from_path(&args.apk_file)?;
+    let class = index.class(&args.class).ok_or_else(|| anyhow::anyhow!("Class '{}' not found in '{}'", args.class, args.apk_file.display()))?;
+    Ok(serde_json::to_value(class)?)
+}
+
+fn xref(arguments: Value) -> Result<Value> {
+    let args: XrefArgs = serde_json::from_value(arguments)?;
+    let xrefs = dex::Xrefs::from_path(&args.apk_file)?;
+    let mut report = xrefs.search(&args.query)?;
+    let total = report.hits.len();
+    report.hits.truncate(args.limit);
+    if let Some(dir) = args.output_dir.or_else(|| Some(args.apk_file.with_extension("")).filter(|dir| dir.is_dir())) {
+        report.locate(&dir);
+    }
+    Ok(json!({ "total": total, "query": report.query, "hits": report.hits }))
+}
+
+fn search(arguments: Value) -> Result<Value> {
+    let args: SearchArgs = serde_json::from_value(arguments)?;
+    let mut report = apk::SearchQuery::new(args.pattern)
+        .with_ignore_case(args.ignore_case)
+        .with_fixed_strings(args.fixed_strings)
+        .with_origins(args.origins)
+        .run(&args.output_dir)?;
+    let total = report.hits.len();
+    report.hits.truncate(args.limit);
+    Ok(json!({ "total": total, "files": report.files, "hits": report.hits }))
+}
+
+fn diff(arguments: Value) -> Result<Value> {
+    let args: DiffArgs = serde_json::from_value(arguments)?;
+    let diff = apk::ApkDiff::compare(&args.old, &args.new)?;
+    Ok(serde_json::to_value(diff)?)
+}
+
+/// A failed verification is a result, not a tool error: the report says what is wrong
+fn verify(arguments: Value) -> Result<Value> {
+    let args: VerifyArgs = serde_json::from_value(arguments)?;
+    let report = signing::verify_file(&args.apk_file)?;
+    Ok(serde_json::to_value(report)?)
+}
+
+async fn pack(config: Config, asset_manager: AssetManager, arguments: Value, progress: Option<ProgressHandler>) -> Result<Value> {
+    let args: PackArgs = serde_json::from_value(arguments)?;
+
+    let signing = if args.sign {
+        let key = KeySource::select(args.keystore, args.keystore_password, args.key_alias, args.key, args.cert)?;
+        Some(match args.schemes {
+            Some(schemes) => SignOptions::with_schemes(key, &schemes)?,
+            None => SignOptions { key, ..SignOptions::default() },
+        })
+    } else {
+        None
+    };
+
+    let builder = apk::Builder::new(config, asset_manager)
+        .with_signing(signing)
+        .with_progress(progress);
+    let report = builder.pack(&args.unpacked_dir, &args.output_apk).await?;
+    Ok(serde_json::to_value(report)?)
+}
+
+/// Wrap a tool outcome into an MCP `CallToolResult`
+fn tool_result(result: Result<Value>) -> Value {
+    match result {
+        Ok(value) => json!({
+            "content": [{
+                "type": "text",
+                "text": serde_json::to_string_pretty(&value).unwrap_or_default()
+            }],
+            "structuredContent": value,
+            "isError": false
+        }),
+        Err(e) => {
+            let message = format!("{:#}", e);
+            json!({
+                "content": [{ "type": "text", "text": message }],
+                "structuredContent": { "error": message },
+                "isError": true
+            })
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! APK Signing Block carrying v2 and v3 signatures
+//!
+//! See <https://source.android.com/docs/security/features/apksigning/v2>.
+
+use super::SigningKey;
+use crate::manifest::chunk::{read_u16, read_u32, read_u64};
+use crate::Result;
+use sha2::{Digest, Sha256};
+
+pub const APK_SIGNATURE_SCHEME_V2_BLOCK_ID: u32 = 0x7109_871a;
+pub const APK_SIGNATURE_SCHEME_V3_BLOCK_ID: u32 = 0xf053_68c0;
+/// v3.1, for key rotation that only applies from Android 13 (API 33) on
+pub const APK_SIGNATURE_SCHEME_V31_BLOCK_ID: u32 = 0x1b93_ad61;
+pub const APK_SIG_BLOCK_MAGIC: &[u8; 16] = b"APK Sig Block 42";
+
+const SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256: u32 = 0x0103;
+const STRIPPING_PROTECTION_ATTR_ID: u32 = 0xbeef_f00d;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// v3 signatures are only checked from Android 9 (API 28) on
+const V3_MIN_SDK: u32 = 28;
+const V3_MAX_SDK: u32 = i32::MAX as u32;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const EOCD_MIN_SIZE: usize = 22;
+
+/// Byte ranges of a ZIP file as seen by the v2+ schemes
+pub struct ZipSections {
+    /// End of the last local entry; an existing signing block starts here
+    pub entries_end: usize,
+    pub central_directory_offset: usize,
+    pub eocd_offset: usize,
+}
+
+/// Locate the central directory and EOCD record, skipping over any existing signing block
+pub fn zip_sections(apk: &[u8]) -> Result<ZipSections> {
+    let eocd_offset = find_eocd(apk)
+        .ok_or_else(|| anyhow::anyhow!("Not a ZIP file: end of central directory record not found"))?;
+
+    let cd_size = read_u32(apk, eocd_offset + 12)? as usize;
+    let cd_offset = read_u32(apk, eocd_offset + 16)? as usize;
+
+    if cd_offset == 0xffff_ffff {
+        anyhow::bail!("ZIP64 archives are not supported");
+    }
+    if cd_offset + cd_size != eocd_offset {
+        anyhow::bail!("Malformed ZIP: central directory does not end at the EOCD record");
+    }
+
+    let mut entries_end = cd_offset;
+    if cd_offset >= 32 && &apk[cd_offset - 16..cd_offset] == APK_SIG_BLOCK_MAGIC {
+        // The size field comes straight from the file and may be anything
+        let block_size = read_u64(apk, cd_offset - 24)?;
+        let start = block_size
+            .checked_add(8)
+            .and_then(|len| (cd_offset as u64).checked_sub(len))
+            .ok_or_else(|| anyhow::anyhow!("Malformed APK Signing Block"))? as usize;
+        if read_u64(apk, start)? != block_size {
+            anyhow::bail!("Malformed APK Signing Block: size fields disagree");
+        }
+        entries_end = start;
+    }
+
+    Ok(ZipSections {
+        entries_end,
+        central_directory_offset: cd_offset,
+        eocd_offset,
+    })
+}
+
+/// `(ID, value)` pairs of the APK Signing Block; empty if the APK has none
+pub fn read_pairs(apk: &[u8]) -> Result<Vec<(u32, &[u8])>> {
+    let sections = zip_sections(apk)?;
+    if sections.entries_end == sections.central_directory_offset {
+        return Ok(Vec::new());
+    }
+
+    // Between the leading size field and the trailing size field plus magic
+    let end = sections.central_directory_offset - 24;
+    let mut pos = sections.entries_end + 8;
+    let mut pairs = Vec::new();
+    while pos < end {
+        if end - pos < 12 {
+            anyhow::bail!("Malformed APK Signing Block: truncated ID-value pair");
+        }
+        let len = read_u64(apk, pos)? as usize;
+        if len < 4 || len > end - pos - 8 {
+            anyhow::bail!("Malformed APK Signing Block: ID-value pair runs past the block");
+        }
+        pairs.push((read_u32(apk, pos + 8)?, &apk[pos + 12..pos + 8 + len]));
+        pos += 8 + len;
+    }
+
+    Ok(pairs)
+}
+
+/// The values of a sequence of length-prefixed values
+pub fn length_prefixed_items(mut data: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut items = Vec::new();
+    while !data.is_empty() {
+        let (item, rest) = split_length_prefixed(data)?;
+        items.push(item);
+        data = rest;
+    }
+    Ok(items)
+}
+
+/// The length-prefixed value at the start of `data`, and what follows it
+pub fn split_length_prefixed(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    if data.len() < 4 {
+        anyhow::bail!("Malformed APK Signing Block: truncated length prefix");
+    }
+    let len = read_u32(data, 0)? as usize;
+    if len > data.len() - 4 {
+        anyhow::bail!("Malformed APK Signing Block: value runs past its parent");
+    }
+    Ok((&data[4..4 + len], &data[4 + len..]))
+}
+
+fn find_eocd(apk: &[u8]) -> Option<usize> {
+    if apk.len() < EOCD_MIN_SIZE {
+        return None;
+    }
+
+    let last = apk.len() - EOCD_MIN_SIZE;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last).rev().find(|&pos| {
+        read_u32(apk, pos).ok() == Some(EOCD_SIGNATURE)
+            && read_u16(apk, pos + 20).ok().map(usize::from) == Some(apk.len() - pos - EOCD_MIN_SIZE)
+    })
+}
+
+/// Replace the signing block of `apk` with one carrying fresh v2 and/or v3 signatures
+pub fn sign(apk: &[u8], key: &SigningKey, v2: bool, v3: bool) -> Result<Vec<u8>> {
+    let sections = zip_sections(apk)?;
+    let (entries, central_directory, mut eocd) = signed_sections(apk, &sections);
+    let digest = content_digest::<Sha256>(&[entries, central_directory, &eocd]);
+
+    let public_key = key.public_key_info()?;
+    let mut pairs = Vec::new();
+
+    if v2 {
+        let attributes = if v3 {
+            vec![[STRIPPING_PROTECTION_ATTR_ID.to_le_bytes(), 3u32.to_le_bytes()].concat()]
+        } else {
+            Vec::new()
+        };
+
+        let signed_data = [
+            length_prefixed_sequence(&[digest_record(&digest)]),
+            length_prefixed_sequence(&[key.certificate().to_vec()]),
+            length_prefixed_sequence(&attributes),
+        ]
+        .concat();
+
+        let signer = [
+            length_prefixed(&signed_data),
+            length_prefixed_sequence(&[signature_record(key, &signed_data)?]),
+            length_prefixed(&public_key),
+        ]
+        .concat();
+
+        pairs.push((APK_SIGNATURE_SCHEME_V2_BLOCK_ID, length_prefixed_sequence(&[signer])));
+    }
+
+    if v3 {
+        let signed_data = [
+            length_prefixed_sequence(&[digest_record(&digest)]),
+            length_prefixed_sequence(&[key.certificate().to_vec()]),
+            V3_MIN_SDK.to_le_bytes().to_vec(),
+            V3_MAX_SDK.to_le_bytes().to_vec(),
+            length_prefixed_sequence(&[]),
+        ]
+        .concat();
+
+        let signer = [
+            length_prefixed(&signed_data),
+            V3_MIN_SDK.to_le_bytes().to_vec(),
+            V3_MAX_SDK.to_le_bytes().to_vec(),
+            length_prefixed_sequence(&[signature_record(key, &signed_data)?]),
+            length_prefixed(&public_key),
+        ]
+        .concat();
+
+        pairs.push((APK_SIGNATURE_SCHEME_V3_BLOCK_ID, length_prefixed_sequence(&[signer])));
+    }
+
+    let block = signing_block(&pairs);
+    write_u32(&mut eocd, 16, (sections.entries_end + block.len()) as u32);
+
+    let mut signed = Vec::with_capacity(entries.len() + block.len() + central_directory.len() + eocd.len());
+    signed.extend_from_slice(entries);
+    signed.extend_from_slice(&block);
+    signed.extend_from_slice(central_directory);
+    signed.extend_from_slice(&eocd);
+    Ok(signed)
+}
+
+/// Entries, central directory and EOCD, the parts of the ZIP that v2+ signatures cover
+///
+/// The EOCD is digested as if the central directory started right where the signing block goes.
+pub fn signed_sections<'a>(apk: &'a [u8], sections: &ZipSections) -> (&'a [u8], &'a [u8], Vec<u8>) {
+    let entries = &apk[..sections.entries_end];
+    let central_directory = &apk[sections.central_directory_offset..sections.eocd_offset];
+    let mut eocd = apk[sections.eocd_offset..].to_vec();
+    write_u32(&mut eocd, 16, sections.entries_end as u32);
+    (entries, central_directory, eocd)
+}
+
+/// Chunked digest over the three ZIP sections, with SHA-256 or SHA-512
+pub fn content_digest<D: Digest>(sections: &[&[u8]]) -> Vec<u8> {
+    let mut chunk_digests = Vec::new();
+    let mut chunk_count = 0u32;
+
+    for section in sections {
+        for chunk in section.chunks(CHUNK_SIZE) {
+            let mut hasher = D::new();
+            hasher.update([0xa5]);
+            hasher.update((chunk.len() as u32).to_le_bytes());
+            hasher.update(chunk);
+            chunk_digests.extend_from_slice(&hasher.finalize());
+            chunk_count += 1;
+        }
+    }
+
+    let mut hasher = D::new();
+    hasher.update([0x5a]);
+    hasher.update(chunk_count.to_le_bytes());
+    hasher.update(&chunk_digests);
+    hasher.finalize().to_vec()
+}
+
+fn digest_record(digest: &[u8]) -> Vec<u8> {
+    [SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256.to_le_bytes().to_vec(), length_prefixed(digest)].concat()
+}
+
+fn signature_record(key: &SigningKey, signed_data: &[u8]) -> Result<Vec<u8>> {
+    let signature = key.sign_sha256(signed_data)?;
+    Ok([SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256.to_le_bytes().to_vec(), length_prefixed(&signature)].concat())
+}
+
+fn signing_block(pairs: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (id, value) in pairs {
+        body.extend_from_slice(&((value.len() + 4) as u64).to_le_bytes());
+        body.extend_from_slice(&id.to_le_bytes());
+        body.extend_from_slice(value);
+    }
+
+    // The size fields count everything except the leading size field itself
+    let block_size = (body.len() + 8 + APK_SIG_BLOCK_MAGIC.len()) as u64;
+
+    let mut block = Vec::with_capacity(body.len() + 32);
+    block.extend_from_slice(&block_size.to_le_bytes());
+    block.extend_from_slice(&body);
+    block.extend_from_slice(&block_size.to_le_bytes());
+    block.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+    block
+}
+
+fn length_prefixed(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 4);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+fn length_prefixed_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    let content: Vec<u8> = items.iter().flat_map(|item| length_prefixed(item)).collect();
+    length_prefixed(&content)
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
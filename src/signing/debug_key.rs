@@ -0,0 +1,83 @@
+use super::{der, rsa_sha256, SigningKey};
+use crate::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::RsaPrivateKey;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const KEY_FILE: &str = "debug-key.pem";
+const CERT_FILE: &str = "debug-cert.pem";
+
+/// Same lifetime as the Android SDK debug keystore
+const VALIDITY_SECS: u64 = 30 * 365 * 24 * 60 * 60;
+
+/// Load the debug key from `keys_dir`, generating it on first use
+pub fn load_or_create(keys_dir: &Path) -> Result<SigningKey> {
+    let key_path = keys_dir.join(KEY_FILE);
+    let cert_path = keys_dir.join(CERT_FILE);
+
+    if key_path.exists() && cert_path.exists() {
+        return SigningKey::from_files(&key_path, &cert_path);
+    }
+
+    eprintln!("Generating debug signing key in '{}'...", keys_dir.display());
+    fs::create_dir_all(keys_dir)?;
+
+    let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 2048)?;
+    let certificate = self_signed_certificate(&private_key)?;
+
+    fs::write(&key_path, private_key.to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
+    fs::write(&cert_path, pem_encode("CERTIFICATE", &certificate))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    SigningKey::new(private_key, certificate)
+}
+
+fn self_signed_certificate(private_key: &RsaPrivateKey) -> Result<Vec<u8>> {
+    let public_key = private_key.to_public_key().to_public_key_der()?;
+
+    let name = der::sequence(&[
+        der::set(&[der::sequence(&[der::oid(der::OID_COUNTRY), der::printable_string("US")])]),
+        der::set(&[der::sequence(&[der::oid(der::OID_ORGANIZATION), der::printable_string("Android")])]),
+        der::set(&[der::sequence(&[der::oid(der::OID_COMMON_NAME), der::printable_string("Android Debug")])]),
+    ]);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let serial = rand::random::<u64>() | 1;
+
+    let tbs_certificate = der::sequence(&[
+        der::context(0, &der::small_integer(2)),
+        der::small_integer(serial),
+        der::algorithm(der::OID_SHA256_WITH_RSA),
+        name.clone(),
+        der::sequence(&[der::time(now), der::time(now + VALIDITY_SECS)]),
+        name,
+        public_key.as_bytes().to_vec(),
+    ]);
+
+    let signature = rsa_sha256(private_key, &tbs_certificate)?;
+
+    Ok(der::sequence(&[
+        tbs_certificate,
+        der::algorithm(der::OID_SHA256_WITH_RSA),
+        der::bit_string(&signature),
+    ]))
+}
+
+fn pem_encode(label: &str, data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
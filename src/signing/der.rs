@@ -0,0 +1,143 @@
+//! Minimal DER encoder covering the ASN.1 types used by X.509 and PKCS#7, and a reader for walking them
+
+pub const OID_RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 1];
+pub const OID_SHA256_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 11];
+pub const OID_SHA256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+pub const OID_PKCS7_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 1];
+pub const OID_PKCS7_SIGNED_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 2];
+pub const OID_COMMON_NAME: &[u64] = &[2, 5, 4, 3];
+pub const OID_COUNTRY: &[u64] = &[2, 5, 4, 6];
+pub const OID_ORGANIZATION: &[u64] = &[2, 5, 4, 10];
+
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x30, &items.concat())
+}
+
+pub fn set(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x31, &items.concat())
+}
+
+/// Context-specific constructed tag, e.g. `[0]`
+pub fn context(number: u8, content: &[u8]) -> Vec<u8> {
+    tlv(0xa0 | number, content)
+}
+
+pub fn null() -> Vec<u8> {
+    vec![0x05, 0x00]
+}
+
+/// INTEGER from unsigned big-endian magnitude bytes
+pub fn integer(magnitude: &[u8]) -> Vec<u8> {
+    let skip = magnitude.iter().take_while(|&&b| b == 0).count();
+    let trimmed = &magnitude[skip.min(magnitude.len().saturating_sub(1))..];
+
+    let mut content = Vec::with_capacity(trimmed.len() + 1);
+    if trimmed.is_empty() || trimmed[0] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(trimmed);
+    tlv(0x02, &content)
+}
+
+pub fn small_integer(value: u64) -> Vec<u8> {
+    integer(&value.to_be_bytes())
+}
+
+pub fn oid(arcs: &[u64]) -> Vec<u8> {
+    let mut content = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        chunk.reverse();
+        content.extend_from_slice(&chunk);
+    }
+    tlv(0x06, &content)
+}
+
+pub fn octet_string(content: &[u8]) -> Vec<u8> {
+    tlv(0x04, content)
+}
+
+pub fn bit_string(content: &[u8]) -> Vec<u8> {
+    let mut bits = Vec::with_capacity(content.len() + 1);
+    bits.push(0);
+    bits.extend_from_slice(content);
+    tlv(0x03, &bits)
+}
+
+pub fn printable_string(value: &str) -> Vec<u8> {
+    tlv(0x13, value.as_bytes())
+}
+
+pub fn algorithm(arcs: &[u64]) -> Vec<u8> {
+    sequence(&[oid(arcs), null()])
+}
+
+/// The element at the start of `data`: its tag, its content and everything after it
+pub fn read(data: &[u8]) -> crate::Result<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first().ok_or_else(|| anyhow::anyhow!("Truncated DER element"))?;
+    let (&first, rest) = rest.split_first().ok_or_else(|| anyhow::anyhow!("Truncated DER length"))?;
+
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 8 || count > rest.len() {
+            anyhow::bail!("Unsupported DER length encoding");
+        }
+        let len = rest[..count].iter().fold(0usize, |len, &b| (len << 8) | b as usize);
+        (len, &rest[count..])
+    };
+
+    if len > rest.len() {
+        anyhow::bail!("DER element runs past the end of its parent");
+    }
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+/// X.509 time: UTCTime up to 2049, GeneralizedTime afterwards (RFC 5280 4.1.2.5)
+pub fn time(unix_seconds: u64) -> Vec<u8> {
+    let days = (unix_seconds / 86_400) as i64;
+    let secs = unix_seconds % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    let clock = format!("{:02}{:02}{:02}{:02}{:02}Z", month, day, secs / 3600, secs / 60 % 60, secs % 60);
+
+    if year < 2050 {
+        tlv(0x17, format!("{:02}{}", year % 100, clock).as_bytes())
+    } else {
+        tlv(0x18, format!("{:04}{}", year, clock).as_bytes())
+    }
+}
+
+/// Gregorian date for a day count since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
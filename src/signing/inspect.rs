@@ -0,0 +1,146 @@
+//! Which signature schemes an APK carries and the certificates in them
+//!
+//! Nothing is verified here: the certificates are read as they are found.
+
+use super::block::{self, APK_SIGNATURE_SCHEME_V2_BLOCK_ID, APK_SIGNATURE_SCHEME_V31_BLOCK_ID, APK_SIGNATURE_SCHEME_V3_BLOCK_ID};
+use super::der;
+use crate::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// Signature schemes present in an APK and the distinct certificates they carry
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SignatureInfo {
+    /// `v1`, `v2`, `v3` and `v3.1`, for the schemes that are present
+    pub schemes: Vec<String>,
+    pub certificates: Vec<CertificateInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Serial number as colon-separated hex
+    pub serial: String,
+    /// SHA-256 of the DER certificate as lowercase hex, as `apksigner` prints it
+    pub sha256: String,
+    pub not_before: String,
+    pub not_after: String,
+}
+
+impl CertificateInfo {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)
+            .map_err(|e| anyhow::anyhow!("Invalid certificate: {}", e))?;
+
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            serial: cert.raw_serial_as_string(),
+            sha256: Sha256::digest(der).iter().map(|b| format!("{:02x}", b)).collect(),
+            not_before: cert.validity().not_before.to_string(),
+            not_after: cert.validity().not_after.to_string(),
+        })
+    }
+}
+
+/// Read the v1 signature files and the APK Signing Block of `apk`
+pub fn inspect(apk: &[u8]) -> Result<SignatureInfo> {
+    let mut info = SignatureInfo::default();
+    let mut certificates: Vec<Vec<u8>> = Vec::new();
+
+    let v1 = v1_certificates(apk)?;
+    if !v1.is_empty() {
+        info.schemes.push("v1".to_string());
+        certificates.extend(v1);
+    }
+
+    for (id, value) in block::read_pairs(apk)? {
+        let scheme = match id {
+            APK_SIGNATURE_SCHEME_V2_BLOCK_ID => "v2",
+            APK_SIGNATURE_SCHEME_V3_BLOCK_ID => "v3",
+            APK_SIGNATURE_SCHEME_V31_BLOCK_ID => "v3.1",
+            // Verity padding, dependency info, source stamps, …
+            _ => continue,
+        };
+        info.schemes.push(scheme.to_string());
+        certificates.extend(block_certificates(value)?);
+    }
+
+    for der in certificates {
+        let certificate = CertificateInfo::from_der(&der)?;
+        if !info.certificates.iter().any(|known| known.sha256 == certificate.sha256) {
+            info.certificates.push(certificate);
+        }
+    }
+
+    Ok(info)
+}
+
+/// Whether a `META-INF/` entry is the PKCS#7 signature block of a v1 signer
+pub fn is_signature_block(name: &str) -> bool {
+    name.strip_prefix("META-INF/")
+        .is_some_and(|file| !file.contains('/') && [".RSA", ".DSA", ".EC"].iter().any(|ext| file.ends_with(ext)))
+}
+
+fn v1_certificates(apk: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+    let names: Vec<String> = archive.file_names().filter(|name| is_signature_block(name)).map(str::to_string).collect();
+
+    let mut certificates = Vec::new();
+    for name in names {
+        let mut data = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut data)?;
+        certificates.extend(pkcs7_certificates(&data).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?);
+    }
+    Ok(certificates)
+}
+
+/// Certificates of a PKCS#7 `ContentInfo` holding `SignedData`, in DER
+pub fn pkcs7_certificates(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let (_, content_info, _) = der::read(data)?;
+    let (_, _content_type, rest) = der::read(content_info)?;
+    let (_, explicit, _) = der::read(rest)?;
+    let (_, mut signed_data, _) = der::read(explicit)?;
+
+    // version, digestAlgorithms and encapContentInfo come before the optional [0] certificates
+    while !signed_data.is_empty() {
+        let (tag, content, rest) = der::read(signed_data)?;
+        if tag == 0xa0 {
+            return der_elements(content);
+        }
+        signed_data = rest;
+    }
+
+    Ok(Vec::new())
+}
+
+/// Each element of `data` with its tag and length, as it was encoded
+fn der_elements(mut data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut elements = Vec::new();
+    while !data.is_empty() {
+        let (_, _, rest) = der::read(data)?;
+        elements.push(data[..data.len() - rest.len()].to_vec());
+        data = rest;
+    }
+    Ok(elements)
+}
+
+/// Certificates of every signer in a v2 or v3 block value
+///
+/// Both schemes start a signer with its signed data, which starts with the digests and then the certificates.
+fn block_certificates(value: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut certificates = Vec::new();
+
+    let (signers, _) = block::split_length_prefixed(value)?;
+    for signer in block::length_prefixed_items(signers)? {
+        let (signed_data, _) = block::split_length_prefixed(signer)?;
+        let (_digests, rest) = block::split_length_prefixed(signed_data)?;
+        let (encoded, _) = block::split_length_prefixed(rest)?;
+        certificates.extend(block::length_prefixed_items(encoded)?.into_iter().map(<[u8]>::to_vec));
+    }
+
+    Ok(certificates)
+}
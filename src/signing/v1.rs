@@ -0,0 +1,176 @@
+//! JAR signing (APK Signature Scheme v1)
+//!
+//! Digests use SHA-256, which Android verifies from API level 18 on.
+
+use super::{der, SigningKey};
+use crate::{zipalign, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+const SIGNATURE_FILE_NAME: &str = "META-INF/CERT.SF";
+const SIGNATURE_BLOCK_NAME: &str = "META-INF/CERT.RSA";
+
+/// Rewrite the APK with fresh `MANIFEST.MF`, `CERT.SF` and `CERT.RSA` entries.
+///
+/// `stripping_protection` lists the v2+ schemes that will also be applied; they are
+/// announced in `X-Android-APK-Signed` so the newer signatures cannot be stripped.
+pub fn sign(apk: &[u8], key: &SigningKey, stripping_protection: &[u32]) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+
+    let mut manifest = Vec::new();
+    manifest.extend_from_slice(b"Manifest-Version: 1.0\r\n");
+    manifest.extend_from_slice(b"Created-By: apkext\r\n");
+    manifest.extend_from_slice(b"\r\n");
+
+    let mut signature_file = Vec::new();
+    signature_file.extend_from_slice(b"Signature-Version: 1.0\r\n");
+    signature_file.extend_from_slice(b"Created-By: apkext\r\n");
+
+    let mut entry_sections = Vec::new();
+    let mut kept_entries = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+
+        if is_signature_entry(&name) {
+            continue;
+        }
+        kept_entries.push(i);
+
+        if file.is_dir() {
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+
+        let mut section = attribute_line("Name", &name);
+        section.extend(attribute_line("SHA-256-Digest", &STANDARD.encode(Sha256::digest(&data))));
+        section.extend_from_slice(b"\r\n");
+
+        manifest.extend_from_slice(&section);
+        entry_sections.push((name, section));
+    }
+
+    signature_file.extend(attribute_line("SHA-256-Digest-Manifest", &STANDARD.encode(Sha256::digest(&manifest))));
+    if !stripping_protection.is_empty() {
+        let schemes: Vec<String> = stripping_protection.iter().map(|id| id.to_string()).collect();
+        signature_file.extend(attribute_line("X-Android-APK-Signed", &schemes.join(", ")));
+    }
+    signature_file.extend_from_slice(b"\r\n");
+
+    for (name, section) in &entry_sections {
+        signature_file.extend(attribute_line("Name", name));
+        signature_file.extend(attribute_line("SHA-256-Digest", &STANDARD.encode(Sha256::digest(section))));
+        signature_file.extend_from_slice(b"\r\n");
+    }
+
+    let signature_block = signature_block(&signature_file, key)?;
+
+    // Write the signature files first, like jarsigner does, then copy the original entries
+    let mut writer = ZipWriter::new(Cursor::new(Vec::with_capacity(apk.len() + manifest.len() * 2)));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, content) in [
+        (MANIFEST_NAME, &manifest),
+        (SIGNATURE_FILE_NAME, &signature_file),
+        (SIGNATURE_BLOCK_NAME, &signature_block),
+    ] {
+        writer.start_file(name, options)?;
+        writer.write_all(content)?;
+    }
+
+    zipalign::copy_entries(&mut archive, &mut writer, &kept_entries)?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Rewrite the APK without any JAR signature files, for when v1 signing is disabled
+pub fn strip(apk: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+
+    let mut kept_entries = Vec::new();
+    for i in 0..archive.len() {
+        if !is_signature_entry(archive.by_index_raw(i)?.name()) {
+            kept_entries.push(i);
+        }
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::with_capacity(apk.len())));
+    zipalign::copy_entries(&mut archive, &mut writer, &kept_entries)?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Files in `META-INF/` that belong to a JAR signature and must be regenerated
+pub fn is_signature_entry(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    let Some(file_name) = upper.strip_prefix("META-INF/") else {
+        return false;
+    };
+
+    if file_name.contains('/') {
+        return false;
+    }
+
+    file_name == "MANIFEST.MF"
+        || file_name.starts_with("SIG-")
+        || [".SF", ".RSA", ".DSA", ".EC"].iter().any(|ext| file_name.ends_with(ext))
+}
+
+/// `Key: value` line wrapped at 72 bytes as the JAR specification requires
+fn attribute_line(key: &str, value: &str) -> Vec<u8> {
+    let line = format!("{}: {}", key, value).into_bytes();
+
+    let mut out = Vec::with_capacity(line.len() + line.len() / 70 * 3 + 2);
+    let (first, mut rest) = line.split_at(line.len().min(72));
+    out.extend_from_slice(first);
+    out.extend_from_slice(b"\r\n");
+
+    while !rest.is_empty() {
+        let (chunk, tail) = rest.split_at(rest.len().min(71));
+        out.push(b' ');
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\r\n");
+        rest = tail;
+    }
+
+    out
+}
+
+/// Detached PKCS#7 `SignedData` over the signature file
+fn signature_block(signature_file: &[u8], key: &SigningKey) -> Result<Vec<u8>> {
+    let (_, cert) = x509_parser::parse_x509_certificate(key.certificate())
+        .map_err(|e| anyhow::anyhow!("Invalid signing certificate: {}", e))?;
+
+    let signature = key.sign_sha256(signature_file)?;
+
+    let signer_info = der::sequence(&[
+        der::small_integer(1),
+        der::sequence(&[
+            cert.tbs_certificate.issuer.as_raw().to_vec(),
+            der::tlv(0x02, cert.tbs_certificate.raw_serial()),
+        ]),
+        der::algorithm(der::OID_SHA256),
+        der::algorithm(der::OID_RSA_ENCRYPTION),
+        der::octet_string(&signature),
+    ]);
+
+    let signed_data = der::sequence(&[
+        der::small_integer(1),
+        der::set(&[der::algorithm(der::OID_SHA256)]),
+        der::sequence(&[der::oid(der::OID_PKCS7_DATA)]),
+        der::context(0, key.certificate()),
+        der::set(&[signer_info]),
+    ]);
+
+    Ok(der::sequence(&[
+        der::oid(der::OID_PKCS7_SIGNED_DATA),
+        der::context(0, &signed_data),
+    ]))
+}
@@ -0,0 +1,750 @@
+//! Verification of v1 (JAR), v2, v3, v3.1 and v4 signatures
+//!
+//! Follows what `apksigner verify` checks: every signature over the signed data, the
+//! digests of the APK contents, that the certificate matches the signing key, and that
+//! newer signatures were not stripped. Problems are collected per scheme rather than
+//! returned as errors, so a tampered APK still yields a full report.
+
+use super::block::{self, APK_SIGNATURE_SCHEME_V2_BLOCK_ID, APK_SIGNATURE_SCHEME_V31_BLOCK_ID, APK_SIGNATURE_SCHEME_V3_BLOCK_ID};
+use super::inspect::{is_signature_block, pkcs7_certificates, CertificateInfo};
+use super::{der, v1};
+use crate::manifest::chunk::read_u32;
+use crate::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, Pss, RsaPublicKey};
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+const STRIPPING_PROTECTION_ATTR_ID: u32 = 0xbeef_f00d;
+/// fs-verity block size used by the v4 scheme and the verity content digest
+const VERITY_BLOCK_SIZE: usize = 4096;
+
+const OID_MESSAGE_DIGEST: &[u64] = &[1, 2, 840, 113549, 1, 9, 4];
+const OID_SHA1: &[u64] = &[1, 3, 14, 3, 2, 26];
+const OID_SHA512: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 3];
+const OID_SHA1_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 5];
+const OID_SHA512_WITH_RSA: &[u64] = &[1, 2, 840, 113549, 1, 1, 13];
+const OID_EC_PUBLIC_KEY: &[u64] = &[1, 2, 840, 10045, 2, 1];
+const OID_ECDSA_WITH_SHA1: &[u64] = &[1, 2, 840, 10045, 4, 1];
+const OID_ECDSA_WITH_SHA256: &[u64] = &[1, 2, 840, 10045, 4, 3, 2];
+const OID_ECDSA_WITH_SHA512: &[u64] = &[1, 2, 840, 10045, 4, 3, 4];
+
+/// Outcome of `verify`: one entry per signature scheme found in the APK
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VerifyReport {
+    /// At least one scheme is present, and every scheme present verified
+    pub verified: bool,
+    /// `v1`, `v2`, `v3`, `v3.1` and `v4`, in that order, for the schemes that are present
+    pub schemes: Vec<SchemeReport>,
+    /// Problems that do not belong to one scheme, e.g. a malformed APK Signing Block
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemeReport {
+    pub scheme: String,
+    pub verified: bool,
+    /// Certificate of each signer that was read, whether or not its signature verified
+    pub signers: Vec<CertificateInfo>,
+    /// Why the scheme did not verify: bad signatures, modified entries, stripped schemes, …
+    pub errors: Vec<String>,
+}
+
+impl SchemeReport {
+    fn new(scheme: &str) -> Self {
+        Self {
+            scheme: scheme.to_string(),
+            verified: false,
+            signers: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        self.verified = self.errors.is_empty() && !self.signers.is_empty();
+        self
+    }
+}
+
+/// Verify every signature of `apk`, and of its v4 `.idsig` file if given
+///
+/// Fails only when `apk` is not a ZIP file at all.
+pub fn verify(apk: &[u8], idsig: Option<&[u8]>) -> Result<VerifyReport> {
+    let sections = block::zip_sections(apk)?;
+    let mut report = VerifyReport::default();
+
+    let pairs = block::read_pairs(apk).unwrap_or_else(|e| {
+        report.errors.push(e.to_string());
+        Vec::new()
+    });
+    let present: Vec<u32> = pairs.iter().map(|(id, _)| *id).collect();
+
+    if let Some(v1) = verify_v1(apk, &present)? {
+        report.schemes.push(v1);
+    }
+
+    let (entries, central_directory, eocd) = block::signed_sections(apk, &sections);
+    let mut content = ContentDigests::new([entries, central_directory, &eocd]);
+    let mut apk_digests = Vec::new();
+    for (id, value) in pairs {
+        let scheme = match id {
+            APK_SIGNATURE_SCHEME_V2_BLOCK_ID => "v2",
+            APK_SIGNATURE_SCHEME_V3_BLOCK_ID => "v3",
+            APK_SIGNATURE_SCHEME_V31_BLOCK_ID => "v3.1",
+            _ => continue,
+        };
+        report.schemes.push(verify_block(scheme, value, &present, &mut content, &mut apk_digests));
+    }
+
+    if let Some(idsig) = idsig {
+        report.schemes.push(verify_v4(apk, idsig, &apk_digests));
+    }
+
+    if report.schemes.is_empty() {
+        report.errors.push("APK is not signed".to_string());
+    }
+    report.verified = report.errors.is_empty() && !report.schemes.is_empty() && report.schemes.iter().all(|scheme| scheme.verified);
+    Ok(report)
+}
+
+/// Verify the APK at `apk_path`, together with `<apk_path>.idsig` if that exists
+pub fn verify_file(apk_path: &Path) -> Result<VerifyReport> {
+    let apk = std::fs::read(apk_path)?;
+
+    let mut idsig_path = apk_path.as_os_str().to_owned();
+    idsig_path.push(".idsig");
+    let idsig = match PathBuf::from(idsig_path) {
+        path if path.is_file() => Some(std::fs::read(path)?),
+        _ => None,
+    };
+
+    verify(&apk, idsig.as_deref())
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Verified:     {}", if self.verified { "yes" } else { "NO" })?;
+        for error in &self.errors {
+            writeln!(f, "Error:        {}", error)?;
+        }
+
+        for scheme in &self.schemes {
+            writeln!(f)?;
+            writeln!(f, "{:<13} {}", format!("{}:", scheme.scheme), if scheme.verified { "verified" } else { "FAILED" })?;
+            for signer in &scheme.signers {
+                writeln!(f, "  Signer:     {}", signer.subject)?;
+                writeln!(f, "  Issuer:     {}", signer.issuer)?;
+                writeln!(f, "  SHA-256:    {}", signer.sha256)?;
+                writeln!(f, "  Valid:      {} to {}", signer.not_before, signer.not_after)?;
+            }
+            for error in &scheme.errors {
+                writeln!(f, "  Error:      {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    /// From the prefix of a JAR digest attribute, e.g. `SHA-256` in `SHA-256-Digest`
+    fn from_jar_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" | "SHA-1" => Some(Self::Sha1),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// From an encoded `AlgorithmIdentifier` OID
+    fn from_oid(oid: &[u8]) -> Option<Self> {
+        [(OID_SHA1, Self::Sha1), (der::OID_SHA256, Self::Sha256), (OID_SHA512, Self::Sha512)]
+            .into_iter()
+            .find(|(arcs, _)| der::oid(arcs) == oid)
+            .map(|(_, algorithm)| algorithm)
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1 => Sha1::digest(data).to_vec(),
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SignatureKind {
+    RsaPkcs1,
+    RsaPss,
+    Ecdsa,
+}
+
+/// How the APK contents are digested for the v2+ schemes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ContentDigest {
+    ChunkedSha256,
+    ChunkedSha512,
+    /// fs-verity Merkle tree root over 4 KB blocks, followed by the data size
+    VerityChunkedSha256,
+}
+
+/// Signature algorithm IDs of the APK Signing Block; DSA is not supported
+fn block_algorithm(id: u32) -> Option<(SignatureKind, DigestAlgorithm, ContentDigest)> {
+    use ContentDigest::*;
+    use DigestAlgorithm::*;
+    use SignatureKind::*;
+
+    match id {
+        0x0101 => Some((RsaPss, Sha256, ChunkedSha256)),
+        0x0102 => Some((RsaPss, Sha512, ChunkedSha512)),
+        0x0103 => Some((RsaPkcs1, Sha256, ChunkedSha256)),
+        0x0104 => Some((RsaPkcs1, Sha512, ChunkedSha512)),
+        0x0201 => Some((Ecdsa, Sha256, ChunkedSha256)),
+        0x0202 => Some((Ecdsa, Sha512, ChunkedSha512)),
+        0x0421 => Some((RsaPkcs1, Sha256, VerityChunkedSha256)),
+        0x0423 => Some((Ecdsa, Sha256, VerityChunkedSha256)),
+        _ => None,
+    }
+}
+
+/// Check `signature` over `data` with a DER `SubjectPublicKeyInfo`
+fn verify_signature(public_key: &[u8], kind: SignatureKind, digest: DigestAlgorithm, data: &[u8], signature: &[u8]) -> Result<()> {
+    let hashed = digest.digest(data);
+
+    if kind == SignatureKind::Ecdsa {
+        let key = p256::ecdsa::VerifyingKey::from_public_key_der(public_key)
+            .map_err(|_| anyhow::anyhow!("Only ECDSA keys on the P-256 curve are supported"))?;
+        let signature = p256::ecdsa::Signature::from_der(signature).map_err(|e| anyhow::anyhow!("Malformed ECDSA signature: {}", e))?;
+        return key.verify_prehash(&hashed, &signature).map_err(|_| anyhow::anyhow!("Signature does not verify"));
+    }
+
+    let key = RsaPublicKey::from_public_key_der(public_key).map_err(|e| anyhow::anyhow!("Invalid RSA public key: {}", e))?;
+    let result = match (kind, digest) {
+        (SignatureKind::RsaPss, DigestAlgorithm::Sha1) => key.verify(Pss::new::<Sha1>(), &hashed, signature),
+        (SignatureKind::RsaPss, DigestAlgorithm::Sha256) => key.verify(Pss::new::<Sha256>(), &hashed, signature),
+        (SignatureKind::RsaPss, DigestAlgorithm::Sha512) => key.verify(Pss::new::<Sha512>(), &hashed, signature),
+        (_, DigestAlgorithm::Sha1) => key.verify(Pkcs1v15Sign::new::<Sha1>(), &hashed, signature),
+        (_, DigestAlgorithm::Sha256) => key.verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, signature),
+        (_, DigestAlgorithm::Sha512) => key.verify(Pkcs1v15Sign::new::<Sha512>(), &hashed, signature),
+    };
+    result.map_err(|_| anyhow::anyhow!("Signature does not verify"))
+}
+
+/// The first certificate of a signer must carry the key its signatures verify with
+fn check_certificate_key(certificate: &[u8], public_key: &[u8]) -> Result<()> {
+    let (_, cert) = x509_parser::parse_x509_certificate(certificate)
+        .map_err(|e| anyhow::anyhow!("Invalid certificate: {}", e))?;
+    if cert.tbs_certificate.subject_pki.raw != public_key {
+        anyhow::bail!("Public key does not match the signer's certificate");
+    }
+    Ok(())
+}
+
+/// Content digests of the three ZIP sections, computed on first use
+struct ContentDigests<'a> {
+    sections: [&'a [u8]; 3],
+    computed: Vec<(ContentDigest, Vec<u8>)>,
+}
+
+impl<'a> ContentDigests<'a> {
+    fn new(sections: [&'a [u8]; 3]) -> Self {
+        Self { sections, computed: Vec::new() }
+    }
+
+    fn get(&mut self, kind: ContentDigest) -> &[u8] {
+        let index = match self.computed.iter().position(|(computed, _)| *computed == kind) {
+            Some(index) => index,
+            None => {
+                let digest = match kind {
+                    ContentDigest::ChunkedSha256 => block::content_digest::<Sha256>(&self.sections),
+                    ContentDigest::ChunkedSha512 => block::content_digest::<Sha512>(&self.sections),
+                    ContentDigest::VerityChunkedSha256 => {
+                        let data = self.sections.concat();
+                        let mut digest = merkle_root(&data, &[]);
+                        digest.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                        digest
+                    }
+                };
+                self.computed.push((kind, digest));
+                self.computed.len() - 1
+            }
+        };
+        &self.computed[index].1
+    }
+}
+
+/// fs-verity Merkle tree root of `data` with SHA-256, each block hashed after `salt`
+fn merkle_root(data: &[u8], salt: &[u8]) -> Vec<u8> {
+    let hash_blocks = |level: &[u8]| -> Vec<u8> {
+        level
+            .chunks(VERITY_BLOCK_SIZE)
+            .flat_map(|block| {
+                let mut hasher = Sha256::new();
+                hasher.update(salt);
+                hasher.update(block);
+                // The last block of a level is zero-padded
+                hasher.update(vec![0; VERITY_BLOCK_SIZE - block.len()]);
+                hasher.finalize()
+            })
+            .collect()
+    };
+
+    if data.is_empty() {
+        return vec![0; 32];
+    }
+    let mut level = hash_blocks(data);
+    while level.len() > VERITY_BLOCK_SIZE {
+        level = hash_blocks(&level);
+    }
+    hash_blocks(&level)
+}
+
+/// Verify one signer-sequence block of the v2, v3 or v3.1 scheme
+///
+/// Content digests that verified are added to `apk_digests`, for the v4 scheme to refer to.
+fn verify_block(scheme: &str, value: &[u8], present: &[u32], content: &mut ContentDigests<'_>, apk_digests: &mut Vec<Vec<u8>>) -> SchemeReport {
+    let mut report = SchemeReport::new(scheme);
+
+    let signers = match block::split_length_prefixed(value).and_then(|(signers, _)| block::length_prefixed_items(signers)) {
+        Ok(signers) => signers,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+    if signers.is_empty() {
+        report.errors.push("No signers".to_string());
+    }
+
+    for (i, signer) in signers.into_iter().enumerate() {
+        if let Err(e) = verify_signer(scheme, signer, present, content, apk_digests, &mut report) {
+            report.errors.push(format!("Signer #{}: {}", i + 1, e));
+        }
+    }
+    report.finish()
+}
+
+fn verify_signer(
+    scheme: &str,
+    signer: &[u8],
+    present: &[u32],
+    content: &mut ContentDigests<'_>,
+    apk_digests: &mut Vec<Vec<u8>>,
+    report: &mut SchemeReport,
+) -> Result<()> {
+    let v3 = scheme != "v2";
+
+    let (signed_data, mut rest) = block::split_length_prefixed(signer)?;
+    let mut sdk_range = None;
+    if v3 {
+        sdk_range = Some((read_u32(rest, 0)?, read_u32(rest, 4)?));
+        rest = &rest[8..];
+    }
+    let (signatures, rest) = block::split_length_prefixed(rest)?;
+    let (public_key, _) = block::split_length_prefixed(rest)?;
+
+    let (digests, rest) = block::split_length_prefixed(signed_data)?;
+    let (certificates, rest) = block::split_length_prefixed(rest)?;
+    let certificates = block::length_prefixed_items(certificates)?;
+    let certificate = *certificates.first().ok_or_else(|| anyhow::anyhow!("No certificates"))?;
+    report.signers.push(CertificateInfo::from_der(certificate)?);
+
+    // Every supported signature must verify, and at least one must be supported
+    let mut signature_ids = Vec::new();
+    for record in block::length_prefixed_items(signatures)? {
+        let id = read_u32(record, 0)?;
+        let (signature, _) = block::split_length_prefixed(&record[4..])?;
+        signature_ids.push(id);
+        if let Some((kind, digest, _)) = block_algorithm(id) {
+            verify_signature(public_key, kind, digest, signed_data, signature).map_err(|e| anyhow::anyhow!("Signature 0x{:04x}: {}", id, e))?;
+        }
+    }
+    if !signature_ids.iter().any(|&id| block_algorithm(id).is_some()) {
+        anyhow::bail!("No supported signature algorithm among {:04x?}", signature_ids);
+    }
+    check_certificate_key(certificate, public_key)?;
+
+    // The digests are signed, so once the signatures verify they can be trusted
+    let mut digest_ids = Vec::new();
+    for record in block::length_prefixed_items(digests)? {
+        let id = read_u32(record, 0)?;
+        let (digest, _) = block::split_length_prefixed(&record[4..])?;
+        digest_ids.push(id);
+        if let Some((_, _, kind)) = block_algorithm(id) {
+            if content.get(kind) != digest {
+                anyhow::bail!("APK contents do not match the signed digest (0x{:04x}); the APK was modified after signing", id);
+            }
+            apk_digests.push(digest.to_vec());
+        }
+    }
+    if digest_ids != signature_ids {
+        anyhow::bail!("Digest and signature algorithms do not match");
+    }
+
+    let attributes = if let Some((min_sdk, max_sdk)) = sdk_range {
+        if (read_u32(rest, 0)?, read_u32(rest, 4)?) != (min_sdk, max_sdk) {
+            anyhow::bail!("SDK range of the signer does not match its signed data");
+        }
+        block::split_length_prefixed(&rest[8..])?.0
+    } else {
+        block::split_length_prefixed(rest)?.0
+    };
+
+    // v2 signers announce a v3 signature the same way v1 announces v2 and v3
+    for attribute in block::length_prefixed_items(attributes)? {
+        if !v3 && read_u32(attribute, 0)? == STRIPPING_PROTECTION_ATTR_ID && read_u32(attribute, 4)? == 3 && !present.contains(&APK_SIGNATURE_SCHEME_V3_BLOCK_ID) {
+            anyhow::bail!("Signed as v3 too, but the v3 signature was stripped");
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the JAR signature; `None` if the APK has none
+fn verify_v1(apk: &[u8], present: &[u32]) -> Result<Option<SchemeReport>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+    let blocks: Vec<String> = archive.file_names().filter(|name| is_signature_block(name)).map(str::to_string).collect();
+    if blocks.is_empty() {
+        return Ok(None);
+    }
+
+    let mut report = SchemeReport::new("v1");
+    let Ok(manifest) = entry_data(&mut archive, MANIFEST_NAME) else {
+        report.errors.push(format!("{} is missing", MANIFEST_NAME));
+        return Ok(Some(report));
+    };
+    let manifest_sections = parse_sections(&manifest);
+
+    for block_name in &blocks {
+        let base = &block_name[..block_name.rfind('.').unwrap_or(block_name.len())];
+        let sf_name = format!("{}.SF", base);
+        let Ok(signature_file) = entry_data(&mut archive, &sf_name) else {
+            report.errors.push(format!("{} has no matching {}", block_name, sf_name));
+            continue;
+        };
+
+        match verify_pkcs7(&entry_data(&mut archive, block_name)?, &signature_file) {
+            Ok(certificate) => report.signers.push(CertificateInfo::from_der(&certificate)?),
+            Err(e) => {
+                report.errors.push(format!("{}: {}", block_name, e));
+                continue;
+            }
+        }
+
+        let sf_sections = parse_sections(&signature_file);
+        report.errors.extend(check_signature_file(&sf_name, &sf_sections, &manifest, &manifest_sections));
+
+        // Stripping protection: the schemes the signer also applied must still be there
+        let signed_with = sf_sections.first().and_then(|main| main.get("X-Android-APK-Signed")).unwrap_or("");
+        for (number, id) in [("2", APK_SIGNATURE_SCHEME_V2_BLOCK_ID), ("3", APK_SIGNATURE_SCHEME_V3_BLOCK_ID)] {
+            if signed_with.split(',').any(|scheme| scheme.trim() == number) && !present.contains(&id) {
+                report.errors.push(format!("{} says the APK was also signed with v{}, but that signature was stripped", sf_name, number));
+            }
+        }
+    }
+
+    // Every entry must be listed in the manifest with a matching digest, and nothing more
+    let mut listed: Vec<&str> = manifest_sections.iter().skip(1).filter_map(|section| section.get("Name")).collect();
+    for i in 0..archive.len() {
+        let (name, is_dir) = {
+            let entry = archive.by_index_raw(i)?;
+            (entry.name().to_string(), entry.is_dir())
+        };
+        if is_dir || v1::is_signature_entry(&name) {
+            continue;
+        }
+        listed.retain(|listed| *listed != name);
+
+        let Some(section) = manifest_sections.iter().skip(1).find(|section| section.get("Name") == Some(name.as_str())) else {
+            report.errors.push(format!("{} is not in {}", name, MANIFEST_NAME));
+            continue;
+        };
+        let data = match entry_data(&mut archive, &name) {
+            Ok(data) => data,
+            Err(e) => {
+                report.errors.push(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+        match check_digests(section, "-Digest", &data) {
+            Some(true) => {}
+            Some(false) => report.errors.push(format!("{} was modified: its digest does not match {}", name, MANIFEST_NAME)),
+            None => report.errors.push(format!("{} has no supported digest in {}", name, MANIFEST_NAME)),
+        }
+    }
+    for name in listed {
+        report.errors.push(format!("{} is listed in {} but missing from the APK", name, MANIFEST_NAME));
+    }
+
+    Ok(Some(report.finish()))
+}
+
+/// Check `MANIFEST.MF` against a signature file, whole or section by section
+fn check_signature_file(sf_name: &str, sf_sections: &[Section<'_>], manifest: &[u8], manifest_sections: &[Section<'_>]) -> Vec<String> {
+    let Some(main) = sf_sections.first() else {
+        return vec![format!("{} is empty", sf_name)];
+    };
+    if check_digests(main, "-Digest-Manifest", manifest) == Some(true) {
+        return Vec::new();
+    }
+
+    // Like jarsigner, fall back to the digests of the individual manifest sections
+    let mut errors = Vec::new();
+    for section in manifest_sections.iter().skip(1) {
+        let Some(name) = section.get("Name") else { continue };
+        match sf_sections.iter().skip(1).find(|sf_section| sf_section.get("Name") == Some(name)) {
+            Some(sf_section) if check_digests(sf_section, "-Digest", section.raw) == Some(true) => {}
+            Some(_) => errors.push(format!("Entry {} of {} does not match {}", name, MANIFEST_NAME, sf_name)),
+            None => errors.push(format!("Entry {} of {} is not in {}", name, MANIFEST_NAME, sf_name)),
+        }
+    }
+    errors
+}
+
+/// Verify the PKCS#7 `SignedData` of a JAR signature block over the signature file
+///
+/// Returns the signer's certificate.
+fn verify_pkcs7(signature_block: &[u8], signature_file: &[u8]) -> Result<Vec<u8>> {
+    let (_, content_info, _) = der::read(signature_block)?;
+    let (_, _content_type, rest) = der::read(content_info)?;
+    let (_, explicit, _) = der::read(rest)?;
+    let (_, mut signed_data, _) = der::read(explicit)?;
+
+    // signerInfos is the last SET, after digestAlgorithms, the certificates and CRLs
+    let mut signer_infos = None;
+    while !signed_data.is_empty() {
+        let (tag, content, rest) = der::read(signed_data)?;
+        if tag == 0x31 {
+            signer_infos = Some(content);
+        }
+        signed_data = rest;
+    }
+    let signer_infos = signer_infos.ok_or_else(|| anyhow::anyhow!("No signer info"))?;
+
+    let (_, signer_info, _) = der::read(signer_infos)?;
+    let (_, _version, rest) = der::read(signer_info)?;
+    let (_, signer_id, rest) = der::read(rest)?;
+    let (_, digest_algorithm, rest) = der::read(rest)?;
+    let (tag, attributes, after_attributes) = der::read(rest)?;
+    let (signed_attributes, rest) = if tag == 0xa0 { (Some(attributes), after_attributes) } else { (None, rest) };
+    let (_, signature_algorithm, rest) = der::read(rest)?;
+    let (_, signature, _) = der::read(rest)?;
+
+    let digest = DigestAlgorithm::from_oid(first_element(digest_algorithm)?)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported digest algorithm"))?;
+    let kind = signature_kind(first_element(signature_algorithm)?)?;
+
+    // The signer is named by issuer and serial number
+    let certificates = pkcs7_certificates(signature_block)?;
+    let certificate = certificates
+        .iter()
+        .find(|certificate| {
+            x509_parser::parse_x509_certificate(certificate).is_ok_and(|(_, cert)| {
+                [cert.tbs_certificate.issuer.as_raw(), &der::tlv(0x02, cert.tbs_certificate.raw_serial())].concat() == signer_id
+            })
+        })
+        .ok_or_else(|| anyhow::anyhow!("Signer certificate not found"))?;
+    let (_, cert) = x509_parser::parse_x509_certificate(certificate).map_err(|e| anyhow::anyhow!("Invalid certificate: {}", e))?;
+    let public_key = cert.tbs_certificate.subject_pki.raw;
+
+    // With authenticated attributes the signature covers them, and they carry the digest
+    let signed = match signed_attributes {
+        Some(attributes) => {
+            let message_digest = message_digest(attributes)?;
+            if message_digest != digest.digest(signature_file) {
+                anyhow::bail!("Signature file does not match the signed digest");
+            }
+            der::tlv(0x31, attributes)
+        }
+        None => signature_file.to_vec(),
+    };
+    verify_signature(public_key, kind, digest, &signed, signature)?;
+
+    Ok(certificate.clone())
+}
+
+fn signature_kind(oid: &[u8]) -> Result<SignatureKind> {
+    let rsa = [der::OID_RSA_ENCRYPTION, OID_SHA1_WITH_RSA, der::OID_SHA256_WITH_RSA, OID_SHA512_WITH_RSA];
+    let ecdsa = [OID_EC_PUBLIC_KEY, OID_ECDSA_WITH_SHA1, OID_ECDSA_WITH_SHA256, OID_ECDSA_WITH_SHA512];
+
+    if rsa.iter().any(|arcs| der::oid(arcs) == oid) {
+        Ok(SignatureKind::RsaPkcs1)
+    } else if ecdsa.iter().any(|arcs| der::oid(arcs) == oid) {
+        Ok(SignatureKind::Ecdsa)
+    } else {
+        anyhow::bail!("Unsupported signature algorithm (DSA signatures are not verified)")
+    }
+}
+
+/// The `messageDigest` value among the authenticated attributes
+fn message_digest(mut attributes: &[u8]) -> Result<&[u8]> {
+    while !attributes.is_empty() {
+        let (_, attribute, rest) = der::read(attributes)?;
+        let (_, _, values) = der::read(attribute)?;
+        if attribute[..attribute.len() - values.len()] == der::oid(OID_MESSAGE_DIGEST) {
+            let (_, values, _) = der::read(values)?;
+            let (_, digest, _) = der::read(values)?;
+            return Ok(digest);
+        }
+        attributes = rest;
+    }
+    anyhow::bail!("No message digest among the signed attributes")
+}
+
+/// The first element of a SEQUENCE's content with its tag and length, e.g. an algorithm OID
+fn first_element(content: &[u8]) -> Result<&[u8]> {
+    let (_, _, rest) = der::read(content)?;
+    Ok(&content[..content.len() - rest.len()])
+}
+
+/// One section of a JAR manifest or signature file
+struct Section<'a> {
+    /// The section exactly as written, including the blank line that ends it
+    raw: &'a [u8],
+    attributes: Vec<(String, String)>,
+}
+
+impl Section<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sections of a JAR manifest, the main section first; continuation lines are joined
+fn parse_sections(data: &[u8]) -> Vec<Section<'_>> {
+    let mut sections = Vec::new();
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let line_end = data[pos..].iter().position(|&b| b == b'\r' || b == b'\n').map_or(data.len(), |i| pos + i);
+        let next = if data[line_end..].starts_with(b"\r\n") { line_end + 2 } else { (line_end + 1).min(data.len()) };
+        let line = &data[pos..line_end];
+
+        if line.is_empty() {
+            if !attributes.is_empty() {
+                sections.push(Section { raw: &data[start..next], attributes: std::mem::take(&mut attributes) });
+            }
+            start = next;
+        } else if let Some(continuation) = line.strip_prefix(b" ") {
+            if let Some((_, value)) = attributes.last_mut() {
+                value.push_str(&String::from_utf8_lossy(continuation));
+            }
+        } else if let Some(colon) = line.windows(2).position(|pair| pair == b": ") {
+            attributes.push((String::from_utf8_lossy(&line[..colon]).into_owned(), String::from_utf8_lossy(&line[colon + 2..]).into_owned()));
+        }
+        pos = next;
+    }
+    if !attributes.is_empty() {
+        sections.push(Section { raw: &data[start..], attributes });
+    }
+
+    sections
+}
+
+/// Whether every supported `<algorithm><suffix>` attribute of `section` matches `data`
+///
+/// `None` when the section has no digest this module can compute.
+fn check_digests(section: &Section<'_>, suffix: &str, data: &[u8]) -> Option<bool> {
+    let mut checked = false;
+    for (key, value) in &section.attributes {
+        let Some(algorithm) = key
+            .len()
+            .checked_sub(suffix.len())
+            .filter(|&split| key.is_char_boundary(split) && key[split..].eq_ignore_ascii_case(suffix))
+            .and_then(|split| DigestAlgorithm::from_jar_name(&key[..split]))
+        else {
+            continue;
+        };
+        if STANDARD.decode(value.trim()).ok()? != algorithm.digest(data) {
+            return Some(false);
+        }
+        checked = true;
+    }
+    checked.then_some(true)
+}
+
+/// Verify a v4 signature, kept next to the APK as `<apk>.idsig`
+///
+/// It signs the fs-verity root hash of the whole APK and the v2 or v3 content digest.
+fn verify_v4(apk: &[u8], idsig: &[u8], apk_digests: &[Vec<u8>]) -> SchemeReport {
+    let mut report = SchemeReport::new("v4");
+
+    let result = (|| -> Result<CertificateInfo> {
+        let version = read_u32(idsig, 0)?;
+        if !(2..=3).contains(&version) {
+            anyhow::bail!("Unsupported .idsig version {}", version);
+        }
+        let (hashing_info, rest) = block::split_length_prefixed(&idsig[4..])?;
+        let (signing_info, _merkle_tree) = block::split_length_prefixed(rest)?;
+
+        let hash_algorithm = read_u32(hashing_info, 0)?;
+        let log2_block_size = *hashing_info.get(4).ok_or_else(|| anyhow::anyhow!("Truncated hashing info"))?;
+        let (salt, rest) = block::split_length_prefixed(&hashing_info[5..])?;
+        let (root_hash, _) = block::split_length_prefixed(rest)?;
+        if hash_algorithm != 1 || log2_block_size != VERITY_BLOCK_SIZE.trailing_zeros() as u8 {
+            anyhow::bail!("Unsupported hashing: algorithm {}, block size 2^{}", hash_algorithm, log2_block_size);
+        }
+
+        let (apk_digest, rest) = block::split_length_prefixed(signing_info)?;
+        let (certificate, rest) = block::split_length_prefixed(rest)?;
+        let (additional_data, rest) = block::split_length_prefixed(rest)?;
+        let (public_key, rest) = block::split_length_prefixed(rest)?;
+        let algorithm = read_u32(rest, 0)?;
+        let (signature, _) = block::split_length_prefixed(&rest[4..])?;
+
+        let certificate_info = CertificateInfo::from_der(certificate)?;
+
+        let length_prefixed = |data: &[u8]| [(data.len() as u32).to_le_bytes().as_slice(), data].concat();
+        let mut signed_data = Vec::new();
+        signed_data.extend_from_slice(&(apk.len() as u64).to_le_bytes());
+        signed_data.extend_from_slice(&hash_algorithm.to_le_bytes());
+        signed_data.push(log2_block_size);
+        for field in [salt, root_hash, apk_digest, certificate, additional_data] {
+            signed_data.extend(length_prefixed(field));
+        }
+        let signed_data = [((signed_data.len() + 4) as u32).to_le_bytes().as_slice(), &signed_data].concat();
+
+        let (kind, digest, _) = block_algorithm(algorithm).ok_or_else(|| anyhow::anyhow!("Unsupported signature algorithm 0x{:04x}", algorithm))?;
+        verify_signature(public_key, kind, digest, &signed_data, signature)?;
+        check_certificate_key(certificate, public_key)?;
+
+        if merkle_root(apk, salt) != root_hash {
+            anyhow::bail!("APK contents do not match the signed Merkle tree root; the APK was modified after signing");
+        }
+        if !apk_digests.iter().any(|known| known == apk_digest) {
+            anyhow::bail!("Signed APK digest does not match a verified v2 or v3 signature");
+        }
+        Ok(certificate_info)
+    })();
+
+    match result {
+        Ok(certificate) => report.signers.push(certificate),
+        Err(e) => report.errors.push(e.to_string()),
+    }
+    report.finish()
+}
+
+fn entry_data(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>> {
+    let mut entry = archive.by_name(name)?;
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
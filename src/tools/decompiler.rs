@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Java decompiler used to turn the converted JARs into source code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Decompiler {
+    #[default]
+    Procyon,
+    Cfr,
+    /// Vineflower, the maintained Fernflower fork
+    #[value(alias = "fernflower")]
+    #[serde(alias = "fernflower")]
+    Vineflower,
+    Jadx,
+}
+
+pub const PROCYON_JAR: &str = "procyon-decompiler-v0.6.1.jar";
+pub const CFR_JAR: &str = "cfr-0.152.jar";
+pub const VINEFLOWER_JAR: &str = "vineflower-1.10.1.jar";
+pub const JADX_JAR: &str = "jadx-1.5.1-all.jar";
+
+impl Decompiler {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Decompiler::Procyon => "Procyon",
+            Decompiler::Cfr => "CFR",
+            Decompiler::Vineflower => "Vineflower",
+            Decompiler::Jadx => "JADX",
+        }
+    }
+
+    /// Backend used to retry classes this one failed on
+    pub fn fallback(&self) -> Decompiler {
+        match self {
+            Decompiler::Vineflower => Decompiler::Cfr,
+            _ => Decompiler::Vineflower,
+        }
+    }
+
+    /// File name of the embedded JAR under `jars/`
+    pub fn jar_name(&self) -> &'static str {
+        match self {
+            Decompiler::Procyon => PROCYON_JAR,
+            Decompiler::Cfr => CFR_JAR,
+            Decompiler::Vineflower => VINEFLOWER_JAR,
+            Decompiler::Jadx => JADX_JAR,
+        }
+    }
+
+    /// Java arguments that decompile `jar` into the source tree at `output_dir`
+    pub fn java_args(&self, tool_jar: &Path, jar: &Path, output_dir: &Path) -> Vec<String> {
+        let tool_jar = tool_jar.to_string_lossy().to_string();
+        let jar = jar.to_string_lossy().to_string();
+        let output_dir = output_dir.to_string_lossy().to_string();
+
+        let args: Vec<&str> = match self {
+            Decompiler::Procyon => vec!["-jar", &tool_jar, "-jar", &jar, "-o", &output_dir],
+            Decompiler::Cfr => vec!["-jar", &tool_jar, &jar, "--outputdir", &output_dir, "--silent", "true"],
+            Decompiler::Vineflower => vec!["-jar", &tool_jar, "-log=WARN", &jar, &output_dir],
+            // The fat JAR has no Main-Class; write sources straight into the output directory
+            Decompiler::Jadx => vec!["-cp", &tool_jar, "jadx.cli.JadxCLI", "--output-dir-src", &output_dir, &jar],
+        };
+
+        args.into_iter().map(String::from).collect()
+    }
+}
+
+impl fmt::Display for Decompiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
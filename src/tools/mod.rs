@@ -1,18 +1,55 @@
-use crate::{assets::AssetManager, config::Config, Result};
+use crate::{assets::AssetManager, config::Config, ApkextError, Result};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
 
+pub mod decompiler;
+pub mod progress;
+
+pub use decompiler::Decompiler;
+pub use progress::{Progress, ProgressHandler, Stream};
+
 pub struct ToolManager {
     config: Config,
     asset_manager: AssetManager,
+    progress: Option<ProgressHandler>,
 }
 
 impl ToolManager {
-    pub fn new(config: Config) -> Self {
-        let asset_manager = AssetManager::new().expect("Failed to create asset manager");
-        Self { config, asset_manager }
+    pub fn new(config: Config, asset_manager: AssetManager) -> Self {
+        Self { config, asset_manager, progress: None }
+    }
+
+    /// Send messages and tool output to `progress` instead of printing them
+    pub fn with_progress(mut self, progress: Option<ProgressHandler>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Directory of the decompilation cache under the config directory
+    pub fn cache_dir(&self) -> std::path::PathBuf {
+        self.asset_manager.get_cache_path()
+    }
+
+    /// Print a progress message unless output is suppressed by the config
+    pub fn report(&self, message: &str) {
+        match &self.progress {
+            Some(progress) => progress(&Progress::Message { text: message.to_string() }),
+            None if !self.config.quiet => println!("{}", message),
+            None => {}
+        }
     }
 
-    pub async fn run_apktool(&self, args: &[&str]) -> Result<()> {
+    /// Announce the start of pipeline step `name`, one of `plan`
+    pub fn step(&self, plan: &[&str], name: &str) {
+        if let Some(progress) = &self.progress {
+            let index = plan.iter().position(|step| *step == name).map_or(plan.len(), |i| i + 1);
+            progress(&Progress::Step { name: name.to_string(), index, total: plan.len() });
+        }
+    }
+
+    pub async fn run_apktool(&self, args: &[&str]) -> Result<(), ApkextError> {
         let jar_path = self.asset_manager.get_jar_path("apktool.jar");
         let framework_path = self.asset_manager.get_tools_path().join("framework");
 
@@ -40,43 +77,20 @@ impl ToolManager {
         let mut cmd = Command::new(&self.config.java.java_path);
         cmd.args(&cmd_args);
 
-        let output = cmd.output().await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Apktool failed: {}", stderr));
-        }
-
-        // Print stdout for user feedback
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
-        Ok(())
+        self.run("Apktool", cmd).await
     }
 
-    pub async fn run_procyon(&self, args: &[&str]) -> Result<()> {
-        let jar_path = self.asset_manager.get_jar_path("procyon-decompiler-v0.6.1.jar");
-        let jar_path_str = jar_path.to_string_lossy().to_string();
-
-        let mut cmd_args = vec!["-jar", &jar_path_str];
-        cmd_args.extend_from_slice(args);
+    /// Decompile `jar` into `output_dir`
+    pub async fn run_decompiler(&self, decompiler: Decompiler, jar: &Path, output_dir: &Path) -> Result<(), ApkextError> {
+        let jar_path = self.asset_manager.get_jar_path(decompiler.jar_name());
 
         let mut cmd = Command::new(&self.config.java.java_path);
-        cmd.args(&cmd_args);
+        cmd.args(decompiler.java_args(&jar_path, jar, output_dir));
 
-        let output = cmd.output().await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Procyon failed: {}", stderr));
-        }
-
-        // Print stdout for user feedback
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
-        Ok(())
+        self.run(decompiler.name(), cmd).await
     }
 
-    pub async fn run_dex2jar(&self, args: &[&str]) -> Result<()> {
+    pub async fn run_dex2jar(&self, args: &[&str]) -> Result<(), ApkextError> {
         // Build classpath from all JAR files in lib directory
         let lib_dir = self.asset_manager.get_script_path("dex-tools-v2.4/dex-tools-v2.4/lib/dex-tools-v2.4/lib");
 
@@ -103,16 +117,61 @@ impl ToolManager {
         ]);
         cmd.args(args);
 
-        let output = cmd.output().await?;
+        self.run("Dex2jar", cmd).await
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Dex2jar failed: {}", stderr));
+    /// Run `cmd`, streaming its output line by line, and fail with its stderr if it exits with an error
+    async fn run(&self, tool: &str, mut cmd: Command) -> Result<(), ApkextError> {
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|source| ApkextError::JavaNotFound { java: self.config.java.java_path.clone(), source })?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let (_, stderr, status) = tokio::try_join!(
+            self.forward(tool, Stream::Stdout, stdout),
+            self.forward(tool, Stream::Stderr, stderr),
+            child.wait(),
+        )?;
+
+        if !status.success() {
+            return Err(ApkextError::ToolFailed {
+                tool: tool.to_string(),
+                code: status.code(),
+                stderr: stderr.trim_end().to_string(),
+            });
         }
 
-        // Print stdout for user feedback
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Pass each line of `reader` on as it arrives; returns the stderr text for error reports
+    async fn forward(&self, tool: &str, stream: Stream, reader: impl AsyncRead + Unpin) -> std::io::Result<String> {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        let mut collected = String::new();
+
+        while reader.read_until(b'\n', &mut buf).await? > 0 {
+            let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+            buf.clear();
+
+            match &self.progress {
+                Some(progress) => progress(&Progress::Output { tool: tool.to_string(), stream, line: line.clone() }),
+                // Print stdout for user feedback
+                None if stream == Stream::Stdout && !self.config.quiet => println!("{}", line),
+                None => {}
+            }
+
+            if stream == Stream::Stderr {
+                collected.push_str(&line);
+                collected.push('\n');
+            }
+        }
+
+        Ok(collected)
+    }
+}
@@ -0,0 +1,25 @@
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Which output stream of an external tool a line came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Progress of an unpack or pack run, delivered while it is still going
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Progress {
+    /// A pipeline step started; `index` counts from 1 up to `total`
+    Step { name: String, index: usize, total: usize },
+    /// A status message such as "[+] Extracting DEX files"
+    Message { text: String },
+    /// One line printed by apktool, dex2jar or a decompiler
+    Output { tool: String, stream: Stream, line: String },
+}
+
+/// Receives progress events; called from the task running the pipeline, so it should not block
+pub type ProgressHandler = Arc<dyn Fn(&Progress) + Send + Sync>;
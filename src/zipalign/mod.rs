@@ -0,0 +1,117 @@
+//! zipalign: uncompressed entries must start at 4-byte boundaries so they can be mmap'ed
+//!
+//! v2+ signatures cover the exact byte layout, so alignment has to happen before signing.
+
+use crate::Result;
+use serde::Serialize;
+use std::fs;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DEFAULT_ALIGNMENT: u16 = 4;
+
+/// Uncompressed native libraries are mapped directly; 16 KiB also satisfies 4 KiB page devices
+const NATIVE_LIBRARY_ALIGNMENT: u16 = 16 * 1024;
+
+/// Uncompressed entry whose data does not start at its required boundary
+#[derive(Debug, Clone, Serialize)]
+pub struct MisalignedEntry {
+    pub name: String,
+    pub data_offset: u64,
+    pub alignment: u16,
+}
+
+/// Rewrite the APK so every uncompressed entry is aligned
+pub fn align(apk: &[u8]) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+    let indices: Vec<usize> = (0..archive.len()).collect();
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::with_capacity(apk.len())));
+    copy_entries(&mut archive, &mut writer, &indices)?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Align an APK file in place
+pub fn align_file(apk_path: &Path) -> Result<()> {
+    let apk = fs::read(apk_path)?;
+    let aligned = align(&apk)?;
+    fs::write(apk_path, aligned)?;
+    Ok(())
+}
+
+/// List the uncompressed entries that are not aligned; empty if the APK is fine
+pub fn verify(apk: &[u8]) -> Result<Vec<MisalignedEntry>> {
+    let mut archive = ZipArchive::new(Cursor::new(apk))?;
+    let mut misaligned = Vec::new();
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if file.is_dir() || file.compression() != CompressionMethod::Stored {
+            continue;
+        }
+
+        let alignment = required_alignment(file.name());
+        if file.data_start() % alignment as u64 != 0 {
+            misaligned.push(MisalignedEntry {
+                name: file.name().to_string(),
+                data_offset: file.data_start(),
+                alignment,
+            });
+        }
+    }
+
+    Ok(misaligned)
+}
+
+/// Check the alignment of an APK file
+pub fn verify_file(apk_path: &Path) -> Result<Vec<MisalignedEntry>> {
+    let apk = fs::read(apk_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", apk_path.display(), e))?;
+    verify(&apk)
+}
+
+/// Copy the given entries from `archive` to `writer`, aligning uncompressed ones
+pub(crate) fn copy_entries<W: Write + Seek>(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    writer: &mut ZipWriter<W>,
+    indices: &[usize],
+) -> Result<()> {
+    for &i in indices {
+        let mut file = archive.by_index(i)?;
+
+        if file.is_dir() || file.compression() != CompressionMethod::Stored {
+            drop(file);
+            writer.raw_copy_file(archive.by_index_raw(i)?)?;
+            continue;
+        }
+
+        let name = file.name().to_string();
+        let alignment = required_alignment(&name);
+
+        let mut options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(file.last_modified());
+        if let Some(mode) = file.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+
+        writer.start_file_aligned(name, options, alignment)?;
+        writer.write_all(&data)?;
+    }
+
+    Ok(())
+}
+
+fn required_alignment(name: &str) -> u16 {
+    if name.starts_with("lib/") && name.ends_with(".so") {
+        NATIVE_LIBRARY_ALIGNMENT
+    } else {
+        DEFAULT_ALIGNMENT
+    }
+}
@@ -0,0 +1,217 @@
+use apkext::aab::{self, resources, xml};
+use std::fs;
+use std::io::Read;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+mod common;
+
+use common::{push_varint, write_zip};
+
+/// Minimal protobuf encoder for building aapt2 messages by hand
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(mut self, number: u32, value: u64) -> Self {
+        push_varint(&mut self.0, (number as u64) << 3);
+        push_varint(&mut self.0, value);
+        self
+    }
+
+    fn bytes(mut self, number: u32, data: &[u8]) -> Self {
+        push_varint(&mut self.0, ((number as u64) << 3) | 2);
+        push_varint(&mut self.0, data.len() as u64);
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    fn string(self, number: u32, text: &str) -> Self {
+        self.bytes(number, text.as_bytes())
+    }
+
+    fn message(self, number: u32, message: Message) -> Self {
+        self.bytes(number, &message.0)
+    }
+}
+
+const ANDROID: &str = "http://schemas.android.com/apk/res/android";
+
+fn manifest() -> Vec<u8> {
+    let label = Message::default()
+        .string(1, ANDROID)
+        .string(2, "label")
+        .string(3, "@string/app_name");
+    // Only the compiled value is kept for this one
+    let version = Message::default()
+        .string(1, ANDROID)
+        .string(2, "versionCode")
+        .message(6, Message::default().message(7, Message::default().varint(6, 42)));
+
+    let application = Message::default().string(3, "application").message(4, label);
+    let root = Message::default()
+        .message(1, Message::default().string(1, "android").string(2, ANDROID))
+        .string(3, "manifest")
+        .message(4, Message::default().string(2, "package").string(3, "com.example.app"))
+        .message(4, version)
+        .message(5, Message::default().message(1, application));
+
+    Message::default().message(1, root).0
+}
+
+fn string_value(text: &str) -> Message {
+    Message::default().message(4, Message::default().message(2, Message::default().string(1, text)))
+}
+
+fn resource_table() -> Vec<u8> {
+    let app_name = Message::default()
+        .string(2, "app_name")
+        .message(6, Message::default().message(1, Message::default()).message(2, string_value("Example & Co")))
+        .message(6, Message::default().message(1, Message::default().string(3, "fr")).message(2, string_value("Exemple")));
+    let strings = Message::default().string(2, "string").message(3, app_name);
+
+    let accent = Message::default()
+        .string(2, "accent")
+        .message(6, Message::default().message(2, Message::default().message(4, Message::default().message(7, Message::default().varint(9, 0xff00_80ff)))));
+    let colors = Message::default().string(2, "color").message(3, accent);
+
+    // A layout is a file under res/, not a value
+    let main = Message::default()
+        .string(2, "main")
+        .message(6, Message::default().message(2, Message::default().message(4, Message::default().message(5, Message::default().string(1, "res/layout/main.xml")))));
+    let layouts = Message::default().string(2, "layout").message(3, main);
+
+    let package = Message::default()
+        .varint(1, 0x7f)
+        .string(2, "com.example.app")
+        .message(3, strings)
+        .message(3, colors)
+        .message(3, layouts);
+    Message::default().message(2, package).0
+}
+
+#[test]
+fn test_manifest_decoding() {
+    let decoded = xml::decode(&manifest()).unwrap();
+
+    assert_eq!(decoded, "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <manifest xmlns:android=\"http://schemas.android.com/apk/res/android\" package=\"com.example.app\" android:versionCode=\"42\">\n    \
+        <application android:label=\"@string/app_name\" />\n\
+        </manifest>\n");
+}
+
+#[test]
+fn test_resource_table_decoding() {
+    let files = resources::decode_table(&resource_table()).unwrap();
+
+    assert_eq!(files.keys().collect::<Vec<_>>(), ["values-fr/strings.xml", "values/colors.xml", "values/strings.xml"]);
+    assert!(files["values/strings.xml"].contains("<string name=\"app_name\">Example &amp; Co</string>"));
+    assert!(files["values-fr/strings.xml"].contains("<string name=\"app_name\">Exemple</string>"));
+    assert!(files["values/colors.xml"].contains("<color name=\"accent\">#ff0080ff</color>"));
+}
+
+#[test]
+fn test_configuration_qualifiers() {
+    let config = Message::default()
+        .string(3, "fr-CA")
+        .varint(17, 1)
+        .varint(18, 480)
+        .varint(24, 26);
+    assert_eq!(resources::qualifiers(&config.0).unwrap(), "fr-rCA-night-xxhdpi-v26");
+
+    let config = Message::default().string(3, "sr-Latn").varint(9, 600);
+    assert_eq!(resources::qualifiers(&config.0).unwrap(), "b+sr+Latn-sw600dp");
+
+    assert_eq!(resources::qualifiers(&[]).unwrap(), "");
+}
+
+#[test]
+fn test_malformed_protobuf() {
+    assert!(xml::decode(&[0x0a, 0x05, 0x01]).is_err());
+    assert!(resources::decode_table(&[0x0f]).is_err());
+}
+
+#[test]
+fn test_bundle_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    let bundle = dir.join("App.aab");
+    let manifest = manifest();
+    let table = resource_table();
+    write_zip(&bundle, &[
+        ("BundleConfig.pb", b"config"),
+        ("base/manifest/AndroidManifest.xml", &manifest),
+        ("base/resources.pb", &table),
+        ("base/dex/classes.dex", b"dex"),
+        ("base/res/drawable/icon.png", b"png"),
+        ("feature/manifest/AndroidManifest.xml", &manifest),
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0"),
+        ("META-INF/CERT.RSA", b"signature"),
+    ]);
+
+    let bundle_dir = dir.join("App").join(aab::BUNDLE_DIR);
+    assert_eq!(aab::extract(&bundle, &bundle_dir).unwrap(), ["base", "feature"]);
+
+    let decoded = dir.join("App").join(aab::DECODED_DIR).join("base");
+    aab::decode_module(&bundle_dir.join("base"), &decoded).unwrap();
+    assert!(fs::read_to_string(decoded.join("AndroidManifest.xml")).unwrap().contains("package=\"com.example.app\""));
+    assert!(fs::read_to_string(decoded.join("res/values-fr/strings.xml")).unwrap().contains("Exemple"));
+    assert_eq!(fs::read(decoded.join("res/drawable/icon.png")).unwrap(), b"png");
+
+    let decoded_dir = dir.join("App").join(aab::DECODED_DIR);
+    assert!(aab::decoded_changes(&bundle_dir, &decoded_dir).unwrap().is_empty());
+
+    let rebuilt = dir.join("Rebuilt.aab");
+    aab::pack(&bundle_dir, &rebuilt).unwrap();
+
+    let mut archive = ZipArchive::new(fs::File::open(&rebuilt).unwrap()).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, [
+        "BundleConfig.pb",
+        "base/dex/classes.dex",
+        "base/manifest/AndroidManifest.xml",
+        "base/res/drawable/icon.png",
+        "base/resources.pb",
+        "feature/manifest/AndroidManifest.xml",
+    ]);
+
+    let mut data = Vec::new();
+    archive.by_name("base/resources.pb").unwrap().read_to_end(&mut data).unwrap();
+    assert_eq!(data, table);
+}
+
+#[test]
+fn test_decoded_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let bundle_dir = temp_dir.path().join(aab::BUNDLE_DIR);
+    fs::create_dir_all(bundle_dir.join("base/manifest")).unwrap();
+    fs::write(bundle_dir.join("base/manifest/AndroidManifest.xml"), manifest()).unwrap();
+    fs::create_dir_all(bundle_dir.join("base/res/drawable")).unwrap();
+    fs::write(bundle_dir.join("base/res/drawable/icon.png"), b"png").unwrap();
+
+    let decoded_dir = temp_dir.path().join(aab::DECODED_DIR);
+    assert!(aab::decoded_changes(&bundle_dir, &decoded_dir).unwrap().is_empty());
+    aab::decode_module(&bundle_dir.join("base"), &decoded_dir.join("base")).unwrap();
+    assert!(aab::decoded_changes(&bundle_dir, &decoded_dir).unwrap().is_empty());
+
+    let manifest_path = decoded_dir.join("base/AndroidManifest.xml");
+    let edited = fs::read_to_string(&manifest_path).unwrap().replace("com.example.app", "com.example.edited");
+    fs::write(&manifest_path, edited).unwrap();
+    fs::remove_file(decoded_dir.join("base/res/drawable/icon.png")).unwrap();
+    fs::write(decoded_dir.join("base/res/drawable/new.png"), b"png").unwrap();
+
+    let changes = aab::decoded_changes(&bundle_dir, &decoded_dir).unwrap();
+    let changes: Vec<String> = changes.iter().map(|change| change.to_string_lossy().to_string()).collect();
+    assert_eq!(changes, ["base/AndroidManifest.xml", "base/res/drawable/icon.png", "base/res/drawable/new.png"]);
+}
+
+#[test]
+fn test_pack_requires_bundle_config() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("base/manifest")).unwrap();
+
+    let error = aab::pack(temp_dir.path(), &temp_dir.path().join("App.aab")).unwrap_err();
+    assert!(error.to_string().contains("BundleConfig.pb"));
+}
@@ -0,0 +1,172 @@
+use apkext::arsc::{config, ResourceTable, ResourceValue};
+use apkext::manifest::Value;
+use std::path::Path;
+
+mod common;
+
+use common::{chunk, string_pool};
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+
+fn sample_table() -> ResourceTable {
+    ResourceTable::from_apk(Path::new(SAMPLE_APK)).unwrap()
+}
+
+/// A 64-byte `ResTable_config` with the given `(offset, byte)` fields set
+fn res_config(fields: &[(usize, u8)]) -> Vec<u8> {
+    let mut config = vec![0; 64];
+    config[..4].copy_from_slice(&64u32.to_le_bytes());
+    for &(offset, value) in fields {
+        config[offset] = value;
+    }
+    config
+}
+
+/// A table with one `string` type whose entries 0 and 5 are stored sparse and compact
+fn sparse_table() -> Vec<u8> {
+    let mut type_header = vec![1, 0x01, 0, 0];
+    type_header.extend_from_slice(&2u32.to_le_bytes());
+    type_header.extend_from_slice(&(84u32 + 8).to_le_bytes());
+    type_header.extend(res_config(&[(8, b'd'), (9, b'e')]));
+
+    let mut type_body = Vec::new();
+    for (index, offset) in [(0u16, 0u16), (5, 2)] {
+        type_body.extend_from_slice(&index.to_le_bytes());
+        type_body.extend_from_slice(&offset.to_le_bytes());
+    }
+    // key index, compact flag with the value type in the high byte, data
+    for (key, data_type, data) in [(0u16, 0x03u16, 0u32), (1, 0x10, 42)] {
+        type_body.extend_from_slice(&key.to_le_bytes());
+        type_body.extend_from_slice(&(0x0008 | data_type << 8).to_le_bytes());
+        type_body.extend_from_slice(&data.to_le_bytes());
+    }
+
+    table(chunk(0x0201, &type_header, &type_body))
+}
+
+/// A table with package `com.example`, whose only type is `string`, with `type_chunk` in it
+fn table(type_chunk: Vec<u8>) -> Vec<u8> {
+    let mut package_header = 0x7fu32.to_le_bytes().to_vec();
+    let mut name: Vec<u8> = "com.example".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    name.resize(256, 0);
+    package_header.extend(name);
+    package_header.extend([0u8; 20]);
+
+    let package_body = [
+        string_pool(&["string"]),
+        string_pool(&["greeting", "answer"]),
+        type_chunk,
+    ]
+    .concat();
+
+    let table_body = [string_pool(&["Hallo"]), chunk(0x0200, &package_header, &package_body)].concat();
+    chunk(0x0002, &1u32.to_le_bytes(), &table_body)
+}
+
+#[test]
+fn test_sample_resource_table() {
+    let table = sample_table();
+
+    assert_eq!(table.packages.len(), 1);
+    assert_eq!(table.packages[0].name, "io.selendroid.testapp");
+    assert_eq!(table.packages[0].id, 0x7f);
+
+    let id = table.id("string/app_name").unwrap();
+    assert_eq!(id, 0x7f05_0002);
+    assert_eq!(table.name(id).as_deref(), Some("string/app_name"));
+    assert_eq!(table.id("@string/app_name"), Some(id));
+    assert_eq!(table.id("R.string.app_name"), Some(id));
+    assert_eq!(table.id("io.selendroid.testapp:string/app_name"), Some(id));
+    assert_eq!(table.id("0x7f050002"), Some(id));
+    assert_eq!(table.id("string/missing"), None);
+
+    let configs: Vec<&str> = table.values(id).into_iter().map(|(config, _)| config).collect();
+    assert_eq!(configs, ["", "de", "fr", "es", "it"]);
+    assert_eq!(table.locales(), ["de", "es", "fr", "it"]);
+
+    let drawables = &table.packages[0].types[0];
+    assert_eq!(drawables.name, "drawable");
+    let qualifiers: Vec<&str> = drawables.configs.iter().map(|config| config.qualifiers.as_str()).collect();
+    assert_eq!(qualifiers, ["ldpi-v4", "mdpi-v4", "hdpi-v4"]);
+}
+
+#[test]
+fn test_resolve_per_configuration() {
+    let table = sample_table();
+    let id = table.id("string/hello").unwrap();
+
+    let value = |qualifiers: &str| match table.resolve(id, qualifiers) {
+        Some((config, ResourceValue::Item { value: Value::String(text) })) => (config.to_string(), text.clone()),
+        other => panic!("unexpected value {:?}", other),
+    };
+    assert_eq!(value("de-rDE"), ("de".to_string(), "Hello Germany, AndroidDemoProject!".to_string()));
+    assert_eq!(value("fr-land-xxhdpi"), ("fr".to_string(), "Hello France, AndroidDemoProject!".to_string()));
+    assert_eq!(value("ja-rJP").0, "");
+    assert_eq!(value("").1, "Hello Default Locale, Selendroid-test-app!");
+
+    let resource = table.lookup("R.string.button", Some("it-rIT")).unwrap();
+    assert_eq!(resource.id, "0x7f050001");
+    assert_eq!(resource.values.len(), 1);
+    assert_eq!(resource.values[0].value, "IT Button");
+    assert_eq!(resource.to_string(), "string/button (0x7f050001)\n  it:          IT Button\n");
+}
+
+#[test]
+fn test_format_values() {
+    let table = sample_table();
+
+    let array = table.lookup("array/arrays_preferedProgrammingLanguage", None).unwrap();
+    assert_eq!(array.values[0].value, "[Ruby, PHP, Scala, Python, Javascript, Java, C++, C#]");
+
+    let icon = table.lookup("drawable/icon", Some("hdpi")).unwrap();
+    assert_eq!(icon.values[0].config, "hdpi-v4");
+    assert_eq!(icon.values[0].value, "res/drawable-hdpi/icon.png");
+    assert_eq!(table.lookup("drawable/icon", Some("mdpi-v21")).unwrap().values[0].config, "mdpi-v4");
+    assert!(table.lookup("drawable/icon", Some("mdpi-v3")).unwrap().values.is_empty());
+
+    let reference = ResourceValue::Item { value: Value::Reference(0x7f02_0001) };
+    assert_eq!(table.format(&reference), "@drawable/icon");
+    let unknown = ResourceValue::Item { value: Value::Reference(0x0101_0000) };
+    assert_eq!(table.format(&unknown), "@0x01010000");
+}
+
+#[test]
+fn test_sparse_compact_entries() {
+    let table = ResourceTable::parse(&sparse_table()).unwrap();
+
+    assert_eq!(table.packages[0].name, "com.example");
+    assert_eq!(table.name(0x7f01_0000).as_deref(), Some("string/greeting"));
+    assert_eq!(table.name(0x7f01_0005).as_deref(), Some("string/answer"));
+    assert_eq!(table.lookup("string/greeting", Some("de-rAT")).unwrap().values[0].value, "Hallo");
+    assert_eq!(table.lookup("string/answer", None).unwrap().values[0].config, "de");
+    assert_eq!(table.lookup("string/answer", None).unwrap().values[0].value, "42");
+
+    let mut truncated = sparse_table();
+    truncated.truncate(truncated.len() - 6);
+    assert!(ResourceTable::parse(&truncated).is_err());
+    assert!(ResourceTable::parse(&string_pool(&["not a table"])).is_err());
+}
+
+#[test]
+fn test_truncated_type_header() {
+    // Only the type id after the chunk header: no flags, entry count or config
+    for header in [&[1u8][..], &[1, 0], &[1, 0, 0, 0, 2, 0, 0, 0]] {
+        let error = ResourceTable::parse(&table(chunk(0x0201, header, &[]))).unwrap_err();
+        assert!(error.to_string().contains("Truncated"), "{}", error);
+    }
+}
+
+#[test]
+fn test_config_qualifiers() {
+    assert_eq!(config::qualifiers(&res_config(&[])), "");
+
+    // "fil" packed into two bytes, region PH, landscape, night, xxhdpi, API 21
+    let filipino = res_config(&[(8, 0xad), (9, 0x05), (10, b'P'), (11, b'H'), (12, 2), (14, 0xe0), (15, 0x01), (24, 21), (29, 0x20)]);
+    assert_eq!(config::qualifiers(&filipino), "fil-rPH-land-night-xxhdpi-v21");
+
+    let serbian_latin = res_config(&[(8, b's'), (9, b'r'), (36, b'L'), (37, b'a'), (38, b't'), (39, b'n')]);
+    assert_eq!(config::qualifiers(&serbian_latin), "b+sr+Latn");
+
+    let tablet = res_config(&[(4, 0x36), (5, 0x01), (28, 0x80 | 0x04), (30, 0x58), (31, 0x02)]);
+    assert_eq!(config::qualifiers(&tablet), "mcc310-ldrtl-sw600dp-xlarge");
+}
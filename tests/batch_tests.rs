@@ -0,0 +1,54 @@
+use apkext::apk::{batch, Extractor};
+use apkext::assets::AssetManager;
+use apkext::config::Config;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_collect_apks_expands_directories_and_globs() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    for name in ["b.apk", "a.apk", "notes.txt"] {
+        fs::write(dir.join(name), b"").unwrap();
+    }
+
+    let dir_str = dir.to_string_lossy().to_string();
+    let pattern = dir.join("a*.apk").to_string_lossy().to_string();
+    let missing = "missing.apk".to_string();
+
+    let apks = batch::collect_apks(&[pattern, dir_str.clone(), missing]).unwrap();
+    assert_eq!(apks, vec![dir.join("a.apk"), dir.join("b.apk"), PathBuf::from("missing.apk")]);
+
+    let no_match = dir.join("z*.apk").to_string_lossy().to_string();
+    assert!(batch::collect_apks(&[no_match]).is_err());
+
+    let empty = TempDir::new().unwrap();
+    assert!(batch::collect_apks(&[empty.path().to_string_lossy().to_string()]).is_err());
+}
+
+#[tokio::test]
+async fn test_unpack_all_rejects_shared_output_dir() {
+    let apks = vec![PathBuf::from("one/App.apk"), PathBuf::from("two/App.apk")];
+    let output = PathBuf::from("out");
+
+    let result = batch::unpack_all(apks, 2, |apk| {
+        Extractor::new(Config::load(), AssetManager::new().unwrap()).with_output_dir(Some(output.join(apk.file_stem().unwrap())))
+    })
+    .await;
+
+    assert!(result.is_err(), "Both APKs would unpack into out/App");
+}
+
+#[tokio::test]
+async fn test_unpack_all_reports_each_apk() {
+    let apks = vec![PathBuf::from("first.apk"), PathBuf::from("second.txt")];
+
+    let outcomes = batch::unpack_all(apks.clone(), 1, |_| Extractor::new(Config::load(), AssetManager::new().unwrap())).await.unwrap();
+
+    assert_eq!(outcomes.len(), 2);
+    for (outcome, apk) in outcomes.iter().zip(&apks) {
+        assert_eq!(&outcome.apk, apk, "Outcomes keep the input order");
+        assert!(outcome.result.is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use apkext::apk::cache::{self, DecompileCache};
+use apkext::tools::Decompiler;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+fn write_jar(path: &Path, classes: &[(&str, &[u8])]) {
+    let mut writer = ZipWriter::new(File::create(path).unwrap());
+    for (name, bytecode) in classes {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(bytecode).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+#[test]
+fn test_source_keys_cover_nested_classes() {
+    let temp_dir = TempDir::new().unwrap();
+    let old_jar = temp_dir.path().join("old.jar");
+    let new_jar = temp_dir.path().join("new.jar");
+
+    write_jar(&old_jar, &[
+        ("a/Foo.class", b"foo"),
+        ("a/Foo$Inner.class", b"inner"),
+        ("a/Bar.class", b"bar"),
+        ("a/package-info.class", b"info"),
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0"),
+    ]);
+    // Same classes in another order, with a changed nested class
+    write_jar(&new_jar, &[
+        ("a/Bar.class", b"bar"),
+        ("a/Foo$Inner.class", b"inner, changed"),
+        ("a/Foo.class", b"foo"),
+    ]);
+
+    let old_keys = cache::source_keys(&old_jar).unwrap();
+    let new_keys = cache::source_keys(&new_jar).unwrap();
+
+    assert_eq!(old_keys.keys().collect::<Vec<_>>(), ["a/Bar.java", "a/Foo.java"]);
+    assert_eq!(old_keys["a/Bar.java"], new_keys["a/Bar.java"]);
+    assert_ne!(old_keys["a/Foo.java"], new_keys["a/Foo.java"]);
+    assert_eq!(old_keys["a/Foo.java"].len(), 64);
+}
+
+#[test]
+fn test_cache_hits_eviction_and_clean() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("cache");
+    let cache = DecompileCache::new(&dir, 25);
+
+    assert!(cache.get(Decompiler::Procyon, "00aa").is_none());
+    cache.put(Decompiler::Procyon, "00aa", "class Old {}").unwrap();
+    cache.put(Decompiler::Procyon, "11bb", "class New {}").unwrap();
+    assert_eq!(cache.get(Decompiler::Procyon, "00aa").as_deref(), Some("class Old {}"));
+    // Each decompiler has its own sources
+    assert!(cache.get(Decompiler::Cfr, "00aa").is_none());
+
+    let stats = cache.stats().unwrap();
+    assert_eq!((stats.entries, stats.size, stats.max_size), (2, 24, 25));
+
+    // The least recently used source goes first
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    for entry in walkdir::WalkDir::new(&dir).into_iter().filter_map(|entry| entry.ok()) {
+        if entry.path().ends_with("11bb.java") {
+            File::options().append(true).open(entry.path()).unwrap().set_modified(an_hour_ago).unwrap();
+        }
+    }
+    cache.put(Decompiler::Cfr, "22cc", "class Cfr {}").unwrap();
+    let eviction = cache.trim().unwrap();
+
+    assert_eq!((eviction.removed, eviction.freed), (1, 12));
+    assert!(cache.get(Decompiler::Procyon, "11bb").is_none());
+    assert!(cache.get(Decompiler::Procyon, "00aa").is_some());
+    assert!(cache.get(Decompiler::Cfr, "22cc").is_some());
+
+    let eviction = cache.clear().unwrap();
+    assert_eq!(eviction.removed, 2);
+    assert!(!dir.exists());
+    assert_eq!(cache.stats().unwrap().entries, 0);
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(cache::parse_size("1048576"), Some(1 << 20));
+    assert_eq!(cache::parse_size("500M"), Some(500 << 20));
+    assert_eq!(cache::parse_size("2g"), Some(2 << 30));
+    assert_eq!(cache::parse_size("64 KB"), Some(64 << 10));
+    assert_eq!(cache::parse_size("1x"), None);
+    assert_eq!(cache::parse_size("M"), None);
+}
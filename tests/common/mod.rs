@@ -0,0 +1,123 @@
+//! Builders for the binary formats the tests feed to apkext
+//!
+//! Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use apkext::apk::{Extractor, UnpackStages};
+use apkext::assets::AssetManager;
+use apkext::config::Config;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// A `ResChunk_header` with `header` after it, then `body`
+pub fn chunk(kind: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+    let header_size = 8 + header.len();
+    let mut out = Vec::new();
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(&(header_size as u16).to_le_bytes());
+    out.extend_from_slice(&((header_size + body.len()) as u32).to_le_bytes());
+    out.extend_from_slice(header);
+    out.extend_from_slice(body);
+    out
+}
+
+/// A UTF-8 string pool
+pub fn string_pool(strings: &[&str]) -> Vec<u8> {
+    let mut offsets = Vec::new();
+    let mut data = Vec::new();
+    for string in strings {
+        offsets.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        data.push(string.chars().count() as u8);
+        data.push(string.len() as u8);
+        data.extend_from_slice(string.as_bytes());
+        data.push(0);
+    }
+    while data.len() % 4 != 0 {
+        data.push(0);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&(1u32 << 8).to_le_bytes());
+    header.extend_from_slice(&(28 + offsets.len() as u32).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    chunk(0x0001, &header, &[offsets, data].concat())
+}
+
+/// A protobuf varint
+pub fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut writer = ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, data) in entries {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+/// A stand-in for `java` that logs which DEX file dex2jar was given and writes the JAR after `-o`
+#[cfg(unix)]
+pub fn fake_dex2jar(dir: &Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join("java");
+    let script = format!(
+        "#!/bin/sh\nfor arg; do case \"$prev\" in -o) echo jar > \"$arg\";; esac; case \"$arg\" in *.dex) basename \"$arg\" >> {};; esac; prev=$arg; done\n",
+        dir.join("converted.log").display()
+    );
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// An extractor that only converts the DEX files, with `fake_dex2jar` in `dir` as Java
+#[cfg(unix)]
+pub fn jar_extractor(dir: &Path) -> Extractor {
+    let mut config = Config::load();
+    config.java.java_path = fake_dex2jar(dir);
+    config.cache = false;
+    config.kotlin = false;
+    Extractor::new(config, AssetManager::new().unwrap()).with_stages(UnpackStages::only_jar())
+}
+
+pub fn sample_apk() -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/sample.apk");
+    fs::read(path).expect("Test APK must exist")
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Offsets of the end of central directory record and of the central directory
+pub fn central_directory(apk: &[u8]) -> (usize, usize) {
+    let eocd = (0..=apk.len() - 22).rev().find(|&pos| read_u32(apk, pos) == 0x0605_4b50).expect("APK must be a ZIP");
+    (eocd, read_u32(apk, eocd + 16) as usize)
+}
+
+/// Start of the APK Signing Block in front of the central directory at `cd_offset`, or `None`
+/// if the APK has none
+pub fn signing_block_start(apk: &[u8], cd_offset: usize) -> Option<usize> {
+    if &apk[cd_offset - 16..cd_offset] != b"APK Sig Block 42" {
+        return None;
+    }
+    let block_size = read_u64(apk, cd_offset - 24) as usize;
+    let block_start = cd_offset - block_size - 8;
+    assert_eq!(read_u64(apk, block_start) as usize, block_size, "Signing block size fields must agree");
+    Some(block_start)
+}
@@ -0,0 +1,90 @@
+use apkext::apk::decompile;
+use apkext::tools::Decompiler;
+use clap::ValueEnum;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+#[test]
+fn test_decompiler_names_parse() {
+    for (name, expected) in [
+        ("procyon", Decompiler::Procyon),
+        ("cfr", Decompiler::Cfr),
+        ("vineflower", Decompiler::Vineflower),
+        ("fernflower", Decompiler::Vineflower),
+        ("jadx", Decompiler::Jadx),
+    ] {
+        assert_eq!(Decompiler::from_str(name, true).unwrap(), expected, "CLI name {}", name);
+        let parsed: Decompiler = serde_json::from_value(serde_json::json!(name)).unwrap();
+        assert_eq!(parsed, expected, "Config name {}", name);
+    }
+
+    assert_eq!(Decompiler::default(), Decompiler::Procyon);
+}
+
+#[test]
+fn test_decompiler_args_reference_input_and_output() {
+    let tool_jar = Path::new("/tools/jars/decompiler.jar");
+    let jar = Path::new("/work/classes.jar");
+    let output_dir = Path::new("/work/src");
+
+    for decompiler in Decompiler::value_variants() {
+        let args = decompiler.java_args(tool_jar, jar, output_dir);
+
+        for expected in ["/tools/jars/decompiler.jar", "/work/classes.jar", "/work/src"] {
+            assert!(args.iter().any(|arg| arg == expected), "{} arguments must contain {}: {:?}", decompiler, expected, args);
+        }
+    }
+}
+
+#[test]
+fn test_fallback_decompiler_selection() {
+    let mut config = apkext::config::Config::load();
+    assert_eq!(config.fallback_decompiler(), Some(Decompiler::Vineflower));
+
+    config.decompiler = Decompiler::Vineflower;
+    assert_eq!(config.fallback_decompiler(), Some(Decompiler::Cfr));
+
+    config.fallback_decompiler = Some(Decompiler::Vineflower);
+    assert_eq!(config.fallback_decompiler(), None, "The fallback must differ from the primary decompiler");
+
+    config.fallback_decompiler = Some(Decompiler::Jadx);
+    config.fallback = false;
+    assert_eq!(config.fallback_decompiler(), None);
+}
+
+#[test]
+fn test_failed_output_detection() {
+    assert!(decompile::looks_failed(""));
+    assert!(decompile::looks_failed("class A {\n    // This method could not be decompiled.\n}"));
+    assert!(decompile::looks_failed("class A {\n    // $VF: Couldn't be decompiled\n}"));
+    assert!(decompile::looks_failed("/*\n * Exception decompiling\n */"));
+    assert!(!decompile::looks_failed("package a;\n\nclass A {\n    void f() {}\n}\n"));
+}
+
+#[test]
+fn test_subset_jar_keeps_nested_classes() {
+    let temp_dir = TempDir::new().unwrap();
+    let jar = temp_dir.path().join("classes.jar");
+
+    let mut writer = ZipWriter::new(fs::File::create(&jar).unwrap());
+    for name in ["a/Foo.class", "a/Foo$1.class", "a/Foo$Inner.class", "a/Bar.class", "a/package-info.class", "META-INF/MANIFEST.MF"] {
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(b"data").unwrap();
+    }
+    writer.finish().unwrap();
+
+    let sources = decompile::top_level_sources(&jar).unwrap();
+    assert_eq!(sources, vec!["a/Bar.java".to_string(), "a/Foo.java".to_string()]);
+
+    let subset = temp_dir.path().join("subset.jar");
+    decompile::write_subset_jar(&jar, &["a/Foo.java".to_string()], &subset).unwrap();
+
+    let archive = ZipArchive::new(fs::File::open(&subset).unwrap()).unwrap();
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(names, vec!["a/Foo$1.class", "a/Foo$Inner.class", "a/Foo.class"]);
+}
@@ -0,0 +1,88 @@
+use apkext::dex::access::{self, MemberKind};
+use apkext::dex::{DexFile, DexIndex};
+use std::path::Path;
+use tempfile::TempDir;
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+
+#[test]
+fn test_header_and_class_members() {
+    let data = apkext::manifest::read_entry(Path::new(SAMPLE_APK), "classes.dex").unwrap();
+    let dex = DexFile::parse(&data).unwrap();
+
+    assert_eq!(dex.header.version, "035");
+    assert_eq!(dex.header.file_size as usize, data.len());
+    assert_eq!(dex.header.signature.len(), 40);
+
+    let class = dex.classes.iter().find(|class| class.name == "Lio/selendroid/testapp/WebViewActivity;").unwrap();
+    let fields: Vec<&str> = class.fields.iter().map(|field| dex.fields[field.field as usize].name.as_str()).collect();
+    assert_eq!(fields, ["arrayAdapter", "mainWebView", "server", "testDataSpinner"]);
+    assert!(class.fields.iter().all(|field| field.access_flags == access::ACC_PRIVATE));
+
+    let mut byte_swapped = data.clone();
+    byte_swapped[0x28..0x2c].copy_from_slice(&0x1234_5678u32.to_be_bytes());
+    assert!(DexFile::parse(&byte_swapped).is_err());
+}
+
+#[test]
+fn test_index_lookup_and_declarations() {
+    let index = DexIndex::from_path(Path::new(SAMPLE_APK)).unwrap();
+
+    assert_eq!(index.dex_files, ["classes.dex"]);
+    assert!(index.classes.windows(2).all(|pair| pair[0].name < pair[1].name));
+
+    let class = index.class("io.selendroid.testapp.WebViewActivity").unwrap();
+    assert_eq!(class.dex, "classes.dex");
+    assert_eq!(class.source_file.as_deref(), Some("WebViewActivity.java"));
+    assert_eq!(class.declaration(), "public class io.selendroid.testapp.WebViewActivity extends android.app.Activity");
+    assert_eq!(class.fields[2].declaration(), "private io.selendroid.testapp.server.HttpServer server");
+
+    let on_create = class.methods.iter().find(|method| method.name == "onCreate").unwrap();
+    assert_eq!(on_create.descriptor, "(Landroid/os/Bundle;)V");
+    assert_eq!(on_create.declaration("WebViewActivity"), "public void onCreate(android.os.Bundle)");
+    assert!(on_create.code_units.unwrap() > 0);
+    let constructor = class.methods.iter().find(|method| method.name == "<init>").unwrap();
+    assert_eq!(constructor.declaration("WebViewActivity"), "public WebViewActivity()");
+    assert_eq!(constructor.flags, ["public", "constructor"]);
+
+    // Descriptors and internal names find the same class
+    for name in ["Lio/selendroid/testapp/WebViewActivity;", "io/selendroid/testapp/WebViewActivity"] {
+        assert_eq!(index.class(name).unwrap().name, class.name);
+    }
+    assert!(index.class("io.selendroid.testapp.Missing").is_none());
+
+    let nested = index.class("io.selendroid.testapp.HomeScreenActivity$1").unwrap();
+    assert!(nested.declaration().ends_with("implements android.view.View$OnLongClickListener"));
+    let text = nested.to_string();
+    assert!(text.contains("\nMethods ("), "{}", text);
+
+    let server: Vec<&str> = index.matching(".server.").map(|class| class.name.as_str()).collect();
+    assert!(server.contains(&"io.selendroid.testapp.server.HttpServer"));
+    assert!(server.iter().all(|name| name.contains(".server.")));
+
+    // A single DEX file indexes the same way
+    let temp_dir = TempDir::new().unwrap();
+    let dex_path = temp_dir.path().join("classes.dex");
+    std::fs::write(&dex_path, apkext::manifest::read_entry(Path::new(SAMPLE_APK), "classes.dex").unwrap()).unwrap();
+    assert_eq!(DexIndex::from_path(&dex_path).unwrap().classes.len(), index.classes.len());
+}
+
+#[test]
+fn test_access_flags() {
+    let flags = access::ACC_PUBLIC | access::ACC_STATIC | access::ACC_VOLATILE | access::ACC_SYNTHETIC;
+    assert_eq!(access::names(flags, MemberKind::Field), ["public", "static", "volatile", "synthetic"]);
+    // The same bit means bridge on a method
+    assert_eq!(access::names(flags, MemberKind::Method), ["public", "static", "bridge", "synthetic"]);
+    assert_eq!(access::java_modifiers(flags, MemberKind::Method), ["public", "static"]);
+
+    let synchronized = access::ACC_PRIVATE | access::ACC_FINAL | access::ACC_DECLARED_SYNCHRONIZED;
+    assert_eq!(access::names(synchronized, MemberKind::Method), ["private", "final", "declared-synchronized"]);
+    assert_eq!(access::java_modifiers(synchronized, MemberKind::Method), ["private", "final", "synchronized"]);
+
+    let interface = access::ACC_PUBLIC | access::ACC_INTERFACE | access::ACC_ABSTRACT;
+    assert_eq!(access::java_modifiers(interface, MemberKind::Class), ["public"]);
+    assert_eq!(access::class_kind(interface), "interface");
+    assert_eq!(access::class_kind(interface | access::ACC_ANNOTATION), "@interface");
+    assert_eq!(access::class_kind(access::ACC_FINAL | access::ACC_ENUM), "enum");
+    assert!(access::java_modifiers(access::ACC_FINAL | access::ACC_ENUM, MemberKind::Class).is_empty());
+}
@@ -0,0 +1,175 @@
+use apkext::apk::ApkDiff;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+
+fn sample_entry(name: &str) -> Vec<u8> {
+    apkext::manifest::read_entry(Path::new(SAMPLE_APK), name).unwrap()
+}
+
+/// Replace `from` with `to` (same length) everywhere in `data`
+fn patch(data: &mut [u8], from: &[u8], to: &[u8]) {
+    assert_eq!(from.len(), to.len());
+    let positions: Vec<usize> = data.windows(from.len()).enumerate().filter(|(_, window)| *window == from).map(|(pos, _)| pos).collect();
+    assert!(!positions.is_empty(), "{:?} not found", String::from_utf8_lossy(from));
+    for pos in positions {
+        data[pos..pos + to.len()].copy_from_slice(to);
+    }
+}
+
+/// Copy the sample APK, replacing, adding and dropping entries
+fn write_apk(path: &Path, replace: &[(&str, Vec<u8>)], drop: &[&str]) {
+    let mut archive = ZipArchive::new(Cursor::new(fs::read(SAMPLE_APK).unwrap())).unwrap();
+    let mut writer = ZipWriter::new(fs::File::create(path).unwrap());
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).unwrap();
+        let name = entry.name().to_string();
+        if drop.iter().any(|dropped| name.starts_with(dropped)) || replace.iter().any(|(replaced, _)| *replaced == name) {
+            continue;
+        }
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).unwrap();
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(&contents).unwrap();
+    }
+    for (name, contents) in replace {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(contents).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+#[test]
+fn test_identical_apks() {
+    let diff = ApkDiff::compare(Path::new(SAMPLE_APK), Path::new(SAMPLE_APK)).unwrap();
+
+    assert!(diff.is_empty(), "{}", diff);
+    assert!(diff.notes.is_empty());
+    assert!(diff.to_string().ends_with("\nNo differences\n"));
+}
+
+#[test]
+fn test_code_resource_and_file_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let new_apk = temp_dir.path().join("new.apk");
+
+    // A changed string constant changes the one method that loads it
+    let mut dex = sample_entry("classes.dex");
+    patch(&mut dex, b"localhost:4450", b"localhost:4451");
+    // Renaming a class shows up as one class removed and one added
+    patch(&mut dex, b"Lio/selendroid/testapp/WebViewActivity;", b"Lio/selendroid/testapp/WebViewActivitz;");
+
+    let mut table = sample_entry("resources.arsc");
+    patch(&mut table, b"Hello Germany", b"Hallo Germany");
+
+    write_apk(
+        &new_apk,
+        &[
+            ("classes.dex", dex),
+            ("resources.arsc", table),
+            ("lib/arm64-v8a/libapp.so", b"\x7fELF".to_vec()),
+            ("res/drawable-ldpi/icon.png", b"not a png".to_vec()),
+        ],
+        &["META-INF/"],
+    );
+
+    let diff = ApkDiff::compare(Path::new(SAMPLE_APK), &new_apk).unwrap();
+
+    assert!(diff.manifest.is_empty() && diff.permissions.is_empty() && diff.components.is_empty());
+    assert_eq!(diff.classes.added, ["io.selendroid.testapp.WebViewActivitz"]);
+    assert_eq!(diff.classes.removed, ["io.selendroid.testapp.WebViewActivity"]);
+    assert!(diff.methods.changed.iter().any(|method| method.starts_with("io.selendroid.testapp.server.HttpServer.serve(")), "{:?}", diff.methods);
+    // Methods whose signature mentions the renamed class
+    assert_eq!(diff.methods.added, [
+        "io.selendroid.testapp.WebViewActivity$1.<init>(Lio/selendroid/testapp/WebViewActivitz;)V",
+        "io.selendroid.testapp.WebViewActivity$SpinnerItem.<init>(Lio/selendroid/testapp/WebViewActivitz;Ljava/lang/String;Ljava/lang/String;)V",
+    ]);
+    assert_eq!(diff.methods.removed.len(), 2);
+
+    assert_eq!(diff.resources.changed, ["string/hello (de)", "res/drawable-ldpi/icon.png"]);
+    assert_eq!(diff.native_libraries.added, ["lib/arm64-v8a/libapp.so"]);
+    assert!(diff.assets.is_empty());
+    assert_eq!(diff.certificates.removed.len(), 1);
+    assert!(diff.certificates.removed[0].contains("Android Debug"));
+
+    let text = diff.to_string();
+    assert!(text.contains("\nClasses (+1 -1 ~"), "{}", text);
+    assert!(text.contains("  + io.selendroid.testapp.WebViewActivitz\n"));
+    let json = serde_json::to_value(&diff).unwrap();
+    assert_eq!(json["native_libraries"]["added"][0], "lib/arm64-v8a/libapp.so");
+}
+
+#[test]
+fn test_unpacked_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let write = |path: &Path, contents: &str| {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    };
+
+    for (name, version, permissions, greeting, line, extra_method) in [
+        ("old", "1.0", "", "Hello", 10, ""),
+        ("new", "1.1", r#"<uses-permission android:name="android.permission.CAMERA"/>"#, "Hello &amp; welcome", 12, ".method public onPause()V\n    .locals 0\n    return-void\n.end method\n"),
+    ] {
+        let project = temp_dir.path().join(name).join("unpacked");
+        write(
+            &project.join("AndroidManifest.xml"),
+            &format!(
+                r#"<?xml version="1.0" encoding="utf-8" standalone="no"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example">
+    {}
+    <application android:label="@string/app_name">
+        <activity android:name=".MainActivity" android:exported="true"/>
+    </application>
+</manifest>"#,
+                permissions
+            ),
+        );
+        write(&project.join("apktool.yml"), &format!("sdkInfo:\n  minSdkVersion: 21\nversionInfo:\n  versionCode: 1\n  versionName: {}\n", version));
+        write(&project.join("res/values/strings.xml"), &format!("<resources>\n    <string name=\"greeting\">{}</string>\n</resources>\n", greeting));
+        write(&project.join("assets").join(format!("{}.txt", name)), name);
+        // Only the debug line numbers of onCreate differ; the greeting constant of greet does
+        write(
+            &project.join("smali/com/example/MainActivity.smali"),
+            &format!(
+                ".class public Lcom/example/MainActivity;\n.super Landroid/app/Activity;\n.source \"MainActivity.java\"\n\n\
+                 .method public onCreate(Landroid/os/Bundle;)V\n    .locals 0\n    .param p1, \"state\"\n    .line {}\n    invoke-super {{p0, p1}}, Landroid/app/Activity;->onCreate(Landroid/os/Bundle;)V\n    return-void\n.end method\n\n\
+                 .method public greet()Ljava/lang/String;\n    .locals 1\n    const-string v0, \"{}\"\n    return-object v0\n.end method\n\n{}",
+                line, greeting, extra_method
+            ),
+        );
+    }
+    fs::copy(SAMPLE_APK, temp_dir.path().join("new.apk")).unwrap();
+
+    let diff = ApkDiff::compare(&temp_dir.path().join("old"), &temp_dir.path().join("new")).unwrap();
+
+    assert_eq!(diff.manifest.len(), 1);
+    assert_eq!(diff.manifest[0].field, "version_name");
+    assert_eq!((diff.manifest[0].old.as_deref(), diff.manifest[0].new.as_deref()), (Some("1.0"), Some("1.1")));
+    assert_eq!(diff.permissions.added, ["android.permission.CAMERA"]);
+    assert!(diff.components.is_empty());
+    assert_eq!(diff.resources.changed, ["string/greeting (default)"]);
+    assert_eq!(diff.assets.added, ["assets/new.txt"]);
+    assert_eq!(diff.assets.removed, ["assets/old.txt"]);
+    assert!(diff.notes.is_empty(), "{:?}", diff.notes);
+    assert_eq!(diff.classes.changed, ["com.example.MainActivity"]);
+    assert_eq!(diff.methods.added, ["com.example.MainActivity.onPause()V"]);
+    assert_eq!(diff.methods.changed, ["com.example.MainActivity.greet()Ljava/lang/String;"]);
+
+    // Without smali, the classes come from the DEX files next to the project
+    fs::remove_dir_all(temp_dir.path().join("old/unpacked/smali")).unwrap();
+    fs::write(temp_dir.path().join("old/classes.dex"), sample_entry("classes.dex")).unwrap();
+    let diff = ApkDiff::compare(&temp_dir.path().join("old"), &temp_dir.path().join("new")).unwrap();
+    assert!(diff.classes.is_empty() && diff.methods.is_empty());
+    assert!(diff.notes[0].contains("come from DEX files but those of"), "{:?}", diff.notes);
+
+    let mixed = ApkDiff::compare(&temp_dir.path().join("old"), &temp_dir.path().join("new.apk"));
+    assert!(mixed.unwrap_err().to_string().contains("two APKs or two unpacked directories"));
+    let missing = ApkDiff::compare(Path::new(SAMPLE_APK), Path::new("missing.apk"));
+    assert_eq!(missing.unwrap_err().exit_code(), 5);
+}
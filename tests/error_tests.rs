@@ -0,0 +1,53 @@
+use apkext::apk::Extractor;
+use apkext::assets::AssetManager;
+use apkext::config::Config;
+use apkext::ApkextError;
+use std::path::PathBuf;
+
+#[test]
+fn test_tool_failure_carries_exit_code_and_stderr() {
+    let error = ApkextError::ToolFailed {
+        tool: "Apktool".to_string(),
+        code: Some(1),
+        stderr: "brut.androlib.AndrolibException".to_string(),
+    };
+
+    assert_eq!(error.to_string(), "Apktool failed with exit code 1: brut.androlib.AndrolibException");
+    assert_eq!(error.exit_code(), 3);
+}
+
+#[test]
+fn test_signature_failure_exit_code() {
+    let error = ApkextError::SignatureInvalid {
+        path: PathBuf::from("App.apk"),
+        reason: "v1 did not verify".to_string(),
+    };
+
+    assert_eq!(error.to_string(), "Signature verification of 'App.apk' failed: v1 did not verify");
+    assert_eq!(error.exit_code(), 8);
+}
+
+#[test]
+fn test_typed_errors_survive_anyhow() {
+    let wrapped: anyhow::Error = ApkextError::OutputExists(PathBuf::from("App")).into();
+    assert!(matches!(ApkextError::from(wrapped), ApkextError::OutputExists(_)));
+
+    let io: anyhow::Error = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied").into();
+    assert!(matches!(ApkextError::from(io), ApkextError::Io(_)));
+
+    let other = ApkextError::from(anyhow::anyhow!("something else"));
+    assert!(matches!(other, ApkextError::Other(_)));
+    assert_eq!(other.exit_code(), 1);
+}
+
+#[tokio::test]
+async fn test_unpack_rejects_invalid_apk() {
+    let extractor = Extractor::new(Config::load(), AssetManager::new().unwrap());
+
+    let error = extractor.unpack("does-not-exist.apk").await.unwrap_err();
+    assert!(matches!(error, ApkextError::InvalidApk { .. }), "{:?}", error);
+    assert_eq!(error.exit_code(), 5);
+
+    let error = extractor.unpack("App.zip").await.unwrap_err();
+    assert!(matches!(error, ApkextError::InvalidApk { .. }), "{:?}", error);
+}
@@ -0,0 +1,84 @@
+use apkext::apk::info::{self, ApkInfo};
+use apkext::ApkextError;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+
+#[test]
+fn test_sample_info() {
+    let info = ApkInfo::read(Path::new(SAMPLE_APK)).unwrap();
+
+    assert_eq!(info.file_size, fs::metadata(SAMPLE_APK).unwrap().len());
+    assert_eq!(info.manifest.package, "io.selendroid.testapp");
+    assert_eq!(info.dex_files, ["classes.dex"]);
+    assert!(info.abis.is_empty());
+    assert_eq!(info.signing.schemes, ["v1"]);
+    assert_eq!(info.signing.certificates.len(), 1);
+    assert_eq!(info.label.as_deref(), Some("selendroid-test-app"));
+    assert_eq!(info.locales, ["de", "es", "fr", "it"]);
+
+    // Largest first, and every entry counted once
+    let categories: Vec<&str> = info.sizes.iter().map(|size| size.category.as_str()).collect();
+    assert_eq!(categories[0], "dex");
+    assert!(categories.contains(&"resources") && categories.contains(&"assets") && categories.contains(&"signature"));
+    let files: usize = info.sizes.iter().map(|size| size.files).sum();
+    let archive = zip::ZipArchive::new(fs::File::open(SAMPLE_APK).unwrap()).unwrap();
+    assert_eq!(files, archive.len());
+
+    let text = info.to_string();
+    assert!(text.starts_with("Package:      io.selendroid.testapp\n"));
+    assert!(text.contains("Activities (7):\n  io.selendroid.testapp.HomeScreenActivity\n"));
+
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!(json["manifest"]["min_sdk"], 10);
+    assert_eq!(json["signing"]["schemes"][0], "v1");
+}
+
+#[test]
+fn test_native_libraries_and_multidex() {
+    let temp_dir = TempDir::new().unwrap();
+    let apk = temp_dir.path().join("app.apk");
+
+    let manifest = apkext::manifest::read_entry(Path::new(SAMPLE_APK), "AndroidManifest.xml").unwrap();
+    let mut writer = ZipWriter::new(fs::File::create(&apk).unwrap());
+    for (name, data) in [
+        ("AndroidManifest.xml", manifest.as_slice()),
+        ("classes2.dex", b"dex2"),
+        ("classes.dex", b"dex1"),
+        ("lib/x86_64/libapp.so", b"elf"),
+        ("lib/arm64-v8a/libapp.so", b"elf"),
+        ("lib/arm64-v8a/libother.so", b"elf"),
+    ] {
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let info = ApkInfo::read(&apk).unwrap();
+    assert_eq!(info.dex_files, ["classes.dex", "classes2.dex"]);
+    assert_eq!(info.abis, ["arm64-v8a", "x86_64"]);
+    assert!(info.signing.schemes.is_empty());
+    assert_eq!(info.sizes.iter().find(|size| size.category == "native").unwrap().files, 3);
+}
+
+#[test]
+fn test_invalid_apk() {
+    let temp_dir = TempDir::new().unwrap();
+    let not_zip = temp_dir.path().join("broken.apk");
+    fs::write(&not_zip, b"not a zip").unwrap();
+
+    assert!(matches!(ApkInfo::read(&not_zip), Err(ApkextError::InvalidApk { .. })));
+    assert!(matches!(ApkInfo::read(&temp_dir.path().join("missing.apk")), Err(ApkextError::InvalidApk { .. })));
+}
+
+#[test]
+fn test_format_size() {
+    assert_eq!(info::format_size(512), "512 B");
+    assert_eq!(info::format_size(2048), "2.0 KB");
+    assert_eq!(info::format_size(5 * 1024 * 1024 + 512 * 1024), "5.5 MB");
+}
@@ -628,4 +628,36 @@ fn test_error_handling_missing_input() {
     assert!(!stderr.is_empty(), "Should provide error message for missing APK");
 
     println!("Error message for missing APK: {}", stderr);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_unpack_refuses_existing_output_dir() {
+    let utils = TestUtils::new();
+    utils.ensure_binary_exists().expect("Binary must exist");
+    utils.ensure_test_apk_exists().expect("Test APK must exist");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let work_dir = temp_dir.path();
+
+    // An output directory holding manual edits must not be touched without --force or --merge
+    let output_dir = work_dir.join("edited");
+    fs::create_dir_all(&output_dir).unwrap();
+    let edited_file = output_dir.join("MyChanges.java");
+    fs::write(&edited_file, b"class MyChanges {}").unwrap();
+
+    let output = Command::new(&utils.binary_path)
+        .arg("unpack")
+        .arg(&utils.test_apk)
+        .arg("--output")
+        .arg(&output_dir)
+        .current_dir(work_dir)
+        .output()
+        .expect("Failed to run unpack command");
+
+    assert!(!output.status.success(), "Unpack must refuse an existing output directory");
+    assert_eq!(output.status.code(), Some(6), "An existing output directory has its own exit code");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--force"), "Error should point to --force and --merge: {}", stderr);
+    assert!(edited_file.exists(), "Existing files must be left alone");
+}
@@ -0,0 +1,336 @@
+use apkext::kotlin::classfile::ClassFile;
+use apkext::kotlin::{self, FacadeKind, KotlinReport};
+use std::fs;
+use tempfile::TempDir;
+
+mod common;
+
+use common::{push_varint};
+
+/// Minimal protobuf encoder for building Kotlin metadata by hand
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(mut self, number: u32, value: u64) -> Self {
+        push_varint(&mut self.0, (number as u64) << 3);
+        push_varint(&mut self.0, value);
+        self
+    }
+
+    fn message(mut self, number: u32, message: Message) -> Self {
+        push_varint(&mut self.0, ((number as u64) << 3) | 2);
+        push_varint(&mut self.0, message.0.len() as u64);
+        self.0.extend_from_slice(&message.0);
+        self
+    }
+}
+
+/// A type naming the class at `index` of `d2`
+fn class_type(index: u64) -> Message {
+    Message::default().varint(6, index)
+}
+
+fn nullable(index: u64) -> Message {
+    class_type(index).varint(3, 1)
+}
+
+/// A class file with nothing but a name, a source file and a `@kotlin.Metadata` annotation
+///
+/// `d1` is written in the one-byte-per-char form, after an empty string table.
+fn class_file(name: &str, source_file: &str, kind: i32, message: Message, d2: &[&str]) -> Vec<u8> {
+    class_file_with_string_table(name, source_file, kind, Message::default(), message, d2)
+}
+
+/// Like `class_file`, with `string_table` as the StringTableTypes in front of the message
+fn class_file_with_string_table(name: &str, source_file: &str, kind: i32, string_table: Message, message: Message, d2: &[&str]) -> Vec<u8> {
+    let mut d1 = vec![0u8];
+    push_varint(&mut d1, string_table.0.len() as u64);
+    d1.extend(&string_table.0);
+    d1.extend(&message.0);
+
+    let mut pool: Vec<Vec<u8>> = Vec::new();
+    let utf8 = |pool: &mut Vec<Vec<u8>>, chars: &[u16]| -> u16 {
+        let mut entry = vec![1, 0, 0];
+        for &char in chars {
+            match char {
+                0x01..=0x7f => entry.push(char as u8),
+                0x00 | 0x80..=0x7ff => entry.extend([0xc0 | (char >> 6) as u8, 0x80 | (char & 0x3f) as u8]),
+                _ => entry.extend([0xe0 | (char >> 12) as u8, 0x80 | (char >> 6 & 0x3f) as u8, 0x80 | (char & 0x3f) as u8]),
+            }
+        }
+        let len = (entry.len() - 3) as u16;
+        entry[1..3].copy_from_slice(&len.to_be_bytes());
+        pool.push(entry);
+        pool.len() as u16
+    };
+    let text = |text: &str| text.encode_utf16().collect::<Vec<u16>>();
+
+    let this_name = utf8(&mut pool, &text(name));
+    pool.push(vec![7, (this_name >> 8) as u8, this_name as u8]);
+    let this_class = pool.len() as u16;
+    let super_name = utf8(&mut pool, &text("java/lang/Object"));
+    pool.push(vec![7, (super_name >> 8) as u8, super_name as u8]);
+    let super_class = pool.len() as u16;
+    let source_attribute = utf8(&mut pool, &text("SourceFile"));
+    let source = utf8(&mut pool, &text(source_file));
+    let annotations_attribute = utf8(&mut pool, &text("RuntimeVisibleAnnotations"));
+    let metadata = utf8(&mut pool, &text("Lkotlin/Metadata;"));
+
+    let mut arguments = Vec::new();
+    let int_argument = |pool: &mut Vec<Vec<u8>>, arguments: &mut Vec<u8>, name: u16, values: &[i32], array: bool| {
+        arguments.extend(name.to_be_bytes());
+        if array {
+            arguments.push(b'[');
+            arguments.extend((values.len() as u16).to_be_bytes());
+        }
+        for value in values {
+            let mut entry = vec![3];
+            entry.extend(value.to_be_bytes());
+            pool.push(entry);
+            arguments.push(b'I');
+            arguments.extend((pool.len() as u16).to_be_bytes());
+        }
+    };
+    let k = utf8(&mut pool, &text("k"));
+    int_argument(&mut pool, &mut arguments, k, &[kind], false);
+    let mv = utf8(&mut pool, &text("mv"));
+    int_argument(&mut pool, &mut arguments, mv, &[1, 9, 0], true);
+
+    for (key, strings) in [("d1", vec![d1.iter().map(|&byte| byte as u16).collect::<Vec<u16>>()]), ("d2", d2.iter().map(|string| text(string)).collect())] {
+        let key = utf8(&mut pool, &text(key));
+        arguments.extend(key.to_be_bytes());
+        arguments.push(b'[');
+        arguments.extend((strings.len() as u16).to_be_bytes());
+        for string in strings {
+            let index = utf8(&mut pool, &string);
+            arguments.push(b's');
+            arguments.extend(index.to_be_bytes());
+        }
+    }
+
+    let mut class = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52];
+    class.extend((pool.len() as u16 + 1).to_be_bytes());
+    class.extend(pool.concat());
+    class.extend([0x00, 0x31]);
+    class.extend(this_class.to_be_bytes());
+    class.extend(super_class.to_be_bytes());
+    class.extend([0, 0, 0, 0, 0, 0]); // interfaces, fields, methods
+    class.extend(2u16.to_be_bytes());
+    class.extend(source_attribute.to_be_bytes());
+    class.extend(2u32.to_be_bytes());
+    class.extend(source.to_be_bytes());
+    class.extend(annotations_attribute.to_be_bytes());
+    class.extend((arguments.len() as u32 + 6).to_be_bytes());
+    class.extend(1u16.to_be_bytes());
+    class.extend(metadata.to_be_bytes());
+    class.extend(4u16.to_be_bytes());
+    class.extend(arguments);
+    class
+}
+
+/// `data class User(name: String, age: Int?)` with a companion object, `fun greet(times: Int = 1): String?`
+/// and `var age: Int?`, plus `fun String.shout(): String` in `Utils.kt`
+fn kotlin_classes() -> Vec<Vec<u8>> {
+    let user_strings = [
+        "com/example/User", "Companion", "name", "kotlin/String", "age", "kotlin/Int", "greet", "times",
+        "(Ljava/lang/String;Ljava/lang/Integer;)V", "(I)Ljava/lang/String;", "Ljava/lang/String;", "getName", "()Ljava/lang/String;", "Ljava/lang/Integer;",
+    ];
+    let parameter = |name: u64, parameter_type: Message| Message::default().varint(2, name).message(3, parameter_type);
+    let signature = |desc: u64| Message::default().varint(2, desc);
+    let user = Message::default()
+        // public final data class
+        .varint(1, 1 << 10 | 6)
+        .varint(3, 0)
+        .varint(4, 1)
+        .varint(7, 1)
+        .message(8, Message::default().message(2, parameter(2, class_type(3))).message(2, parameter(4, nullable(5))).message(100, signature(8)))
+        .message(9, Message::default().varint(2, 6).message(3, nullable(3)).message(6, parameter(7, class_type(5)).varint(1, 2)).message(100, signature(9)))
+        .message(10, Message::default().varint(2, 2).message(3, class_type(3)).message(100, Message::default()
+            .message(1, Message::default().varint(1, 2).varint(2, 10))
+            .message(3, Message::default().varint(1, 11).varint(2, 12))))
+        // var
+        .message(10, Message::default().varint(11, 518 | 1 << 8).varint(2, 4).message(3, nullable(5)).message(100, Message::default()
+            .message(1, Message::default().varint(2, 13))));
+
+    // private constructor of a companion object
+    let companion = Message::default()
+        .varint(1, 6 << 6 | 6)
+        .varint(3, 0)
+        .message(8, Message::default().varint(1, 2).message(100, signature(1)));
+
+    let utils = Message::default().message(3, Message::default().varint(2, 0).message(5, class_type(1)).message(3, class_type(1)));
+
+    vec![
+        class_file("com/example/User", "User.kt", 1, user, &user_strings),
+        class_file("com/example/User$Companion", "User.kt", 1, companion, &["com/example/User.Companion", "()V"]),
+        class_file("com/example/UtilsKt", "Utils.kt", 2, utils, &["shout", "kotlin/String"]),
+        // A lambda
+        class_file("com/example/User$greet$1", "User.kt", 3, Message::default(), &[]),
+    ]
+}
+
+fn report() -> KotlinReport {
+    let mut report = KotlinReport::default();
+    for class in kotlin_classes() {
+        report.add_class(&class).unwrap();
+    }
+    report
+}
+
+#[test]
+fn test_metadata_declarations() {
+    let report = report();
+    assert_eq!((report.classes.len(), report.facades.len(), report.synthetic_classes), (2, 1, 1));
+    assert!(report.errors.is_empty());
+
+    let user = &report.classes[0];
+    assert_eq!(user.jvm_class, "com.example.User");
+    assert_eq!(user.name, "com.example.User");
+    assert_eq!(user.declaration, "data class User(name: String, age: Int?)");
+    assert_eq!(user.modifiers, ["data"]);
+    assert_eq!(user.source_file.as_deref(), Some("User.kt"));
+    assert_eq!(user.companion_object.as_deref(), Some("com.example.User$Companion"));
+    assert_eq!(user.nested_classes, ["com.example.User$Companion"]);
+    assert_eq!(user.constructors[0].jvm_signature.as_deref(), Some("<init>(Ljava/lang/String;Ljava/lang/Integer;)V"));
+    assert_eq!(user.functions[0].declaration, "fun greet(times: Int = ...): String?");
+    assert_eq!(user.functions[0].jvm_signature.as_deref(), Some("greet(I)Ljava/lang/String;"));
+    assert_eq!(user.properties[0].declaration, "val name: String");
+    assert_eq!((user.properties[0].field.as_deref(), user.properties[0].getter.as_deref()), (Some("name:Ljava/lang/String;"), Some("getName()Ljava/lang/String;")));
+    // The field name defaults to the property name
+    assert_eq!(user.properties[1].declaration, "var age: Int?");
+    assert_eq!(user.properties[1].field.as_deref(), Some("age:Ljava/lang/Integer;"));
+
+    let companion = &report.classes[1];
+    assert_eq!((companion.kind.as_str(), companion.declaration.as_str()), ("companion object", "companion object Companion"));
+    assert_eq!(companion.constructors[0].declaration, "private constructor()");
+
+    let utils = &report.facades[0];
+    assert_eq!((utils.jvm_class.as_str(), utils.kind, utils.source_file.as_deref()), ("com.example.UtilsKt", FacadeKind::File, Some("Utils.kt")));
+    assert_eq!(utils.functions[0].declaration, "fun String.shout(): String");
+
+    // Classes without metadata are not Kotlin
+    let mut plain = KotlinReport::default();
+    plain.add_class(b"\xca\xfe\xba\xbe not kotlin").unwrap();
+    assert!(plain.is_empty());
+}
+
+#[test]
+fn test_annotate_sources() {
+    let temp_dir = TempDir::new().unwrap();
+    let src_dir = temp_dir.path();
+    fs::create_dir_all(src_dir.join("com/example")).unwrap();
+    // As Procyon writes them
+    fs::write(src_dir.join("com/example/User.java"), [
+        "package com.example;",
+        "",
+        "public final class User",
+        "{",
+        "    @NotNull",
+        "    private final String name;",
+        "    @Nullable",
+        "    private Integer age;",
+        "    ",
+        "    public User(@NotNull final String name, @Nullable final Integer age) {",
+        "        this.name = name;",
+        "        this.age = age;",
+        "    }",
+        "    ",
+        "    @NotNull",
+        "    public final String getName() {",
+        "        return this.name;",
+        "    }",
+        "    ",
+        "    @Nullable",
+        "    public final String greet(final int times) {",
+        "        final String name = this.getName();",
+        "        return \"}\" + name;",
+        "    }",
+        "    ",
+        "    public static final class Companion",
+        "    {",
+        "        private Companion() {",
+        "        }",
+        "    }",
+        "}",
+        "",
+    ]
+    .join("\n"))
+    .unwrap();
+    fs::write(src_dir.join("com/example/UtilsKt.java"), "package com.example;\n\npublic final class UtilsKt\n{\n    @NotNull\n    public static final String shout(@NotNull final String $this$shout) {\n        return $this$shout;\n    }\n}\n").unwrap();
+
+    let report = report();
+    assert_eq!(kotlin::annotate_sources(&report, src_dir).unwrap(), 2);
+
+    let user = fs::read_to_string(src_dir.join("com/example/User.java")).unwrap();
+    let comments: Vec<&str> = user.lines().filter(|line| line.contains(kotlin::annotate::COMMENT_PREFIX)).map(str::trim).collect();
+    assert_eq!(comments, [
+        "// Kotlin: data class User(name: String, age: Int?)",
+        "// Kotlin: val name: String",
+        "// Kotlin: var age: Int?",
+        "// Kotlin: constructor(name: String, age: Int?)",
+        "// Kotlin: val name: String",
+        "// Kotlin: fun greet(times: Int = ...): String?",
+        "// Kotlin: companion object Companion",
+        "// Kotlin: private constructor()",
+    ]);
+    // Comments go above the annotations, with the indentation of the declaration
+    assert!(user.contains("\n    // Kotlin: fun greet(times: Int = ...): String?\n    @Nullable\n    public final String greet("), "{}", user);
+    assert!(user.ends_with("}\n"));
+
+    let utils = fs::read_to_string(src_dir.join("com/example/UtilsKt.java")).unwrap();
+    assert!(utils.contains("// Kotlin: top-level declarations of Utils.kt\npublic final class UtilsKt\n"), "{}", utils);
+    assert!(utils.contains("    // Kotlin: fun String.shout(): String\n    @NotNull\n"), "{}", utils);
+
+    // Annotated sources are left alone
+    assert_eq!(kotlin::annotate_sources(&report, src_dir).unwrap(), 0);
+}
+
+#[test]
+fn test_descriptor_names_in_string_table() {
+    // DESC_TO_CLASS_ID for the class name, then for a string too short to be a descriptor
+    let string_table = Message::default()
+        .message(1, Message::default().varint(3, 2))
+        .message(1, Message::default().varint(3, 2));
+    let class = class_file_with_string_table(
+        "com/example/Gr\u{f6}\u{df}e$Inner", "Gr\u{f6}\u{df}e.kt", 1, string_table,
+        Message::default().varint(1, 6).varint(3, 0).message(10, Message::default().varint(2, 1).message(3, class_type(0))),
+        &["Lcom/example/Gr\u{f6}\u{df}e$Inner;", "\u{e9}"],
+    );
+
+    let mut report = KotlinReport::default();
+    report.add_class(&class).unwrap();
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(report.classes[0].name, "com.example.Gr\u{f6}\u{df}e.Inner");
+    assert_eq!(report.classes[0].properties[0].declaration, "val \u{e9}: com.example.Gr\u{f6}\u{df}e.Inner");
+}
+
+/// A class file whose constant pool is `constants` followed by the class name
+fn class_file_with_constants(constants: &[&[u8]]) -> Vec<u8> {
+    let mut class = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52];
+    // Longs and doubles take two entries
+    let slots: usize = constants.iter().map(|constant| if matches!(constant[0], 5 | 6) { 2 } else { 1 }).sum();
+    class.extend((slots as u16 + 3).to_be_bytes());
+    class.extend(constants.concat());
+    class.extend([1, 0, 5]);
+    class.extend(b"a/Foo");
+    class.extend([7]);
+    class.extend((slots as u16 + 1).to_be_bytes());
+    class.extend([0x00, 0x21]);
+    class.extend((slots as u16 + 2).to_be_bytes());
+    class.extend([0, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // superclass, interfaces, fields, methods, attributes
+    class
+}
+
+#[test]
+fn test_long_and_double_constants() {
+    let long = [&[5u8][..], &[0; 8]].concat();
+    let double = [&[6u8][..], &[0; 8]].concat();
+    let class = class_file_with_constants(&[&long, &[3, 0, 0, 0, 1], &double]);
+    assert_eq!(ClassFile::parse(&class).unwrap().name, "a/Foo");
+
+    // A long in the last slot has no room for its second entry
+    let mut truncated = class_file_with_constants(&[&long]);
+    truncated[9] = 2;
+    assert!(ClassFile::parse(&truncated).is_err());
+}
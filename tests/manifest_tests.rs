@@ -0,0 +1,157 @@
+use apkext::manifest::{self, axml, xml, Manifest, Value};
+use std::path::Path;
+
+mod common;
+
+use common::{chunk, string_pool};
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+const ANDROID: &str = "http://schemas.android.com/apk/res/android";
+
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// `(namespace, name, data type, data)` with string indices into the pool
+fn start_element(name: u32, attributes: &[(u32, u32, u8, u32)]) -> Vec<u8> {
+    let mut body = words(&[u32::MAX, name]);
+    body.extend_from_slice(&20u16.to_le_bytes());
+    body.extend_from_slice(&20u16.to_le_bytes());
+    body.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+    body.extend_from_slice(&[0; 6]);
+    for &(namespace, attribute, data_type, data) in attributes {
+        let raw = if data_type == 0x03 { data } else { u32::MAX };
+        body.extend(words(&[namespace, attribute, raw]));
+        body.extend_from_slice(&[8, 0, 0, data_type]);
+        body.extend(words(&[data]));
+    }
+    chunk(0x0102, &words(&[1, u32::MAX]), &body)
+}
+
+fn end_element(name: u32) -> Vec<u8> {
+    chunk(0x0103, &words(&[1, u32::MAX]), &words(&[u32::MAX, name]))
+}
+
+/// A shrunk manifest: the attribute names are blank, only the resource map says what they are
+fn shrunk_manifest() -> Vec<u8> {
+    // 0-2 are attributes, matching the resource map
+    let strings = ["", "", "", "android", ANDROID, "manifest", "package", "com.example.app", "application", "provider", ".data.Provider", "com.example.app.data"];
+    let resource_map = chunk(0x0180, &[], &words(&[0x0101_021b, 0x0101_0003, 0x0101_0018]));
+
+    let body = [
+        string_pool(&strings),
+        resource_map,
+        chunk(0x0100, &words(&[1, u32::MAX]), &words(&[3, 4])),
+        start_element(5, &[(u32::MAX, 6, 0x03, 7), (4, 0, 0x10, 7)]),
+        start_element(8, &[]),
+        start_element(9, &[(4, 1, 0x03, 10), (4, 2, 0x03, 11)]),
+        end_element(9),
+        end_element(8),
+        end_element(5),
+        chunk(0x0101, &words(&[1, u32::MAX]), &words(&[3, 4])),
+    ]
+    .concat();
+    chunk(0x0003, &[], &body)
+}
+
+#[test]
+fn test_sample_manifest() {
+    let manifest = Manifest::from_apk(Path::new(SAMPLE_APK)).unwrap();
+
+    assert_eq!(manifest.package, "io.selendroid.testapp");
+    assert_eq!(manifest.version_code, Some(1));
+    assert_eq!(manifest.version_name.as_deref(), Some("0.12.0-SNAPSHOT"));
+    assert_eq!(manifest.min_sdk, Some(10));
+    assert_eq!(manifest.target_sdk, None);
+    assert!(manifest.debuggable);
+    assert_eq!(manifest.permissions.len(), 5);
+    assert!(manifest.permissions.contains(&"android.permission.INTERNET".to_string()));
+
+    assert_eq!(manifest.activities.len(), 7);
+    let launcher = &manifest.activities[0];
+    assert_eq!(launcher.name, "io.selendroid.testapp.HomeScreenActivity");
+    assert_eq!(launcher.intent_filters[0].actions, ["android.intent.action.MAIN"]);
+    assert_eq!(launcher.intent_filters[0].categories, ["android.intent.category.LAUNCHER"]);
+
+    assert_eq!(manifest.services.len(), 1);
+    assert_eq!(manifest.services[0].name, "io.selendroid.testapp.services.impl.UserServiceImpl");
+    assert!(manifest.receivers.is_empty());
+}
+
+#[test]
+fn test_sample_manifest_as_xml() {
+    let data = manifest::read_entry(Path::new(SAMPLE_APK), manifest::MANIFEST_ENTRY).unwrap();
+    let xml = axml::decode(&data).unwrap();
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest xmlns:android=\"http://schemas.android.com/apk/res/android\""));
+    assert!(xml.contains("<uses-sdk android:minSdkVersion=\"10\" />"));
+    assert!(xml.contains("android:icon=\"@0x7f020001\""));
+    assert!(xml.trim_end().ends_with("</manifest>"));
+}
+
+#[test]
+fn test_text_manifest_round_trip() {
+    let decoded = axml::decode(&manifest::read_entry(Path::new(SAMPLE_APK), "AndroidManifest.xml").unwrap()).unwrap();
+    let root = xml::parse(&decoded).unwrap();
+
+    let from_text = Manifest::from_element(&root).unwrap();
+    let from_binary = Manifest::from_apk(Path::new(SAMPLE_APK)).unwrap();
+    assert_eq!(from_text, from_binary);
+    assert_eq!(root.attributes.iter().find(|attribute| attribute.name == "versionCode").unwrap().namespace.as_deref(), Some(ANDROID));
+}
+
+#[test]
+fn test_text_xml() {
+    let root = xml::parse(
+        "<?xml version=\"1.0\"?>\n<!-- comment -->\n<resources xmlns:tools='urn:tools'>\n  <string name=\"a\" tools:ignore=\"x\">Fish &amp; chips &#x263A;</string>\n  <string name=\"b\"><![CDATA[<b>bold</b>]]></string>\n  <item type=\"id\" name=\"c\"/>\n</resources>\n",
+    )
+    .unwrap();
+
+    assert_eq!(root.name, "resources");
+    assert_eq!(root.children.len(), 3);
+    assert_eq!(root.children[0].text, "Fish & chips \u{263a}");
+    assert_eq!(root.children[0].attributes[1].namespace.as_deref(), Some("urn:tools"));
+    assert_eq!(root.children[1].text, "<b>bold</b>");
+    assert_eq!(root.children[2].attr("type"), Some(&Value::String("id".to_string())));
+
+    assert!(xml::parse("<a><b></a>").is_err());
+    assert!(xml::parse("<a>").is_err());
+}
+
+#[test]
+fn test_attribute_names_from_resource_map() {
+    let root = axml::parse(&shrunk_manifest()).unwrap();
+    assert_eq!(root.namespaces, [("android".to_string(), ANDROID.to_string())]);
+    assert_eq!(root.attr("versionCode"), Some(&Value::Int(7)));
+
+    let manifest = Manifest::from_element(&root).unwrap();
+    assert_eq!(manifest.package, "com.example.app");
+    assert_eq!(manifest.version_code, Some(7));
+    assert_eq!(manifest.providers.len(), 1);
+    assert_eq!(manifest.providers[0].name, "com.example.app.data.Provider");
+    assert_eq!(manifest.providers[0].authorities.as_deref(), Some("com.example.app.data"));
+
+    let xml = root.to_xml();
+    assert!(xml.contains("<provider android:name=\".data.Provider\" android:authorities=\"com.example.app.data\" />"));
+}
+
+#[test]
+fn test_class_names() {
+    assert_eq!(manifest::class_name("com.example", ".Main"), "com.example.Main");
+    assert_eq!(manifest::class_name("com.example", "Main"), "com.example.Main");
+    assert_eq!(manifest::class_name("com.example", "org.other.Main"), "org.other.Main");
+}
+
+#[test]
+fn test_malformed_binary_xml() {
+    assert!(axml::parse(b"<manifest/>").is_err());
+
+    let mut truncated = shrunk_manifest();
+    truncated.truncate(truncated.len() / 2);
+    assert!(axml::parse(&truncated).is_err());
+
+    // A well-formed document whose root is not <manifest>
+    let other = chunk(0x0003, &[], &[string_pool(&["layout"]), start_element(0, &[]), end_element(0)].concat());
+    assert_eq!(axml::parse(&other).unwrap().name, "layout");
+    assert!(Manifest::parse(&other).is_err());
+}
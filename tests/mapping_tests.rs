@@ -0,0 +1,224 @@
+use apkext::kotlin::classfile::ClassFile;
+use apkext::mapping::{self, Mapping, Remapper};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const MAPPING: &str = "\
+# compiler: R8
+com.example.Account -> a.a:
+    java.lang.String owner -> a
+    com.example.Account$Entry[] entries -> b
+    1:4:void deposit(long,com.example.Account$Entry):22:25 -> a
+    5:5:com.example.Account$Entry com.example.Ledger.first():30:30 -> a
+    6:6:int size() -> b
+com.example.Account$Entry -> a.b:
+com.example.Account$Page -> a.a$d:
+com.example.SavingsAccount -> a.c:
+    void addInterest() -> c
+com.example.Unrenamed -> com.example.Unrenamed:
+    int count -> a
+";
+
+fn mapping() -> Mapping {
+    Mapping::parse(MAPPING).unwrap()
+}
+
+#[test]
+fn test_parse_mapping() {
+    let mapping = mapping();
+    assert_eq!(mapping.len(), 4);
+    assert_eq!(mapping.class("a/a"), Some("com/example/Account"));
+    assert_eq!(mapping.class("a/b"), Some("com/example/Account$Entry"));
+    assert_eq!(mapping.class("com/example/Unrenamed"), None);
+    assert_eq!(mapping.map_class("java/lang/String"), "java/lang/String");
+
+    assert_eq!(mapping.map_descriptor("(JLa/b;)[La/a;"), "(JLcom/example/Account$Entry;)[Lcom/example/Account;");
+    assert_eq!(
+        mapping.map_signature("<T:La/a;>Ljava/lang/Object;Ljava/util/List<+La/a<TT;>.d;>;"),
+        "<T:Lcom/example/Account;>Ljava/lang/Object;Ljava/util/List<+Lcom/example/Account<TT;>.Page;>;"
+    );
+    // Not a signature: left as it is
+    assert_eq!(mapping.map_signature("La/a"), "La/a");
+
+    // Descriptors are in obfuscated types; inlined members of other classes are not members
+    let parents = HashMap::from([("a/c".to_string(), vec!["a/a".to_string()])]);
+    let remapper = Remapper::new(&mapping, parents);
+    assert_eq!(remapper.field("a/a", "a", "Ljava/lang/String;"), Some("owner"));
+    assert_eq!(remapper.field("a/a", "b", "[La/b;"), Some("entries"));
+    assert_eq!(remapper.method("a/a", "a", "(JLa/b;)V"), Some("deposit"));
+    assert_eq!(remapper.method("a/a", "a", "()La/b;"), None);
+    assert_eq!(remapper.field("com/example/Unrenamed", "a", "I"), Some("count"));
+
+    // Inherited members are found through the class a reference names
+    assert_eq!(remapper.method("a/c", "b", "()I"), Some("size"));
+    assert_eq!(remapper.method("a/c", "c", "()V"), Some("addInterest"));
+    assert_eq!(remapper.method("a/c", "<init>", "()V"), None);
+}
+
+#[test]
+fn test_parse_mapping_rejects_garbage() {
+    assert!(Mapping::parse("not a mapping\n").is_err());
+    assert!(Mapping::parse("    int a -> b\n").is_err());
+}
+
+/// Builds a class file constant pool and body by hand
+#[derive(Default)]
+struct ClassBuilder {
+    pool: Vec<u8>,
+    count: u16,
+}
+
+impl ClassBuilder {
+    fn entry(&mut self, bytes: &[u8]) -> u16 {
+        self.pool.extend_from_slice(bytes);
+        self.count += 1;
+        self.count
+    }
+
+    fn utf8(&mut self, text: &str) -> u16 {
+        let mut bytes = vec![1];
+        bytes.extend_from_slice(&(text.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+        self.entry(&bytes)
+    }
+
+    fn class(&mut self, name: &str) -> u16 {
+        let name = self.utf8(name);
+        self.entry(&[&[7][..], &name.to_be_bytes()].concat())
+    }
+
+    fn field_ref(&mut self, class: u16, name: &str, descriptor: &str) -> u16 {
+        let (name, descriptor) = (self.utf8(name), self.utf8(descriptor));
+        let name_and_type = self.entry(&[&[12][..], &name.to_be_bytes(), &descriptor.to_be_bytes()].concat());
+        self.entry(&[&[9][..], &class.to_be_bytes(), &name_and_type.to_be_bytes()].concat())
+    }
+}
+
+/// `class a.a { a.b[] b; int size(); }` with a reference to its own field `a`
+fn class_file() -> Vec<u8> {
+    let mut builder = ClassBuilder::default();
+    let this = builder.class("a/a");
+    let object = builder.class("java/lang/Object");
+    builder.field_ref(this, "a", "Ljava/lang/String;");
+    let field_name = builder.utf8("b");
+    let field_descriptor = builder.utf8("[La/b;");
+    let method_name = builder.utf8("b");
+    let method_descriptor = builder.utf8("()I");
+    let signature = builder.utf8("Signature");
+    let field_signature = builder.utf8("Ljava/util/List<La/b;>;");
+
+    let mut data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52];
+    data.extend_from_slice(&(builder.count + 1).to_be_bytes());
+    data.extend_from_slice(&builder.pool);
+    data.extend_from_slice(&[0, 0x21]);
+    data.extend_from_slice(&this.to_be_bytes());
+    data.extend_from_slice(&object.to_be_bytes());
+    data.extend_from_slice(&[0, 0]); // interfaces
+    data.extend_from_slice(&[0, 1, 0, 0]);
+    data.extend_from_slice(&field_name.to_be_bytes());
+    data.extend_from_slice(&field_descriptor.to_be_bytes());
+    data.extend_from_slice(&[0, 1]);
+    data.extend_from_slice(&signature.to_be_bytes());
+    data.extend_from_slice(&[0, 0, 0, 2]);
+    data.extend_from_slice(&field_signature.to_be_bytes());
+    data.extend_from_slice(&[0, 1, 0x04, 0x01]); // public abstract
+    data.extend_from_slice(&method_name.to_be_bytes());
+    data.extend_from_slice(&method_descriptor.to_be_bytes());
+    data.extend_from_slice(&[0, 0]); // method attributes
+    data.extend_from_slice(&[0, 0]); // class attributes
+    data
+}
+
+fn contains(data: &[u8], text: &str) -> bool {
+    data.windows(text.len()).any(|window| window == text.as_bytes())
+}
+
+#[test]
+fn test_remap_jars() {
+    let dir = TempDir::new().unwrap();
+    let jar = dir.path().join("classes.jar");
+    let mut writer = ZipWriter::new(File::create(&jar).unwrap());
+    writer.start_file("a/a.class", FileOptions::default()).unwrap();
+    writer.write_all(&class_file()).unwrap();
+    writer.start_file("META-INF/MANIFEST.MF", FileOptions::default()).unwrap();
+    writer.write_all(b"Manifest-Version: 1.0\n").unwrap();
+    writer.finish().unwrap();
+
+    let renamed = mapping::remap_jars(std::slice::from_ref(&jar), &mapping()).unwrap();
+    assert_eq!(renamed, 1);
+
+    let mut archive = ZipArchive::new(File::open(&jar).unwrap()).unwrap();
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(names, ["META-INF/MANIFEST.MF", "com/example/Account.class"]);
+
+    let mut data = Vec::new();
+    archive.by_name("com/example/Account.class").unwrap().read_to_end(&mut data).unwrap();
+    assert_eq!(ClassFile::parse(&data).unwrap().name, "com/example/Account");
+    for expected in ["owner", "entries", "[Lcom/example/Account$Entry;", "size", "Ljava/util/List<Lcom/example/Account$Entry;>;"] {
+        assert!(contains(&data, expected), "missing {}", expected);
+    }
+}
+
+#[test]
+fn test_remap_smali() {
+    let dir = TempDir::new().unwrap();
+    let smali = dir.path().join("smali/a");
+    fs::create_dir_all(&smali).unwrap();
+    fs::create_dir_all(dir.path().join("smali_classes2/a")).unwrap();
+    fs::write(smali.join("a.smali"), "\
+.class public La/a;
+.super Ljava/lang/Object;
+
+.field private a:Ljava/lang/String;
+
+.method public a(JLa/b;)V
+    .registers 4
+    iget-object v0, p0, La/a;->a:Ljava/lang/String;
+    const-string v0, \"La/a;\"
+    return-void
+.end method
+").unwrap();
+    fs::write(dir.path().join("smali_classes2/a/c.smali"), "\
+.class public La/c;
+.super La/a;
+
+.method public c()V
+    .registers 4
+    .annotation system Ldalvik/annotation/Signature;
+        value = {
+            \"Ljava/util/List<\",
+            \"La/b;\",
+            \">;\"
+        }
+    .end annotation
+    invoke-virtual {p0, v0, v1, v2}, La/c;->a(JLa/b;)V # La/a;->a
+    new-array v0, v0, [La/b;
+    invoke-virtual {v0}, [La/b;->clone()Ljava/lang/Object;
+    return-void
+.end method
+").unwrap();
+
+    let renamed = mapping::smali::remap_project(dir.path(), &mapping()).unwrap();
+    assert_eq!(renamed, 2);
+    assert!(!smali.join("a.smali").exists());
+
+    let account = fs::read_to_string(dir.path().join("smali/com/example/Account.smali")).unwrap();
+    assert!(account.contains(".class public Lcom/example/Account;"));
+    assert!(account.contains(".field private owner:Ljava/lang/String;"));
+    assert!(account.contains(".method public deposit(JLcom/example/Account$Entry;)V"));
+    assert!(account.contains("iget-object v0, p0, Lcom/example/Account;->owner:Ljava/lang/String;"));
+    assert!(account.contains("const-string v0, \"La/a;\""));
+
+    let savings = fs::read_to_string(dir.path().join("smali_classes2/com/example/SavingsAccount.smali")).unwrap();
+    assert!(savings.contains(".super Lcom/example/Account;"));
+    assert!(savings.contains(".method public addInterest()V"));
+    assert!(savings.contains("\"Lcom/example/Account$Entry;\","));
+    assert!(savings.contains("Lcom/example/SavingsAccount;->deposit(JLcom/example/Account$Entry;)V # La/a;->a"));
+    assert!(savings.contains("new-array v0, v0, [Lcom/example/Account$Entry;"));
+    assert!(savings.contains("[Lcom/example/Account$Entry;->clone()Ljava/lang/Object;"));
+}
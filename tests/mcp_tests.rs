@@ -0,0 +1,152 @@
+use apkext::assets::AssetManager;
+use apkext::config::Config;
+use apkext::mcp::Server;
+use jsonrpc_core::IoHandler;
+use serde_json::{json, Value};
+use std::fs;
+use tempfile::TempDir;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+fn handler() -> (IoHandler, UnboundedReceiver<String>) {
+    let server = Server::new(Config::load(), AssetManager::new().unwrap()).unwrap();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (server.handler(sender), receiver)
+}
+
+async fn request(io: &IoHandler, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let response = io.handle_request(&request.to_string()).await.expect("no response");
+    serde_json::from_str(&response).unwrap()
+}
+
+#[tokio::test]
+async fn test_initialize_negotiates_version() {
+    let (io, _) = handler();
+
+    let response = request(&io, "initialize", json!({ "protocolVersion": "2025-03-26" })).await;
+    assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+    assert_eq!(response["result"]["serverInfo"]["name"], "apkext");
+
+    // Unknown or missing versions get the newest one
+    let response = request(&io, "initialize", json!({ "protocolVersion": "1999-01-01" })).await;
+    assert_eq!(response["result"]["protocolVersion"], "2025-06-18");
+    let response = request(&io, "initialize", json!({})).await;
+    assert_eq!(response["result"]["protocolVersion"], "2025-06-18");
+
+    let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+    assert!(io.handle_request(&notification.to_string()).await.is_none());
+}
+
+#[tokio::test]
+async fn test_tools_list() {
+    let (io, _) = handler();
+
+    let response = request(&io, "tools/list", json!({})).await;
+    let names: Vec<&str> = response["result"]["tools"].as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+    for name in ["unpack", "info", "resource", "classes", "methods", "xref", "search", "diff", "verify", "pack"] {
+        assert!(names.contains(&name), "missing {}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_tools_call() {
+    let (io, _) = handler();
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    fs::write(temp_dir.path().join("src/Api.java"), "String HOST = \"api.example.com\";\n").unwrap();
+
+    let response = request(&io, "tools/call", json!({
+        "name": "search",
+        "arguments": { "output_dir": temp_dir.path(), "pattern": "api.example.com", "fixed_strings": true }
+    })).await;
+    let result = &response["result"];
+    assert_eq!(result["isError"], false);
+    assert_eq!(result["structuredContent"]["total"], 1);
+    assert_eq!(result["content"][0]["type"], "text");
+
+    // A tool that fails answers with a result that says so, not with a protocol error
+    let response = request(&io, "tools/call", json!({
+        "name": "info",
+        "arguments": { "apk_file": temp_dir.path().join("missing.apk") }
+    })).await;
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"]["structuredContent"]["error"].as_str().unwrap().contains("missing.apk"));
+
+    let response = request(&io, "tools/call", json!({ "name": "frobnicate", "arguments": {} })).await;
+    assert_eq!(response["error"]["code"], -32602);
+    assert!(response["error"]["message"].as_str().unwrap().contains("Unknown tool: frobnicate"));
+}
+
+#[tokio::test]
+async fn test_tools_call_reports_progress() {
+    let (io, mut notifications) = handler();
+    let temp_dir = TempDir::new().unwrap();
+    let apk = temp_dir.path().join("broken.apk");
+    fs::write(&apk, b"not a zip").unwrap();
+
+    let response = request(&io, "tools/call", json!({
+        "name": "unpack",
+        "arguments": { "apk_file": apk, "output_dir": temp_dir.path().join("out") },
+        "_meta": { "progressToken": "unpack-1" }
+    })).await;
+    assert_eq!(response["result"]["isError"], true);
+
+    let mut messages = Vec::new();
+    while let Ok(notification) = notifications.try_recv() {
+        let notification: Value = serde_json::from_str(&notification).unwrap();
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"]["progressToken"], "unpack-1");
+        assert_eq!(notification["params"]["progress"], messages.len() as u64 + 1);
+        messages.push(notification["params"]["message"].as_str().unwrap().to_string());
+    }
+    assert!(messages[0].starts_with("[+] Extracting under"), "{:?}", messages);
+    assert!(messages.iter().any(|message| message.ends_with("verify_signature")), "{:?}", messages);
+}
+
+/// A stand-in for `java` that takes its time and then fails
+#[cfg(unix)]
+fn slow_java(dir: &TempDir) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.path().join("java");
+    fs::write(&path, "#!/bin/sh\nsleep 2\nexit 1\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_long_call_does_not_block_ping() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let temp_dir = TempDir::new().unwrap();
+    // A readable APK gets as far as apktool
+    let apk = temp_dir.path().join("app.apk");
+    let mut writer = zip::ZipWriter::new(fs::File::create(&apk).unwrap());
+    writer.start_file("classes.dex", zip::write::FileOptions::default()).unwrap();
+    writer.finish().unwrap();
+    let mut config = Config::load();
+    config.java.java_path = slow_java(&temp_dir);
+    let server = Server::new(config, AssetManager::new().unwrap()).unwrap();
+
+    let (mut client, server_input) = tokio::io::duplex(1 << 16);
+    let (server_output, client_output) = tokio::io::duplex(1 << 16);
+    let serving = tokio::spawn(async move { server.serve(server_input, server_output).await });
+
+    let unpack = json!({
+        "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+        "params": { "name": "unpack", "arguments": { "apk_file": apk, "output_dir": temp_dir.path().join("out") } }
+    });
+    let ping = json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" });
+    client.write_all(format!("{}\n{}\n", unpack, ping).as_bytes()).await.unwrap();
+    client.shutdown().await.unwrap();
+
+    let mut lines = BufReader::new(client_output).lines();
+    let mut ids = Vec::new();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        let response: Value = serde_json::from_str(&line).unwrap();
+        ids.push(response["id"].clone());
+    }
+    assert_eq!(ids, [json!(2), json!(1)]);
+    serving.await.unwrap().unwrap();
+}
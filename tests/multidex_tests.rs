@@ -0,0 +1,87 @@
+#![cfg(unix)]
+
+use apkext::apk::extractor::{dex_index, smali_dirs};
+use apkext::ApkextError;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+mod common;
+
+use common::{jar_extractor, write_zip};
+
+#[test]
+fn test_dex_index() {
+    assert_eq!(dex_index("classes.dex"), Some(1));
+    assert_eq!(dex_index("classes2.dex"), Some(2));
+    assert_eq!(dex_index("classes10.dex"), Some(10));
+
+    // classes.dex is the first one, and dx never writes leading zeros
+    for name in ["classes1.dex", "classes02.dex", "classes0.dex", "classesX.dex", "class.dex", "classes.jar", "lib/classes2.dex"] {
+        assert_eq!(dex_index(name), None, "{}", name);
+    }
+
+    let mut names = vec!["classes10.dex", "classes2.dex", "classes.dex", "classes3.dex"];
+    names.sort_by_key(|name| dex_index(name));
+    assert_eq!(names, ["classes.dex", "classes2.dex", "classes3.dex", "classes10.dex"]);
+}
+
+#[test]
+fn test_smali_dirs_in_load_order() {
+    let temp_dir = TempDir::new().unwrap();
+    for dir in ["smali_classes10", "smali_assets", "smali", "smali_classes2", "smalix", "res"] {
+        fs::create_dir(temp_dir.path().join(dir)).unwrap();
+    }
+    fs::write(temp_dir.path().join("smali_classes3"), "").unwrap();
+
+    let dirs: Vec<String> = smali_dirs(temp_dir.path()).iter().map(|dir| dir.file_name().unwrap().to_string_lossy().to_string()).collect();
+    assert_eq!(dirs, ["smali", "smali_classes2", "smali_classes10", "smali_assets"]);
+    assert!(smali_dirs(&temp_dir.path().join("missing")).is_empty());
+}
+
+/// An APK with the sample's manifest and an entry for each of `entries`
+fn write_apk(path: &Path, entries: &[&str]) {
+    let mut sample = ZipArchive::new(File::open("testdata/sample.apk").unwrap()).unwrap();
+    let mut manifest = Vec::new();
+    sample.by_name("AndroidManifest.xml").unwrap().read_to_end(&mut manifest).unwrap();
+
+    let mut zip_entries: Vec<(&str, &[u8])> = vec![("AndroidManifest.xml", &manifest)];
+    zip_entries.extend(entries.iter().map(|name| (*name, name.as_bytes())));
+    write_zip(path, &zip_entries);
+}
+
+#[tokio::test]
+async fn test_one_jar_per_dex() {
+    let temp_dir = TempDir::new().unwrap();
+    let apk = temp_dir.path().join("app.apk");
+    write_apk(&apk, &["classes3.dex", "classes.dex", "classes1.dex", "classes02.dex", "classes2.dex", "assets/classes4.dex"]);
+
+    let report = jar_extractor(temp_dir.path()).unpack(apk.to_str().unwrap()).await.unwrap();
+
+    let output = temp_dir.path().join("app");
+    assert_eq!(report.jars, [output.join("classes.jar"), output.join("classes2.jar"), output.join("classes3.jar")]);
+    for jar in &report.jars {
+        assert!(jar.is_file(), "{}", jar.display());
+    }
+    let converted = fs::read_to_string(temp_dir.path().join("converted.log")).unwrap();
+    assert_eq!(converted, "classes.dex\nclasses2.dex\nclasses3.dex\n");
+    assert!(!output.join("classes.dex").exists());
+}
+
+#[tokio::test]
+async fn test_class_dex_fallback() {
+    let temp_dir = TempDir::new().unwrap();
+    let apk = temp_dir.path().join("old.apk");
+    write_apk(&apk, &["class.dex"]);
+
+    let report = jar_extractor(temp_dir.path()).unpack(apk.to_str().unwrap()).await.unwrap();
+    assert_eq!(report.jars, [temp_dir.path().join("old/classes.jar")]);
+    assert_eq!(fs::read_to_string(temp_dir.path().join("converted.log")).unwrap(), "classes.dex\n");
+
+    let apk = temp_dir.path().join("empty.apk");
+    write_apk(&apk, &["classes1.dex"]);
+    let error = jar_extractor(temp_dir.path()).unpack(apk.to_str().unwrap()).await.unwrap_err();
+    assert!(matches!(error, ApkextError::InvalidApk { .. }), "{:?}", error);
+}
@@ -0,0 +1,180 @@
+use apkext::apk::output::{self, ReusedOutputs, StageInputs};
+use apkext::apk::UnpackStages;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn write(dir: &Path, relative: &str, content: &str) {
+    let path = dir.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn read(dir: &Path, relative: &str) -> String {
+    fs::read_to_string(dir.join(relative)).unwrap()
+}
+
+#[test]
+fn test_merge_refreshes_generated_and_keeps_modified_files() {
+    let output_dir = TempDir::new().unwrap();
+    let out = output_dir.path();
+
+    // First unpack
+    write(out, "src/a/Foo.java", "class Foo {}");
+    write(out, "src/a/Bar.java", "class Bar {}");
+    write(out, "src/a/Gone.java", "class Gone {}");
+    write(out, "unpacked/apktool.yml", "version: 1");
+    output::write_manifest(out).unwrap();
+
+    // The user edits one file and adds another
+    write(out, "src/a/Foo.java", "class Foo { /* my fix */ }");
+    write(out, "notes.txt", "todo");
+
+    // Second unpack produces new output
+    let staging_dir = TempDir::new().unwrap();
+    let staging = staging_dir.path();
+    write(staging, "src/a/Foo.java", "class Foo { int x; }");
+    write(staging, "src/a/Bar.java", "class Bar { int y; }");
+    write(staging, "src/a/New.java", "class New {}");
+    write(staging, "unpacked/apktool.yml", "version: 1");
+
+    let summary = output::merge(staging, out, &UnpackStages::default(), &ReusedOutputs::default()).unwrap();
+
+    assert_eq!(read(out, "src/a/Foo.java"), "class Foo { /* my fix */ }");
+    assert_eq!(read(out, "src/a/Bar.java"), "class Bar { int y; }");
+    assert_eq!(read(out, "src/a/New.java"), "class New {}");
+    assert_eq!(read(out, "notes.txt"), "todo");
+    assert!(!out.join("src/a/Gone.java").exists(), "Stale unmodified files must be removed");
+
+    assert_eq!(summary.kept, vec![PathBuf::from("src/a/Foo.java")]);
+    assert_eq!(summary.updated, 3);
+    assert_eq!(summary.removed, 1);
+
+    // The edit must survive another merge as well
+    let summary = output::merge(staging, out, &UnpackStages::default(), &ReusedOutputs::default()).unwrap();
+    assert_eq!(read(out, "src/a/Foo.java"), "class Foo { /* my fix */ }");
+    assert_eq!(summary.kept, vec![PathBuf::from("src/a/Foo.java")]);
+}
+
+#[test]
+fn test_merge_without_manifest_keeps_differing_files() {
+    let output_dir = TempDir::new().unwrap();
+    let out = output_dir.path();
+    write(out, "src/a/Foo.java", "class Foo { /* edited */ }");
+
+    let staging_dir = TempDir::new().unwrap();
+    write(staging_dir.path(), "src/a/Foo.java", "class Foo {}");
+    write(staging_dir.path(), "src/a/Bar.java", "class Bar {}");
+
+    let summary = output::merge(staging_dir.path(), out, &UnpackStages::default(), &ReusedOutputs::default()).unwrap();
+
+    assert_eq!(read(out, "src/a/Foo.java"), "class Foo { /* edited */ }");
+    assert_eq!(read(out, "src/a/Bar.java"), "class Bar {}");
+    assert_eq!(summary.kept.len(), 1);
+    assert!(out.join(output::MANIFEST_FILE).exists());
+}
+
+#[test]
+fn test_merge_keeps_files_of_skipped_stages() {
+    let output_dir = TempDir::new().unwrap();
+    let out = output_dir.path();
+    write(out, "classes.jar", "old jar");
+    write(out, "src/a/Foo.java", "class Foo {}");
+    write(out, "unpacked/apktool.yml", "version: 1");
+    output::write_manifest(out).unwrap();
+
+    // A JAR-only run must not treat the sources and apktool output as stale
+    let staging_dir = TempDir::new().unwrap();
+    write(staging_dir.path(), "classes.jar", "new jar");
+
+    let summary = output::merge(staging_dir.path(), out, &UnpackStages::only_jar(), &ReusedOutputs::default()).unwrap();
+
+    assert_eq!(read(out, "classes.jar"), "new jar");
+    assert_eq!(read(out, "src/a/Foo.java"), "class Foo {}");
+    assert_eq!(read(out, "unpacked/apktool.yml"), "version: 1");
+    assert_eq!(summary.removed, 0);
+}
+
+#[test]
+fn test_merge_keeps_reused_outputs() {
+    let output_dir = TempDir::new().unwrap();
+    let out = output_dir.path();
+    write(out, "classes.jar", "jar 1");
+    write(out, "classes2.jar", "jar 2");
+    write(out, "src/a/One.java", "class One {}");
+    write(out, "src/b/Two.java", "class Two {}");
+    write(out, "unpacked/apktool.yml", "version: 1");
+    output::write_manifest(out).unwrap();
+
+    // Only classes2.dex changed: apktool and classes.jar with its sources were skipped
+    let staging_dir = TempDir::new().unwrap();
+    write(staging_dir.path(), "classes2.jar", "jar 2, updated");
+    write(staging_dir.path(), "src/b/Two.java", "class Two { int x; }");
+
+    let reused = ReusedOutputs {
+        unpacked: true,
+        jars: vec!["classes.jar".to_string()],
+        sources: vec!["classes.jar".to_string()],
+        source_files: ["a/One.java".to_string()].into(),
+    };
+    assert!(reused.contains("unpacked/apktool.yml") && reused.contains("src/a/One.java"));
+    assert!(!reused.contains("classes2.jar") && !reused.contains("src/b/Two.java") && !reused.contains("a/One.java"));
+
+    let summary = output::merge(staging_dir.path(), out, &UnpackStages::default(), &reused).unwrap();
+
+    assert_eq!(read(out, "classes.jar"), "jar 1");
+    assert_eq!(read(out, "classes2.jar"), "jar 2, updated");
+    assert_eq!(read(out, "src/a/One.java"), "class One {}");
+    assert_eq!(read(out, "src/b/Two.java"), "class Two { int x; }");
+    assert_eq!(read(out, "unpacked/apktool.yml"), "version: 1");
+    assert_eq!((summary.updated, summary.removed), (2, 0));
+
+    // The reused files stay tracked, so a later full merge still removes them when they go stale
+    let summary = output::merge(staging_dir.path(), out, &UnpackStages::default(), &ReusedOutputs::default()).unwrap();
+    assert_eq!(summary.removed, 3);
+    assert!(!out.join("src/a/One.java").exists());
+}
+
+#[test]
+fn test_stage_inputs_round_trip() {
+    let output_dir = TempDir::new().unwrap();
+    let out = output_dir.path();
+    assert_eq!(StageInputs::load(out), StageInputs::default());
+
+    write(out, "classes.dex", "dex");
+    let hash = output::file_sha256(&out.join("classes.dex")).unwrap();
+    assert_eq!(hash.len(), 64);
+
+    let mut inputs = StageInputs::default();
+    inputs.jars.insert("classes.dex".to_string(), hash.clone());
+    inputs.sources.insert("classes.dex".to_string(), hash);
+    inputs.save(out).unwrap();
+    assert_eq!(StageInputs::load(out), inputs);
+
+    // The stage inputs are not a generated file that a merge could refresh or remove
+    output::write_manifest(out).unwrap();
+    let files = read(out, output::MANIFEST_FILE);
+    assert!(files.contains("classes.dex") && !files.contains(output::STAGES_FILE));
+
+    fs::write(out.join(output::STAGES_FILE), "not json").unwrap();
+    assert_eq!(StageInputs::load(out), StageInputs::default());
+}
+
+#[test]
+fn test_unpack_stage_selection() {
+    assert!(UnpackStages::default().validate().is_ok());
+    assert!(UnpackStages::only_jar().validate().is_ok());
+    assert!(UnpackStages::only_resources().validate().is_ok());
+
+    let decompile_without_jar = UnpackStages { jar: false, ..UnpackStages::default() };
+    assert!(decompile_without_jar.validate().is_err());
+
+    let only_jar = UnpackStages::only_jar();
+    assert!(only_jar.produces("classes2.jar"));
+    assert!(!only_jar.produces("src/a/Foo.java"));
+    assert!(!only_jar.produces("unpacked/AndroidManifest.xml"));
+    assert!(!only_jar.produces("notes/extra.jar"));
+    // Decoded natively when apktool does not run
+    assert!(only_jar.produces("AndroidManifest.xml"));
+    assert!(!UnpackStages::default().produces("AndroidManifest.xml"));
+}
@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+use apkext::assets::AssetManager;
+use apkext::config::Config;
+use apkext::tools::{Progress, ProgressHandler, Stream, ToolManager};
+use apkext::ApkextError;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// A stand-in for `java` that prints to both streams and fails
+fn fake_java(dir: &TempDir) -> String {
+    let path = dir.path().join("java");
+    fs::write(&path, "#!/bin/sh\necho 'processing classes.dex'\necho 'disk full' >&2\nexit 3\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[tokio::test]
+async fn test_tool_output_is_streamed_and_failure_is_typed() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::load();
+    config.java.java_path = fake_java(&temp_dir);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let handler: ProgressHandler = Arc::new(move |event: &Progress| sink.lock().unwrap().push(event.clone()));
+
+    let tool_manager = ToolManager::new(config, AssetManager::new().unwrap()).with_progress(Some(handler));
+    tool_manager.step(&["extract_dex", "dex2jar"], "dex2jar");
+    let error = tool_manager.run_dex2jar(&["classes.dex"]).await.unwrap_err();
+
+    match error {
+        ApkextError::ToolFailed { tool, code, stderr } => {
+            assert_eq!(tool, "Dex2jar");
+            assert_eq!(code, Some(3));
+            assert_eq!(stderr, "disk full");
+        }
+        other => panic!("Expected ToolFailed, got {:?}", other),
+    }
+
+    let events = events.lock().unwrap();
+    assert_eq!(events[0], Progress::Step { name: "dex2jar".to_string(), index: 2, total: 2 });
+    assert!(events.contains(&Progress::Output {
+        tool: "Dex2jar".to_string(),
+        stream: Stream::Stdout,
+        line: "processing classes.dex".to_string(),
+    }));
+    assert!(events.contains(&Progress::Output {
+        tool: "Dex2jar".to_string(),
+        stream: Stream::Stderr,
+        line: "disk full".to_string(),
+    }));
+}
+
+#[tokio::test]
+async fn test_missing_java_is_reported() {
+    let mut config = Config::load();
+    config.java.java_path = "/nonexistent/bin/java".to_string();
+
+    let error = ToolManager::new(config, AssetManager::new().unwrap()).run_dex2jar(&[]).await.unwrap_err();
+    assert!(matches!(error, ApkextError::JavaNotFound { .. }), "{:?}", error);
+}
@@ -0,0 +1,82 @@
+use apkext::apk::search::Origin;
+use apkext::apk::SearchQuery;
+use apkext::ApkextError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn write(dir: &Path, path: &str, contents: &[u8]) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// The files an unpack leaves, each mentioning the API host
+fn unpack_output() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    write(dir, "src/com/example/Api.java", b"class Api {\n    String HOST = \"https://api.example.com\";\n}\n");
+    write(dir, "unpacked/smali/com/example/Api.smali", b".class Lcom/example/Api;\n    const-string v0, \"https://api.example.com\"\n");
+    write(dir, "unpacked/smali_classes2/com/example/Api$Retry.smali", b".class Lcom/example/Api$Retry;\n    const-string v0, \"https://API.example.com/retry\"\n");
+    write(dir, "unpacked/smali_classes2/com/example/Gone.smali", b"    const-string v0, \"https://api.example.com/gone\"\n");
+    write(dir, "unpacked/AndroidManifest.xml", b"<manifest>\n    <meta-data android:value=\"https://api.example.com\"/>\n</manifest>\n");
+    write(dir, "unpacked/res/xml/network_security_config.xml", b"<domain>api.example.com</domain>\n");
+    write(dir, "unpacked/res/values/strings.xml", b"<resources>\n    <string name=\"app_name\">Example</string>\n    <string name=\"terms\">See\n        https://api.example.com/terms</string>\n</resources>\n");
+    write(dir, "unpacked/res/values-de/strings.xml", b"<resources>\n    <string name=\"terms\">https://api.example.com/de</string>\n</resources>\n");
+    write(dir, "unpacked/assets/config.json", b"{\"host\": \"api.example.com\"}\n");
+    write(dir, "unpacked/assets/blob.bin", b"api.example.com\0\x01\x02");
+    temp_dir
+}
+
+#[test]
+fn test_search_groups_by_origin() {
+    let temp_dir = unpack_output();
+    let report = SearchQuery::new(r"api\.example\.com").run(temp_dir.path()).unwrap();
+
+    let hits: Vec<(Origin, &str, usize)> = report.hits.iter().map(|hit| (hit.origin, hit.path.to_str().unwrap(), hit.line)).collect();
+    assert_eq!(hits, [
+        (Origin::Java, "src/com/example/Api.java", 2),
+        (Origin::Smali, "unpacked/smali/com/example/Api.smali", 2),
+        (Origin::Smali, "unpacked/smali_classes2/com/example/Gone.smali", 1),
+        (Origin::Xml, "unpacked/AndroidManifest.xml", 2),
+        (Origin::Xml, "unpacked/res/xml/network_security_config.xml", 1),
+        (Origin::Strings, "unpacked/res/values/strings.xml", 4),
+        (Origin::Strings, "unpacked/res/values-de/strings.xml", 2),
+        (Origin::Assets, "unpacked/assets/config.json", 1),
+    ]);
+    // The binary asset is skipped
+    assert_eq!(report.files, 9);
+
+    // Smali maps to the decompiled source of its class, when there is one
+    assert_eq!(report.hits[1].source, Some(PathBuf::from("src/com/example/Api.java")));
+    assert_eq!(report.hits[2].source, None);
+    // A value spanning lines belongs to the resource opened before
+    assert_eq!(report.hits[5].resource.as_deref(), Some("string/terms"));
+    assert_eq!(report.hits[1].text, "const-string v0, \"https://api.example.com\"");
+
+    let text = report.to_string();
+    assert!(text.starts_with("8 matches of 'api\\.example\\.com' in 9 files\n"), "{}", text);
+    assert!(text.contains("\nString resources (2):\n  unpacked/res/values/strings.xml:4 [string/terms]: https://api.example.com/terms</string>\n"), "{}", text);
+    assert!(text.contains("    source: src/com/example/Api.java\n"), "{}", text);
+}
+
+#[test]
+fn test_search_options() {
+    let temp_dir = unpack_output();
+
+    // Nested classes map to the source of the outer class
+    let report = SearchQuery::new("API.example").with_ignore_case(true).with_origins(vec![Origin::Smali]).run(temp_dir.path()).unwrap();
+    assert_eq!(report.hits.len(), 3);
+    assert_eq!(report.hits[1].source, Some(PathBuf::from("src/com/example/Api.java")));
+
+    // Without -F the dot matches anything
+    let regex = SearchQuery::new("com.terms").with_origins(vec![Origin::Strings]).run(temp_dir.path()).unwrap();
+    let fixed = SearchQuery::new("com.terms").with_fixed_strings(true).with_origins(vec![Origin::Strings]).run(temp_dir.path()).unwrap();
+    assert_eq!((regex.hits.len(), fixed.hits.len()), (1, 0));
+    assert_eq!(SearchQuery::new("api.example.com").with_fixed_strings(true).with_origins(vec![Origin::Assets]).run(temp_dir.path()).unwrap().hits.len(), 1);
+    assert!(SearchQuery::new("api.example.com").with_fixed_strings(true).run(temp_dir.path()).unwrap().hits.iter().all(|hit| hit.text.contains("api.example.com")));
+
+    assert!(SearchQuery::new("(unclosed").run(temp_dir.path()).is_err());
+    let empty = TempDir::new().unwrap();
+    assert!(matches!(SearchQuery::new("x").run(empty.path()), Err(ApkextError::InvalidProject { .. })));
+}
@@ -0,0 +1,157 @@
+use apkext::signing::{self, KeySource, SignOptions, Signer};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+
+mod common;
+
+use common::{central_directory, read_u32, read_u64, sample_apk, signing_block_start};
+
+const V2_BLOCK_ID: u32 = 0x7109_871a;
+const V3_BLOCK_ID: u32 = 0xf053_68c0;
+
+/// ID-value pairs of the APK Signing Block, or `None` if the APK has none
+fn signing_block_ids(apk: &[u8]) -> Option<Vec<u32>> {
+    let (_, cd_offset) = central_directory(apk);
+    let block_start = signing_block_start(apk, cd_offset)?;
+
+    let mut ids = Vec::new();
+    let mut pos = block_start + 8;
+    while pos < cd_offset - 24 {
+        let pair_len = read_u64(apk, pos) as usize;
+        ids.push(read_u32(apk, pos + 8));
+        pos += 8 + pair_len;
+    }
+    Some(ids)
+}
+
+fn entry_names(apk: &[u8]) -> Vec<String> {
+    let archive = zip::ZipArchive::new(Cursor::new(apk)).expect("Signed APK must be a valid ZIP");
+    archive.file_names().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_debug_key_is_created_once() {
+    let keys_dir = TempDir::new().unwrap();
+
+    let first = Signer::new(&SignOptions::default(), keys_dir.path()).expect("Debug key must be generated");
+    assert!(keys_dir.path().join("debug-key.pem").exists());
+    assert!(keys_dir.path().join("debug-cert.pem").exists());
+
+    let second = Signer::new(&SignOptions::default(), keys_dir.path()).expect("Debug key must be reloaded");
+    assert_eq!(first.key().certificate_sha256(), second.key().certificate_sha256());
+}
+
+#[test]
+fn test_sign_all_schemes() {
+    let keys_dir = TempDir::new().unwrap();
+    let signer = Signer::new(&SignOptions::default(), keys_dir.path()).unwrap();
+
+    let signed = signer.sign(&sample_apk()).expect("Signing must succeed");
+
+    let names = entry_names(&signed);
+    for name in ["META-INF/MANIFEST.MF", "META-INF/CERT.SF", "META-INF/CERT.RSA"] {
+        assert!(names.iter().any(|n| n == name), "{} must be present", name);
+    }
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(&signed[..])).unwrap();
+    let mut signature_file = String::new();
+    archive.by_name("META-INF/CERT.SF").unwrap().read_to_string(&mut signature_file).unwrap();
+    assert!(signature_file.contains("X-Android-APK-Signed: 2, 3"));
+
+    let ids = signing_block_ids(&signed).expect("APK Signing Block must be present");
+    assert_eq!(ids, vec![V2_BLOCK_ID, V3_BLOCK_ID]);
+}
+
+#[test]
+fn test_resign_replaces_signatures() {
+    let keys_dir = TempDir::new().unwrap();
+    let signer = Signer::new(&SignOptions::default(), keys_dir.path()).unwrap();
+
+    let signed = signer.sign(&sample_apk()).unwrap();
+    let resigned = signer.sign(&signed).unwrap();
+
+    assert_eq!(entry_names(&signed).len(), entry_names(&resigned).len());
+    assert_eq!(signing_block_ids(&resigned), Some(vec![V2_BLOCK_ID, V3_BLOCK_ID]));
+}
+
+#[test]
+fn test_sign_v1_only() {
+    let keys_dir = TempDir::new().unwrap();
+    let schemes = vec!["v1".to_string()];
+    let options = SignOptions::with_schemes(KeySource::Debug, &schemes).unwrap();
+    let signer = Signer::new(&options, keys_dir.path()).unwrap();
+
+    let signed = signer.sign(&sample_apk()).unwrap();
+
+    assert!(entry_names(&signed).iter().any(|n| n == "META-INF/CERT.RSA"));
+    assert_eq!(signing_block_ids(&signed), None);
+}
+
+#[test]
+fn test_sign_v2_only_strips_jar_signature() {
+    let keys_dir = TempDir::new().unwrap();
+    let signer = Signer::new(&SignOptions::default(), keys_dir.path()).unwrap();
+    let signed = signer.sign(&sample_apk()).unwrap();
+
+    let schemes = vec!["v2".to_string()];
+    let options = SignOptions::with_schemes(KeySource::Debug, &schemes).unwrap();
+    let signer = Signer::new(&options, keys_dir.path()).unwrap();
+    let resigned = signer.sign(&signed).unwrap();
+
+    assert!(!entry_names(&resigned).iter().any(|n| n.starts_with("META-INF/CERT.")));
+    assert_eq!(signing_block_ids(&resigned), Some(vec![V2_BLOCK_ID]));
+}
+
+#[test]
+fn test_inspect_signatures() {
+    let original = signing::inspect(&sample_apk()).unwrap();
+    assert_eq!(original.schemes, ["v1"]);
+    assert_eq!(original.certificates.len(), 1);
+    assert!(original.certificates[0].subject.contains("CN=Android Debug"));
+
+    let keys_dir = TempDir::new().unwrap();
+    let signer = Signer::new(&SignOptions::default(), keys_dir.path()).unwrap();
+    let signed = signing::inspect(&signer.sign(&sample_apk()).unwrap()).unwrap();
+
+    // The same certificate in all three schemes is listed once
+    assert_eq!(signed.schemes, ["v1", "v2", "v3"]);
+    assert_eq!(signed.certificates.len(), 1);
+    assert_eq!(signed.certificates[0].sha256, signer.key().certificate_sha256());
+}
+
+#[test]
+fn test_invalid_options() {
+    let keys_dir = TempDir::new().unwrap();
+
+    assert!(SignOptions::with_schemes(KeySource::Debug, &["v4".to_string()]).is_err());
+    assert!(KeySource::select(None, None, None, Some(PathBuf::from("key.pem")), None).is_err());
+
+    let none = SignOptions::with_schemes(KeySource::Debug, &[]).unwrap();
+    assert!(Signer::new(&none, keys_dir.path()).is_err());
+}
+
+#[test]
+fn test_v1_signature_verifies_with_jarsigner() {
+    if Command::new("jarsigner").arg("-help").output().is_err() {
+        println!("jarsigner not found, skipping");
+        return;
+    }
+
+    let work_dir = TempDir::new().unwrap();
+    let signer = Signer::new(&SignOptions::default(), work_dir.path()).unwrap();
+    let apk_path = work_dir.path().join("signed.apk");
+    fs::write(&apk_path, sample_apk()).unwrap();
+    signer.sign_file(&apk_path).unwrap();
+
+    let output = Command::new("jarsigner")
+        .args(["-verify", "-strict"])
+        .arg(&apk_path)
+        .output()
+        .expect("Failed to run jarsigner");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("jar verified"), "jarsigner must accept the v1 signature:\n{}", stdout);
+}
@@ -0,0 +1,108 @@
+use apkext::apk::splits::{self, ContainerKind, SplitEntry, SplitSet};
+use std::fs;
+use std::io::Read;
+use tempfile::TempDir;
+use zip::{CompressionMethod, ZipArchive};
+
+mod common;
+
+use common::{write_zip};
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_base_apk_detection() {
+    // bundletool
+    let apks = strings(&["splits/base-master.apk", "splits/base-arm64_v8a.apk", "splits/base-xxhdpi.apk"]);
+    assert_eq!(splits::base_entry(&apks), Some("splits/base-master.apk"));
+
+    // APKMirror
+    let apkm = strings(&["base.apk", "split_config.en.apk", "split_config.arm64_v8a.apk"]);
+    assert_eq!(splits::base_entry(&apkm), Some("base.apk"));
+
+    // XAPK
+    let xapk = strings(&["com.example.app.apk", "config.arm64_v8a.apk", "config.xxhdpi.apk"]);
+    assert_eq!(splits::base_entry(&xapk), Some("com.example.app.apk"));
+
+    let ambiguous = strings(&["one.apk", "two.apk"]);
+    assert_eq!(splits::base_entry(&ambiguous), None);
+
+    assert_eq!(splits::split_name("split_config.en.apk"), "config.en");
+    assert_eq!(splits::split_name("splits/base-xxhdpi.apk"), "base-xxhdpi");
+}
+
+#[test]
+fn test_container_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    let container = dir.join("App.xapk");
+    write_zip(&container, &[
+        ("manifest.json", b"{\"package_name\":\"com.example.app\"}"),
+        ("com.example.app.apk", b"base"),
+        ("config.arm64_v8a.apk", b"abi"),
+        ("config.fr.apk", b"lang"),
+    ]);
+
+    let apks_dir = dir.join("apks");
+    let metadata_dir = dir.join("out").join(splits::CONTAINER_DIR);
+    let set = splits::extract_container(&container, &apks_dir, &metadata_dir).unwrap();
+
+    assert_eq!(set, SplitSet {
+        container: ContainerKind::Xapk,
+        base: "com.example.app.apk".to_string(),
+        splits: vec![
+            SplitEntry { name: "config.arm64_v8a".to_string(), entry: "config.arm64_v8a.apk".to_string() },
+            SplitEntry { name: "config.fr".to_string(), entry: "config.fr.apk".to_string() },
+        ],
+    });
+    assert_eq!(fs::read(apks_dir.join("config.fr.apk")).unwrap(), b"lang");
+    assert!(metadata_dir.join("manifest.json").exists());
+
+    set.save(&dir.join("out")).unwrap();
+    assert_eq!(SplitSet::load(&dir.join("out")).unwrap(), Some(set.clone()));
+    assert_eq!(SplitSet::load(dir).unwrap(), None);
+
+    let rebuilt = dir.join("Rebuilt.xapk");
+    splits::write_container(&set, &apks_dir, &metadata_dir, &rebuilt).unwrap();
+
+    let mut archive = ZipArchive::new(fs::File::open(&rebuilt).unwrap()).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, strings(&["com.example.app.apk", "config.arm64_v8a.apk", "config.fr.apk", "manifest.json"]));
+
+    let mut base = archive.by_name("com.example.app.apk").unwrap();
+    assert_eq!(base.compression(), CompressionMethod::Stored);
+    let mut data = Vec::new();
+    base.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"base");
+}
+
+#[test]
+fn test_merge_resources_combines_base_and_splits() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+
+    let files = [
+        "unpacked/res/values/strings.xml",
+        "unpacked/AndroidManifest.xml",
+        "splits/config.fr/res/values-fr/strings.xml",
+        "splits/config.arm64_v8a/lib/arm64-v8a/libnative.so",
+    ];
+    for file in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, file).unwrap();
+    }
+
+    let merged = dir.join(splits::MERGED_DIR);
+    let split_dirs = vec![dir.join("splits/config.fr"), dir.join("splits/config.arm64_v8a")];
+    splits::merge_resources(&dir.join("unpacked"), &split_dirs, &merged).unwrap();
+
+    assert!(merged.join("res/values/strings.xml").exists());
+    assert!(merged.join("res/values-fr/strings.xml").exists());
+    assert!(merged.join("lib/arm64-v8a/libnative.so").exists());
+    assert!(!merged.join("AndroidManifest.xml").exists());
+}
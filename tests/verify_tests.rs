@@ -0,0 +1,170 @@
+use apkext::signing::{self, SignOptions, Signer};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::sync::OnceLock;
+use tempfile::TempDir;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+mod common;
+
+use common::{central_directory, sample_apk, signing_block_start};
+#[cfg(unix)]
+use common::jar_extractor;
+
+/// The sample signed with v1, v2 and v3 by a fresh debug key, shared because key generation is slow
+fn signed_apk() -> &'static [u8] {
+    static SIGNED: OnceLock<Vec<u8>> = OnceLock::new();
+    SIGNED.get_or_init(|| {
+        let keys_dir = TempDir::new().unwrap();
+        let signer = Signer::new(&SignOptions::default(), keys_dir.path()).unwrap();
+        signer.sign(&sample_apk()).unwrap()
+    })
+}
+
+/// The APK without its APK Signing Block, as a stripping attack would leave it
+fn strip_signing_block(apk: &[u8]) -> Vec<u8> {
+    let (eocd, cd_offset) = central_directory(apk);
+    let block_start = signing_block_start(apk, cd_offset).unwrap();
+
+    let mut stripped = [&apk[..block_start], &apk[cd_offset..]].concat();
+    let new_eocd = eocd - (cd_offset - block_start);
+    stripped[new_eocd + 16..new_eocd + 20].copy_from_slice(&(block_start as u32).to_le_bytes());
+    stripped
+}
+
+/// The signed sample with a signing block size that points far before the start of the file
+fn huge_signing_block_size() -> Vec<u8> {
+    let mut apk = signed_apk().to_vec();
+    let (_, cd_offset) = central_directory(&apk);
+    apk[cd_offset - 24..cd_offset - 16].copy_from_slice(&u64::MAX.to_le_bytes());
+    apk
+}
+
+/// Copy the APK, replacing the contents of one entry and keeping everything else as it was
+fn replace_entry(apk: &[u8], name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut archive = ZipArchive::new(Cursor::new(apk)).unwrap();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).unwrap();
+        if entry.name() == name {
+            let entry_name = entry.name().to_string();
+            drop(entry);
+            writer.start_file(entry_name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        } else {
+            writer.raw_copy_file(entry).unwrap();
+        }
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn scheme_names(report: &signing::VerifyReport) -> Vec<&str> {
+    report.schemes.iter().map(|scheme| scheme.scheme.as_str()).collect()
+}
+
+#[test]
+fn test_verify_sample_v1_signature() {
+    let report = signing::verify(&sample_apk(), None).unwrap();
+
+    assert!(report.verified, "{:?}", report);
+    assert_eq!(scheme_names(&report), ["v1"]);
+    let signer = &report.schemes[0].signers[0];
+    assert!(signer.subject.contains("Android Debug"), "{}", signer.subject);
+    assert_eq!(signer.sha256.len(), 64);
+}
+
+#[test]
+fn test_verify_all_schemes() {
+    let report = signing::verify(signed_apk(), None).unwrap();
+
+    assert!(report.verified, "{}", report);
+    assert_eq!(scheme_names(&report), ["v1", "v2", "v3"]);
+    let fingerprints: Vec<&str> = report.schemes.iter().map(|scheme| scheme.signers[0].sha256.as_str()).collect();
+    assert!(fingerprints.iter().all(|fingerprint| *fingerprint == fingerprints[0]));
+    assert!(report.to_string().starts_with("Verified:     yes\n"));
+}
+
+#[test]
+fn test_modified_entry_is_flagged() {
+    let tampered = replace_entry(signed_apk(), "classes.dex", b"dex\n035\0tampered");
+    let report = signing::verify(&tampered, None).unwrap();
+
+    assert!(!report.verified);
+    let v1 = &report.schemes[0];
+    assert!(!v1.verified);
+    assert!(v1.errors.iter().any(|error| error.starts_with("classes.dex was modified")), "{:?}", v1.errors);
+    for scheme in &report.schemes[1..] {
+        assert!(!scheme.verified, "{} must notice the modified contents", scheme.scheme);
+        assert!(scheme.errors[0].contains("do not match the signed digest"), "{:?}", scheme.errors);
+    }
+}
+
+#[test]
+fn test_added_entry_is_flagged() {
+    let mut archive = ZipArchive::new(Cursor::new(sample_apk())).unwrap();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        writer.raw_copy_file(archive.by_index_raw(i).unwrap()).unwrap();
+    }
+    writer.start_file("assets/payload.bin", FileOptions::default()).unwrap();
+    writer.write_all(b"payload").unwrap();
+    let report = signing::verify(&writer.finish().unwrap().into_inner(), None).unwrap();
+
+    assert!(!report.verified);
+    assert_eq!(report.schemes[0].errors, ["assets/payload.bin is not in META-INF/MANIFEST.MF"]);
+}
+
+#[test]
+fn test_stripped_signature_is_flagged() {
+    let report = signing::verify(&strip_signing_block(signed_apk()), None).unwrap();
+
+    assert!(!report.verified);
+    assert_eq!(scheme_names(&report), ["v1"]);
+    let errors = &report.schemes[0].errors;
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("also signed with v2, but that signature was stripped"));
+    assert!(errors[1].contains("also signed with v3"));
+}
+
+#[test]
+fn test_unsigned_apk() {
+    let mut archive = ZipArchive::new(Cursor::new(sample_apk())).unwrap();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).unwrap();
+        if !entry.name().starts_with("META-INF/") {
+            writer.raw_copy_file(entry).unwrap();
+        }
+    }
+    let report = signing::verify(&writer.finish().unwrap().into_inner(), None).unwrap();
+
+    assert!(!report.verified);
+    assert!(report.schemes.is_empty());
+    assert_eq!(report.errors, ["APK is not signed"]);
+
+    assert!(signing::verify(b"not a zip file", None).is_err());
+}
+
+#[test]
+fn test_huge_signing_block_size_is_malformed() {
+    let apk = huge_signing_block_size();
+
+    let error = signing::verify(&apk, None).unwrap_err();
+    assert!(error.to_string().contains("Malformed APK Signing Block"), "{}", error);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unpack_survives_unreadable_signing_block() {
+    let apk = huge_signing_block_size();
+    let temp_dir = TempDir::new().unwrap();
+    let apk_path = temp_dir.path().join("app.apk");
+    fs::write(&apk_path, apk).unwrap();
+
+    let report = jar_extractor(temp_dir.path()).unpack(apk_path.to_str().unwrap()).await.unwrap();
+    let signature = report.signature.unwrap();
+    assert!(!signature.verified);
+    assert!(signature.errors[0].contains("Malformed APK Signing Block"), "{:?}", signature.errors);
+    assert_eq!(report.jars, [temp_dir.path().join("app/classes.jar")]);
+}
@@ -0,0 +1,83 @@
+use apkext::dex::xref::{self, FieldAccess};
+use apkext::dex::{XrefQuery, Xrefs};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const SAMPLE_APK: &str = "testdata/sample.apk";
+const ON_CREATE: &str = "Lio/selendroid/testapp/WebViewActivity;->onCreate(Landroid/os/Bundle;)V";
+const GET_INSTANCE: &str = "Lio/selendroid/testapp/server/HttpServer;->getInstance()Lio/selendroid/testapp/server/HttpServer;";
+
+#[test]
+fn test_callers_callees_and_fields() {
+    let xrefs = Xrefs::from_path(Path::new(SAMPLE_APK)).unwrap();
+
+    // Full names, simple class names and smali references find the same call
+    for method in ["io.selendroid.testapp.server.HttpServer.getInstance", "HttpServer.getInstance", GET_INSTANCE] {
+        let report = xrefs.search(&XrefQuery::Callers(method.to_string())).unwrap();
+        assert_eq!(report.hits.len(), 1, "{}", method);
+        assert_eq!(report.hits[0].method, ON_CREATE);
+        assert_eq!((report.hits[0].instruction, report.hits[0].offset), ("invoke-static", 0));
+        assert_eq!(report.hits[0].target, GET_INSTANCE);
+    }
+    // A descriptor picks one overload
+    assert!(xrefs.search(&XrefQuery::Callers("HttpServer.getInstance(I)".to_string())).unwrap().hits.is_empty());
+    assert!(xrefs.search(&XrefQuery::Callers("Server.getInstance".to_string())).unwrap().hits.is_empty());
+    assert!(xrefs.search(&XrefQuery::Callers("getInstance".to_string())).is_err());
+
+    let callees = xrefs.search(&XrefQuery::Callees("WebViewActivity.onCreate".to_string())).unwrap();
+    assert_eq!(callees.hits[0].target, GET_INSTANCE);
+    assert!(callees.hits.iter().all(|hit| hit.method == ON_CREATE && hit.instruction.starts_with("invoke-")));
+    assert!(callees.hits.windows(2).all(|pair| pair[0].offset < pair[1].offset));
+
+    let field = xrefs.search(&XrefQuery::Field("WebViewActivity.server:Lio/selendroid/testapp/server/HttpServer;".to_string())).unwrap();
+    assert_eq!(field.hits.len(), 2);
+    assert!(field.hits.iter().all(|hit| hit.access == Some(FieldAccess::Write) && hit.instruction == "iput-object"));
+}
+
+#[test]
+fn test_strings_map_to_smali_and_source() {
+    let xrefs = Xrefs::from_path(Path::new(SAMPLE_APK)).unwrap();
+    let mut report = xrefs.search(&XrefQuery::String("localhost:4450".to_string())).unwrap();
+
+    assert_eq!(report.hits.len(), 3);
+    assert_eq!(report.hits[0].method, ON_CREATE);
+    assert_eq!(report.hits[0].target, "\"http://localhost:4450/\"");
+    assert!(report.hits[1].target.contains("<a href=\\'http://localhost:4450/\\'>"), "{}", report.hits[1].target);
+
+    // The part of an unpack that maps onCreate
+    let temp_dir = TempDir::new().unwrap();
+    let smali = temp_dir.path().join("unpacked/smali/io/selendroid/testapp/WebViewActivity.smali");
+    let source = temp_dir.path().join("src/io/selendroid/testapp/WebViewActivity.java");
+    fs::create_dir_all(smali.parent().unwrap()).unwrap();
+    fs::create_dir_all(source.parent().unwrap()).unwrap();
+    fs::write(&smali, [
+        ".class public Lio/selendroid/testapp/WebViewActivity;",
+        ".method public onCreate(Landroid/os/Bundle;)V",
+        "    .locals 3",
+        "    # const-string v1, \"http://localhost:4450/\"",
+        "    const-string v1, \"http://localhost:4450/\"",
+        ".end method",
+    ]
+    .join("\n"))
+    .unwrap();
+    fs::write(&source, "public class WebViewActivity {\n    void onCreate() {\n        load(\"http://localhost:4450/\");\n    }\n}\n").unwrap();
+
+    report.locate(temp_dir.path());
+    let hit = &report.hits[0];
+    assert_eq!(hit.smali.as_ref().map(|location| (location.path.as_path(), location.line)), Some((smali.as_path(), Some(5))));
+    assert_eq!(hit.source.as_ref().map(|location| (location.path.as_path(), location.line)), Some((source.as_path(), Some(3))));
+    // HttpServer was not unpacked
+    assert!(report.hits[1].smali.is_none() && report.hits[1].source.is_none());
+
+    let text = report.to_string();
+    assert!(text.starts_with("Uses of strings containing \"localhost:4450\" (3):\n"), "{}", text);
+    assert!(text.contains("WebViewActivity.smali:5"), "{}", text);
+}
+
+#[test]
+fn test_smali_literal() {
+    assert_eq!(xref::smali_literal("it's \"quoted\"\\"), "\"it\\'s \\\"quoted\\\"\\\\\"");
+    assert_eq!(xref::smali_literal("a\tb\n"), "\"a\\tb\\n\"");
+    assert_eq!(xref::smali_literal("é😀"), "\"\\u00e9\\ud83d\\ude00\"");
+}
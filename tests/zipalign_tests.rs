@@ -0,0 +1,64 @@
+use apkext::zipalign;
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// ZIP whose stored entries start at odd offsets
+fn misaligned_zip() -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    writer.start_file("a", stored).unwrap();
+    writer.write_all(b"xy").unwrap();
+    writer.start_file("resources.arsc", stored).unwrap();
+    writer.write_all(b"resource table").unwrap();
+    writer.start_file("classes.dex", deflated).unwrap();
+    writer.write_all(&[7u8; 1000]).unwrap();
+    writer.start_file("lib/arm64-v8a/libnative.so", stored).unwrap();
+    writer.write_all(b"\x7fELF").unwrap();
+
+    writer.finish().unwrap().into_inner()
+}
+
+fn read_entry(zip: &[u8], name: &str) -> Vec<u8> {
+    let mut archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+    let mut data = Vec::new();
+    archive.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn test_verify_reports_misaligned_entries() {
+    let misaligned = zipalign::verify(&misaligned_zip()).unwrap();
+    let names: Vec<&str> = misaligned.iter().map(|entry| entry.name.as_str()).collect();
+
+    assert!(names.contains(&"resources.arsc"));
+    assert!(names.contains(&"lib/arm64-v8a/libnative.so"));
+    assert!(!names.contains(&"classes.dex"), "Compressed entries need no alignment");
+}
+
+#[test]
+fn test_align_fixes_alignment_and_keeps_content() {
+    let original = misaligned_zip();
+    let aligned = zipalign::align(&original).unwrap();
+
+    assert!(zipalign::verify(&aligned).unwrap().is_empty());
+
+    let mut archive = ZipArchive::new(Cursor::new(&aligned[..])).unwrap();
+    let library = archive.by_name("lib/arm64-v8a/libnative.so").unwrap();
+    assert_eq!(library.data_start() % 16384, 0);
+    drop(library);
+
+    for name in ["a", "resources.arsc", "classes.dex", "lib/arm64-v8a/libnative.so"] {
+        assert_eq!(read_entry(&original, name), read_entry(&aligned, name), "{} must be unchanged", name);
+    }
+}
+
+#[test]
+fn test_align_is_idempotent() {
+    let aligned = zipalign::align(&misaligned_zip()).unwrap();
+    let realigned = zipalign::align(&aligned).unwrap();
+
+    assert_eq!(aligned, realigned);
+}